@@ -1,6 +1,16 @@
-pub mod critbit;
-pub mod error;
-pub mod orderbook;
-pub mod params;
-pub mod state;
-pub mod utils;
+#[cfg(feature = "client")]
+pub use ::aob::client;
+#[cfg(feature = "keeper")]
+pub use ::aob::keeper;
+#[cfg(feature = "no-entrypoint")]
+pub use ::aob::loader;
+#[cfg(feature = "sim")]
+pub use ::aob::sim;
+#[cfg(feature = "snapshot")]
+pub use ::aob::snapshot;
+pub use ::aob::{critbit, error, events, histbuf, orderbook, params, state, utils};
+
+#[cfg(feature = "cpi")]
+pub mod cpi;
+pub mod idl;
+pub mod instruction;
@@ -0,0 +1,133 @@
+//! Helpers for caller programs which invoke the AO program.
+//!
+//! Each helper takes a [`CpiContext`] built by the caller over the instruction's accounts, along with the signer
+//! seeds and the remaining accounts, maps the matching [`params`][crate::aob::params] struct onto the instruction's
+//! arguments, performs the call and deserializes the instruction's return data.
+//!
+//! Downstream programs get this module by depending on the program with the `cpi` feature:
+//!
+//! ```toml
+//! anchor-agnostic-orderbook = { version = "0.1.0", features = ["cpi"] }
+//! ```
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::get_return_data;
+
+use crate::aob::error::ErrorCode;
+use crate::aob::orderbook::{L2Snapshot, OrderResult, OrderSummary};
+use crate::aob::params::{
+    CancelOrderParams, ConsumeEventsParams, NewOrderParams, ReduceOrderParams,
+};
+use crate::aob::state::Side;
+use crate::aob::utils::saturating_u64;
+pub use crate::cpi::accounts::{
+    AssertBestPrice, CancelOrder, CloseMarket, ConsumeEvents, ContinueOrder, GetL2Snapshot, NewOrder,
+    ReduceOrder,
+};
+
+fn read_return_data<T: AnchorDeserialize>() -> Result<T> {
+    match get_return_data() {
+        Some((program_id, data)) if program_id == crate::id() => {
+            T::try_from_slice(&data).map_err(|_| error!(ErrorCode::FailedToDeserialize))
+        }
+        _ => err!(ErrorCode::FailedToDeserialize),
+    }
+}
+
+/// Places a new order and returns its [`OrderResult`].
+///
+/// The remaining accounts hold the optional resumption ticket, followed by the market's trade history, candles
+/// and fee vault accounts, if it has them.
+pub fn new_order<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, NewOrder<'info>>,
+    params: NewOrderParams,
+) -> Result<OrderResult> {
+    crate::cpi::new_order(
+        ctx,
+        saturating_u64(params.max_base_qty),
+        saturating_u64(params.max_quote_qty),
+        params.limit_price,
+        params.side as u8,
+        params.match_limit,
+        params.callback_info,
+        params.post_only,
+        params.post_allowed,
+        params.self_trade_behavior as u8,
+        params.self_trade_id_len,
+        params.max_avg_price,
+    )?;
+    read_return_data()
+}
+
+/// Resumes the taker order held by the market's resumption ticket and returns the [`OrderResult`] of this step.
+pub fn continue_order<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, ContinueOrder<'info>>,
+    match_limit: u64,
+) -> Result<OrderResult> {
+    crate::cpi::continue_order(ctx, match_limit)?;
+    read_return_data()
+}
+
+/// Cancels an order and returns the [`OrderSummary`] of what was left of it in the orderbook.
+pub fn cancel_order<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, CancelOrder<'info>>,
+    params: CancelOrderParams,
+) -> Result<OrderSummary> {
+    crate::cpi::cancel_order(ctx, params.order_id.0, params.expected_callback_id)?;
+    read_return_data()
+}
+
+/// Shrinks a resting order while keeping its priority, and returns the [`OrderSummary`] of what it released.
+pub fn reduce_order<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, ReduceOrder<'info>>,
+    params: ReduceOrderParams,
+) -> Result<OrderSummary> {
+    crate::cpi::reduce_order(
+        ctx,
+        params.order_id.0,
+        saturating_u64(params.new_base_qty),
+        params.expected_callback_id,
+    )?;
+    read_return_data()
+}
+
+/// Fails unless the best price on the given side of the book is at least as good as `limit_price` for a taker.
+pub fn assert_best_price<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, AssertBestPrice<'info>>,
+    side: Side,
+    limit_price: u64,
+) -> Result<()> {
+    crate::cpi::assert_best_price(ctx, side, limit_price)
+}
+
+/// Returns the top `depth` price levels of both sides of the book.
+pub fn get_l2_snapshot<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, GetL2Snapshot<'info>>,
+    depth: u64,
+) -> Result<L2Snapshot> {
+    crate::cpi::get_l2_snapshot(ctx, depth)?;
+    read_return_data()
+}
+
+/// Pops events off the event queue and returns the number of events which were consumed.
+///
+/// The remaining accounts hold the market's fee vault, if it has one.
+pub fn consume_events<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, ConsumeEvents<'info>>,
+    params: ConsumeEventsParams,
+) -> Result<u64> {
+    crate::cpi::consume_events(
+        ctx,
+        params.number_of_entries_to_consume,
+        params.min_remaining_compute_units,
+    )?;
+    read_return_data()
+}
+
+/// Closes the market.
+///
+/// The remaining accounts hold the co-signers and the market's fee vault, if any.
+pub fn close_market<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, CloseMarket<'info>>,
+) -> Result<()> {
+    crate::cpi::close_market(ctx)
+}
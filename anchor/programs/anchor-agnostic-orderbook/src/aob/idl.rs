@@ -0,0 +1,264 @@
+//! Definitions of the core types which clients decode, for the IDL.
+//!
+//! Anchor only parses the program crate when generating the IDL, so the types of the shared `aob` crate which
+//! show up in the event queue, the return data or the instruction arguments are mirrored here. Each mirror has the
+//! same Borsh layout as the core type it describes, order ids being plain `u128`s since the IDL doesn't support
+//! tuple structs.
+//!
+//! Quantities are mirrored as `u64`s, which is the layout of the default build: with the `u128-quantities` feature,
+//! the events and order summaries hold 128-bit quantities instead.
+use anchor_lang::prelude::*;
+
+/// Mirrors [`Side`][crate::aob::state::Side].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// Mirrors [`SelfTradeBehavior`][crate::aob::state::SelfTradeBehavior].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub enum SelfTradeBehavior {
+    DecrementTake,
+    CancelProvide,
+    AbortTransaction,
+}
+
+/// Mirrors [`TradingSession`][crate::aob::state::TradingSession].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub enum TradingSession {
+    Open,
+    PreOpen,
+    Closed,
+}
+
+/// Mirrors [`Event`][crate::aob::state::Event].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub enum Event {
+    Fill {
+        taker_side: Side,
+        maker_order_id: u128,
+        quote_size: u64,
+        base_size: u64,
+        slot: u64,
+        unix_timestamp: i64,
+        maker_callback_info: Vec<u8>,
+        taker_callback_info: Vec<u8>,
+    },
+    Out {
+        side: Side,
+        order_id: u128,
+        base_size: u64,
+        delete: bool,
+        slot: u64,
+        unix_timestamp: i64,
+        callback_info: Vec<u8>,
+    },
+}
+
+/// Mirrors [`OrderSummary`][crate::aob::orderbook::OrderSummary].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct OrderSummary {
+    pub posted_order_id: Option<u128>,
+    pub total_base_qty: u64,
+    pub total_quote_qty: u64,
+    pub total_fee_qty: u64,
+    pub total_base_qty_posted: u64,
+    pub avg_fill_price_fp32: u64,
+    pub worst_fill_price_fp32: u64,
+}
+
+/// Mirrors [`PriceLevel`][crate::aob::orderbook::PriceLevel].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct PriceLevel {
+    pub price: u64,
+    pub base_quantity: u64,
+}
+
+/// Mirrors [`OrderResult`][crate::aob::orderbook::OrderResult].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct OrderResult {
+    pub summary: OrderSummary,
+    pub fill_levels: Vec<PriceLevel>,
+}
+
+/// Mirrors [`Auction`][crate::aob::orderbook::Auction].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub struct Auction {
+    pub price: u64,
+    pub base_qty: u64,
+}
+
+#[cfg(all(test, not(feature = "u128-quantities")))]
+mod tests {
+    use super::*;
+    use crate::aob::{orderbook, state};
+
+    #[test]
+    fn enums_match_core_layout() {
+        assert_eq!(
+            Side::Ask.try_to_vec().unwrap(),
+            state::Side::Ask.try_to_vec().unwrap()
+        );
+        assert_eq!(
+            SelfTradeBehavior::AbortTransaction.try_to_vec().unwrap(),
+            state::SelfTradeBehavior::AbortTransaction
+                .try_to_vec()
+                .unwrap()
+        );
+        assert_eq!(
+            TradingSession::Closed.try_to_vec().unwrap(),
+            state::TradingSession::Closed.try_to_vec().unwrap()
+        );
+    }
+
+    #[test]
+    fn events_match_core_layout() {
+        let fill = Event::Fill {
+            taker_side: Side::Bid,
+            maker_order_id: 42 << 64,
+            quote_size: 100,
+            base_size: 10,
+            slot: 7,
+            unix_timestamp: -1,
+            maker_callback_info: vec![1; 4],
+            taker_callback_info: vec![2; 4],
+        };
+        let core_fill = state::Event::Fill {
+            taker_side: state::Side::Bid,
+            maker_order_id: state::OrderId(42 << 64),
+            quote_size: 100,
+            base_size: 10,
+            slot: 7,
+            unix_timestamp: -1,
+            maker_callback_info: vec![1; 4],
+            taker_callback_info: vec![2; 4],
+        };
+        assert_eq!(
+            fill.try_to_vec().unwrap(),
+            BorshSerialize::try_to_vec(&core_fill).unwrap()
+        );
+
+        let out = Event::Out {
+            side: Side::Ask,
+            order_id: 3,
+            base_size: 5,
+            delete: true,
+            slot: 8,
+            unix_timestamp: 9,
+            callback_info: vec![3; 4],
+        };
+        let core_out = state::Event::Out {
+            side: state::Side::Ask,
+            order_id: state::OrderId(3),
+            base_size: 5,
+            delete: true,
+            slot: 8,
+            unix_timestamp: 9,
+            callback_info: vec![3; 4],
+        };
+        assert_eq!(
+            out.try_to_vec().unwrap(),
+            BorshSerialize::try_to_vec(&core_out).unwrap()
+        );
+    }
+
+    #[test]
+    fn order_summary_matches_core_layout() {
+        let summary = OrderSummary {
+            posted_order_id: Some(12),
+            total_base_qty: 1,
+            total_quote_qty: 2,
+            total_fee_qty: 3,
+            total_base_qty_posted: 4,
+            avg_fill_price_fp32: 5,
+            worst_fill_price_fp32: 6,
+        };
+        let core_summary = orderbook::OrderSummary {
+            posted_order_id: Some(state::OrderId(12)),
+            total_base_qty: 1,
+            total_quote_qty: 2,
+            total_fee_qty: 3,
+            total_base_qty_posted: 4,
+            avg_fill_price_fp32: 5,
+            worst_fill_price_fp32: 6,
+        };
+        let bytes = summary.try_to_vec().unwrap();
+        assert_eq!(bytes, core_summary.try_to_vec().unwrap());
+        assert_eq!(bytes.len(), orderbook::ORDER_SUMMARY_SIZE as usize);
+    }
+
+    #[test]
+    fn order_result_matches_core_layout() {
+        let result = OrderResult {
+            summary: OrderSummary {
+                posted_order_id: None,
+                total_base_qty: 30,
+                total_quote_qty: 320,
+                total_fee_qty: 0,
+                total_base_qty_posted: 0,
+                avg_fill_price_fp32: (32 << 32) / 3,
+                worst_fill_price_fp32: 11 << 32,
+            },
+            fill_levels: vec![
+                PriceLevel {
+                    price: 10 << 32,
+                    base_quantity: 10,
+                },
+                PriceLevel {
+                    price: 11 << 32,
+                    base_quantity: 20,
+                },
+            ],
+        };
+        let core_result = orderbook::OrderResult {
+            summary: orderbook::OrderSummary {
+                posted_order_id: None,
+                total_base_qty: 30,
+                total_quote_qty: 320,
+                total_fee_qty: 0,
+                total_base_qty_posted: 0,
+                avg_fill_price_fp32: (32 << 32) / 3,
+                worst_fill_price_fp32: 11 << 32,
+            },
+            fill_levels: vec![
+                orderbook::PriceLevel {
+                    price: 10 << 32,
+                    base_quantity: 10,
+                },
+                orderbook::PriceLevel {
+                    price: 11 << 32,
+                    base_quantity: 20,
+                },
+            ],
+        };
+        assert_eq!(
+            result.try_to_vec().unwrap(),
+            core_result.try_to_vec().unwrap()
+        );
+    }
+
+    #[test]
+    fn auction_matches_core_layout() {
+        let auction = Auction {
+            price: 11 << 32,
+            base_qty: 15,
+        };
+        let core_auction = orderbook::Auction {
+            price: 11 << 32,
+            base_qty: 15,
+        };
+        assert_eq!(
+            Some(auction).try_to_vec().unwrap(),
+            Some(core_auction).try_to_vec().unwrap()
+        );
+    }
+}
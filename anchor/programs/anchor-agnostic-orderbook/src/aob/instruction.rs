@@ -0,0 +1,388 @@
+//! Builders for the instructions of the Anchor AO program.
+//!
+//! Each builder takes the program's generated [`accounts`][crate::accounts] struct and the instruction's
+//! arguments, and prepends the Anchor discriminator to the serialized arguments. The optional accounts
+//! which an instruction looks up among its remaining accounts, such as the market's [`FeeVault`] or the
+//! co-signers of an administrative instruction, are passed as `remaining_accounts`.
+//!
+//! [`FeeVault`]: crate::aob::state::FeeVault
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::InstructionData;
+
+use crate::aob::params::{
+    CancelOrderParams, ConsumeEventsParams, CreateMarketParams, NewOrderParams, ReduceOrderParams,
+};
+use crate::aob::state::Side;
+use crate::aob::utils::saturating_u64;
+use crate::{accounts, instruction};
+
+fn build(
+    accounts: impl ToAccountMetas,
+    remaining_accounts: &[AccountMeta],
+    data: impl InstructionData,
+) -> Instruction {
+    let mut account_metas = accounts.to_account_metas(None);
+    account_metas.extend_from_slice(remaining_accounts);
+    Instruction {
+        program_id: crate::id(),
+        accounts: account_metas,
+        data: data.data(),
+    }
+}
+
+/// Creates a market from freshly generated event queue, bids and asks accounts.
+pub fn create_market(accounts: accounts::CreateMarket, params: CreateMarketParams) -> Instruction {
+    build(
+        accounts,
+        &[],
+        instruction::CreateMarket {
+            caller_authority: Pubkey::new_from_array(params.caller_authority),
+            callback_info_len: params.callback_info_len,
+            callback_id_len: params.callback_id_len,
+            min_base_order_size: params.min_base_order_size,
+            tick_size: params.tick_size,
+            base_lot_size: params.base_lot_size,
+            cranker_base_reward: params.cranker_base_reward,
+            cranker_per_event_reward: params.cranker_per_event_reward,
+            order_priority: params.order_priority as u8,
+            event_queue_overflow_policy: params.event_queue_overflow_policy as u8,
+            max_post_only_spread_ticks: params.max_post_only_spread_ticks,
+            disable_self_trade_detection: params.disable_self_trade_detection,
+            price_rounding_policy: params.price_rounding_policy as u8,
+            max_match_iterations: params.max_match_iterations,
+            coalesce_fills: params.coalesce_fills,
+            order_capacity: params.order_capacity,
+            event_capacity: params.event_capacity,
+        },
+    )
+}
+
+/// Creates a market whose accounts are program-derived addresses, see
+/// [`MarketAddresses`][crate::aob::state::MarketAddresses].
+pub fn create_market_with_seed(
+    accounts: accounts::CreateMarketWithSeed,
+    seed: [u8; 32],
+    params: CreateMarketParams,
+) -> Instruction {
+    build(
+        accounts,
+        &[],
+        instruction::CreateMarketWithSeed {
+            seed,
+            caller_authority: Pubkey::new_from_array(params.caller_authority),
+            callback_info_len: params.callback_info_len,
+            callback_id_len: params.callback_id_len,
+            min_base_order_size: params.min_base_order_size,
+            tick_size: params.tick_size,
+            base_lot_size: params.base_lot_size,
+            cranker_base_reward: params.cranker_base_reward,
+            cranker_per_event_reward: params.cranker_per_event_reward,
+            order_priority: params.order_priority as u8,
+            event_queue_overflow_policy: params.event_queue_overflow_policy as u8,
+            max_post_only_spread_ticks: params.max_post_only_spread_ticks,
+            disable_self_trade_detection: params.disable_self_trade_detection,
+            price_rounding_policy: params.price_rounding_policy as u8,
+            max_match_iterations: params.max_match_iterations,
+            coalesce_fills: params.coalesce_fills,
+            order_capacity: params.order_capacity,
+            event_capacity: params.event_capacity,
+        },
+    )
+}
+
+/// Places a new order. The remaining accounts hold the optional resumption ticket, followed by the market's
+/// trade history, candles and fee vault accounts, if it has them.
+pub fn new_order(
+    accounts: accounts::NewOrder,
+    params: NewOrderParams,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::NewOrder {
+            max_base_qty: saturating_u64(params.max_base_qty),
+            max_quote_qty: saturating_u64(params.max_quote_qty),
+            limit_price: params.limit_price,
+            side: params.side as u8,
+            match_limit: params.match_limit,
+            callback_info: params.callback_info,
+            post_only: params.post_only,
+            post_allowed: params.post_allowed,
+            self_trade_behavior: params.self_trade_behavior as u8,
+            self_trade_id_len: params.self_trade_id_len,
+            max_avg_price: params.max_avg_price,
+        },
+    )
+}
+
+/// Resumes the taker order held by the market's resumption ticket.
+pub fn continue_order(
+    accounts: accounts::ContinueOrder,
+    match_limit: u64,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::ContinueOrder { match_limit },
+    )
+}
+
+#[allow(missing_docs)]
+pub fn cancel_order(accounts: accounts::CancelOrder, params: CancelOrderParams) -> Instruction {
+    build(
+        accounts,
+        &[],
+        instruction::CancelOrder {
+            order_id: params.order_id.0,
+            expected_callback_id: params.expected_callback_id,
+        },
+    )
+}
+
+#[allow(missing_docs)]
+pub fn reduce_order(accounts: accounts::ReduceOrder, params: ReduceOrderParams) -> Instruction {
+    build(
+        accounts,
+        &[],
+        instruction::ReduceOrder {
+            order_id: params.order_id.0,
+            new_base_qty: saturating_u64(params.new_base_qty),
+            expected_callback_id: params.expected_callback_id,
+        },
+    )
+}
+
+/// Fails unless the best price on the given side of the book is at least as good as `limit_price` for a taker.
+pub fn assert_best_price(
+    accounts: accounts::AssertBestPrice,
+    side: Side,
+    limit_price: u64,
+) -> Instruction {
+    build(
+        accounts,
+        &[],
+        instruction::AssertBestPrice { side, limit_price },
+    )
+}
+
+/// Writes an [`L2Snapshot`][crate::aob::orderbook::L2Snapshot] of the book into the return data.
+pub fn get_l2_snapshot(accounts: accounts::GetL2Snapshot, depth: u64) -> Instruction {
+    build(accounts, &[], instruction::GetL2Snapshot { depth })
+}
+
+/// Pops events off the event queue. The remaining accounts hold the market's fee vault, if it has one.
+pub fn consume_events(
+    accounts: accounts::ConsumeEvents,
+    params: ConsumeEventsParams,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::ConsumeEvents {
+            number_of_entries_to_consume: params.number_of_entries_to_consume,
+            min_remaining_compute_units: params.min_remaining_compute_units,
+        },
+    )
+}
+
+/// Closes the market. The remaining accounts hold the co-signers and the market's fee vault, if any.
+pub fn close_market(
+    accounts: accounts::CloseMarket,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(accounts, remaining_accounts, instruction::CloseMarket {})
+}
+
+/// Closes one side of the book. The remaining accounts hold the co-signers, if any.
+pub fn close_book_side(
+    accounts: accounts::CloseBookSide,
+    side: Side,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::CloseBookSide { side },
+    )
+}
+
+/// Closes the event queue. The remaining accounts hold the co-signers, if any.
+pub fn close_event_queue(
+    accounts: accounts::CloseEventQueue,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::CloseEventQueue {},
+    )
+}
+
+/// Sets the event queue watermarks. The remaining accounts hold the co-signers, if any.
+pub fn set_event_queue_watermarks(
+    accounts: accounts::SetEventQueueWatermarks,
+    high_watermark_bps: u64,
+    low_watermark_bps: u64,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::SetEventQueueWatermarks {
+            high_watermark_bps,
+            low_watermark_bps,
+        },
+    )
+}
+
+/// Selects whether anyone can consume events. The remaining accounts hold the co-signers, if any.
+pub fn set_crank_mode(
+    accounts: accounts::SetCrankMode,
+    permissionless: bool,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::SetCrankMode { permissionless },
+    )
+}
+
+/// Sets the authority signers. The remaining accounts hold the current co-signers, if any.
+pub fn set_authority_signers(
+    accounts: accounts::SetAuthoritySigners,
+    threshold: u64,
+    signers: Vec<Pubkey>,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::SetAuthoritySigners { threshold, signers },
+    )
+}
+
+/// Creates the market's trade history. The remaining accounts hold the co-signers, if any.
+pub fn create_trade_history(
+    accounts: accounts::CreateTradeHistory,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::CreateTradeHistory {},
+    )
+}
+
+/// Creates the market's candles. The remaining accounts hold the co-signers, if any.
+pub fn create_candles(
+    accounts: accounts::CreateCandles,
+    slots_per_candle: u64,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::CreateCandles { slots_per_candle },
+    )
+}
+
+/// Creates the market's fee vault. The remaining accounts hold the co-signers, if any.
+pub fn create_fee_vault(
+    accounts: accounts::CreateFeeVault,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(accounts, remaining_accounts, instruction::CreateFeeVault {})
+}
+
+#[allow(missing_docs)]
+pub fn deposit_fee(accounts: accounts::DepositFee, amount: u64) -> Instruction {
+    build(accounts, &[], instruction::DepositFee { amount })
+}
+
+/// Grows the event queue. The remaining accounts hold the co-signers, if any.
+pub fn resize_event_queue(
+    accounts: accounts::ResizeEventQueue,
+    new_len: u64,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::ResizeEventQueue { new_len },
+    )
+}
+
+/// Grows the bids or asks account. The remaining accounts hold the co-signers, if any.
+pub fn resize_book_side(
+    accounts: accounts::ResizeBookSide,
+    side: Side,
+    new_order_capacity: u64,
+    remaining_accounts: &[AccountMeta],
+) -> Instruction {
+    build(
+        accounts,
+        remaining_accounts,
+        instruction::ResizeBookSide {
+            side,
+            new_order_capacity,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aob::state::SelfTradeBehavior;
+
+    #[test]
+    fn new_order_instruction() {
+        let keys: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        let fee_vault = AccountMeta::new(Pubkey::new_unique(), false);
+        let ix = new_order(
+            accounts::NewOrder {
+                market: keys[0],
+                event_queue: keys[1],
+                bids: keys[2],
+                asks: keys[3],
+                authority: keys[4],
+            },
+            NewOrderParams {
+                max_base_qty: 10,
+                max_quote_qty: 100,
+                limit_price: 10 << 32,
+                side: Side::Ask,
+                match_limit: 5,
+                callback_info: vec![1; 32],
+                post_only: false,
+                post_allowed: true,
+                self_trade_behavior: SelfTradeBehavior::CancelProvide,
+                self_trade_id_len: None,
+                max_avg_price: None,
+            },
+            &[fee_vault.clone()],
+        );
+
+        assert_eq!(ix.program_id, crate::id());
+        assert_eq!(ix.accounts.last(), Some(&fee_vault));
+        let args = instruction::NewOrder::try_from_slice(&ix.data[8..]).unwrap();
+        assert_eq!(args.side, Side::Ask as u8);
+        assert_eq!(args.callback_info, vec![1; 32]);
+        assert_eq!(
+            args.self_trade_behavior,
+            SelfTradeBehavior::CancelProvide as u8
+        );
+        assert_ne!(
+            ix.data[..8],
+            instruction::CancelOrder {
+                order_id: 0,
+                expected_callback_id: None
+            }
+            .data()[..8]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! A local matching server backed by the in-memory [`SimMarket`], for iterating on front-ends and strategies
+//! without a validator.
+//!
+//! ```text
+//! tryout [--listen 127.0.0.1:7878] [--snapshot DIR]
+//! ```
+//!
+//! When a snapshot directory is given, the market is loaded from the raw account data found in its `market.bin`,
+//! `bids.bin`, `asks.bin` and `event_queue.bin` files. Otherwise an empty market is created.
+//!
+//! Clients send one JSON request per line, of the form `{"method": ..., "params": {...}}`, and receive one JSON
+//! response per line, either `{"result": ...}` or `{"error": ...}`. Order ids are passed around as hexadecimal
+//! strings.
+//!
+//! - `new_order`: `side` (`"bid"` or `"ask"`), `limit_price`, `max_base_qty`, `max_quote_qty`, and optionally
+//!   `match_limit`, `callback_info` (an array of bytes), `post_only`, `post_allowed` and `self_trade_behavior`
+//!   (`"decrement_take"`, `"cancel_provide"` or `"abort_transaction"`)
+//! - `cancel`: `order_id`
+//! - `l2`: `side` and optionally `depth`
+//! - `events`: optionally `consume`, which pops the returned events off the event queue
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+};
+
+use anchor_agnostic_orderbook::aob::{
+    orderbook::OrderSummary,
+    params::NewOrderParams,
+    sim::{SimError, SimMarket},
+    state::{
+        Event, MarketState, OrderId, SelfTradeBehavior, Side, EVENT_QUEUE_HEADER_LEN, REGISTER_SIZE,
+    },
+    utils::Quantity,
+};
+use serde_json::{json, Value};
+
+const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:7878";
+const DEFAULT_CALLBACK_INFO_LEN: usize = 32;
+const DEFAULT_BOOK_LEN: usize = 1 << 20;
+const DEFAULT_EVENT_QUEUE_CAPACITY: usize = 1024;
+const DEFAULT_L2_DEPTH: usize = 20;
+
+fn main() {
+    let mut listen_address = DEFAULT_LISTEN_ADDRESS.to_owned();
+    let mut snapshot = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--listen", Some(value)) => listen_address = value,
+            ("--snapshot", Some(value)) => snapshot = Some(value),
+            _ => {
+                eprintln!("Usage: tryout [--listen ADDRESS] [--snapshot DIR]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut market = match snapshot {
+        Some(dir) => load_snapshot(Path::new(&dir)).unwrap_or_else(|e| {
+            eprintln!("Failed to load snapshot from {}: {}", dir, e);
+            std::process::exit(1);
+        }),
+        None => empty_market(),
+    };
+
+    let listener = TcpListener::bind(&listen_address).unwrap_or_else(|e| {
+        eprintln!("Failed to listen on {}: {}", listen_address, e);
+        std::process::exit(1);
+    });
+    println!("Listening on {}", listen_address);
+    // Clients are served one at a time, which keeps the market's operations totally ordered
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = serve(&mut market, stream) {
+                    eprintln!("Connection closed: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Failed to accept connection: {}", e),
+        }
+    }
+}
+
+fn empty_market() -> SimMarket {
+    let market_state = MarketState {
+        callback_info_len: DEFAULT_CALLBACK_INFO_LEN as u64,
+        callback_id_len: DEFAULT_CALLBACK_INFO_LEN as u64,
+        tick_size: 1,
+        base_lot_size: 1,
+        ..MarketState::default()
+    };
+    let event_queue_len = EVENT_QUEUE_HEADER_LEN
+        + REGISTER_SIZE
+        + DEFAULT_EVENT_QUEUE_CAPACITY * Event::compute_slot_size(DEFAULT_CALLBACK_INFO_LEN);
+    SimMarket::new(market_state, DEFAULT_BOOK_LEN, event_queue_len)
+}
+
+fn load_snapshot(dir: &Path) -> Result<SimMarket, String> {
+    let read = |name: &str| std::fs::read(dir.join(name)).map_err(|e| format!("{}: {}", name, e));
+    SimMarket::from_snapshot(
+        &read("market.bin")?,
+        read("bids.bin")?,
+        read("asks.bin")?,
+        read("event_queue.bin")?,
+    )
+    .map_err(|e| describe_sim_error(&e))
+}
+
+fn serve(market: &mut SimMarket, stream: TcpStream) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match handle(market, &line) {
+            Ok(result) => json!({ "result": result }),
+            Err(error) => json!({ "error": error }),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+fn handle(market: &mut SimMarket, line: &str) -> Result<Value, String> {
+    let request: Value =
+        serde_json::from_str(line).map_err(|e| format!("Invalid request: {}", e))?;
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    match request.get("method").and_then(Value::as_str) {
+        Some("new_order") => {
+            let params = parse_new_order(&params, market.market_state.callback_info_len as usize)?;
+            let summary = market
+                .new_order(params)
+                .map_err(|e| describe_sim_error(&e))?;
+            Ok(summary_to_json(&summary))
+        }
+        Some("cancel") => {
+            let order_id = get_order_id(&params, "order_id")?;
+            let summary = market
+                .cancel_order(order_id)
+                .map_err(|e| describe_sim_error(&e))?;
+            Ok(summary_to_json(&summary))
+        }
+        Some("l2") => {
+            let side = get_side(&params)?;
+            let depth = get_opt_u64(&params, "depth")?.unwrap_or(DEFAULT_L2_DEPTH as u64) as usize;
+            let levels = market.l2(side, depth).map_err(|e| e.to_string())?;
+            Ok(levels
+                .into_iter()
+                .map(|(price, base_qty)| json!({ "price": price, "base_qty": base_qty }))
+                .collect())
+        }
+        Some("events") => {
+            let events: Vec<Value> = market.events().iter().map(event_to_json).collect();
+            if get_bool(&params, "consume", false) {
+                market.consume_events(events.len() as u64);
+            }
+            Ok(Value::Array(events))
+        }
+        Some(method) => Err(format!("Unknown method {}", method)),
+        None => Err("Missing method".to_owned()),
+    }
+}
+
+fn parse_new_order(params: &Value, callback_info_len: usize) -> Result<NewOrderParams, String> {
+    let callback_info = match params.get("callback_info") {
+        Some(Value::Array(bytes)) => bytes
+            .iter()
+            .map(|b| {
+                b.as_u64()
+                    .filter(|b| *b <= u8::MAX as u64)
+                    .map(|b| b as u8)
+                    .ok_or_else(|| "callback_info must be an array of bytes".to_owned())
+            })
+            .collect::<Result<Vec<u8>, String>>()?,
+        None => vec![0; callback_info_len],
+        Some(_) => return Err("callback_info must be an array of bytes".to_owned()),
+    };
+    let self_trade_behavior = match params.get("self_trade_behavior").and_then(Value::as_str) {
+        None | Some("decrement_take") => SelfTradeBehavior::DecrementTake,
+        Some("cancel_provide") => SelfTradeBehavior::CancelProvide,
+        Some("abort_transaction") => SelfTradeBehavior::AbortTransaction,
+        Some(other) => return Err(format!("Unknown self_trade_behavior {}", other)),
+    };
+    Ok(NewOrderParams {
+        max_base_qty: get_u64(params, "max_base_qty")? as Quantity,
+        max_quote_qty: get_u64(params, "max_quote_qty")? as Quantity,
+        limit_price: get_u64(params, "limit_price")?,
+        side: get_side(params)?,
+        match_limit: get_opt_u64(params, "match_limit")?.unwrap_or(u64::MAX),
+        callback_info,
+        post_only: get_bool(params, "post_only", false),
+        post_allowed: get_bool(params, "post_allowed", true),
+        self_trade_behavior,
+        self_trade_id_len: None,
+        max_avg_price: None,
+    })
+}
+
+fn get_side(params: &Value) -> Result<Side, String> {
+    match params.get("side").and_then(Value::as_str) {
+        Some("bid") => Ok(Side::Bid),
+        Some("ask") => Ok(Side::Ask),
+        _ => Err("side must be either \"bid\" or \"ask\"".to_owned()),
+    }
+}
+
+fn get_bool(params: &Value, key: &str, default: bool) -> bool {
+    params.get(key).and_then(Value::as_bool).unwrap_or(default)
+}
+
+fn get_u64(params: &Value, key: &str) -> Result<u64, String> {
+    get_opt_u64(params, key)?.ok_or_else(|| format!("Missing {}", key))
+}
+
+/// Integers are accepted either as JSON numbers or as decimal strings
+fn get_opt_u64(params: &Value, key: &str) -> Result<Option<u64>, String> {
+    match params.get(key) {
+        None => Ok(None),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| format!("Invalid {}", key)),
+        Some(Value::String(s)) => s.parse().map(Some).map_err(|_| format!("Invalid {}", key)),
+        Some(_) => Err(format!("Invalid {}", key)),
+    }
+}
+
+fn get_order_id(params: &Value, key: &str) -> Result<OrderId, String> {
+    match params.get(key) {
+        Some(Value::String(s)) => s.parse().map_err(|_| format!("Invalid {}", key)),
+        Some(_) => Err(format!("Invalid {}", key)),
+        None => Err(format!("Missing {}", key)),
+    }
+}
+
+fn side_to_json(side: &Side) -> Value {
+    match side {
+        Side::Bid => json!("bid"),
+        Side::Ask => json!("ask"),
+    }
+}
+
+fn summary_to_json(summary: &OrderSummary) -> Value {
+    json!({
+        "posted_order_id": summary.posted_order_id.map(|id| id.to_string()),
+        "total_base_qty": summary.total_base_qty,
+        "total_quote_qty": summary.total_quote_qty,
+        "total_fee_qty": summary.total_fee_qty,
+        "total_base_qty_posted": summary.total_base_qty_posted,
+        "avg_fill_price_fp32": summary.avg_fill_price_fp32,
+        "worst_fill_price_fp32": summary.worst_fill_price_fp32,
+    })
+}
+
+fn event_to_json(event: &Event) -> Value {
+    match event {
+        Event::Fill {
+            taker_side,
+            maker_order_id,
+            quote_size,
+            base_size,
+            slot,
+            unix_timestamp,
+            maker_callback_info,
+            taker_callback_info,
+        } => json!({
+            "type": "fill",
+            "taker_side": side_to_json(taker_side),
+            "maker_order_id": maker_order_id.to_string(),
+            "quote_size": quote_size,
+            "base_size": base_size,
+            "slot": slot,
+            "unix_timestamp": unix_timestamp,
+            "maker_callback_info": maker_callback_info,
+            "taker_callback_info": taker_callback_info,
+        }),
+        Event::Out {
+            side,
+            order_id,
+            base_size,
+            delete,
+            slot,
+            unix_timestamp,
+            callback_info,
+        } => json!({
+            "type": "out",
+            "side": side_to_json(side),
+            "order_id": order_id.to_string(),
+            "base_size": base_size,
+            "delete": delete,
+            "slot": slot,
+            "unix_timestamp": unix_timestamp,
+            "callback_info": callback_info,
+        }),
+    }
+}
+
+fn describe_sim_error(e: &SimError) -> String {
+    match e {
+        SimError::Orderbook(e) => e.to_string(),
+        SimError::Audit(e) => format!("Audit failure: {:?}", e),
+    }
+}
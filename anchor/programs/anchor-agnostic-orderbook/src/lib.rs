@@ -3,23 +3,34 @@ use std::ops::{DerefMut};
 
 
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::log::sol_log_compute_units;
-use borsh::BorshDeserialize;
+use anchor_lang::solana_program::{
+    program::{invoke, set_return_data},
+    system_instruction,
+};
 use borsh::BorshSerialize;
 
 use num_traits::FromPrimitive;
 
-use crate::aob::critbit::Slab;
+use crate::aob::critbit::{Slab, SlabExt};
 use crate::aob::error::ErrorCode;
+use crate::aob::events::{EventQueueHighWatermark, EventQueueLowWatermark};
+#[cfg(feature = "emit-events")]
+use crate::aob::events::{FillEvent, OutEvent};
 use crate::aob::orderbook::OrderBookState;
-use crate::aob::orderbook::OrderSummary;
+use crate::aob::orderbook::{Auction, OrderResult, OrderSummary};
+use crate::aob::orderbook::{L2Snapshot, MAX_L2_SNAPSHOT_DEPTH};
 use crate::aob::params::NewOrderParams;
-use crate::aob::state::get_side_from_order_id;
-use crate::aob::state::{AccountTag, EventQueueHeader, MarketState};
-use crate::aob::state::{EventQueue, EVENT_QUEUE_HEADER_LEN};
-use crate::aob::state::{SelfTradeBehavior, Side};
-use crate::aob::utils::fp32_mul;
-use crate::aob::utils::round_price;
+use crate::aob::state::ResumptionTicket;
+use crate::aob::state::{AccountTag, Asks, Bids, EventQueueHeader, MarketState, OrderId};
+use crate::aob::state::{Candles, TradeHistory, CANDLES_LEN, TRADE_HISTORY_LEN};
+use crate::aob::state::{Event, EventQueue, EVENT_QUEUE_HEADER_LEN, REGISTER_SIZE};
+use crate::aob::state::{EventQueueOverflowPolicy, OrderPriority, SelfTradeBehavior, Side};
+use crate::aob::state::{FeeVault, PriceRoundingPolicy, TradingSession, FEE_VAULT_LEN};
+use crate::aob::state::{WatermarkCrossing, MAX_AUTHORITY_SIGNERS, MAX_BPS};
+use crate::aob::utils::check_account_key;
+use crate::aob::utils::{fp32_mul_floor, lots_to_base_qty, Quantity};
+use crate::aob::utils::remaining_compute_units;
+use ::aob::{log_compute_units, log_debug, log_error};
 
 pub mod aob;
 
@@ -29,6 +40,7 @@ declare_id!("aaobKniTtDGvCZces7GH5UReLYP671bBkB96ahr9x3e");
 pub mod anchor_agnostic_orderbook {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_market(
         ctx: Context<CreateMarket>,
         caller_authority: Pubkey,
@@ -36,40 +48,108 @@ pub mod anchor_agnostic_orderbook {
         callback_id_len: u64,
         min_base_order_size: u64,
         tick_size: u64,
-        cranker_reward: u64,
+        base_lot_size: u64,
+        cranker_base_reward: u64,
+        cranker_per_event_reward: u64,
+        order_priority: u8,
+        event_queue_overflow_policy: u8,
+        max_post_only_spread_ticks: u64,
+        disable_self_trade_detection: bool,
+        price_rounding_policy: u8,
+        max_match_iterations: u64,
+        coalesce_fills: bool,
+        order_capacity: u64,
+        event_capacity: u64,
     ) -> Result<()> {
-        let market_state = &mut ctx.accounts.market.load_init()?;
-        *market_state.deref_mut() = aob::state::MarketState {
-            tag: AccountTag::Market as u64,
-            caller_authority: caller_authority.to_bytes(),
-            event_queue: ctx.accounts.event_queue.key.to_bytes(),
-            bids: ctx.accounts.bids.key.to_bytes(),
-            asks: ctx.accounts.asks.key.to_bytes(),
+        initialize_market(
+            &ctx.accounts.market,
+            &ctx.accounts.event_queue,
+            &ctx.accounts.bids.to_account_info(),
+            &ctx.accounts.asks.to_account_info(),
+            [0; 32],
+            [0; 4],
+            caller_authority,
             callback_info_len,
             callback_id_len,
-            fee_budget: 0,
-            initial_lamports: ctx.accounts.market.to_account_info().lamports(),
             min_base_order_size,
             tick_size,
-            cranker_reward,
-        };
-
-        let event_queue_header = EventQueueHeader::initialize(callback_info_len as usize);
-        event_queue_header
-            .serialize(&mut (&mut ctx.accounts.event_queue.data.borrow_mut() as &mut [u8]))
-            .unwrap();
+            base_lot_size,
+            cranker_base_reward,
+            cranker_per_event_reward,
+            order_priority,
+            event_queue_overflow_policy,
+            max_post_only_spread_ticks,
+            disable_self_trade_detection,
+            price_rounding_policy,
+            max_match_iterations,
+            coalesce_fills,
+            order_capacity,
+            event_capacity,
+        )
+    }
 
-        Slab::initialize(
+    /// Creates a market whose accounts are program-derived addresses, see [`MarketAddresses`][crate::aob::state::MarketAddresses].
+    ///
+    /// The market's address is derived from the caller authority and the `seed`, and the addresses of its event
+    /// queue, bids and asks from the market's address. The seed and the bumps are stored in the [`MarketState`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_market_with_seed(
+        ctx: Context<CreateMarketWithSeed>,
+        seed: [u8; 32],
+        caller_authority: Pubkey,
+        callback_info_len: u64,
+        callback_id_len: u64,
+        min_base_order_size: u64,
+        tick_size: u64,
+        base_lot_size: u64,
+        cranker_base_reward: u64,
+        cranker_per_event_reward: u64,
+        order_priority: u8,
+        event_queue_overflow_policy: u8,
+        max_post_only_spread_ticks: u64,
+        disable_self_trade_detection: bool,
+        price_rounding_policy: u8,
+        max_match_iterations: u64,
+        coalesce_fills: bool,
+        order_capacity: u64,
+        event_capacity: u64,
+    ) -> Result<()> {
+        let bumps = [
+            ctx.bumps["market"],
+            ctx.bumps["event_queue"],
+            ctx.bumps["bids"],
+            ctx.bumps["asks"],
+        ];
+        initialize_market(
+            &ctx.accounts.market,
+            &ctx.accounts.event_queue,
             &ctx.accounts.bids.to_account_info(),
             &ctx.accounts.asks.to_account_info(),
-            *ctx.accounts.market.to_account_info().key,
-            callback_info_len as usize,
-        );
-
-        Ok(())
+            seed,
+            bumps,
+            caller_authority,
+            callback_info_len,
+            callback_id_len,
+            min_base_order_size,
+            tick_size,
+            base_lot_size,
+            cranker_base_reward,
+            cranker_per_event_reward,
+            order_priority,
+            event_queue_overflow_policy,
+            max_post_only_spread_ticks,
+            disable_self_trade_detection,
+            price_rounding_policy,
+            max_match_iterations,
+            coalesce_fills,
+            order_capacity,
+            event_capacity,
+        )
     }
 
     #[allow(clippy::too_many_arguments)]
+    /// Places a new order. Its [`OrderSummary`] is written to the event queue's register, and its [`OrderResult`],
+    /// which also lists the price levels it was matched against, to the return data.
     pub fn new_order(
         ctx: Context<NewOrder>,
         max_base_qty: u64,
@@ -81,202 +161,583 @@ pub mod anchor_agnostic_orderbook {
         post_only: bool,
         post_allowed: bool,
         self_trade_behavior: u8,
+        self_trade_id_len: Option<u64>,
+        max_avg_price: Option<u64>,
     ) -> Result<()> {
+        // The instruction arguments remain 64-bit with the u128-quantities feature
+        let max_base_qty = max_base_qty as Quantity;
+        let max_quote_qty = max_quote_qty as Quantity;
         let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.lock()?;
+        if !post_only && market_state.resumption_ticket != [0; 32] {
+            log_error!("A taker order is pending resumption");
+            return err!(ErrorCode::ResumptionPending);
+        }
         let side = Side::from_u8(side).ok_or(ErrorCode::FailedToDeserialize)?;
         let self_trade_behavior = SelfTradeBehavior::from_u8(self_trade_behavior)
             .ok_or(ErrorCode::FailedToDeserialize)?;
-        let limit_price = round_price(market_state.tick_size, limit_price, side);
+        let limit_price = market_state.round_price(limit_price, side)?;
         let callback_info_len = market_state.callback_info_len as usize;
 
-        msg!("New Order: Creating order book");
-        sol_log_compute_units();
-        let mut order_book = OrderBookState::new(
-            &ctx.accounts.bids,
-            &ctx.accounts.asks,
-            market_state.callback_info_len as usize,
-            market_state.callback_id_len as usize,
-        )?;
-        sol_log_compute_units();
+        log_debug!("New Order: Creating order book");
+        log_compute_units!();
+        let mut order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
+        log_compute_units!();
 
         if callback_info.len() != market_state.callback_info_len as usize {
-            msg!("Invalid callback information");
+            log_error!("Invalid callback information");
             return Err(Error::from(ProgramError::InvalidArgument).with_source(source!()));
         }
 
-        msg!("New Order: Creating event queue");
-        sol_log_compute_units();
-        let header = {
-            let mut event_queue_data: &[u8] =
-                &ctx.accounts.event_queue.data.borrow()[0..EVENT_QUEUE_HEADER_LEN];
-            EventQueueHeader::deserialize(&mut event_queue_data)
-                .unwrap()
-                .check()?
-        };
+        log_debug!("New Order: Creating event queue");
+        log_compute_units!();
+        let header = EventQueueHeader::load(&ctx.accounts.event_queue.data.borrow())?.check()?;
+        header.check_market(&ctx.accounts.market.key())?;
         let mut event_queue =
             EventQueue::new_safe(header, &ctx.accounts.event_queue, callback_info_len)?;
-        sol_log_compute_units();
+        event_queue.set_clock(&Clock::get()?);
+        event_queue.overflow_policy = market_state.get_event_queue_overflow_policy()?;
+        log_compute_units!();
 
-        msg!("New Order: Creating new order");
-        sol_log_compute_units();
-        let order_summary = order_book.new_order(
+        log_debug!("New Order: Creating new order");
+        log_compute_units!();
+        let count_before = event_queue.header.count;
+        let trade_history_account = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.trade_history,
+            ErrorCode::WrongTradeHistoryAccount,
+        )?;
+        let mut trade_history = trade_history_account.map(TradeHistory::get).transpose()?;
+        let candles_account = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.candles,
+            ErrorCode::WrongCandlesAccount,
+        )?;
+        let mut candles = candles_account.map(Candles::get).transpose()?;
+        let order_result: OrderResult = order_book.new_order_with_fill_levels(
             NewOrderParams {
                 max_base_qty,
                 max_quote_qty,
                 limit_price,
                 side,
                 match_limit,
-                callback_info,
+                callback_info: callback_info.clone(),
                 post_only,
                 post_allowed,
-                self_trade_behavior,
+                self_trade_behavior: self_trade_behavior.clone(),
+                self_trade_id_len,
+                max_avg_price,
             },
             &mut event_queue,
-            market_state.min_base_order_size,
+            market_state,
+            trade_history.as_deref_mut().map(|t| &mut t.prices),
+            candles.as_deref_mut(),
         )?;
-        sol_log_compute_units();
-        msg!("Order summary : {:?}", order_summary);
-        event_queue.write_to_register(order_summary);
+        let order_summary = &order_result.summary;
+        log_compute_units!();
+        log_debug!("Order summary : {:?}", order_summary);
+
+        // An order which ran into its match limit can be resumed through the optional ticket account, unless its
+        // average price is bounded, since the bound covers the whole order
+        let ticket_account = ctx.remaining_accounts.iter().find(|a| {
+            let key = a.key.to_bytes();
+            key != market_state.trade_history
+                && key != market_state.candles
+                && key != market_state.fee_vault
+        });
+        if let Some(ticket_account) = ticket_account {
+            let base_qty_remaining = max_base_qty - order_summary.total_base_qty;
+            let quote_qty_remaining = max_quote_qty - order_summary.total_quote_qty;
+            if !post_only
+                && max_avg_price.is_none()
+                && order_summary.posted_order_id.is_none()
+                && order_book.can_match(
+                    side,
+                    limit_price,
+                    base_qty_remaining,
+                    quote_qty_remaining,
+                    market_state.base_lot_size,
+                )
+            {
+                let mut ticket = Account::<ResumptionTicket>::try_from_unchecked(ticket_account)?;
+                if ticket.active {
+                    return err!(ErrorCode::InvalidResumptionTicket);
+                }
+                *ticket = ResumptionTicket {
+                    active: true,
+                    market: ctx.accounts.market.key(),
+                    side: side as u8,
+                    limit_price,
+                    max_base_qty: base_qty_remaining,
+                    max_quote_qty: quote_qty_remaining,
+                    post_allowed,
+                    self_trade_behavior: self_trade_behavior as u8,
+                    self_trade_id_len,
+                    callback_info,
+                };
+                ticket.exit(ctx.program_id)?;
+                market_state.resumption_ticket = ticket_account.key.to_bytes();
+            }
+        }
+        set_return_data(&order_result.try_to_vec()?);
+        event_queue.write_to_register(order_result.summary);
+        #[cfg(feature = "emit-events")]
+        emit_queue_events(ctx.accounts.market.key(), &event_queue);
+        emit_watermark_crossing(
+            ctx.accounts.market.key(),
+            market_state,
+            count_before,
+            &event_queue,
+        );
 
-        let mut event_queue_header_data: &mut [u8] =
-            &mut ctx.accounts.event_queue.data.borrow_mut();
-        event_queue
-            .header
-            .serialize(&mut event_queue_header_data)
-            .unwrap();
-        msg!("Committing changes");
-        sol_log_compute_units();
+        event_queue.write_header();
+        log_debug!("Committing changes");
+        log_compute_units!();
         order_book.commit_changes();
-        sol_log_compute_units();
-
-        // Verify that fees were transfered. Fees are expected to be transfered by the caller
-        // program in order to reduce the CPI call stack depth.
-        if ctx.accounts.market.to_account_info().lamports() - market_state.initial_lamports
-            < market_state
-                .fee_budget
-                .checked_add(market_state.cranker_reward)
-                .unwrap()
+        log_compute_units!();
+
+        collect_order_fee(
+            &ctx.accounts.market.to_account_info(),
+            ctx.remaining_accounts,
+            market_state,
+            event_queue.events_pushed(),
+        )?;
+
+        market_state.unlock();
+
+        Ok(())
+    }
+
+    /// Resumes matching the taker order held by the market's pending [`ResumptionTicket`].
+    ///
+    /// The ticket is released once the order no longer matches against the book, in which case what
+    /// remains of it is posted if it was allowed to. The [`OrderSummary`] of this step is written to the event
+    /// queue's register, and its [`OrderResult`] to the return data.
+    pub fn continue_order(ctx: Context<ContinueOrder>, match_limit: u64) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.lock()?;
+        check_account_key(
+            &ctx.accounts.authority,
+            &market_state.caller_authority,
+            ErrorCode::WrongCallerAuthority,
+        )?;
+        check_account_key(
+            &ctx.accounts.event_queue,
+            &market_state.event_queue,
+            ErrorCode::WrongEventQueueAccount,
+        )?;
+        let ticket = &mut ctx.accounts.ticket;
+        if !ticket.active
+            || ticket.key().to_bytes() != market_state.resumption_ticket
+            || ticket.market != ctx.accounts.market.key()
+        {
+            return err!(ErrorCode::InvalidResumptionTicket);
+        }
+        let side = Side::from_u8(ticket.side).ok_or(ErrorCode::FailedToDeserialize)?;
+        let self_trade_behavior = SelfTradeBehavior::from_u8(ticket.self_trade_behavior)
+            .ok_or(ErrorCode::FailedToDeserialize)?;
+
+        let mut order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
+        let header = EventQueueHeader::load(&ctx.accounts.event_queue.data.borrow())?.check()?;
+        header.check_market(&ctx.accounts.market.key())?;
+        let mut event_queue = EventQueue::new_safe(
+            header,
+            &ctx.accounts.event_queue,
+            market_state.callback_info_len as usize,
+        )?;
+        event_queue.set_clock(&Clock::get()?);
+        event_queue.overflow_policy = market_state.get_event_queue_overflow_policy()?;
+
+        let count_before = event_queue.header.count;
+        let trade_history_account = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.trade_history,
+            ErrorCode::WrongTradeHistoryAccount,
+        )?;
+        let mut trade_history = trade_history_account.map(TradeHistory::get).transpose()?;
+        let candles_account = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.candles,
+            ErrorCode::WrongCandlesAccount,
+        )?;
+        let mut candles = candles_account.map(Candles::get).transpose()?;
+        let order_result: OrderResult = order_book.new_order_with_fill_levels(
+            NewOrderParams {
+                max_base_qty: ticket.max_base_qty,
+                max_quote_qty: ticket.max_quote_qty,
+                limit_price: ticket.limit_price,
+                side,
+                match_limit,
+                callback_info: ticket.callback_info.clone(),
+                post_only: false,
+                post_allowed: ticket.post_allowed,
+                self_trade_behavior,
+                self_trade_id_len: ticket.self_trade_id_len,
+                max_avg_price: None,
+            },
+            &mut event_queue,
+            market_state,
+            trade_history.as_deref_mut().map(|t| &mut t.prices),
+            candles.as_deref_mut(),
+        )?;
+        let order_summary = &order_result.summary;
+        log_debug!("Order summary : {:?}", order_summary);
+
+        ticket.max_base_qty -= order_summary.total_base_qty;
+        ticket.max_quote_qty -= order_summary.total_quote_qty;
+        if order_summary.posted_order_id.is_some()
+            || !order_book.can_match(
+                side,
+                ticket.limit_price,
+                ticket.max_base_qty,
+                ticket.max_quote_qty,
+                market_state.base_lot_size,
+            )
         {
-            msg!("Fees were not correctly payed during caller runtime.");
-            return err!(ErrorCode::FeeNotPayed);
+            ticket.active = false;
+            market_state.resumption_ticket = [0; 32];
         }
-        market_state.fee_budget =
-            ctx.accounts.market.to_account_info().lamports() - market_state.initial_lamports;
-        order_book.release(&ctx.accounts.bids, &ctx.accounts.asks);
+
+        set_return_data(&order_result.try_to_vec()?);
+        event_queue.write_to_register(order_result.summary);
+        #[cfg(feature = "emit-events")]
+        emit_queue_events(ctx.accounts.market.key(), &event_queue);
+        emit_watermark_crossing(
+            ctx.accounts.market.key(),
+            market_state,
+            count_before,
+            &event_queue,
+        );
+        event_queue.write_header();
+        order_book.commit_changes();
+
+        collect_order_fee(
+            &ctx.accounts.market.to_account_info(),
+            ctx.remaining_accounts,
+            market_state,
+            event_queue.events_pushed(),
+        )?;
+
+        market_state.unlock();
 
         Ok(())
     }
 
-    pub fn cancel_order(ctx: Context<CancelOrder>, order_id: u128) -> Result<()> {
+    /// Cancels an order. The [`OrderSummary`] of what was left of it in the orderbook is written to the event
+    /// queue's register, and to the return data.
+    ///
+    /// When `expected_callback_id` is set, the order is only cancelled if its callback id matches it, so that
+    /// caller programs can make sure that users only cancel their own orders.
+    pub fn cancel_order(
+        ctx: Context<CancelOrder>,
+        order_id: u128,
+        expected_callback_id: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let order_id = OrderId(order_id);
         let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.lock()?;
         let callback_info_len = market_state.callback_info_len as usize;
 
-        let mut order_book = OrderBookState::new(
-            &ctx.accounts.bids,
-            &ctx.accounts.asks,
-            market_state.callback_info_len as usize,
-            market_state.callback_id_len as usize,
-        )?;
+        let mut order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
 
-        let header = {
-            let mut event_queue_data: &[u8] =
-                &ctx.accounts.event_queue.data.borrow()[0..EVENT_QUEUE_HEADER_LEN];
-            EventQueueHeader::deserialize(&mut event_queue_data).unwrap()
-        };
+        let header = EventQueueHeader::load(&ctx.accounts.event_queue.data.borrow())?;
+        header.check_market(&ctx.accounts.market.key())?;
         let event_queue =
             EventQueue::new_safe(header, &ctx.accounts.event_queue, callback_info_len)?;
 
-        let slab = order_book.get_tree(get_side_from_order_id(order_id));
-        let node = slab
-            .remove_by_key(order_id)
-            .ok_or(ErrorCode::OrderNotFound)?;
-        let leaf_node = node.as_leaf().unwrap();
-        let total_base_qty = leaf_node.base_quantity;
-        let total_quote_qty = fp32_mul(leaf_node.base_quantity, leaf_node.price());
+        let (leaf_node, _) = order_book.cancel_order(order_id, expected_callback_id.as_deref())?;
+        let total_base_qty = lots_to_base_qty(leaf_node.base_quantity, market_state.base_lot_size);
+        let total_quote_qty =
+            fp32_mul_floor(total_base_qty, leaf_node.price()).map_err(ErrorCode::from)?;
 
         let order_summary = OrderSummary {
             posted_order_id: None,
             total_base_qty,
             total_quote_qty,
+            total_fee_qty: 0,
             total_base_qty_posted: 0,
+            avg_fill_price_fp32: 0,
+            worst_fill_price_fp32: 0,
         };
 
+        set_return_data(&order_summary.try_to_vec()?);
         event_queue.write_to_register(order_summary);
 
         order_book.commit_changes();
-        order_book.release(&ctx.accounts.bids, &ctx.accounts.asks);
+
+        market_state.unlock();
 
         Ok(())
     }
 
+    /// Shrinks a resting order to `new_base_qty` while keeping its priority in the book, and pushes a partial out
+    /// event for the released base quantity. The [`OrderSummary`] of what was released is written to the event
+    /// queue's register, and to the return data, in the same way as for a cancel.
+    ///
+    /// When `expected_callback_id` is set, the order is only reduced if its callback id matches it.
+    pub fn reduce_order(
+        ctx: Context<ReduceOrder>,
+        order_id: u128,
+        new_base_qty: u64,
+        expected_callback_id: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let order_id = OrderId(order_id);
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.lock()?;
+        check_account_key(
+            &ctx.accounts.authority,
+            &market_state.caller_authority,
+            ErrorCode::WrongCallerAuthority,
+        )?;
+        let callback_info_len = market_state.callback_info_len as usize;
+
+        let mut order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
+
+        let header = EventQueueHeader::load(&ctx.accounts.event_queue.data.borrow())?.check()?;
+        header.check_market(&ctx.accounts.market.key())?;
+        let mut event_queue =
+            EventQueue::new_safe(header, &ctx.accounts.event_queue, callback_info_len)?;
+        event_queue.set_clock(&Clock::get()?);
+        event_queue.overflow_policy = market_state.get_event_queue_overflow_policy()?;
+        let count_before = event_queue.header.count;
+
+        let total_base_qty = order_book.reduce_order(
+            order_id,
+            new_base_qty as Quantity,
+            expected_callback_id.as_deref(),
+            &mut event_queue,
+            market_state,
+        )?;
+        let total_quote_qty =
+            fp32_mul_floor(total_base_qty, order_id.price()).map_err(ErrorCode::from)?;
+
+        let order_summary = OrderSummary {
+            posted_order_id: None,
+            total_base_qty,
+            total_quote_qty,
+            total_fee_qty: 0,
+            total_base_qty_posted: 0,
+            avg_fill_price_fp32: 0,
+            worst_fill_price_fp32: 0,
+        };
+
+        set_return_data(&order_summary.try_to_vec()?);
+        event_queue.write_to_register(order_summary);
+        #[cfg(feature = "emit-events")]
+        emit_queue_events(ctx.accounts.market.key(), &event_queue);
+        emit_watermark_crossing(
+            ctx.accounts.market.key(),
+            market_state,
+            count_before,
+            &event_queue,
+        );
+
+        event_queue.write_header();
+        order_book.commit_changes();
+
+        market_state.unlock();
+
+        Ok(())
+    }
+
+    /// Fails unless the best price (FP32) on the given side of the book is at least as good as `limit_price` for
+    /// a taker: a best ask at or below it, or a best bid at or above it. An empty side always fails.
+    ///
+    /// This instruction doesn't modify any account, so that it can be composed with a swap in order to guard it
+    /// against slippage atomically.
+    pub fn assert_best_price(
+        ctx: Context<AssertBestPrice>,
+        side: Side,
+        limit_price: u64,
+    ) -> Result<()> {
+        let market_state = ctx.accounts.market.load()?;
+        let order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, &market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
+        let best_price = order_book.find_best_price(side);
+
+        let within_bound = match (side, best_price) {
+            (Side::Bid, Some(price)) => price >= limit_price,
+            (Side::Ask, Some(price)) => price <= limit_price,
+            (_, None) => false,
+        };
+        if !within_bound {
+            log_error!(
+                "The best {:?} price {:?} doesn't satisfy the bound {}",
+                side,
+                best_price,
+                limit_price
+            );
+            return err!(ErrorCode::BestPriceOutOfBounds);
+        }
+        Ok(())
+    }
+
+    /// Writes the top `depth` price levels of both sides of the book into the return data, as a serialized
+    /// [`L2Snapshot`]. The depth is capped to [`MAX_L2_SNAPSHOT_DEPTH`].
+    ///
+    /// This instruction doesn't modify any account and is meant to be run through `simulateTransaction`, so that
+    /// clients can read the book without parsing the slabs themselves.
+    pub fn get_l2_snapshot(ctx: Context<GetL2Snapshot>, depth: u64) -> Result<()> {
+        let market_state = ctx.accounts.market.load()?;
+        let order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, &market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
+        let depth = (depth as usize).min(MAX_L2_SNAPSHOT_DEPTH);
+        let snapshot = L2Snapshot {
+            bids: order_book.price_levels(Side::Bid, depth, market_state.base_lot_size),
+            asks: order_book.price_levels(Side::Ask, depth, market_state.base_lot_size),
+        };
+
+        set_return_data(&snapshot.try_to_vec()?);
+        Ok(())
+    }
+
+    /// Writes the indicative opening auction of the book into the return data, as a serialized
+    /// `Option<Auction>`: the price at which the crossed orders would trade if the market opened now, and the
+    /// base quantity which would trade, see [`OrderBookState::auction`].
+    ///
+    /// Like [`get_l2_snapshot`], this instruction doesn't modify any account and is meant to be run through
+    /// `simulateTransaction`, typically while the market is pre-open.
+    pub fn get_indicative_auction(ctx: Context<GetIndicativeAuction>) -> Result<()> {
+        let market_state = ctx.accounts.market.load()?;
+        let order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, &market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
+        let auction: Option<Auction> =
+            order_book.auction(market_state.last_trade_price, market_state.base_lot_size);
+
+        set_return_data(&auction.try_to_vec()?);
+        Ok(())
+    }
+
+    /// Pops up to `number_of_entries_to_consume` events off the queue.
+    ///
+    /// The authority has to be the caller authority, unless the market's crank is permissionless, see
+    /// [`set_crank_mode`].
+    ///
+    /// When `min_remaining_compute_units` is non-zero, popping also stops as soon as the
+    /// remaining compute budget falls under it. The number of events actually consumed is
+    /// written to the return data as a little-endian u64.
     pub fn consume_events(
         ctx: Context<ConsumeEvents>,
         number_of_entries_to_consume: u64,
+        min_remaining_compute_units: u64,
     ) -> Result<()> {
         let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.lock()?;
+        if market_state.permissionless_crank == 0 {
+            check_account_key(
+                &ctx.accounts.authority,
+                &market_state.caller_authority,
+                ErrorCode::WrongCallerAuthority,
+            )?;
+        }
 
-        let header = {
-            let mut event_queue_data: &[u8] =
-                &ctx.accounts.event_queue.data.borrow()[0..EVENT_QUEUE_HEADER_LEN];
-            EventQueueHeader::deserialize(&mut event_queue_data).unwrap()
-        };
+        let header = EventQueueHeader::load(&ctx.accounts.event_queue.data.borrow())?;
+        header.check_market(&ctx.accounts.market.key())?;
         let mut event_queue = EventQueue::new_safe(
             header,
             &ctx.accounts.event_queue,
             market_state.callback_info_len as usize,
         )?;
 
+        if event_queue.header.count == 0 {
+            log_error!("The event queue is empty");
+            return err!(ErrorCode::NoOperations);
+        }
+
+        // Pop Events
+        let count_before = event_queue.header.count;
+        let capped_number_of_entries_to_consume =
+            std::cmp::min(count_before, number_of_entries_to_consume);
+        let number_of_entries_consumed = if min_remaining_compute_units == 0 {
+            event_queue.pop_n(capped_number_of_entries_to_consume);
+            capped_number_of_entries_to_consume
+        } else {
+            let mut consumed = 0;
+            while consumed < capped_number_of_entries_to_consume
+                && remaining_compute_units() >= min_remaining_compute_units
+            {
+                event_queue.pop_n(1);
+                consumed += 1;
+            }
+            consumed
+        };
+        emit_watermark_crossing(
+            ctx.accounts.market.key(),
+            market_state,
+            count_before,
+            &event_queue,
+        );
+        event_queue.write_header();
+
         // Reward payout
-        let capped_number_of_entries_consumed =
-            std::cmp::min(event_queue.header.count, number_of_entries_to_consume);
-        let reward = (market_state.fee_budget * capped_number_of_entries_consumed)
-            .checked_div(event_queue.header.count)
-            .ok_or(ErrorCode::NoOperations)
-            .unwrap();
-        market_state.fee_budget -= reward;
-        let market_account = ctx.accounts.market.to_account_info();
-        **market_account.try_borrow_mut_lamports()? -= reward;
+        let reward = market_state.take_crank_reward(number_of_entries_consumed);
+        let reward_source = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.fee_vault,
+            ErrorCode::WrongFeeVaultAccount,
+        )?
+        .cloned()
+        .unwrap_or_else(|| ctx.accounts.market.to_account_info());
+        **reward_source.try_borrow_mut_lamports()? -= reward;
         let reward_target_account = ctx.accounts.reward_target.to_account_info();
         **reward_target_account.try_borrow_mut_lamports()? += reward;
 
-        // Pop Events
-        event_queue.pop_n(number_of_entries_to_consume);
-        let mut event_queue_data: &mut [u8] = &mut ctx.accounts.event_queue.data.borrow_mut();
-        event_queue.header.serialize(&mut event_queue_data).unwrap();
-
-        msg!(
+        log_debug!(
             "Number of events consumed: {:?}",
-            capped_number_of_entries_consumed
+            number_of_entries_consumed
         );
+        set_return_data(&number_of_entries_consumed.to_le_bytes());
+
+        market_state.unlock();
 
         Ok(())
     }
 
     pub fn close_market(ctx: Context<CloseMarket>) -> Result<()> {
         let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
 
-        // Check if there are still orders in the book
-        let orderbook_state = OrderBookState::new(
-            &ctx.accounts.bids,
-            &ctx.accounts.asks,
-            market_state.callback_info_len as usize,
-            market_state.callback_id_len as usize,
-        )
-        .unwrap();
-        if !orderbook_state.is_empty() {
-            msg!("The orderbook must be empty");
-            return err!(ErrorCode::MarketStillActive);
+        // Check if there are still orders in the book. Sides which were already closed through
+        // close_book_side are skipped, and any account can be passed in their place.
+        let bids_open = market_state.bids != [0; 32];
+        let asks_open = market_state.asks != [0; 32];
+        if bids_open {
+            check_book_side_empty(
+                &ctx.accounts.bids,
+                &ctx.accounts.market.key(),
+                market_state,
+                Side::Bid,
+            )?;
+        }
+        if asks_open {
+            check_book_side_empty(
+                &ctx.accounts.asks,
+                &ctx.accounts.market.key(),
+                market_state,
+                Side::Ask,
+            )?;
         }
 
-        // Check if all events have been processed
-        let header = {
-            let mut event_queue_data: &[u8] =
-                &ctx.accounts.event_queue.data.borrow()[0..EVENT_QUEUE_HEADER_LEN];
-            EventQueueHeader::deserialize(&mut event_queue_data).unwrap()
-        };
-        if header.count != 0 {
-            msg!("The event queue needs to be empty");
-            return err!(ErrorCode::MarketStillActive);
+        // Check if all events have been processed, unless the event queue was already closed through
+        // close_event_queue.
+        let event_queue_open = market_state.event_queue != [0; 32];
+        if event_queue_open {
+            check_event_queue_empty(
+                &ctx.accounts.event_queue,
+                &ctx.accounts.market.key(),
+                market_state,
+            )?;
         }
 
         market_state.tag = AccountTag::Uninitialized as u64;
@@ -287,39 +748,848 @@ pub mod anchor_agnostic_orderbook {
         let lamports_target_account = ctx.accounts.lamports_target_account.to_account_info();
 
         let mut market_lamports = market.try_borrow_mut_lamports()?;
-        let mut event_queue_lamports = event_queue.try_borrow_mut_lamports()?;
-        let mut bids_lamports = bids.try_borrow_mut_lamports()?;
-        let mut asks_lamports = asks.try_borrow_mut_lamports()?;
         let mut target_lamports = lamports_target_account.try_borrow_mut_lamports()?;
 
-        **target_lamports +=
-            **market_lamports + **bids_lamports + **asks_lamports + **event_queue_lamports;
-
+        **target_lamports += **market_lamports;
         **market_lamports = 0;
-        **bids_lamports = 0;
-        **asks_lamports = 0;
+
+        for (account, open) in [
+            (&event_queue, event_queue_open),
+            (&bids, bids_open),
+            (&asks, asks_open),
+        ] {
+            if open {
+                let mut account_lamports = account.try_borrow_mut_lamports()?;
+                **target_lamports += **account_lamports;
+                **account_lamports = 0;
+            }
+        }
+
+        let fee_vault = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.fee_vault,
+            ErrorCode::WrongFeeVaultAccount,
+        )?;
+        if let Some(fee_vault) = fee_vault {
+            let mut fee_vault_lamports = fee_vault.try_borrow_mut_lamports()?;
+            **target_lamports += **fee_vault_lamports;
+            **fee_vault_lamports = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Reclaims the bids or asks account of a market once that side of the book is empty, ahead of
+    /// [`close_market`]. This is meant for the last stages of a wind-down, such as a one-sided auction
+    /// approaching settlement: the market stops accepting the instructions which need both sides of the book.
+    pub fn close_book_side(ctx: Context<CloseBookSide>, side: Side) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        check_book_side_empty(
+            &ctx.accounts.book_side,
+            &ctx.accounts.market.key(),
+            market_state,
+            side,
+        )?;
+
+        match side {
+            Side::Bid => market_state.bids = [0; 32],
+            Side::Ask => market_state.asks = [0; 32],
+        }
+
+        let book_side = ctx.accounts.book_side.to_account_info();
+        let lamports_target_account = ctx.accounts.lamports_target_account.to_account_info();
+        let mut book_side_lamports = book_side.try_borrow_mut_lamports()?;
+        let mut target_lamports = lamports_target_account.try_borrow_mut_lamports()?;
+        **target_lamports += **book_side_lamports;
+        **book_side_lamports = 0;
+        book_side.try_borrow_mut_data()?.fill(0);
+
+        Ok(())
+    }
+
+    /// Reclaims the event queue account of a market once both sides of the book were closed through
+    /// [`close_book_side`] and all events have been consumed, ahead of [`close_market`].
+    pub fn close_event_queue(ctx: Context<CloseEventQueue>) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        if market_state.bids != [0; 32] || market_state.asks != [0; 32] {
+            log_error!("Both sides of the book need to be closed first");
+            return err!(ErrorCode::MarketStillActive);
+        }
+        check_event_queue_empty(
+            &ctx.accounts.event_queue,
+            &ctx.accounts.market.key(),
+            market_state,
+        )?;
+
+        market_state.event_queue = [0; 32];
+
+        let event_queue = ctx.accounts.event_queue.to_account_info();
+        let lamports_target_account = ctx.accounts.lamports_target_account.to_account_info();
+        let mut event_queue_lamports = event_queue.try_borrow_mut_lamports()?;
+        let mut target_lamports = lamports_target_account.try_borrow_mut_lamports()?;
+        **target_lamports += **event_queue_lamports;
         **event_queue_lamports = 0;
+        event_queue.try_borrow_mut_data()?.fill(0);
 
-        orderbook_state.release(&ctx.accounts.bids, &ctx.accounts.asks);
+        Ok(())
+    }
+
+    pub fn set_event_queue_watermarks(
+        ctx: Context<SetEventQueueWatermarks>,
+        high_watermark_bps: u64,
+        low_watermark_bps: u64,
+    ) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+
+        if high_watermark_bps > MAX_BPS
+            || low_watermark_bps > MAX_BPS
+            || (high_watermark_bps != 0 && low_watermark_bps > high_watermark_bps)
+        {
+            log_error!("Watermarks must satisfy low <= high <= {}", MAX_BPS);
+            return err!(ErrorCode::InvalidWatermarks);
+        }
+
+        market_state.event_queue_high_watermark_bps = high_watermark_bps;
+        market_state.event_queue_low_watermark_bps = low_watermark_bps;
+
+        Ok(())
+    }
+
+    /// Selects whether `consume_events` can be called by anyone collecting the cranker reward, or only by the
+    /// caller authority.
+    pub fn set_crank_mode(ctx: Context<SetCrankMode>, permissionless: bool) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+
+        market_state.permissionless_crank = permissionless as u8;
+
+        Ok(())
+    }
+
+    /// Moves the market to another [`TradingSession`]. Orders accumulate without matching during a pre-open
+    /// session, and only cancels are allowed while the market is closed, which also serves to pause it.
+    pub fn set_trading_session(
+        ctx: Context<SetTradingSession>,
+        session: TradingSession,
+    ) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+
+        market_state.set_trading_session(session)
+    }
+
+    /// Ends the market's pre-open session with an opening auction, which matches the crossed orders at a single
+    /// price, see [`OrderBookState::uncross`], and then opens the market. The [`Auction`], if the book was
+    /// crossed, is written to the return data.
+    ///
+    /// At most `match_limit` pairs of orders are matched by each instruction. When the book still crosses once the
+    /// limit is reached, the market stays pre-open and the instruction has to be sent again, which resumes the
+    /// auction at the same price and writes the base quantity traded by that step to the return data.
+    pub fn open_session(ctx: Context<OpenSession>, match_limit: u64) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.lock()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        if market_state.get_trading_session()? != TradingSession::PreOpen {
+            log_error!("Only pre-open markets are opened with an auction");
+            return err!(ErrorCode::InvalidSessionTransition);
+        }
+
+        let mut order_book =
+            OrderBookState::load(&ctx.accounts.bids, &ctx.accounts.asks, market_state)?;
+        order_book.check_market(&ctx.accounts.market.key())?;
+        let header = EventQueueHeader::load(&ctx.accounts.event_queue.data.borrow())?.check()?;
+        header.check_market(&ctx.accounts.market.key())?;
+        let mut event_queue = EventQueue::new_safe(
+            header,
+            &ctx.accounts.event_queue,
+            market_state.callback_info_len as usize,
+        )?;
+        event_queue.set_clock(&Clock::get()?);
+        event_queue.overflow_policy = market_state.get_event_queue_overflow_policy()?;
+
+        let count_before = event_queue.header.count;
+        let trade_history_account = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.trade_history,
+            ErrorCode::WrongTradeHistoryAccount,
+        )?;
+        let mut trade_history = trade_history_account.map(TradeHistory::get).transpose()?;
+        let candles_account = find_optional_account(
+            ctx.remaining_accounts,
+            &market_state.candles,
+            ErrorCode::WrongCandlesAccount,
+        )?;
+        let mut candles = candles_account.map(Candles::get).transpose()?;
+        let auction: Option<Auction> = order_book.uncross(
+            &mut event_queue,
+            market_state,
+            trade_history.as_deref_mut().map(|t| &mut t.prices),
+            candles.as_deref_mut(),
+            match_limit,
+        )?;
+        log_debug!("Opening auction : {:?}", auction);
+        if market_state.auction_price == 0 {
+            market_state.set_trading_session(TradingSession::Open)?;
+        }
+
+        set_return_data(&auction.try_to_vec()?);
+        #[cfg(feature = "emit-events")]
+        emit_queue_events(ctx.accounts.market.key(), &event_queue);
+        emit_watermark_crossing(
+            ctx.accounts.market.key(),
+            market_state,
+            count_before,
+            &event_queue,
+        );
+        event_queue.write_header();
+        order_book.commit_changes();
+
+        collect_order_fee(
+            &ctx.accounts.market.to_account_info(),
+            ctx.remaining_accounts,
+            market_state,
+            event_queue.events_pushed(),
+        )?;
+
+        market_state.unlock();
+
+        Ok(())
+    }
+
+    /// Sets the keys which can co-sign the market's administrative instructions, and how many of them have to,
+    /// see [`MarketState::check_admin_authority`]. A threshold of 0 leaves them to the caller authority alone.
+    pub fn set_authority_signers(
+        ctx: Context<SetAuthoritySigners>,
+        threshold: u64,
+        signers: Vec<Pubkey>,
+    ) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+
+        let distinct = signers
+            .iter()
+            .enumerate()
+            .all(|(i, signer)| !signers[..i].contains(signer) && *signer != Pubkey::default());
+        if signers.len() > MAX_AUTHORITY_SIGNERS
+            || threshold > signers.len() as u64
+            || (threshold == 0 && !signers.is_empty())
+            || !distinct
+        {
+            log_error!(
+                "Expected 0 < threshold <= number of distinct signers <= {}",
+                MAX_AUTHORITY_SIGNERS
+            );
+            return err!(ErrorCode::InvalidAuthorityThreshold);
+        }
+
+        market_state.authority_threshold = threshold;
+        market_state.authority_signers = [[0; 32]; MAX_AUTHORITY_SIGNERS];
+        for (entry, signer) in market_state.authority_signers.iter_mut().zip(&signers) {
+            *entry = signer.to_bytes();
+        }
 
         Ok(())
     }
+
+    /// Creates the market's [`TradeHistory`] account, which then has to be provided to the instructions which
+    /// match orders, after the optional resumption ticket.
+    pub fn create_trade_history(ctx: Context<CreateTradeHistory>) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        if market_state.trade_history != [0; 32] {
+            return err!(ErrorCode::AlreadyInitialized);
+        }
+
+        let mut trade_history = TradeHistory::get_unchecked(&ctx.accounts.trade_history);
+        trade_history.tag = AccountTag::TradeHistory as u64;
+        trade_history.market = ctx.accounts.market.key().to_bytes();
+        market_state.trade_history = ctx.accounts.trade_history.key.to_bytes();
+
+        Ok(())
+    }
+
+    /// Creates the market's [`Candles`] account, which then has to be provided to the instructions which match
+    /// orders, after the optional resumption ticket.
+    pub fn create_candles(ctx: Context<CreateCandles>, slots_per_candle: u64) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        if market_state.candles != [0; 32] {
+            return err!(ErrorCode::AlreadyInitialized);
+        }
+        if slots_per_candle == 0 {
+            return err!(ErrorCode::InvalidCandleInterval);
+        }
+
+        let mut candles = Candles::get_unchecked(&ctx.accounts.candles);
+        candles.tag = AccountTag::Candles as u64;
+        candles.market = ctx.accounts.market.key().to_bytes();
+        candles.slots_per_candle = slots_per_candle;
+        market_state.candles = ctx.accounts.candles.key.to_bytes();
+
+        Ok(())
+    }
+
+    /// Creates the market's [`FeeVault`] account and moves the current fee budget into it. From then on, the
+    /// vault has to be provided to the instructions which match orders or consume events, and fees have to be
+    /// paid through [`deposit_fee`].
+    pub fn create_fee_vault(ctx: Context<CreateFeeVault>) -> Result<()> {
+        let market_state = &mut ctx.accounts.market.load_mut()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        if market_state.fee_vault != [0; 32] {
+            return err!(ErrorCode::AlreadyInitialized);
+        }
+
+        let mut fee_vault = FeeVault::get_unchecked(&ctx.accounts.fee_vault);
+        fee_vault.tag = AccountTag::FeeVault as u64;
+        fee_vault.market = ctx.accounts.market.key().to_bytes();
+        market_state.fee_vault = ctx.accounts.fee_vault.key.to_bytes();
+
+        let market = ctx.accounts.market.to_account_info();
+        **market.try_borrow_mut_lamports()? -= market_state.fee_budget;
+        **ctx.accounts.fee_vault.try_borrow_mut_lamports()? += market_state.fee_budget;
+
+        Ok(())
+    }
+
+    /// Transfers `amount` lamports from the payer to the market's [`FeeVault`], to be charged as fees by the
+    /// subsequent orders.
+    pub fn deposit_fee(ctx: Context<DepositFee>, amount: u64) -> Result<()> {
+        let market_state = ctx.accounts.market.load()?;
+        check_account_key(
+            &ctx.accounts.fee_vault,
+            &market_state.fee_vault,
+            ErrorCode::WrongFeeVaultAccount,
+        )?;
+        invoke(
+            &system_instruction::transfer(
+                ctx.accounts.payer.key,
+                ctx.accounts.fee_vault.key,
+                amount,
+            ),
+            &[
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.fee_vault.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+        FeeVault::get(&ctx.accounts.fee_vault)?.deposit(amount);
+
+        Ok(())
+    }
+
+    pub fn resize_event_queue(ctx: Context<ResizeEventQueue>, new_len: u64) -> Result<()> {
+        let market_state = ctx.accounts.market.load()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        check_account_key(
+            &ctx.accounts.event_queue,
+            &market_state.event_queue,
+            ErrorCode::WrongEventQueueAccount,
+        )?;
+
+        let mut header =
+            EventQueueHeader::load(&ctx.accounts.event_queue.data.borrow())?.check()?;
+        header.check_market(&ctx.accounts.market.key())?;
+
+        let old_len = ctx.accounts.event_queue.data_len();
+        let new_len = new_len as usize;
+        let event_size = Event::compute_slot_size(market_state.callback_info_len as usize);
+        if new_len <= old_len
+            || (new_len - EVENT_QUEUE_HEADER_LEN - REGISTER_SIZE) % event_size != 0
+        {
+            log_error!("The event queue can only grow by a multiple of the event size");
+            return err!(ErrorCode::InvalidEventQueueSize);
+        }
+
+        // Top up the rent exemption of the event queue account
+        let rent_due = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(ctx.accounts.event_queue.lamports());
+        if rent_due > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    ctx.accounts.payer.key,
+                    ctx.accounts.event_queue.key,
+                    rent_due,
+                ),
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    ctx.accounts.event_queue.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+
+        ctx.accounts.event_queue.realloc(new_len, false)?;
+        header.resize(new_len);
+
+        let mut event_queue = EventQueue::new_safe(
+            header,
+            &ctx.accounts.event_queue,
+            market_state.callback_info_len as usize,
+        )?;
+        event_queue.relocate_after_growth(old_len - EVENT_QUEUE_HEADER_LEN - REGISTER_SIZE);
+        event_queue.write_header();
+
+        log_debug!("Event queue resized to {} bytes", new_len);
+
+        Ok(())
+    }
+
+    /// Grows the bids or asks account of a market so that it holds `new_order_capacity` orders.
+    ///
+    /// The account is reallocated in place, and the slab's callback memory is moved past the grown node arena, see
+    /// [`Slab::relocate_after_growth`]. A single instruction can only grow an account by
+    /// [`MAX_PERMITTED_DATA_INCREASE`][anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE] bytes.
+    pub fn resize_book_side(
+        ctx: Context<ResizeBookSide>,
+        side: Side,
+        new_order_capacity: u64,
+    ) -> Result<()> {
+        let market_state = ctx.accounts.market.load()?;
+        market_state.check_admin_authority(&ctx.accounts.authority, ctx.remaining_accounts)?;
+        let (key, account_tag, error) = match side {
+            Side::Bid => (
+                &market_state.bids,
+                AccountTag::Bids,
+                ErrorCode::WrongBidsAccount,
+            ),
+            Side::Ask => (
+                &market_state.asks,
+                AccountTag::Asks,
+                ErrorCode::WrongAsksAccount,
+            ),
+        };
+        check_account_key(&ctx.accounts.book_side, key, error)?;
+
+        let callback_info_len = market_state.callback_info_len as usize;
+        let old_len = ctx.accounts.book_side.data_len();
+        let new_len = Slab::compute_allocation_size(new_order_capacity as usize, callback_info_len);
+        if new_len <= old_len {
+            log_error!("The orderbook account can only grow");
+            return err!(ErrorCode::InvalidOrderbookSize);
+        }
+
+        // Top up the rent exemption of the orderbook account
+        let rent_due = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(ctx.accounts.book_side.lamports());
+        if rent_due > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    ctx.accounts.payer.key,
+                    ctx.accounts.book_side.key,
+                    rent_due,
+                ),
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    ctx.accounts.book_side.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+
+        ctx.accounts.book_side.realloc(new_len, true)?;
+
+        let mut data = ctx.accounts.book_side.try_borrow_mut_data()?;
+        let mut slab = Slab::new(&mut data[..], callback_info_len).map_err(ErrorCode::from)?;
+        slab.check_account_tag(account_tag)?;
+        slab.check_market(&ctx.accounts.market.key())?;
+        slab.relocate_after_growth();
+
+        log_debug!("Orderbook account resized to {} bytes", new_len);
+
+        Ok(())
+    }
+}
+
+/// Initializes the state, event queue and orderbook of a new market.
+///
+/// The `seed` and `bumps` (of the market, event queue, bids and asks) are zeroed unless the market's accounts are
+/// program-derived addresses.
+#[allow(clippy::too_many_arguments)]
+fn initialize_market<'info>(
+    market: &AccountLoader<'info, MarketState>,
+    event_queue: &AccountInfo<'info>,
+    bids: &AccountInfo<'info>,
+    asks: &AccountInfo<'info>,
+    seed: [u8; 32],
+    bumps: [u8; 4],
+    caller_authority: Pubkey,
+    callback_info_len: u64,
+    callback_id_len: u64,
+    min_base_order_size: u64,
+    tick_size: u64,
+    base_lot_size: u64,
+    cranker_base_reward: u64,
+    cranker_per_event_reward: u64,
+    order_priority: u8,
+    event_queue_overflow_policy: u8,
+    max_post_only_spread_ticks: u64,
+    disable_self_trade_detection: bool,
+    price_rounding_policy: u8,
+    max_match_iterations: u64,
+    coalesce_fills: bool,
+    order_capacity: u64,
+    event_capacity: u64,
+) -> Result<()> {
+    if base_lot_size == 0 {
+        return err!(ErrorCode::InvalidBaseLotSize);
+    }
+    MarketState::check_parameters(
+        callback_info_len,
+        callback_id_len,
+        min_base_order_size,
+        tick_size,
+    )?;
+    EventQueue::check_capacity(event_queue, event_capacity, callback_info_len)?;
+    Slab::check_capacity(bids, AccountTag::Bids, order_capacity, callback_info_len)?;
+    Slab::check_capacity(asks, AccountTag::Asks, order_capacity, callback_info_len)?;
+    let order_priority =
+        OrderPriority::from_u8(order_priority).ok_or(ErrorCode::FailedToDeserialize)?;
+    let event_queue_overflow_policy =
+        EventQueueOverflowPolicy::from_u8(event_queue_overflow_policy)
+            .ok_or(ErrorCode::FailedToDeserialize)?;
+    let price_rounding_policy = PriceRoundingPolicy::from_u8(price_rounding_policy)
+        .ok_or(ErrorCode::FailedToDeserialize)?;
+    let market_state = &mut market.load_init()?;
+    *market_state.deref_mut() = aob::state::MarketState {
+        tag: AccountTag::Market as u64,
+        caller_authority: caller_authority.to_bytes(),
+        event_queue: event_queue.key.to_bytes(),
+        bids: bids.key.to_bytes(),
+        asks: asks.key.to_bytes(),
+        callback_info_len,
+        callback_id_len,
+        fee_budget: 0,
+        initial_lamports: market.to_account_info().lamports(),
+        min_base_order_size,
+        tick_size,
+        base_lot_size,
+        cranker_base_reward,
+        cranker_per_event_reward,
+        event_queue_high_watermark_bps: 0,
+        event_queue_low_watermark_bps: 0,
+        order_priority: order_priority as u64,
+        event_queue_overflow_policy: event_queue_overflow_policy as u64,
+        locked: 0,
+        max_post_only_spread_ticks,
+        last_trade_price: 0,
+        base_volume: 0,
+        quote_volume: 0,
+        net_taker_flow: 0,
+        resumption_ticket: [0; 32],
+        trade_history: [0; 32],
+        candles: [0; 32],
+        fee_vault: [0; 32],
+        authority_threshold: 0,
+        authority_signers: [[0; 32]; MAX_AUTHORITY_SIGNERS],
+        price_cumulative: 0,
+        price_cumulative_timestamp: 0,
+        self_trade_detection_disabled: disable_self_trade_detection as u64,
+        price_rounding_policy: price_rounding_policy as u64,
+        max_match_iterations,
+        coalesce_fills: coalesce_fills as u64,
+        auction_price: 0,
+        seed,
+        market_bump: bumps[0],
+        event_queue_bump: bumps[1],
+        bids_bump: bumps[2],
+        asks_bump: bumps[3],
+        permissionless_crank: 0,
+        trading_session: TradingSession::Open as u8,
+        _padding: [0; 2],
+    };
+
+    EventQueueHeader::initialize(
+        market.key(),
+        callback_info_len as usize,
+        event_queue.data_len(),
+    )
+    .write(&mut event_queue.data.borrow_mut());
+
+    Slab::initialize(bids, asks, market.key(), callback_info_len as usize);
+
+    Ok(())
+}
+
+/// Checks that `account` is the market's bids or asks account, depending on `side`, and that it holds no orders.
+fn check_book_side_empty(
+    account: &AccountInfo,
+    market: &Pubkey,
+    market_state: &MarketState,
+    side: Side,
+) -> Result<()> {
+    let (key, account_tag, error) = match side {
+        Side::Bid => (
+            &market_state.bids,
+            AccountTag::Bids,
+            ErrorCode::WrongBidsAccount,
+        ),
+        Side::Ask => (
+            &market_state.asks,
+            AccountTag::Asks,
+            ErrorCode::WrongAsksAccount,
+        ),
+    };
+    check_account_key(account, key, error)?;
+    let mut data = account.try_borrow_mut_data()?;
+    let slab = Slab::new(&mut data[..], market_state.callback_info_len as usize)
+        .map_err(ErrorCode::from)?;
+    slab.check_account_tag(account_tag)?;
+    slab.check_market(market)?;
+    if slab.root().is_some() {
+        log_error!("The orderbook must be empty");
+        return err!(ErrorCode::MarketStillActive);
+    }
+    Ok(())
+}
+
+/// Checks that `account` is the market's event queue account, and that it holds no events.
+fn check_event_queue_empty(
+    account: &AccountInfo,
+    market: &Pubkey,
+    market_state: &MarketState,
+) -> Result<()> {
+    check_account_key(
+        account,
+        &market_state.event_queue,
+        ErrorCode::WrongEventQueueAccount,
+    )?;
+    let header = EventQueueHeader::get(account)?;
+    header.check_market(market)?;
+    if header.count != 0 {
+        log_error!("The event queue needs to be empty");
+        return err!(ErrorCode::MarketStillActive);
+    }
+    Ok(())
+}
+
+/// Charges the fee for the events pushed by an order to the deposits made to the market's [`FeeVault`].
+///
+/// Markets without a fee vault expect the fee to be transfered to the market account by the caller program
+/// during its runtime, in order to reduce the CPI call stack depth.
+fn collect_order_fee(
+    market: &AccountInfo,
+    remaining_accounts: &[AccountInfo],
+    market_state: &mut MarketState,
+    number_of_events: u64,
+) -> Result<()> {
+    let fee_vault = find_optional_account(
+        remaining_accounts,
+        &market_state.fee_vault,
+        ErrorCode::WrongFeeVaultAccount,
+    )?;
+    if let Some(fee_vault) = fee_vault {
+        let fee = market_state.crank_reward(number_of_events)?;
+        FeeVault::get(fee_vault)?.take_fee(fee)?;
+        market_state.fee_budget = market_state.fee_budget.checked_add(fee).unwrap();
+        return Ok(());
+    }
+    market_state.verify_and_update_fee_budget(market.lamports(), number_of_events)
+}
+
+/// Finds one of the market's optional accounts, such as its [`TradeHistory`] or [`Candles`] account, among the
+/// remaining accounts. A zeroed `key` means that the market doesn't have the account.
+fn find_optional_account<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    key: &[u8; 32],
+    error: ErrorCode,
+) -> Result<Option<&'a AccountInfo<'info>>> {
+    if key == &[0; 32] {
+        return Ok(None);
+    }
+    remaining_accounts
+        .iter()
+        .find(|a| &a.key.to_bytes() == key)
+        .map(Some)
+        .ok_or_else(|| error!(error))
+}
+
+/// Emits an Anchor event for each event pushed to the queue by the current instruction, so that indexers can
+/// follow the trades from the program logs.
+#[cfg(feature = "emit-events")]
+fn emit_queue_events(market: Pubkey, event_queue: &EventQueue<&mut [u8]>) {
+    let count = event_queue.header.count;
+    for index in count.saturating_sub(event_queue.events_pushed())..count {
+        match event_queue.peek_at(index) {
+            Some(Event::Fill {
+                taker_side,
+                maker_order_id,
+                quote_size,
+                base_size,
+                slot,
+                unix_timestamp,
+                maker_callback_info,
+                taker_callback_info,
+            }) => emit!(FillEvent {
+                market,
+                taker_side,
+                maker_order_id: maker_order_id.0,
+                quote_size,
+                base_size,
+                slot,
+                unix_timestamp,
+                maker_callback_info,
+                taker_callback_info,
+            }),
+            Some(Event::Out {
+                side,
+                order_id,
+                base_size,
+                delete,
+                slot,
+                unix_timestamp,
+                callback_info,
+            }) => emit!(OutEvent {
+                market,
+                side,
+                order_id: order_id.0,
+                base_size,
+                delete,
+                slot,
+                unix_timestamp,
+                callback_info,
+            }),
+            None => {}
+        }
+    }
+}
+
+/// Logs and emits an Anchor event when the event queue occupancy crosses one of the market's watermarks.
+fn emit_watermark_crossing(
+    market: Pubkey,
+    market_state: &MarketState,
+    count_before: u64,
+    event_queue: &EventQueue<&mut [u8]>,
+) {
+    let count = event_queue.header.count;
+    let capacity = event_queue.capacity();
+    match market_state.check_watermarks(count_before, count, capacity) {
+        Some(WatermarkCrossing::High) => {
+            log_debug!("Event queue high watermark crossed: {}/{}", count, capacity);
+            emit!(EventQueueHighWatermark {
+                market,
+                count,
+                capacity
+            });
+        }
+        Some(WatermarkCrossing::Low) => {
+            log_debug!("Event queue low watermark crossed: {}/{}", count, capacity);
+            emit!(EventQueueLowWatermark {
+                market,
+                count,
+                capacity
+            });
+        }
+        None => {}
+    }
 }
 
 #[derive(Accounts)]
+#[instruction(
+    caller_authority: Pubkey,
+    callback_info_len: u64,
+    callback_id_len: u64,
+    min_base_order_size: u64,
+    tick_size: u64,
+    base_lot_size: u64,
+    cranker_base_reward: u64,
+    cranker_per_event_reward: u64,
+    order_priority: u8,
+    event_queue_overflow_policy: u8,
+    max_post_only_spread_ticks: u64,
+    disable_self_trade_detection: bool,
+    price_rounding_policy: u8,
+    max_match_iterations: u64,
+    coalesce_fills: bool,
+    order_capacity: u64,
+    event_capacity: u64
+)]
 pub struct CreateMarket<'info> {
-    // TODO PDAs?
-    #[account(init, payer = payer)]
+    #[account(init, payer = payer, space = MarketState::SPACE)]
     pub market: AccountLoader<'info, MarketState>,
     /// CHECK:
-    #[account(init, payer = payer, space = 10240)]
+    #[account(
+        init,
+        payer = payer,
+        space = EventQueue::compute_allocation_size(event_capacity as usize, callback_info_len as usize)
+    )]
     pub event_queue: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = Bids::space(order_capacity as usize, callback_info_len as usize)
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        init,
+        payer = payer,
+        space = Asks::space(order_capacity as usize, callback_info_len as usize)
+    )]
+    pub asks: AccountLoader<'info, Asks>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    seed: [u8; 32],
+    caller_authority: Pubkey,
+    callback_info_len: u64,
+    callback_id_len: u64,
+    min_base_order_size: u64,
+    tick_size: u64,
+    base_lot_size: u64,
+    cranker_base_reward: u64,
+    cranker_per_event_reward: u64,
+    order_priority: u8,
+    event_queue_overflow_policy: u8,
+    max_post_only_spread_ticks: u64,
+    disable_self_trade_detection: bool,
+    price_rounding_policy: u8,
+    max_match_iterations: u64,
+    coalesce_fills: bool,
+    order_capacity: u64,
+    event_capacity: u64
+)]
+pub struct CreateMarketWithSeed<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = MarketState::SPACE,
+        seeds = [MarketState::SEED, caller_authority.as_ref(), seed.as_ref()],
+        bump
+    )]
+    pub market: AccountLoader<'info, MarketState>,
     /// CHECK:
-    #[account(init, payer = payer, space = 10240)]
-    pub bids: AccountInfo<'info>,
-    /// CHECK:
-    #[account(init, payer = payer, space = 10240)]
-    pub asks: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = EventQueue::compute_allocation_size(event_capacity as usize, callback_info_len as usize),
+        seeds = [EventQueue::SEED, market.key().as_ref()],
+        bump
+    )]
+    pub event_queue: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = Bids::space(order_capacity as usize, callback_info_len as usize),
+        seeds = [Bids::SEED, market.key().as_ref()],
+        bump
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        init,
+        payer = payer,
+        space = Asks::space(order_capacity as usize, callback_info_len as usize),
+        seeds = [Asks::SEED, market.key().as_ref()],
+        bump
+    )]
+    pub asks: AccountLoader<'info, Asks>,
     #[account(mut)]
     pub payer: Signer<'info>,
     pub system_program: Program<'info, System>,
@@ -332,14 +1602,41 @@ pub struct NewOrder<'info> {
     /// CHECK:
     #[account(mut)]
     pub event_queue: AccountInfo<'info>,
-    /// CHECK:
+    #[account(
+        mut,
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        mut,
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
     #[account(mut)]
-    pub bids: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ContinueOrder<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
     /// CHECK:
     #[account(mut)]
-    pub asks: AccountInfo<'info>,
+    pub event_queue: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        mut,
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
     #[account(mut)]
     pub authority: Signer<'info>,
+    #[account(mut)]
+    pub ticket: Account<'info, ResumptionTicket>,
 }
 
 #[derive(Accounts)]
@@ -349,16 +1646,80 @@ pub struct CancelOrder<'info> {
     /// CHECK:
     #[account(mut)]
     pub event_queue: AccountInfo<'info>,
-    /// CHECK:
+    #[account(
+        mut,
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        mut,
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
     #[account(mut)]
-    pub bids: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReduceOrder<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
     /// CHECK:
     #[account(mut)]
-    pub asks: AccountInfo<'info>,
+    pub event_queue: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        mut,
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
     #[account(mut)]
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct AssertBestPrice<'info> {
+    pub market: AccountLoader<'info, MarketState>,
+    #[account(
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
+}
+
+#[derive(Accounts)]
+pub struct GetL2Snapshot<'info> {
+    pub market: AccountLoader<'info, MarketState>,
+    #[account(
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
+}
+
+#[derive(Accounts)]
+pub struct GetIndicativeAuction<'info> {
+    pub market: AccountLoader<'info, MarketState>,
+    #[account(
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
+}
+
 #[derive(Accounts)]
 pub struct ConsumeEvents<'info> {
     pub market: AccountLoader<'info, MarketState>,
@@ -375,13 +1736,13 @@ pub struct ConsumeEvents<'info> {
 pub struct CloseMarket<'info> {
     #[account(mut)]
     pub market: AccountLoader<'info, MarketState>,
-    /// CHECK:
+    /// CHECK: checked against the market state, unless the event queue was already closed
     #[account(mut)]
     pub event_queue: AccountInfo<'info>,
-    /// CHECK:
+    /// CHECK: checked against the market state, unless the bids were already closed
     #[account(mut)]
     pub bids: AccountInfo<'info>,
-    /// CHECK:
+    /// CHECK: checked against the market state, unless the asks were already closed
     #[account(mut)]
     pub asks: AccountInfo<'info>,
     #[account(mut)]
@@ -389,3 +1750,169 @@ pub struct CloseMarket<'info> {
     #[account(mut)]
     pub lamports_target_account: Signer<'info>,
 }
+
+#[derive(Accounts)]
+pub struct CloseBookSide<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK: checked against the market state
+    #[account(mut)]
+    pub book_side: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub lamports_target_account: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseEventQueue<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK: checked against the market state
+    #[account(mut)]
+    pub event_queue: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub lamports_target_account: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetEventQueueWatermarks<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCrankMode<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTradingSession<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OpenSession<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK:
+    #[account(mut)]
+    pub event_queue: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = bids.load()?.market == market.key().to_bytes() @ ErrorCode::WrongBidsAccount
+    )]
+    pub bids: AccountLoader<'info, Bids>,
+    #[account(
+        mut,
+        constraint = asks.load()?.market == market.key().to_bytes() @ ErrorCode::WrongAsksAccount
+    )]
+    pub asks: AccountLoader<'info, Asks>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAuthoritySigners<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTradeHistory<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK:
+    #[account(
+        init,
+        payer = payer,
+        space = TRADE_HISTORY_LEN,
+        seeds = [TradeHistory::SEED, market.key().as_ref()],
+        bump
+    )]
+    pub trade_history: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCandles<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK:
+    #[account(
+        init,
+        payer = payer,
+        space = CANDLES_LEN,
+        seeds = [Candles::SEED, market.key().as_ref()],
+        bump
+    )]
+    pub candles: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateFeeVault<'info> {
+    #[account(mut)]
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK:
+    #[account(
+        init,
+        payer = payer,
+        space = FEE_VAULT_LEN,
+        seeds = [FeeVault::SEED, market.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositFee<'info> {
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK: checked against the market state
+    #[account(mut)]
+    pub fee_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResizeEventQueue<'info> {
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK:
+    #[account(mut)]
+    pub event_queue: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResizeBookSide<'info> {
+    pub market: AccountLoader<'info, MarketState>,
+    /// CHECK: checked against the market state
+    #[account(mut)]
+    pub book_side: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
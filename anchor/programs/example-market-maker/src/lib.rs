@@ -0,0 +1,313 @@
+/*!
+An example caller program, which quotes both sides of an AO market on behalf of its users and settles their fills.
+
+It demonstrates the callback information convention which the AO program is designed around: every order carries
+the address of the [`User`] account which placed it, followed by a byte of [`CallbackInfo`] flags. The address
+doubles as the callback id, so that the AO program detects the self trades of a user. Once matched, the orders come
+back through the event queue, where [`settle_events`][example_market_maker::settle_events] reads the owners of both
+sides of each fill, updates their positions and only then pops the settled events through the AO program.
+
+The program's authority over the market is the [`AUTHORITY_SEED`] program-derived address, which has to be set as
+the market's caller authority when the market is created.
+*/
+use std::rc::Rc;
+
+use anchor_agnostic_orderbook::aob::cpi as aob_cpi;
+use anchor_agnostic_orderbook::aob::orderbook::MAX_QUOTE_QTY;
+use anchor_agnostic_orderbook::aob::params::{ConsumeEventsParams, NewOrderParams};
+use anchor_agnostic_orderbook::aob::state::{
+    Event, EventQueue, EventQueueHeader, SelfTradeBehavior, Side,
+};
+use anchor_agnostic_orderbook::program::AnchorAgnosticOrderbook;
+use anchor_lang::prelude::*;
+
+declare_id!("D9kHXWKLES1ZyfGNqU4FeHzmrVzvYYcLZQmnYoiwgGCe");
+
+/// The seed of the program's authority over a market, along with the market's key
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+/// The seed of a user's account, along with the market's key and the user's wallet
+pub const USER_SEED: &[u8] = b"user";
+/// The callback information length with which markets have to be created
+pub const CALLBACK_INFO_LEN: u64 = 33;
+/// The callback id length with which markets have to be created, which covers the owner's address
+pub const CALLBACK_ID_LEN: u64 = 32;
+
+#[program]
+pub mod example_market_maker {
+    use super::*;
+
+    /// Creates the account which holds the positions of a user on a market.
+    pub fn create_user(ctx: Context<CreateUser>) -> Result<()> {
+        let user = &mut ctx.accounts.user;
+        user.market = ctx.accounts.market.key();
+        user.owner = ctx.accounts.owner.key();
+        user.bump = *ctx.bumps.get("user").unwrap();
+        Ok(())
+    }
+
+    /// Posts a bid and an ask of `base_qty` each, on behalf of the user. Both orders are post-only, so that
+    /// quoting never takes liquidity.
+    pub fn place_quotes(
+        ctx: Context<PlaceQuotes>,
+        bid_price: u64,
+        ask_price: u64,
+        base_qty: u64,
+    ) -> Result<()> {
+        let callback_info = CallbackInfo {
+            owner: ctx.accounts.user.key(),
+            flags: CallbackInfo::QUOTE,
+        }
+        .to_bytes();
+        let market = ctx.accounts.market.key();
+        let bump = *ctx.bumps.get("authority").unwrap();
+        let seeds: &[&[u8]] = &[AUTHORITY_SEED, market.as_ref(), &[bump]];
+        for (side, limit_price) in [(Side::Bid, bid_price), (Side::Ask, ask_price)] {
+            let accounts = aob_cpi::NewOrder {
+                market: ctx.accounts.market.to_account_info(),
+                event_queue: ctx.accounts.event_queue.to_account_info(),
+                bids: ctx.accounts.bids.to_account_info(),
+                asks: ctx.accounts.asks.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            };
+            aob_cpi::new_order(
+                CpiContext::new_with_signer(
+                    ctx.accounts.aob_program.to_account_info(),
+                    accounts,
+                    &[seeds],
+                ),
+                NewOrderParams {
+                    max_base_qty: base_qty,
+                    max_quote_qty: MAX_QUOTE_QTY,
+                    limit_price,
+                    side,
+                    match_limit: 0,
+                    callback_info: callback_info.clone(),
+                    post_only: true,
+                    post_allowed: true,
+                    self_trade_behavior: SelfTradeBehavior::AbortTransaction,
+                    self_trade_id_len: None,
+                    max_avg_price: None,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Settles up to `max_events` events, and then pops them off the event queue.
+    ///
+    /// The remaining accounts hold the [`User`] accounts of the owners of the events' orders. Settlement stops at
+    /// the first fill whose owners aren't among them, so that the crank never pops an event which wasn't settled.
+    pub fn settle_events<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleEvents<'info>>,
+        max_events: u64,
+    ) -> Result<()> {
+        let mut users = ctx
+            .remaining_accounts
+            .iter()
+            .map(Account::<User>::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        let market = ctx.accounts.market.key();
+        if users.iter().any(|user| user.market != market) {
+            return err!(ExampleError::WrongMarket);
+        }
+
+        let event_queue_info = ctx.accounts.event_queue.to_account_info();
+        let events = {
+            let header = EventQueueHeader::load(&event_queue_info.data.borrow())?;
+            let event_queue = EventQueue::new(
+                header,
+                Rc::clone(&event_queue_info.data),
+                CALLBACK_INFO_LEN as usize,
+            );
+            event_queue.peek_n(max_events)
+        };
+
+        let mut settled = 0;
+        for event in events {
+            if let Event::Fill {
+                taker_side,
+                quote_size,
+                base_size,
+                maker_callback_info,
+                taker_callback_info,
+                ..
+            } = event
+            {
+                let maker_info = CallbackInfo::from_bytes(&maker_callback_info);
+                let taker_info = CallbackInfo::from_bytes(&taker_callback_info);
+                let (maker, taker) =
+                    match (find_user(&users, maker_info), find_user(&users, taker_info)) {
+                        (Some(maker), Some(taker)) => (maker, taker),
+                        _ => break,
+                    };
+                // A bidding taker buys the base from the maker
+                let (base_delta, quote_delta) = match taker_side {
+                    Side::Bid => (base_size as i64, -(quote_size as i64)),
+                    Side::Ask => (-(base_size as i64), quote_size as i64),
+                };
+                users[taker].base_position += base_delta;
+                users[taker].quote_position += quote_delta;
+                users[maker].base_position -= base_delta;
+                users[maker].quote_position -= quote_delta;
+                if maker_info.unwrap().flags & CallbackInfo::QUOTE != 0 {
+                    users[maker].quote_fills += 1;
+                }
+            }
+            settled += 1;
+        }
+        for user in &users {
+            user.exit(&crate::ID)?;
+        }
+        if settled == 0 {
+            return Ok(());
+        }
+
+        let bump = *ctx.bumps.get("authority").unwrap();
+        let seeds: &[&[u8]] = &[AUTHORITY_SEED, market.as_ref(), &[bump]];
+        let accounts = aob_cpi::ConsumeEvents {
+            market: ctx.accounts.market.to_account_info(),
+            event_queue: event_queue_info,
+            authority: ctx.accounts.authority.to_account_info(),
+            reward_target: ctx.accounts.reward_target.to_account_info(),
+        };
+        let consumed = aob_cpi::consume_events(
+            CpiContext::new_with_signer(
+                ctx.accounts.aob_program.to_account_info(),
+                accounts,
+                &[seeds],
+            ),
+            ConsumeEventsParams {
+                number_of_entries_to_consume: settled,
+                min_remaining_compute_units: 0,
+            },
+        )?;
+        msg!("Settled and consumed {} events", consumed);
+        Ok(())
+    }
+}
+
+/// Finds the user account which owns an order, from the owner's address held in its callback information.
+fn find_user(users: &[Account<User>], callback_info: Option<CallbackInfo>) -> Option<usize> {
+    let owner = callback_info?.owner;
+    users.iter().position(|user| user.key() == owner)
+}
+
+/// The callback information which the program attaches to its orders
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CallbackInfo {
+    /// The address of the [`User`] account which placed the order
+    pub owner: Pubkey,
+    /// A combination of the flags below
+    pub flags: u8,
+}
+
+impl CallbackInfo {
+    /// Set for the orders placed by [`place_quotes`][example_market_maker::place_quotes]
+    pub const QUOTE: u8 = 1;
+
+    #[allow(missing_docs)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.owner.to_bytes().to_vec();
+        bytes.push(self.flags);
+        bytes
+    }
+
+    #[allow(missing_docs)]
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [owner @ .., flags] if owner.len() == 32 => Some(Self {
+                owner: Pubkey::new(owner),
+                flags: *flags,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The positions of a user on a market, as settled from the fills of its orders
+#[account]
+#[derive(Default)]
+pub struct User {
+    #[allow(missing_docs)]
+    pub market: Pubkey,
+    /// The wallet which places the user's orders
+    pub owner: Pubkey,
+    /// The net base quantity bought
+    pub base_position: i64,
+    /// The net quote quantity received
+    pub quote_position: i64,
+    /// The number of fills of the user's quotes
+    pub quote_fills: u64,
+    #[allow(missing_docs)]
+    pub bump: u8,
+}
+
+impl User {
+    /// The size of a user account, Anchor discriminator included
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 8 + 8 + 1;
+}
+
+#[derive(Accounts)]
+pub struct CreateUser<'info> {
+    /// CHECK: only used as a seed
+    pub market: UncheckedAccount<'info>,
+    #[account(
+        init,
+        seeds = [USER_SEED, market.key().as_ref(), owner.key().as_ref()],
+        bump,
+        payer = owner,
+        space = User::SPACE
+    )]
+    pub user: Account<'info, User>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceQuotes<'info> {
+    /// CHECK: checked by the AO program
+    #[account(mut)]
+    pub market: UncheckedAccount<'info>,
+    /// CHECK: checked by the AO program
+    #[account(mut)]
+    pub event_queue: UncheckedAccount<'info>,
+    /// CHECK: checked by the AO program
+    #[account(mut)]
+    pub bids: UncheckedAccount<'info>,
+    /// CHECK: checked by the AO program
+    #[account(mut)]
+    pub asks: UncheckedAccount<'info>,
+    /// CHECK: the program's authority over the market
+    #[account(mut, seeds = [AUTHORITY_SEED, market.key().as_ref()], bump)]
+    pub authority: UncheckedAccount<'info>,
+    #[account(
+        seeds = [USER_SEED, market.key().as_ref(), owner.key().as_ref()],
+        bump = user.bump,
+        has_one = owner
+    )]
+    pub user: Account<'info, User>,
+    pub owner: Signer<'info>,
+    pub aob_program: Program<'info, AnchorAgnosticOrderbook>,
+}
+
+#[derive(Accounts)]
+pub struct SettleEvents<'info> {
+    /// CHECK: checked by the AO program
+    pub market: UncheckedAccount<'info>,
+    /// CHECK: checked by the AO program, which owns it
+    #[account(mut, owner = anchor_agnostic_orderbook::ID)]
+    pub event_queue: UncheckedAccount<'info>,
+    /// CHECK: the program's authority over the market
+    #[account(mut, seeds = [AUTHORITY_SEED, market.key().as_ref()], bump)]
+    pub authority: UncheckedAccount<'info>,
+    /// CHECK: receives the crank rewards
+    pub reward_target: UncheckedAccount<'info>,
+    pub aob_program: Program<'info, AnchorAgnosticOrderbook>,
+}
+
+#[error_code]
+pub enum ExampleError {
+    #[msg("A user account belongs to another market")]
+    WrongMarket,
+}
@@ -22,7 +22,18 @@ describe('anchor-agnostic-orderbook', () => {
             new BN(32),
             new BN(10),
             new BN(1),
-            new BN(0)
+            new BN(1),
+            new BN(0),
+            new BN(0),
+            0,
+            0,
+            new BN(0),
+            false,
+            0,
+            new BN(0),
+            false,
+            new BN(100),
+            new BN(100)
         )
         .accounts({
           market: marketKeypair.publicKey,
@@ -49,6 +60,8 @@ describe('anchor-agnostic-orderbook', () => {
             false,
             true,
             1,
+            null,
+            null,
         )
         .accounts({
           market: marketKeypair.publicKey,
@@ -73,6 +86,8 @@ describe('anchor-agnostic-orderbook', () => {
             false,
             true,
             1,
+            null,
+            null,
         )
         .accounts({
           market: marketKeypair.publicKey,
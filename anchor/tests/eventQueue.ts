@@ -23,6 +23,40 @@ export enum Side {
   Ask = 1,
 }
 
+/**
+ * The fields of the packed `EventHead` which starts each event queue slot, in the order of the Rust struct along
+ * with their sizes in bytes. Quantities are 64-bit, as in programs built without the `u128-quantities` feature.
+ */
+const EVENT_HEAD_FIELDS: [string, number][] = [
+  ["tag", 1],
+  ["side", 1],
+  ["delete", 1],
+  ["orderId", 16],
+  ["quoteSize", 8],
+  ["baseSize", 8],
+  ["slot", 8],
+  ["unixTimestamp", 8],
+];
+
+/**
+ * The byte offsets of the fields of an event's head, along with the head's length
+ */
+export const EventHead = (() => {
+  const offsets: { [field: string]: number } = {};
+  let len = 0;
+  for (const [field, size] of EVENT_HEAD_FIELDS) {
+    offsets[field] = len;
+    len += size;
+  }
+  return { offsets, len };
+})();
+
+const field = (data: Buffer, name: string) => {
+  const start = EventHead.offsets[name];
+  const size = EVENT_HEAD_FIELDS.find(([field]) => field === name)![1];
+  return data.slice(start, start + size);
+};
+
 /**
  * Event queue header object
  */
@@ -32,16 +66,21 @@ export class EventQueueHeader {
   count: BN;
   eventSize: BN;
   seqNum: BN;
+  baseVolume: BN;
+  quoteVolume: BN;
+  fillCount: BN;
+  capacity: number;
+  market: PublicKey;
 
-  static LEN: number = 37;
-  static REGISTER_SIZE: number = 42;
+  static LEN: number = 109;
+  static REGISTER_SIZE: number = 66;
 
   /**
    * @param callBackInfoLen number of bytes in the callback info
    * @returns event queue slot size
    */
   static computeSlotSize(callBackInfoLen: BN) {
-    return callBackInfoLen.muln(2).addn(1 + 33);
+    return callBackInfoLen.muln(2).addn(EventHead.len);
   }
 
   static schema: Schema = new Map([
@@ -55,6 +94,11 @@ export class EventQueueHeader {
           ["count", "u64"],
           ["eventSize", "u64"],
           ["seqNum", "u64"],
+          ["baseVolume", "u128"],
+          ["quoteVolume", "u128"],
+          ["fillCount", "u64"],
+          ["capacity", "u32"],
+          ["market", [32]],
         ],
       },
     ],
@@ -66,12 +110,22 @@ export class EventQueueHeader {
     count: BN;
     eventSize: BN;
     seqNum: BN;
+    baseVolume: BN;
+    quoteVolume: BN;
+    fillCount: BN;
+    capacity: number;
+    market: Uint8Array;
   }) {
     this.tag = arg.tag as AccountTag;
     this.head = arg.head;
     this.count = arg.count;
     this.eventSize = arg.eventSize;
     this.seqNum = arg.seqNum;
+    this.baseVolume = arg.baseVolume;
+    this.quoteVolume = arg.quoteVolume;
+    this.fillCount = arg.fillCount;
+    this.capacity = arg.capacity;
+    this.market = new PublicKey(arg.market);
   }
 }
 
@@ -83,6 +137,8 @@ export class EventFill {
   makerOrderId: BN;
   quoteSize: BN;
   baseSize: BN;
+  slot: BN;
+  unixTimestamp: BN;
   makerCallbackInfo: number[];
   takerCallbackInfo: number[];
 
@@ -91,6 +147,8 @@ export class EventFill {
     makerOrderId: BN;
     quoteSize: BN;
     baseSize: BN;
+    slot: BN;
+    unixTimestamp: BN;
     makerCallbackInfo: number[];
     takerCallbackInfo: number[];
   }) {
@@ -98,6 +156,8 @@ export class EventFill {
     this.makerOrderId = arg.makerOrderId;
     this.quoteSize = arg.quoteSize;
     this.baseSize = arg.baseSize;
+    this.slot = arg.slot;
+    this.unixTimestamp = arg.unixTimestamp;
     this.makerCallbackInfo = arg.makerCallbackInfo;
     this.takerCallbackInfo = arg.takerCallbackInfo;
   }
@@ -110,13 +170,20 @@ export class EventFill {
    */
   static deserialize(callbackInfoLen: number, data: Buffer) {
     return new EventFill({
-      takerSide: data[1],
-      makerOrderId: new BN(data.slice(2, 18), "le"),
-      quoteSize: new BN(data.slice(18, 26), "le"),
-      baseSize: new BN(data.slice(26, 34), "le"),
-      makerCallbackInfo: [...data.slice(34, 34 + callbackInfoLen)],
+      takerSide: data[EventHead.offsets.side],
+      makerOrderId: new BN(field(data, "orderId"), "le"),
+      quoteSize: new BN(field(data, "quoteSize"), "le"),
+      baseSize: new BN(field(data, "baseSize"), "le"),
+      slot: new BN(field(data, "slot"), "le"),
+      unixTimestamp: new BN(field(data, "unixTimestamp"), "le").fromTwos(64),
+      makerCallbackInfo: [
+        ...data.slice(EventHead.len, EventHead.len + callbackInfoLen),
+      ],
       takerCallbackInfo: [
-        ...data.slice(34 + callbackInfoLen, 34 + 2 * callbackInfoLen),
+        ...data.slice(
+            EventHead.len + callbackInfoLen,
+            EventHead.len + 2 * callbackInfoLen
+        ),
       ],
     });
   }
@@ -130,6 +197,8 @@ export class EventOut {
   orderId: BN;
   baseSize: BN;
   delete: boolean;
+  slot: BN;
+  unixTimestamp: BN;
   callBackInfo: number[];
 
   constructor(arg: {
@@ -137,12 +206,16 @@ export class EventOut {
     orderId: BN;
     baseSize: BN;
     delete: number;
+    slot: BN;
+    unixTimestamp: BN;
     callBackInfo: number[];
   }) {
     this.side = arg.side as Side;
     this.orderId = arg.orderId;
     this.baseSize = arg.baseSize;
     this.delete = arg.delete === 1;
+    this.slot = arg.slot;
+    this.unixTimestamp = arg.unixTimestamp;
     this.callBackInfo = arg.callBackInfo;
   }
 
@@ -154,11 +227,15 @@ export class EventOut {
    */
   static deserialize(callbackInfoLen: number, data: Buffer) {
     return new EventOut({
-      side: data[1],
-      orderId: new BN(data.slice(2, 18), "le"),
-      baseSize: new BN(data.slice(18, 26), "le"),
-      delete: data[26],
-      callBackInfo: [...data.slice(27, 27 + callbackInfoLen)],
+      side: data[EventHead.offsets.side],
+      orderId: new BN(field(data, "orderId"), "le"),
+      baseSize: new BN(field(data, "baseSize"), "le"),
+      delete: data[EventHead.offsets.delete],
+      slot: new BN(field(data, "slot"), "le"),
+      unixTimestamp: new BN(field(data, "unixTimestamp"), "le").fromTwos(64),
+      callBackInfo: [
+        ...data.slice(EventHead.len, EventHead.len + callbackInfoLen),
+      ],
     });
   }
 }
@@ -233,7 +310,7 @@ export class EventQueue {
     let data = Buffer.from(
         this.buffer.slice(offset, offset + this.header.eventSize.toNumber())
     );
-    switch (data[0]) {
+    switch (data[EventHead.offsets.tag]) {
       case EventType.Fill:
         return EventFill.deserialize(this.callBackInfoLen, data) as EventFill;
       case EventType.Out:
@@ -1,16 +1,14 @@
-use std::convert::identity;
-use std::convert::TryInto;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::identity;
+use core::convert::TryInto;
 
-use anchor_lang::prelude::*;
-use anchor_lang::solana_program::account_info::AccountInfo;
-use anchor_lang::solana_program::pubkey::Pubkey;
-use borsh::{BorshDeserialize, BorshSerialize};
 use bytemuck::{try_from_bytes, try_from_bytes_mut, Pod, Zeroable};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use crate::aob::error::ErrorCode;
-use crate::aob::state::AccountTag;
+use crate::error::SlabError;
+use crate::storage::Storage;
 
 // A Slab contains the data for a slab header and an array of nodes of a critbit tree
 // whose leafs contain the data referencing an order of the orderbook.
@@ -20,9 +18,7 @@ use crate::aob::state::AccountTag;
 
 pub type NodeHandle = u32;
 
-pub type IoError = std::io::Error;
-
-#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq, Clone, Copy, Pod, Zeroable)]
+#[derive(Debug, PartialEq, Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 pub struct InnerNode {
     prefix_len: u64,
@@ -43,6 +39,7 @@ impl InnerNode {
 pub struct LeafNode {
     pub key: u128,
     pub callback_info_pt: u64,
+    /// The order's remaining quantity, in base lots of the market
     pub base_quantity: u64,
 }
 
@@ -52,21 +49,21 @@ pub(crate) const FREE_NODE_SIZE: usize = 4;
 pub(crate) const NODE_TAG_SIZE: usize = 8;
 pub const SLOT_SIZE: usize = NODE_TAG_SIZE + NODE_SIZE;
 
+/// The number of orders at the front of a price level among which [`Slab::find_largest_at_price`] looks for the
+/// largest one, so that size priority costs the same whatever the size of the level.
+pub const SIZE_PRIORITY_DEPTH: usize = 32;
+
 impl LeafNode {
     pub fn price(&self) -> u64 {
         (self.key >> 64) as u64
     }
 
-    pub fn order_id(&self) -> u128 {
-        self.key
-    }
-
     pub fn set_base_quantity(&mut self, quantity: u64) {
         self.base_quantity = quantity;
     }
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq, Clone, Copy, Pod, Zeroable)]
+#[derive(Debug, PartialEq, Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 pub struct FreeNode {
     next: u32,
@@ -134,12 +131,11 @@ impl<'a> NodeRef<'a> {
         }
     }
 
-    #[cfg(any(test, feature = "utils"))]
-    fn prefix_len(&self) -> std::io::Result<u64> {
+    fn prefix_len(&self) -> Option<u64> {
         match &self {
-            Self::Inner(i) => Ok(i.prefix_len),
-            Self::Leaf(_) => Ok(128),
-            _ => Err(std::io::ErrorKind::InvalidData.into()),
+            Self::Inner(i) => Some(i.prefix_len),
+            Self::Leaf(_) => Some(128),
+            _ => None,
         }
     }
 
@@ -171,9 +167,10 @@ impl<'a> NodeRef<'a> {
 ////////////////////////////////////
 // Slabs
 
-#[derive(BorshDeserialize, BorshSerialize, Debug)]
+#[derive(Debug, PartialEq, Clone)]
 struct SlabHeader {
-    account_tag: AccountTag,
+    market_address: [u8; 32],
+    account_tag: u8,
     bump_index: u64,
     free_list_len: u64,
     free_list_head: u32,
@@ -184,38 +181,76 @@ struct SlabHeader {
 
     root_node: u32,
     leaf_count: u64,
-    market_address: Pubkey,
+    /// The sum of the base quantities (in lots) of the orders resting in the slab, wrapping around on overflow
+    resting_base_lots: u64,
 }
-pub const SLAB_HEADER_LEN: usize = 97;
-pub const PADDED_SLAB_HEADER_LEN: usize = SLAB_HEADER_LEN + 7;
 
-pub struct Slab<'a> {
+impl SlabHeader {
+    /// Reads the header from its packed little-endian layout, which is the Borsh layout of the on-chain accounts
+    fn read(buffer: &[u8]) -> Option<Self> {
+        let mut cursor = buffer.get(..SLAB_HEADER_LEN)?;
+        let mut take = |len: usize| {
+            let (field, rest) = cursor.split_at(len);
+            cursor = rest;
+            field
+        };
+        Some(Self {
+            market_address: take(32).try_into().unwrap(),
+            account_tag: take(1)[0],
+            bump_index: u64::from_le_bytes(take(8).try_into().unwrap()),
+            free_list_len: u64::from_le_bytes(take(8).try_into().unwrap()),
+            free_list_head: u32::from_le_bytes(take(4).try_into().unwrap()),
+            callback_memory_offset: u64::from_le_bytes(take(8).try_into().unwrap()),
+            callback_free_list_len: u64::from_le_bytes(take(8).try_into().unwrap()),
+            callback_free_list_head: u64::from_le_bytes(take(8).try_into().unwrap()),
+            callback_bump_index: u64::from_le_bytes(take(8).try_into().unwrap()),
+            root_node: u32::from_le_bytes(take(4).try_into().unwrap()),
+            leaf_count: u64::from_le_bytes(take(8).try_into().unwrap()),
+            resting_base_lots: u64::from_le_bytes(take(8).try_into().unwrap()),
+        })
+    }
+
+    fn write(&self, buffer: &mut [u8]) {
+        let mut cursor = &mut buffer[..SLAB_HEADER_LEN];
+        let mut put = |field: &[u8]| {
+            let (dst, rest) = core::mem::take(&mut cursor).split_at_mut(field.len());
+            dst.copy_from_slice(field);
+            cursor = rest;
+        };
+        put(&self.market_address);
+        put(&[self.account_tag]);
+        put(&self.bump_index.to_le_bytes());
+        put(&self.free_list_len.to_le_bytes());
+        put(&self.free_list_head.to_le_bytes());
+        put(&self.callback_memory_offset.to_le_bytes());
+        put(&self.callback_free_list_len.to_le_bytes());
+        put(&self.callback_free_list_head.to_le_bytes());
+        put(&self.callback_bump_index.to_le_bytes());
+        put(&self.root_node.to_le_bytes());
+        put(&self.leaf_count.to_le_bytes());
+        put(&self.resting_base_lots.to_le_bytes());
+    }
+}
+
+/// The slab header is preceded by the space for an account discriminator, which the slab leaves alone.
+pub const SLAB_DISCRIMINATOR_LEN: usize = 8;
+pub const SLAB_HEADER_LEN: usize = 105;
+pub const PADDED_SLAB_HEADER_LEN: usize = SLAB_DISCRIMINATOR_LEN + SLAB_HEADER_LEN + 7;
+
+/// A critbit tree laid out in a [`Storage`], which is the data of the bids or asks account on-chain.
+pub struct Slab<S> {
     header: SlabHeader,
-    // TODO `Slab` cannot implement `Clone` due to this mutable reference
-    // which is limiting for Anchor
-    // turns out this is quite limiting, especially since many AccountInfo derives `Clone`
-    pub buffer: &'a mut [u8],
+    pub buffer: S,
     pub callback_info_len: usize,
 }
 
 // Data access methods
-impl<'a> Slab<'a> {
-    /// Releases the memory temporarily held by Slab, replacing the memory that was
-    /// originally took out of the account_info
-    pub fn release(self, account: &AccountInfo<'a>) {
-        account.data.replace(self.buffer);
-    }
-
-    pub fn check_account_tag(&self, account_tag: AccountTag) -> Result<()> {
-        if self.header.account_tag != account_tag {
-            return Err(error!(ErrorCode::WrongAccountTag));
-        }
-        Ok(())
-    }
-
-    pub fn new(buffer: &'a mut [u8], callback_info_len: usize) -> Result<Self> {
-        let header = SlabHeader::deserialize(&mut (buffer as &[u8]))
-            .map_err(|_| ErrorCode::FailedToDeserialize)?;
+impl<S: Storage> Slab<S> {
+    pub fn new(buffer: S, callback_info_len: usize) -> Result<Self, SlabError> {
+        let header = buffer
+            .get(SLAB_DISCRIMINATOR_LEN..)
+            .and_then(SlabHeader::read)
+            .ok_or(SlabError::FailedToDeserialize)?;
         let slab = Self {
             header,
             buffer,
@@ -224,59 +259,137 @@ impl<'a> Slab<'a> {
         Ok(slab)
     }
 
-    pub(crate) fn write_header(&mut self) {
+    /// Tells the bids and asks apart, the tag values being up to the owner of the buffer
+    pub fn account_tag(&self) -> u8 {
+        self.header.account_tag
+    }
+
+    /// The address of the market which the slab belongs to
+    pub fn market_address(&self) -> &[u8; 32] {
+        &self.header.market_address
+    }
+
+    /// The sum of the base quantities (in lots) of the orders resting in the slab
+    pub fn resting_base_lots(&self) -> u64 {
+        self.header.resting_base_lots
+    }
+
+    pub fn write_header(&mut self) {
         self.header
-            .serialize(&mut &mut self.buffer[..SLAB_HEADER_LEN])
-            .unwrap()
+            .write(&mut self.buffer[SLAB_DISCRIMINATOR_LEN..]);
     }
 
-    pub fn initialize(
-        bids_account: &AccountInfo<'a>,
-        asks_account: &AccountInfo<'a>,
-        market_address: Pubkey,
+    /// Relocates the slab's callback memory after its buffer has been grown, so that the node arena can use the
+    /// new capacity.
+    ///
+    /// The callback memory is moved to the end of the new node arena, and the pointers to it held by the leaves,
+    /// the callback free list and the header are shifted accordingly. The node handles are left untouched.
+    pub fn relocate_after_growth(&mut self) {
+        let old_offset = self.header.callback_memory_offset as usize;
+        let new_offset = PADDED_SLAB_HEADER_LEN + 2 * self.capacity() as usize * SLOT_SIZE;
+        if new_offset <= old_offset {
+            return;
+        }
+        let shift = new_offset - old_offset;
+        let used_end = self.header.callback_bump_index as usize;
+        self.buffer.copy_within(old_offset..used_end, new_offset);
+        self.buffer[old_offset..new_offset].fill(0);
+
+        for handle in 0..self.header.bump_index as u32 {
+            if let Some(NodeRefMut::Leaf(leaf)) = self.get_node_mut(handle) {
+                leaf.callback_info_pt += shift as u64;
+            }
+        }
+
+        if self.header.callback_free_list_len > 0 {
+            self.header.callback_free_list_head += shift as u64;
+            let mut pt = self.header.callback_free_list_head as usize;
+            for _ in 1..self.header.callback_free_list_len {
+                let next =
+                    u64::from_le_bytes(self.buffer[pt..pt + 8].try_into().unwrap()) + shift as u64;
+                self.buffer[pt..pt + 8].copy_from_slice(&next.to_le_bytes());
+                pt = next as usize;
+            }
+        }
+
+        self.header.callback_memory_offset = new_offset as u64;
+        self.header.callback_bump_index += shift as u64;
+        self.write_header();
+    }
+}
+
+// Layout methods, which don't depend on the storage. They are defined for borrowed buffers so that they can be
+// called as `Slab::compute_allocation_size`.
+impl Slab<&mut [u8]> {
+    /// The size of an orderbook buffer (bids or asks) which holds `order_capacity` orders: the node arena has
+    /// room for the `2 * order_capacity - 1` nodes of a full tree, and the callback memory for the callback info
+    /// of each order.
+    pub fn compute_allocation_size(order_capacity: usize, callback_info_len: usize) -> usize {
+        PADDED_SLAB_HEADER_LEN + order_capacity * (2 * SLOT_SIZE + callback_info_len)
+    }
+
+    /// Writes the header of an empty slab using all of the buffer, which has to be zeroed.
+    pub fn initialize_buffer(
+        buffer: &mut [u8],
+        account_tag: u8,
+        market_address: [u8; 32],
         callback_info_len: usize,
     ) {
-        let order_capacity = (asks_account.data.borrow().len() - PADDED_SLAB_HEADER_LEN)
-            / (SLOT_SIZE * 2 + callback_info_len);
-
-        let asks_callback_memory_offset = PADDED_SLAB_HEADER_LEN + 2 * order_capacity * SLOT_SIZE;
-        let mut header = SlabHeader {
-            account_tag: AccountTag::Asks,
+        let order_capacity =
+            (buffer.len() - PADDED_SLAB_HEADER_LEN) / (SLOT_SIZE * 2 + callback_info_len);
+        let callback_memory_offset = PADDED_SLAB_HEADER_LEN + 2 * order_capacity * SLOT_SIZE;
+        let header = SlabHeader {
+            account_tag,
             bump_index: 0,
             free_list_len: 0,
             free_list_head: 0,
             root_node: 0,
             leaf_count: 0,
+            resting_base_lots: 0,
             market_address,
-            callback_memory_offset: asks_callback_memory_offset as u64,
-            callback_bump_index: asks_callback_memory_offset as u64,
+            callback_memory_offset: callback_memory_offset as u64,
+            callback_bump_index: callback_memory_offset as u64,
             callback_free_list_head: 0,
             callback_free_list_len: 0,
         };
-        header
-            .serialize(&mut ((&mut asks_account.data.borrow_mut()) as &mut [u8]))
-            .unwrap();
+        header.write(&mut buffer[SLAB_DISCRIMINATOR_LEN..]);
+    }
+}
 
-        let bids_order_capacity = (bids_account.data.borrow().len() - PADDED_SLAB_HEADER_LEN)
-            / (SLOT_SIZE * 2 + callback_info_len);
-        let bids_callback_memory_offset =
-            PADDED_SLAB_HEADER_LEN + 2 * bids_order_capacity * SLOT_SIZE;
+impl Slab<Vec<u8>> {
+    /// Creates an empty slab backed by a heap buffer which holds `order_capacity` orders.
+    pub fn new_in_memory(
+        order_capacity: usize,
+        callback_info_len: usize,
+        account_tag: u8,
+        market_address: [u8; 32],
+    ) -> Self {
+        let mut buffer = vec![0; Slab::compute_allocation_size(order_capacity, callback_info_len)];
+        Slab::initialize_buffer(&mut buffer, account_tag, market_address, callback_info_len);
+        Self::new(buffer, callback_info_len).unwrap()
+    }
 
-        header.account_tag = AccountTag::Bids;
-        header.callback_memory_offset = bids_callback_memory_offset as u64;
-        header
-            .serialize(&mut ((&mut bids_account.data.borrow_mut()) as &mut [u8]))
-            .unwrap();
+    /// Loads a slab from a copy of raw account data, such as the data of a bids or asks account fetched over RPC.
+    ///
+    /// The slab owns its copy, so changes made to it are never written back to `data`.
+    pub fn from_bytes(data: &[u8], callback_info_len: usize) -> Result<Self, SlabError> {
+        Self::new(data.to_vec(), callback_info_len)
     }
 }
 
 // Tree nodes manipulation methods
-impl<'a> Slab<'a> {
+impl<S: Storage> Slab<S> {
     fn capacity(&self) -> u64 {
         ((self.buffer.len() - PADDED_SLAB_HEADER_LEN) / (2 * SLOT_SIZE + self.callback_info_len))
             as u64
     }
 
+    /// The number of nodes the tree of a full slab is made of, as `capacity` leaves are joined by one less inner
+    /// node.
+    fn node_capacity(&self) -> u64 {
+        (2 * self.capacity()).saturating_sub(1)
+    }
+
     pub fn get_node(&self, key: u32) -> Option<NodeRef> {
         let mut offset = PADDED_SLAB_HEADER_LEN + (key as usize) * SLOT_SIZE;
         // println!("key: {:?}, slot_size: {:?}", key, self.slot_size);
@@ -350,14 +463,14 @@ impl<'a> Slab<'a> {
         Some(node)
     }
 
-    fn allocate(&mut self, node_type: &NodeTag) -> std::result::Result<u32, IoError> {
+    fn allocate(&mut self, node_type: &NodeTag) -> Result<u32, SlabError> {
         if self.header.free_list_len == 0 {
-            if self.header.bump_index as usize == self.capacity() as usize {
-                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            if self.header.bump_index == self.node_capacity() {
+                return Err(SlabError::OutOfSpace);
             }
 
-            if self.header.bump_index == std::u32::MAX as u64 {
-                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            if self.header.bump_index == u32::MAX as u64 {
+                return Err(SlabError::OutOfSpace);
             }
             let key = self.header.bump_index;
             let offset = PADDED_SLAB_HEADER_LEN + (key as usize) * SLOT_SIZE;
@@ -440,13 +553,12 @@ impl<'a> Slab<'a> {
         ))
         .unwrap();
         if old_tag == NodeTag::Leaf {
-            let callback_info_index = self
-                .get_node(key)
-                .unwrap()
-                .as_leaf()
-                .unwrap()
-                .callback_info_pt;
-            self.clear_callback_info(callback_info_index as usize);
+            let leaf = *self.get_node(key).unwrap().as_leaf().unwrap();
+            self.clear_callback_info(leaf.callback_info_pt as usize);
+            self.header.resting_base_lots = self
+                .header
+                .resting_base_lots
+                .wrapping_sub(leaf.base_quantity);
         }
         let new_tag = if self.header.free_list_len == 0 {
             NodeTag::LastFree
@@ -463,16 +575,13 @@ impl<'a> Slab<'a> {
         self.header.free_list_len += 1;
     }
 
-    fn insert_node(&mut self, node: &Node) -> std::result::Result<u32, IoError> {
+    fn insert_node(&mut self, node: &Node) -> Result<u32, SlabError> {
         let handle = self.allocate(&node.tag())?;
-        self.write_node(node, handle);
+        self.store_node(node, handle);
         Ok(handle)
     }
 
-    pub fn write_callback_info(
-        &mut self,
-        callback_info: &[u8],
-    ) -> std::result::Result<u64, IoError> {
+    pub fn write_callback_info(&mut self, callback_info: &[u8]) -> Result<u64, SlabError> {
         let h = if self.header.callback_free_list_len > 0 {
             let next_free_spot = u64::from_le_bytes(
                 self.buffer[self.header.callback_free_list_head as usize
@@ -492,11 +601,12 @@ impl<'a> Slab<'a> {
         self.buffer
             .get_mut(h..h + self.callback_info_len)
             .map(|s| s.copy_from_slice(callback_info))
-            .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+            .ok_or(SlabError::OutOfSpace)?;
         Ok(h as u64)
     }
 
-    fn clear_callback_info(&mut self, callback_info_pt: usize) {
+    /// Gives back the callback memory written by [`Slab::write_callback_info`], once no leaf points to it anymore.
+    pub fn clear_callback_info(&mut self, callback_info_pt: usize) {
         self.buffer[callback_info_pt..callback_info_pt + 8]
             .copy_from_slice(&self.header.callback_free_list_head.to_le_bytes());
         self.header.callback_free_list_head = callback_info_pt as u64;
@@ -507,7 +617,34 @@ impl<'a> Slab<'a> {
         &self.buffer[callback_info_pt..callback_info_pt + self.callback_info_len]
     }
 
+    /// Overwrites an existing node, keeping track of the change in resting base quantity when it's a leaf.
     pub fn write_node(&mut self, node: &Node, handle: NodeHandle) {
+        if let (Node::Leaf(new), Some(NodeRef::Leaf(old))) = (node, self.get_node(handle)) {
+            self.header.resting_base_lots = self
+                .header
+                .resting_base_lots
+                .wrapping_add(new.base_quantity)
+                .wrapping_sub(old.base_quantity);
+        }
+        self.store_node(node, handle);
+    }
+
+    /// Updates the base quantity of a leaf in place, keeping track of the change in resting base quantity.
+    pub fn set_leaf_base_quantity(&mut self, handle: NodeHandle, base_quantity: u64) {
+        let old_base_quantity = match self.get_node_mut(handle) {
+            Some(NodeRefMut::Leaf(leaf)) => {
+                core::mem::replace(&mut leaf.base_quantity, base_quantity)
+            }
+            _ => unreachable!(),
+        };
+        self.header.resting_base_lots = self
+            .header
+            .resting_base_lots
+            .wrapping_add(base_quantity)
+            .wrapping_sub(old_base_quantity);
+    }
+
+    fn store_node(&mut self, node: &Node, handle: NodeHandle) {
         match (node, self.get_node_mut(handle)) {
             (Node::Inner(i), Some(NodeRefMut::Inner(r))) => {
                 *r = *i;
@@ -521,7 +658,7 @@ impl<'a> Slab<'a> {
 }
 
 // Critbit tree walks
-impl<'a> Slab<'a> {
+impl<S: Storage> Slab<S> {
     pub fn root(&self) -> Option<NodeHandle> {
         if self.header.leaf_count == 0 {
             return None;
@@ -552,21 +689,167 @@ impl<'a> Slab<'a> {
         self.find_min_max(true)
     }
 
+    /// Returns the handle of the largest order among the [`SIZE_PRIORITY_DEPTH`] earliest ones resting at the given
+    /// price.
+    ///
+    /// Orders of equal size are ranked by time priority, which depends on the side of the slab:
+    /// ask keys grow with time whereas bid keys decrease with time, which `keys_decrease_with_time` tells apart.
+    pub fn find_largest_at_price(
+        &self,
+        price: u64,
+        keys_decrease_with_time: bool,
+    ) -> Option<NodeHandle> {
+        // Walk down to the subtree containing all orders at this price level
+        let mut level_root: NodeHandle = self.root()?;
+        loop {
+            match self.get_node(level_root).unwrap() {
+                NodeRef::Inner(inner) if inner.prefix_len < 64 => {
+                    level_root = inner.walk_down((price as u128) << 64).0;
+                }
+                NodeRef::Inner(inner) => {
+                    if (inner.key >> 64) as u64 != price {
+                        return None;
+                    }
+                    break;
+                }
+                NodeRef::Leaf(leaf) => {
+                    if leaf.price() != price {
+                        return None;
+                    }
+                    break;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        let earliest_child = keys_decrease_with_time as usize;
+        let mut largest: Option<(NodeHandle, u64)> = None;
+        let mut visited = 0;
+        // The keys of a level only differ in their 64 low bits, so that a path below its root goes through 64 inner
+        // nodes at most, each of which leaves one child on the stack
+        let mut stack: [NodeHandle; 65] = [0; 65];
+        stack[0] = level_root;
+        let mut stack_len = 1;
+        while stack_len > 0 && visited < SIZE_PRIORITY_DEPTH {
+            stack_len -= 1;
+            let handle = stack[stack_len];
+            match self.get_node(handle).unwrap() {
+                NodeRef::Inner(inner) => {
+                    // The earliest child is pushed last in order to be visited first
+                    stack[stack_len] = inner.children[1 - earliest_child];
+                    stack[stack_len + 1] = inner.children[earliest_child];
+                    stack_len += 2;
+                }
+                NodeRef::Leaf(leaf) => {
+                    visited += 1;
+                    if largest.map_or(true, |(_, qty)| leaf.base_quantity > qty) {
+                        largest = Some((handle, leaf.base_quantity));
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+        largest.map(|(handle, _)| handle)
+    }
+
+    /// Returns all the leaves of the slab ordered by key, in descending order if `descending` is set.
+    pub fn leaves(&self, descending: bool) -> Vec<LeafNode> {
+        let (first_child, second_child) = if descending { (1, 0) } else { (0, 1) };
+        let mut leaves = Vec::with_capacity(self.header.leaf_count as usize);
+        let mut stack: Vec<NodeHandle> = self.root().into_iter().collect();
+        while let Some(handle) = stack.pop() {
+            match self.get_node(handle).unwrap() {
+                NodeRef::Inner(inner) => {
+                    stack.push(inner.children[second_child]);
+                    stack.push(inner.children[first_child]);
+                }
+                NodeRef::Leaf(leaf) => leaves.push(*leaf),
+                _ => unreachable!(),
+            }
+        }
+        leaves
+    }
+
+    /// Returns up to `depth` price levels of the slab as (price, base lots) pairs, in descending price order if
+    /// `descending` is set. The traversal stops as soon as the last level is complete.
+    pub fn price_levels(&self, descending: bool, depth: usize) -> Vec<(u64, u64)> {
+        let (first_child, second_child) = if descending { (1, 0) } else { (0, 1) };
+        let mut levels: Vec<(u64, u64)> = Vec::with_capacity(depth);
+        let mut stack: Vec<NodeHandle> = self.root().into_iter().collect();
+        while let Some(handle) = stack.pop() {
+            match self.get_node(handle).unwrap() {
+                NodeRef::Inner(inner) => {
+                    stack.push(inner.children[second_child]);
+                    stack.push(inner.children[first_child]);
+                }
+                NodeRef::Leaf(leaf) => {
+                    let level_count = levels.len();
+                    match levels.last_mut() {
+                        Some((price, base_lots)) if *price == leaf.price() => {
+                            *base_lots += leaf.base_quantity
+                        }
+                        _ if level_count == depth => break,
+                        _ => levels.push((leaf.price(), leaf.base_quantity)),
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+        levels
+    }
+
     pub fn insert_leaf(
         &mut self,
         new_leaf_node: &Node,
-    ) -> std::result::Result<(NodeHandle, Option<Node>), ErrorCode> {
+    ) -> Result<(NodeHandle, Option<Node>), SlabError> {
+        self.insert_leaf_below(new_leaf_node, self.root())
+            .map(|(handle, clobbered, _)| (handle, clobbered))
+    }
+
+    /// Inserts a leaf like [`Slab::insert_leaf`], but descends from `hint` rather than from the root when `hint` is
+    /// an inner node whose prefix covers the leaf's key. Any other hint, stale ones included, falls back to a
+    /// descent from the root.
+    ///
+    /// Also returns the hint for the next insertion: the deepest inner node which covered the key before this
+    /// insertion. Orders posted one after the other at or near the same price share most of their descent, which
+    /// the returned hints skip.
+    pub fn insert_leaf_with_hint(
+        &mut self,
+        new_leaf_node: &Node,
+        hint: NodeHandle,
+    ) -> Result<(NodeHandle, Option<Node>, NodeHandle), SlabError> {
+        let key = new_leaf_node.as_leaf().unwrap().key;
+        let covers_key = self.root().is_some()
+            && (hint as u64) < self.header.bump_index
+            && matches!(
+                self.get_node(hint),
+                Some(NodeRef::Inner(inner))
+                    if (inner.key ^ key).leading_zeros() as u64 >= inner.prefix_len
+            );
+        let start = if covers_key { Some(hint) } else { self.root() };
+        self.insert_leaf_below(new_leaf_node, start)
+    }
+
+    /// Inserts a leaf within the subtree of `start`, which has to be the root or an inner node covering the leaf's
+    /// key, and returns the deepest inner node that covered the key along with the result of the insertion.
+    fn insert_leaf_below(
+        &mut self,
+        new_leaf_node: &Node,
+        start: Option<NodeHandle>,
+    ) -> Result<(NodeHandle, Option<Node>, NodeHandle), SlabError> {
         let new_leaf = new_leaf_node.as_leaf().unwrap();
-        let mut root: NodeHandle = match self.root() {
+        let mut root: NodeHandle = match start {
             Some(h) => h,
             None => {
                 // create a new root if none exists
-                let new_leaf_key = self
-                    .insert_node(new_leaf_node)
-                    .map_err(|_| ErrorCode::SlabOutOfSpace)?;
+                let new_leaf_key = self.insert_node(new_leaf_node)?;
                 self.header.root_node = new_leaf_key;
                 self.header.leaf_count += 1;
-                return Ok((new_leaf_key, None));
+                self.header.resting_base_lots = self
+                    .header
+                    .resting_base_lots
+                    .wrapping_add(new_leaf.base_quantity);
+                return Ok((new_leaf_key, None, new_leaf_key));
             }
         };
         let mut parent_node: Option<NodeHandle> = None;
@@ -582,7 +865,16 @@ impl<'a> Slab<'a> {
                     if let NodeRefMut::Leaf(root_leaf) = self.get_node_mut(root).unwrap() {
                         *root_leaf = *new_leaf;
                     };
-                    return Ok((root, Some(Node::Leaf(root_leaf_copy))));
+                    self.header.resting_base_lots = self
+                        .header
+                        .resting_base_lots
+                        .wrapping_add(new_leaf.base_quantity)
+                        .wrapping_sub(root_leaf_copy.base_quantity);
+                    return Ok((
+                        root,
+                        Some(Node::Leaf(root_leaf_copy)),
+                        parent_node.unwrap_or(root),
+                    ));
                 }
             }
             let shared_prefix_len: u32 = (root_key ^ new_leaf.key).leading_zeros();
@@ -605,13 +897,9 @@ impl<'a> Slab<'a> {
             drop(root_contents);
 
             // Write new leaf to slab
-            let new_leaf_handle = self
-                .insert_node(new_leaf_node)
-                .map_err(|_| ErrorCode::SlabOutOfSpace)?;
+            let new_leaf_handle = self.insert_node(new_leaf_node)?;
 
-            let new_root_node_handle = self
-                .allocate(&NodeTag::Inner)
-                .map_err(|_| ErrorCode::SlabOutOfSpace)?;
+            let new_root_node_handle = self.allocate(&NodeTag::Inner)?;
 
             if let NodeRefMut::Inner(mut i) = self.get_node_mut(new_root_node_handle).unwrap() {
                 i.prefix_len = shared_prefix_len as u64;
@@ -633,12 +921,39 @@ impl<'a> Slab<'a> {
             }
 
             self.header.leaf_count += 1;
-            return Ok((new_leaf_handle, None));
+            self.header.resting_base_lots = self
+                .header
+                .resting_base_lots
+                .wrapping_add(new_leaf.base_quantity);
+            return Ok((
+                new_leaf_handle,
+                None,
+                parent_node.unwrap_or(new_root_node_handle),
+            ));
         }
     }
 
     /// This function corrupts the node's callback information when erasing it!
     pub fn remove_by_key(&mut self, search_key: u128) -> Option<Node> {
+        self.remove_leaf(search_key, |_, _| ())
+            .map(|(node, ())| node)
+    }
+
+    /// Removes a leaf like [`Slab::remove_by_key`], along with a copy of its callback information taken
+    /// before it is erased, in a single descent of the tree.
+    pub fn remove_by_key_with_callback(&mut self, search_key: u128) -> Option<(Node, Vec<u8>)> {
+        self.remove_leaf(search_key, |slab, leaf| {
+            slab.get_callback_info(leaf.callback_info_pt as usize)
+                .to_vec()
+        })
+    }
+
+    /// Removes the leaf of the given key, after reading it through `read`.
+    fn remove_leaf<R>(
+        &mut self,
+        search_key: u128,
+        read: impl FnOnce(&Self, &LeafNode) -> R,
+    ) -> Option<(Node, R)> {
         let mut grandparent_h: Option<NodeHandle> = None;
         let mut parent_h = self.root()?;
         // We have to initialize the values to work around the type checker
@@ -664,11 +979,12 @@ impl<'a> Slab<'a> {
             }
         }
         if let Some(leaf_copy) = remove_root {
+            let read = read(self, leaf_copy.as_leaf().unwrap());
             self.remove(parent_h);
 
             self.header.root_node = 0;
             self.header.leaf_count = 0;
-            return Some(leaf_copy);
+            return Some((leaf_copy, read));
         }
         loop {
             match self.get_node(child_h).unwrap() {
@@ -706,9 +1022,10 @@ impl<'a> Slab<'a> {
         }
         self.header.leaf_count -= 1;
         let removed_leaf = self.get_node(child_h).unwrap().to_owned();
+        let read = read(self, removed_leaf.as_leaf().unwrap());
         self.remove(child_h);
         self.remove(parent_h);
-        Some(removed_leaf)
+        Some((removed_leaf, read))
     }
 
     pub fn remove_min(&mut self) -> Option<Node> {
@@ -724,7 +1041,6 @@ impl<'a> Slab<'a> {
     /////////////////////////////////////////
     // Misc
 
-    #[cfg(any(test, feature = "utils"))]
     pub fn find_by_key(&self, search_key: u128) -> Option<NodeHandle> {
         let mut node_handle: NodeHandle = self.root()?;
         loop {
@@ -750,16 +1066,16 @@ impl<'a> Slab<'a> {
 
     #[cfg(test)]
     fn traverse<T: CallbackInfo>(&self) -> Vec<(Node, T)> {
-        fn walk_rec<'a, S: CallbackInfo>(
-            slab: &'a Slab,
+        fn walk_rec<S: Storage, C: CallbackInfo>(
+            slab: &Slab<S>,
             sub_root: NodeHandle,
-            buf: &mut Vec<(Node, S)>,
+            buf: &mut Vec<(Node, C)>,
         ) {
             let n = slab.get_node(sub_root).unwrap().to_owned();
             match n {
                 Node::Leaf(ref l) => {
                     let callback_info =
-                        S::from_bytes(slab.get_callback_info(l.callback_info_pt as usize));
+                        C::from_bytes(slab.get_callback_info(l.callback_info_pt as usize));
                     buf.push((n, callback_info));
                 }
                 Node::Inner(inner) => {
@@ -786,9 +1102,9 @@ impl<'a> Slab<'a> {
         println!("Callback info length {:?}", self.callback_info_len);
         println!("Slot size {:?}", SLOT_SIZE);
         println!("Header (parsed):");
-        let mut header_data = Vec::new();
+        let mut header_data = vec![0; SLAB_HEADER_LEN];
         println!("{:?}", self.header);
-        self.header.serialize(&mut header_data).unwrap();
+        self.header.write(&mut header_data);
 
         println!("Header (raw):");
         hexdump::hexdump(&header_data);
@@ -807,12 +1123,13 @@ impl<'a> Slab<'a> {
         // hexdump::hexdump(&self.buffer.borrow()[SLAB_HEADER_LEN..]);
     }
 
-    #[cfg(test)]
-    fn check_invariants(&self) {
+    /// Checks that the tree is well-formed, its free list consistent and its resting quantity in sync with its
+    /// leaves, panicking otherwise. Meant for tests and fuzzing.
+    pub fn check_invariants(&self) {
         // first check the live tree contents
         let mut count = 0;
-        fn check_rec(
-            slab: &Slab,
+        fn check_rec<S: Storage>(
+            slab: &Slab<S>,
             key: NodeHandle,
             last_prefix_len: u64,
             last_prefix: u128,
@@ -875,6 +1192,12 @@ impl<'a> Slab<'a> {
             count + self.header.free_list_len as u64,
             identity(self.header.bump_index)
         );
+        assert_eq!(
+            self.leaves(false)
+                .iter()
+                .fold(0u64, |sum, l| sum.wrapping_add(l.base_quantity)),
+            self.header.resting_base_lots
+        );
 
         let mut free_nodes_remaining = self.header.free_list_len;
         let mut next_free_node = self.header.free_list_head;
@@ -901,13 +1224,15 @@ impl<'a> Slab<'a> {
     }
 }
 
+#[cfg(test)]
 trait CallbackInfo: Sized {
     fn from_bytes(data: &[u8]) -> Self;
 }
 
-impl CallbackInfo for Pubkey {
+#[cfg(test)]
+impl CallbackInfo for [u8; 32] {
     fn from_bytes(data: &[u8]) -> Self {
-        Self::new(data)
+        data.try_into().unwrap()
     }
 }
 
@@ -916,10 +1241,24 @@ impl CallbackInfo for Pubkey {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
     use rand::prelude::*;
 
     use super::*;
 
+    // The tags of the bids and asks accounts of the AO
+    const BIDS: u8 = 3;
+    const ASKS: u8 = 4;
+
+    /// Stands in for the public keys which callers store as callback information
+    fn new_unique_key() -> [u8; 32] {
+        static COUNTER: AtomicU64 = AtomicU64::new(1);
+        let mut key = [0; 32];
+        key[..8].copy_from_slice(&COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+        key
+    }
+
     // #[test]
     // fn test_node_serialization() {
     //     let mut rng = StdRng::seed_from_u64(42);
@@ -950,7 +1289,7 @@ mod tests {
                 buffer: slab_data,
                 callback_info_len: 32,
                 header: SlabHeader {
-                    account_tag: AccountTag::Asks,
+                    account_tag: ASKS,
                     bump_index: 0,
                     free_list_len: 0,
                     free_list_head: 0,
@@ -960,11 +1299,12 @@ mod tests {
                     callback_bump_index: callback_memory_offset as u64,
                     root_node: 0,
                     leaf_count: 0,
-                    market_address: Pubkey::new_unique(),
+                    resting_base_lots: 0,
+                    market_address: new_unique_key(),
                 },
             };
 
-            let mut model: BTreeMap<u128, (Node, Pubkey)> = BTreeMap::new();
+            let mut model: BTreeMap<u128, (Node, [u8; 32])> = BTreeMap::new();
 
             let mut all_keys = vec![];
 
@@ -975,9 +1315,9 @@ mod tests {
 
             for i in 0..100 {
                 let key = rng.gen();
-                let owner = Pubkey::new_unique();
+                let owner = new_unique_key();
                 let qty = rng.gen();
-                let callback_info_offset = slab.write_callback_info(&owner.to_bytes()).unwrap();
+                let callback_info_offset = slab.write_callback_info(&owner).unwrap();
                 let leaf = Node::Leaf(LeafNode {
                     key,
                     callback_info_pt: callback_info_offset,
@@ -985,7 +1325,7 @@ mod tests {
                 });
 
                 println!("key : {:x}", key);
-                // println!("owner : {:?}", &owner.to_bytes());
+                // println!("owner : {:?}", &owner);
                 println!("{}", i);
                 slab.insert_leaf(&leaf).unwrap();
                 model.insert(key, (leaf, owner)).ok_or(()).unwrap_err();
@@ -1002,7 +1342,7 @@ mod tests {
                         .map(|s| {
                             (
                                 s.to_owned(),
-                                Pubkey::new(slab.get_callback_info(
+                                <[u8; 32]>::from_bytes(slab.get_callback_info(
                                     s.as_leaf().unwrap().callback_info_pt as usize,
                                 )),
                             )
@@ -1014,7 +1354,7 @@ mod tests {
                 // test find_min
                 let slab_min = slab.get_node(slab.find_min().unwrap()).unwrap().to_owned();
                 let model_min = model.iter().next().unwrap().1;
-                let owner = Pubkey::new(
+                let owner = <[u8; 32]>::from_bytes(
                     slab.get_callback_info(slab_min.as_leaf().unwrap().callback_info_pt as usize),
                 );
                 assert_eq!(&(slab_min, owner), model_min);
@@ -1022,7 +1362,7 @@ mod tests {
                 // test find_max
                 let slab_max = slab.get_node(slab.find_max().unwrap()).unwrap().to_owned();
                 let model_max = model.iter().next_back().unwrap().1;
-                let owner = Pubkey::new(
+                let owner = <[u8; 32]>::from_bytes(
                     slab.get_callback_info(slab_max.as_leaf().unwrap().callback_info_pt as usize),
                 );
                 assert_eq!(&(slab_max, owner), model_max);
@@ -1030,6 +1370,287 @@ mod tests {
         }
     }
 
+    fn new_test_slab(bytes: &mut [u8], account_tag: u8) -> Slab<&mut [u8]> {
+        let order_capacity = (bytes.len() - PADDED_SLAB_HEADER_LEN) / (SLOT_SIZE * 2 + 32);
+        let callback_memory_offset = PADDED_SLAB_HEADER_LEN + 2 * order_capacity * SLOT_SIZE;
+        Slab {
+            buffer: bytes,
+            callback_info_len: 32,
+            header: SlabHeader {
+                account_tag,
+                bump_index: 0,
+                free_list_len: 0,
+                free_list_head: 0,
+                callback_memory_offset: callback_memory_offset as u64,
+                callback_free_list_len: 0,
+                callback_free_list_head: 0,
+                callback_bump_index: callback_memory_offset as u64,
+                root_node: 0,
+                leaf_count: 0,
+                resting_base_lots: 0,
+                market_address: new_unique_key(),
+            },
+        }
+    }
+
+    fn insert_test_order<S: Storage>(
+        slab: &mut Slab<S>,
+        price: u64,
+        lower: u64,
+        base_quantity: u64,
+    ) -> u128 {
+        let callback_info_pt = slab.write_callback_info(&new_unique_key()).unwrap();
+        let key = ((price as u128) << 64) | lower as u128;
+        slab.insert_leaf(&Node::Leaf(LeafNode {
+            key,
+            callback_info_pt,
+            base_quantity,
+        }))
+        .unwrap();
+        key
+    }
+
+    #[test]
+    fn hinted_inserts() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut bytes = vec![0u8; 80_000];
+        let mut plain = new_test_slab(&mut bytes, ASKS);
+        let mut hinted_bytes = vec![0u8; 80_000];
+        let mut hinted = new_test_slab(&mut hinted_bytes, ASKS);
+
+        let mut hint = 0;
+        for sequence in 0..200u64 {
+            let price = 1_000 + rng.gen_range(0..4);
+            let key = ((price as u128) << 64) | sequence as u128;
+            let callback_info = new_unique_key();
+            let callback_info_pt = plain.write_callback_info(&callback_info).unwrap();
+            assert_eq!(
+                hinted.write_callback_info(&callback_info),
+                Ok(callback_info_pt)
+            );
+            let leaf = Node::Leaf(LeafNode {
+                key,
+                callback_info_pt,
+                base_quantity: sequence,
+            });
+            plain.insert_leaf(&leaf).unwrap();
+            hint = hinted.insert_leaf_with_hint(&leaf, hint).unwrap().2;
+            if sequence % 10 == 9 {
+                // Removals free nodes, which leaves the hint stale from time to time
+                let removed =
+                    ((1_000 + rng.gen_range(0..4) as u128) << 64) | (sequence - 5) as u128;
+                assert_eq!(plain.remove_by_key(removed), hinted.remove_by_key(removed));
+            }
+        }
+        assert_eq!(plain.leaves(false), hinted.leaves(false));
+
+        // Hints which don't cover the key fall back to a descent from the root
+        let leaf = Node::Leaf(LeafNode {
+            key: 5 << 64,
+            callback_info_pt: hinted.write_callback_info(&new_unique_key()).unwrap(),
+            base_quantity: 1,
+        });
+        hinted.insert_leaf_with_hint(&leaf, u32::MAX).unwrap();
+        assert_eq!(hinted.find_min(), hinted.find_by_key(5 << 64));
+    }
+
+    #[test]
+    fn remove_with_callback() {
+        let mut bytes = vec![0u8; 80_000];
+        let mut slab = new_test_slab(&mut bytes, ASKS);
+        let mut orders = vec![];
+        for lower in 0..3 {
+            let callback_info = new_unique_key();
+            let callback_info_pt = slab.write_callback_info(&callback_info).unwrap();
+            let leaf = LeafNode {
+                key: (10 << 64) | lower,
+                callback_info_pt,
+                base_quantity: 5,
+            };
+            slab.insert_leaf(&Node::Leaf(leaf)).unwrap();
+            orders.push((leaf, callback_info));
+        }
+
+        // Removes inner leaves first, then the root leaf
+        for (leaf, callback_info) in [orders[1], orders[0], orders[2]] {
+            assert_eq!(
+                slab.remove_by_key_with_callback(leaf.key),
+                Some((Node::Leaf(leaf), callback_info.to_vec()))
+            );
+        }
+        assert_eq!(slab.remove_by_key_with_callback(orders[0].0.key), None);
+        assert_eq!(slab.root(), None);
+    }
+
+    #[test]
+    fn size_priority_within_level() {
+        let key_of =
+            |slab: &Slab<&mut [u8]>, h: NodeHandle| slab.get_node(h).unwrap().key().unwrap();
+
+        // Asks: sequence numbers are stored as is, the earliest order has the smallest key
+        let mut bytes = vec![0u8; 80_000];
+        let mut asks = new_test_slab(&mut bytes, ASKS);
+        insert_test_order(&mut asks, 20, 0, 1_000);
+        let fifo_first = insert_test_order(&mut asks, 10, 1, 5);
+        let largest_first = insert_test_order(&mut asks, 10, 2, 50);
+        insert_test_order(&mut asks, 10, 3, 50);
+        insert_test_order(&mut asks, 10, 4, 7);
+
+        assert_eq!(key_of(&asks, asks.find_min().unwrap()), fifo_first);
+        assert_eq!(
+            key_of(&asks, asks.find_largest_at_price(10, false).unwrap()),
+            largest_first
+        );
+        assert_eq!(asks.find_largest_at_price(15, false), None);
+
+        // Bids: sequence numbers are inverted, the earliest order has the largest key
+        let mut bytes = vec![0u8; 80_000];
+        let mut bids = new_test_slab(&mut bytes, BIDS);
+        insert_test_order(&mut bids, 5, !0, 1_000);
+        let fifo_first = insert_test_order(&mut bids, 10, !1, 5);
+        let largest_first = insert_test_order(&mut bids, 10, !2, 50);
+        insert_test_order(&mut bids, 10, !3, 50);
+        insert_test_order(&mut bids, 10, !4, 7);
+
+        assert_eq!(key_of(&bids, bids.find_max().unwrap()), fifo_first);
+        assert_eq!(
+            key_of(&bids, bids.find_largest_at_price(10, true).unwrap()),
+            largest_first
+        );
+    }
+
+    #[test]
+    fn size_priority_depth() {
+        let key_of =
+            |slab: &Slab<&mut [u8]>, h: NodeHandle| slab.get_node(h).unwrap().key().unwrap();
+
+        // Only the earliest orders of the level are compared, the larger order behind them waits for its turn
+        let mut bytes = vec![0u8; 80_000];
+        let mut asks = new_test_slab(&mut bytes, ASKS);
+        let keys: Vec<u128> = (0..SIZE_PRIORITY_DEPTH as u64)
+            .map(|sequence_number| insert_test_order(&mut asks, 10, sequence_number, 5))
+            .collect();
+        let late = insert_test_order(&mut asks, 10, SIZE_PRIORITY_DEPTH as u64, 50);
+        assert_eq!(
+            key_of(&asks, asks.find_largest_at_price(10, false).unwrap()),
+            keys[0]
+        );
+
+        // Once the front of the level is taken, the larger order comes within reach
+        asks.remove_by_key(keys[0]).unwrap();
+        assert_eq!(
+            key_of(&asks, asks.find_largest_at_price(10, false).unwrap()),
+            late
+        );
+    }
+
+    #[test]
+    fn leaves_in_key_order() {
+        let mut bytes = vec![0u8; 80_000];
+        let mut slab = new_test_slab(&mut bytes, ASKS);
+        let mut keys: Vec<u128> = [(30, 0), (10, 1), (20, 2), (10, 3)]
+            .iter()
+            .map(|&(price, lower)| insert_test_order(&mut slab, price, lower, 1))
+            .collect();
+        keys.sort_unstable();
+
+        let ascending: Vec<u128> = slab.leaves(false).iter().map(|l| l.key).collect();
+        assert_eq!(ascending, keys);
+        keys.reverse();
+        let descending: Vec<u128> = slab.leaves(true).iter().map(|l| l.key).collect();
+        assert_eq!(descending, keys);
+    }
+
+    #[test]
+    fn price_levels_aggregation() {
+        let mut bytes = vec![0u8; 80_000];
+        let mut slab = new_test_slab(&mut bytes, ASKS);
+        for &(price, lower, base_quantity) in
+            &[(30, 0, 1), (10, 1, 2), (20, 2, 4), (10, 3, 8), (40, 4, 16)]
+        {
+            insert_test_order(&mut slab, price, lower, base_quantity);
+        }
+
+        assert_eq!(slab.price_levels(false, 2), vec![(10, 10), (20, 4)]);
+        assert_eq!(slab.price_levels(true, 3), vec![(40, 16), (30, 1), (20, 4)]);
+        assert_eq!(slab.price_levels(false, 10).len(), 4);
+        assert!(slab.price_levels(false, 0).is_empty());
+    }
+
+    #[test]
+    fn relocate_after_growth() {
+        let callback_infos = |slab: &Slab<&mut [u8]>| -> Vec<(u128, Vec<u8>)> {
+            slab.leaves(false)
+                .iter()
+                .map(|l| {
+                    let callback_info = slab.get_callback_info(l.callback_info_pt as usize);
+                    (l.key, callback_info.to_vec())
+                })
+                .collect()
+        };
+
+        let old_len = Slab::compute_allocation_size(8, 32);
+        let mut bytes = vec![0u8; old_len];
+        let mut slab = new_test_slab(&mut bytes, ASKS);
+        let keys: Vec<u128> = (0..8)
+            .map(|i| insert_test_order(&mut slab, 10 + i, i, 1))
+            .collect();
+        slab.remove_by_key(keys[2]).unwrap();
+        slab.remove_by_key(keys[5]).unwrap();
+        slab.write_header();
+        let expected = callback_infos(&slab);
+
+        let mut grown = vec![0u8; Slab::compute_allocation_size(16, 32)];
+        grown[..old_len].copy_from_slice(&bytes);
+        let mut slab = Slab::new(&mut grown[..], 32).unwrap();
+        slab.relocate_after_growth();
+        assert_eq!(callback_infos(&slab), expected);
+
+        // The freed callback memory is reused first, then the slab fills up to its new capacity
+        for i in 0..10 {
+            insert_test_order(&mut slab, 100 + i, i, 1);
+        }
+        assert!(slab.write_callback_info(&[0; 32]).is_err());
+        assert_eq!(slab.header.bump_index, slab.node_capacity());
+        let callback_infos = callback_infos(&slab);
+        assert_eq!(callback_infos.len(), 16);
+        assert_eq!(&callback_infos[..6], &expected[..]);
+
+        let slab = Slab::new(&mut grown[..], 32).unwrap();
+        assert_eq!(
+            slab.header.callback_memory_offset as usize,
+            PADDED_SLAB_HEADER_LEN + 32 * SLOT_SIZE
+        );
+    }
+
+    #[test]
+    fn in_memory_slab() {
+        let mut slab = Slab::new_in_memory(4, 32, ASKS, [7; 32]);
+        assert_eq!(slab.capacity(), 4);
+        assert_eq!(slab.market_address(), &[7; 32]);
+        for i in 0..4 {
+            insert_test_order(&mut slab, 10 + i, i, 2);
+        }
+        slab.check_invariants();
+        // The tree of the four orders uses up the node arena
+        let leaf = Node::Leaf(LeafNode {
+            key: 20 << 64,
+            callback_info_pt: 0,
+            base_quantity: 1,
+        });
+        assert_eq!(slab.insert_leaf(&leaf).err(), Some(SlabError::OutOfSpace));
+        assert_eq!(slab.resting_base_lots(), 8);
+        slab.set_leaf_base_quantity(slab.find_min().unwrap(), 5);
+        slab.check_invariants();
+        assert_eq!(slab.resting_base_lots(), 11);
+
+        // The heap buffer is laid out exactly like an account's data
+        slab.write_header();
+        let slab = Slab::new(&mut slab.buffer[..], 32).unwrap();
+        assert_eq!(slab.account_tag(), ASKS);
+        assert_eq!(slab.price_levels(false, 1), vec![(10, 5)]);
+    }
+
     #[test]
     fn simulate_operations() {
         use rand::distributions::WeightedIndex;
@@ -1044,7 +1665,7 @@ mod tests {
             buffer: slab_data,
             callback_info_len: 32,
             header: SlabHeader {
-                account_tag: AccountTag::Asks,
+                account_tag: ASKS,
                 bump_index: 0,
                 free_list_len: 0,
                 free_list_head: 0,
@@ -1054,10 +1675,11 @@ mod tests {
                 callback_bump_index: callback_memory_offset as u64,
                 root_node: 0,
                 leaf_count: 0,
-                market_address: Pubkey::new_unique(),
+                resting_base_lots: 0,
+                market_address: new_unique_key(),
             },
         };
-        let mut model: BTreeMap<u128, (Node, Pubkey)> = BTreeMap::new();
+        let mut model: BTreeMap<u128, (Node, [u8; 32])> = BTreeMap::new();
 
         let mut all_keys = vec![];
         let mut rng = StdRng::seed_from_u64(1);
@@ -1095,7 +1717,7 @@ mod tests {
             for i in 0..100_000 {
                 slab.check_invariants();
                 let model_state = model.values().collect::<Vec<_>>();
-                let slab_state: Vec<(Node, Pubkey)> = slab.traverse();
+                let slab_state: Vec<(Node, [u8; 32])> = slab.traverse();
                 assert_eq!(model_state, slab_state.iter().collect::<Vec<_>>());
 
                 match weights[dist.sample(&mut rng)].0 {
@@ -1105,10 +1727,9 @@ mod tests {
                             Op::InsertDup => *all_keys.choose(&mut rng).unwrap(),
                             _ => unreachable!(),
                         };
-                        let owner = Pubkey::new_unique();
+                        let owner = new_unique_key();
                         let qty = rng.gen();
-                        let callback_info_offset =
-                            slab.write_callback_info(&owner.to_bytes()).unwrap();
+                        let callback_info_offset = slab.write_callback_info(&owner).unwrap();
                         let leaf = Node::Leaf(LeafNode {
                             key,
                             callback_info_pt: callback_info_offset,
@@ -1122,7 +1743,7 @@ mod tests {
                             .insert_leaf(&leaf)
                             .map(|(_, n)| {
                                 n.map(|node| {
-                                    let owner = Pubkey::new(slab.get_callback_info(
+                                    let owner = <[u8; 32]>::from_bytes(slab.get_callback_info(
                                         node.as_leaf().unwrap().callback_info_pt as usize,
                                     ));
                                     (node, owner)
@@ -1153,7 +1774,7 @@ mod tests {
                         } else {
                             let slab_min =
                                 slab.get_node(slab.find_min().unwrap()).unwrap().to_owned();
-                            let owner = Pubkey::new(slab.get_callback_info(
+                            let owner = <[u8; 32]>::from_bytes(slab.get_callback_info(
                                 slab_min.as_leaf().unwrap().callback_info_pt as usize,
                             ));
                             let model_min = model.iter().next().unwrap().1;
@@ -1166,7 +1787,7 @@ mod tests {
                         } else {
                             let slab_max =
                                 slab.get_node(slab.find_max().unwrap()).unwrap().to_owned();
-                            let owner = Pubkey::new(slab.get_callback_info(
+                            let owner = <[u8; 32]>::from_bytes(slab.get_callback_info(
                                 slab_max.as_leaf().unwrap().callback_info_pt as usize,
                             ));
                             let model_max = model.iter().next_back().unwrap().1;
@@ -0,0 +1,29 @@
+//! Errors of the orderbook core, which the programs map to their own error codes.
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlabError {
+    /// The slab has no room left for another order or its callback information
+    OutOfSpace,
+    /// The buffer is too small to hold a slab header
+    FailedToDeserialize,
+}
+
+impl fmt::Display for SlabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlabError::OutOfSpace => f.write_str("The slab is out of space"),
+            SlabError::FailedToDeserialize => f.write_str("Failed to deserialize the slab header"),
+        }
+    }
+}
+
+/// A fixed point operation whose result doesn't fit into a quantity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumericalOverflow;
+
+impl fmt::Display for NumericalOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("A numerical operation overflowed")
+    }
+}
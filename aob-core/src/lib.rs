@@ -0,0 +1,16 @@
+/*!
+The Solana-free core of the agnostic orderbook: the critbit slabs which hold the resting orders, and the fixed point
+arithmetic of the matching engine.
+
+Everything works on plain byte buffers laid out exactly like the on-chain accounts, see [`storage::Storage`], so
+that exchanges can replay the orderbook off-chain with the same code the programs run. The crate is `no_std` and
+only needs an allocator.
+*/
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod critbit;
+pub mod error;
+pub mod storage;
+pub mod utils;
@@ -0,0 +1,11 @@
+//! The memory which the orderbook structures are laid out in.
+use core::ops::DerefMut;
+
+/// A contiguous byte buffer holding a slab or an event queue.
+///
+/// On-chain, the storage is the data of an account, borrowed as a `&mut [u8]`. Off-chain, a heap-allocated
+/// `Vec<u8>` can be used directly, so that simulations and tests run the real engine without any account. Any
+/// buffer which dereferences to a byte slice is a storage.
+pub trait Storage: DerefMut<Target = [u8]> {}
+
+impl<T: DerefMut<Target = [u8]>> Storage for T {}
@@ -0,0 +1,194 @@
+use crate::error::NumericalOverflow;
+
+/// A base or quote quantity, in native units.
+///
+/// With the `u128-quantities` feature, quantities are 128 bits wide, so that the quote quantities of tokens with very
+/// large supplies don't overflow. Order sizes in the slabs remain 64-bit numbers of base lots.
+#[cfg(not(feature = "u128-quantities"))]
+pub type Quantity = u64;
+/// A base or quote quantity, in native units, 128 bits wide since the `u128-quantities` feature is enabled.
+#[cfg(feature = "u128-quantities")]
+pub type Quantity = u128;
+
+/// a is fp0, b is fp32 and result is a/b fp0, saturated to `u64::MAX`
+#[cfg(not(feature = "u128-quantities"))]
+pub fn fp32_div(a: Quantity, b_fp32: u64) -> Quantity {
+    (((a as u128) << 32) / (b_fp32 as u128)).min(u64::MAX as u128) as u64
+}
+
+/// a is fp0, b is fp32 and result is a/b fp0, saturated to `u128::MAX`
+#[cfg(feature = "u128-quantities")]
+pub fn fp32_div(a: Quantity, b_fp32: u64) -> Quantity {
+    fp32_div_floor(a, b_fp32).unwrap_or(u128::MAX)
+}
+
+/// a is fp0, b is fp32 and result is a*b fp0
+#[cfg(not(feature = "u128-quantities"))]
+pub fn fp32_mul(a: Quantity, b_fp32: u64) -> Quantity {
+    (((a as u128) * (b_fp32 as u128)) >> 32) as u64
+}
+
+/// a is fp0, b is fp32 and result is a*b fp0, saturated to `u128::MAX`
+#[cfg(feature = "u128-quantities")]
+pub fn fp32_mul(a: Quantity, b_fp32: u64) -> Quantity {
+    fp32_mul_floor(a, b_fp32).unwrap_or(u128::MAX)
+}
+
+/// a is fp0, b is fp32 and result is a/b fp0 rounded down, failing when b is zero or the result doesn't fit into a
+/// [`Quantity`]
+#[cfg(not(feature = "u128-quantities"))]
+pub fn fp32_div_floor(a: Quantity, b_fp32: u64) -> Result<Quantity, NumericalOverflow> {
+    if b_fp32 == 0 {
+        return Err(NumericalOverflow);
+    }
+    u64::try_from(((a as u128) << 32) / (b_fp32 as u128)).map_err(|_| NumericalOverflow)
+}
+
+/// a is fp0, b is fp32 and result is a/b fp0 rounded down, failing when b is zero or the result doesn't fit into a
+/// [`Quantity`]
+#[cfg(feature = "u128-quantities")]
+pub fn fp32_div_floor(a: Quantity, b_fp32: u64) -> Result<Quantity, NumericalOverflow> {
+    if b_fp32 == 0 {
+        return Err(NumericalOverflow);
+    }
+    // Dividing before shifting keeps every intermediate result within 128 bits
+    let b = b_fp32 as u128;
+    (a / b)
+        .checked_mul(1 << 32)
+        .and_then(|q| q.checked_add(((a % b) << 32) / b))
+        .ok_or(NumericalOverflow)
+}
+
+/// a is fp0, b is fp32 and result is a*b fp0 rounded down, failing when the result doesn't fit into a [`Quantity`]
+#[cfg(not(feature = "u128-quantities"))]
+pub fn fp32_mul_floor(a: Quantity, b_fp32: u64) -> Result<Quantity, NumericalOverflow> {
+    u64::try_from(((a as u128) * (b_fp32 as u128)) >> 32).map_err(|_| NumericalOverflow)
+}
+
+/// a is fp0, b is fp32 and result is a*b fp0 rounded down, failing when the result doesn't fit into a [`Quantity`]
+#[cfg(feature = "u128-quantities")]
+pub fn fp32_mul_floor(a: Quantity, b_fp32: u64) -> Result<Quantity, NumericalOverflow> {
+    // The product is split over the high and low 64 bits of a, so that each partial product fits into 128 bits
+    let b = b_fp32 as u128;
+    let low = ((a & u64::MAX as u128) * b) >> 32;
+    (a >> 64)
+        .checked_mul(b)
+        .filter(|high| *high < 1 << 96)
+        .and_then(|high| (high << 32).checked_add(low))
+        .ok_or(NumericalOverflow)
+}
+
+/// a is fp0, b is fp32 and result is a*b fp0 rounded up, failing when the result doesn't fit into a [`Quantity`]
+///
+/// The matching engine rounds the quote quantities which a taker or a bid pays up, and the ones it receives down,
+/// so that rounding never credits more quote than was debited.
+#[allow(clippy::unnecessary_cast)]
+pub fn fp32_mul_ceil(a: Quantity, b_fp32: u64) -> Result<Quantity, NumericalOverflow> {
+    // Only the low 64 bits of a contribute to the fractional part of the product
+    let fractional = ((a as u128 & u64::MAX as u128) * b_fp32 as u128) & 0xffff_ffff;
+    let floor = fp32_mul_floor(a, b_fp32)?;
+    if fractional == 0 {
+        Ok(floor)
+    } else {
+        floor.checked_add(1).ok_or(NumericalOverflow)
+    }
+}
+
+/// a is fp0, b is fp32 and result is a/b fp0 rounded up, failing when b is zero or the result doesn't fit into a
+/// [`Quantity`]
+#[allow(clippy::unnecessary_cast)]
+pub fn fp32_div_ceil(a: Quantity, b_fp32: u64) -> Result<Quantity, NumericalOverflow> {
+    let floor = fp32_div_floor(a, b_fp32)?;
+    let b = b_fp32 as Quantity;
+    if (((a % b) as u128) << 32) % b_fp32 as u128 == 0 {
+        Ok(floor)
+    } else {
+        floor.checked_add(1).ok_or(NumericalOverflow)
+    }
+}
+
+/// Rounds a given base quantity down to a multiple of the market's lot size
+pub fn round_to_lot(base_qty: Quantity, base_lot_size: u64) -> Quantity {
+    base_qty - base_qty % base_lot_size as Quantity
+}
+
+/// Converts a number of base lots, as the slabs store order sizes, into a base quantity
+#[allow(clippy::unnecessary_cast)]
+pub fn lots_to_base_qty(base_lots: u64, base_lot_size: u64) -> Quantity {
+    base_lots as Quantity * base_lot_size as Quantity
+}
+
+/// Converts a base quantity into a number of base lots, rounding down
+///
+/// The base quantity must be small enough for the number of lots to fit into a `u64`, which the order bounds
+/// guarantee.
+#[allow(clippy::unnecessary_cast)]
+pub fn base_qty_to_lots(base_qty: Quantity, base_lot_size: u64) -> u64 {
+    (base_qty / base_lot_size as Quantity) as u64
+}
+
+/// The low 64 bits of a quantity, which feed the wrapping 64-bit volume counters of the market accounts
+#[allow(clippy::unnecessary_cast)]
+pub fn low_u64(qty: Quantity) -> u64 {
+    qty as u64
+}
+
+/// Converts a quantity into a `u64`, saturating, for the interfaces which remain 64-bit
+#[allow(clippy::unnecessary_cast)]
+pub fn saturating_u64(qty: Quantity) -> u64 {
+    qty.min(u64::MAX as Quantity) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_fp32_math() {
+        let price = 3 << 31; // 1.5
+        assert_eq!(fp32_mul_floor(10, price), Ok(15));
+        assert_eq!(fp32_div_floor(15, price), Ok(10));
+        assert_eq!(
+            fp32_mul_floor(Quantity::MAX, 2 << 32),
+            Err(NumericalOverflow)
+        );
+        assert_eq!(
+            fp32_div_floor(Quantity::MAX, 1 << 31),
+            Err(NumericalOverflow)
+        );
+        assert_eq!(fp32_div_floor(1, 0), Err(NumericalOverflow));
+    }
+
+    #[test]
+    fn rounding_directions() {
+        let price = (1 << 32) / 3; // Slightly under 1/3
+        assert_eq!(fp32_mul_floor(3, price), Ok(0));
+        assert_eq!(fp32_mul_ceil(3, price), Ok(1));
+        assert_eq!(fp32_div_floor(1, price), Ok(3));
+        assert_eq!(fp32_div_ceil(1, price), Ok(4));
+
+        // Exact results aren't rounded
+        let price = 3 << 31; // 1.5
+        assert_eq!(fp32_mul_ceil(10, price), Ok(15));
+        assert_eq!(fp32_div_ceil(15, price), Ok(10));
+
+        assert_eq!(fp32_mul_ceil(Quantity::MAX, 1 << 32), Ok(Quantity::MAX));
+        assert_eq!(
+            fp32_mul_ceil(Quantity::MAX, (1 << 32) + 1),
+            Err(NumericalOverflow)
+        );
+        assert_eq!(fp32_div_ceil(1, 0), Err(NumericalOverflow));
+    }
+
+    #[cfg(feature = "u128-quantities")]
+    #[test]
+    fn wide_fp32_math() {
+        let price = 3 << 31; // 1.5
+        for a in [0, 8, 1 << 64, 1 << 100] {
+            assert_eq!(fp32_mul(a, price), a + a / 2);
+            assert_eq!(fp32_div(fp32_mul(a, price), price), a);
+        }
+        assert_eq!(fp32_mul(u128::MAX, u64::MAX), u128::MAX);
+        assert_eq!(fp32_div(u128::MAX, 1), u128::MAX);
+    }
+}
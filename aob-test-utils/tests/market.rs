@@ -0,0 +1,58 @@
+use aob::state::Side;
+use aob_test_utils::{add_program, market_params, TestMarket};
+use solana_program_test::ProgramTest;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn bootstrap_market() {
+    let mut program_test = ProgramTest::default();
+    add_program(&mut program_test);
+    let mut ctx = program_test.start_with_context().await;
+
+    let caller_authority = Keypair::new();
+    let params = market_params(caller_authority.pubkey());
+    let mut market = TestMarket::create(&mut ctx, caller_authority, params)
+        .await
+        .unwrap();
+    market.create_fee_vault(&mut ctx).await.unwrap();
+    market.deposit_fee(&mut ctx, 1_000_000).await.unwrap();
+
+    let ask = market
+        .new_order(&mut ctx, market.limit_order(Side::Ask, 10 << 32, 100))
+        .await
+        .unwrap();
+    assert_eq!(ask.total_base_qty_posted, 100);
+    let bid = market
+        .new_order(&mut ctx, market.limit_order(Side::Bid, 10 << 32, 40))
+        .await
+        .unwrap();
+    assert_eq!(bid.posted_order_id, None);
+    assert_eq!(bid.total_base_qty, 40);
+    assert_eq!(bid.total_quote_qty, 400);
+
+    // One fill
+    assert_eq!(market.crank(&mut ctx).await.unwrap(), 1);
+    assert_eq!({ market.event_queue_header(&mut ctx).await.count }, 0);
+
+    let summary = market
+        .cancel_order(&mut ctx, ask.posted_order_id.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(summary.total_base_qty, 60);
+    let loader = market.load(&mut ctx).await;
+    assert!(loader.orders(Side::Ask).is_empty());
+    assert_eq!(market.crank(&mut ctx).await.unwrap(), 0);
+
+    // Reducing an order releases the difference through an out event, and leaves it resting
+    let ask = market
+        .new_order(&mut ctx, market.limit_order(Side::Ask, 11 << 32, 50))
+        .await
+        .unwrap();
+    let ask = ask.posted_order_id.unwrap();
+    let summary = market.reduce_order(&mut ctx, ask, 20).await.unwrap();
+    assert_eq!(summary.total_base_qty, 30);
+    assert_eq!(summary.total_quote_qty, 330);
+    assert_eq!(market.crank(&mut ctx).await.unwrap(), 1);
+    let loader = market.load(&mut ctx).await;
+    assert_eq!(loader.orders(Side::Ask), &[(ask, 20)]);
+}
@@ -0,0 +1,183 @@
+//! Clients which follow a market's accounts over RPC, for indexers which build trade feeds.
+//!
+//! The event queue doesn't keep a count of the events pushed to it, so an [`EventCursor`] tells the new events
+//! apart from the ones it already yielded by tracking where the queue's tail was at the previous poll, along with
+//! the queue's sequence number. Since the sequence number is incremented for every event pushed, the tail can only
+//! have wrapped around the whole queue since the previous poll if the sequence number moved by at least the
+//! queue's capacity. In that case, the cursor checks whether the slot before the previous tail was overwritten.
+use anchor_lang::prelude::Pubkey;
+#[cfg(feature = "client")]
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::RpcClient,
+};
+
+use crate::{
+    critbit::Storage,
+    state::{Event, EventQueue},
+};
+
+/// The events which were pushed to the event queue since the previous poll.
+#[derive(Debug)]
+pub struct EventBatch {
+    /// The new events which are still in the queue, oldest first
+    pub events: Vec<Event>,
+    /// The number of new events which were consumed or evicted before they could be read. When the queue has been
+    /// cycled through entirely since the previous poll, this is a lower bound.
+    pub missed_events: u64,
+}
+
+/// Where the queue's tail was at the previous poll
+struct Position {
+    seq_num: u64,
+    capacity: u64,
+    tail_slot: u64,
+    /// The content of the slot before the tail
+    last_slot_data: Vec<u8>,
+}
+
+/// Follows an event queue, and yields each event pushed to it once.
+///
+/// The first poll yields the events which are in the queue. The cursor starts over in the same way when the queue
+/// has been grown, or when its account has been reinitialized.
+pub struct EventCursor {
+    #[allow(missing_docs)]
+    pub event_queue: Pubkey,
+    #[allow(missing_docs)]
+    pub callback_info_len: usize,
+    position: Option<Position>,
+}
+
+impl EventCursor {
+    #[allow(missing_docs)]
+    pub fn new(event_queue: Pubkey, callback_info_len: usize) -> Self {
+        Self {
+            event_queue,
+            callback_info_len,
+            position: None,
+        }
+    }
+
+    /// Fetches the event queue account and yields the events pushed since the previous poll.
+    #[cfg(feature = "client")]
+    pub fn poll(&mut self, rpc_client: &RpcClient) -> Result<EventBatch, ClientError> {
+        let data = rpc_client.get_account_data(&self.event_queue)?;
+        let event_queue = EventQueue::from_bytes(&data, self.callback_info_len)
+            .map_err(|e| ClientErrorKind::Custom(e.to_string()))?;
+        Ok(self.advance(&event_queue))
+    }
+
+    /// Yields the events pushed to a freshly loaded event queue since the previous poll.
+    pub fn advance(&mut self, event_queue: &EventQueue<impl Storage>) -> EventBatch {
+        let seq_num = event_queue.header.seq_num();
+        let capacity = event_queue.capacity();
+        let count = event_queue.header.count;
+        let tail_slot = (event_queue.head_slot() + count) % capacity;
+
+        let pushed = match &self.position {
+            Some(position) if position.capacity == capacity && position.seq_num <= seq_num => {
+                let slots = (tail_slot + capacity - position.tail_slot) % capacity;
+                let last_slot = (position.tail_slot + capacity - 1) % capacity;
+                let wrapped = seq_num - position.seq_num >= capacity
+                    && event_queue.slot_data(last_slot) != position.last_slot_data;
+                if wrapped {
+                    slots + capacity
+                } else {
+                    slots
+                }
+            }
+            _ => count,
+        };
+        let new_events = pushed.min(count);
+
+        self.position = Some(Position {
+            seq_num,
+            capacity,
+            tail_slot,
+            last_slot_data: event_queue.slot_data((tail_slot + capacity - 1) % capacity),
+        });
+        EventBatch {
+            events: event_queue
+                .peek_n(count)
+                .into_iter()
+                .skip((count - new_events) as usize)
+                .collect(),
+            missed_events: pushed - new_events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{EventQueueOverflowPolicy, OrderId, Side};
+
+    fn out_event(sequence: u64) -> Event {
+        Event::Out {
+            side: Side::Ask,
+            order_id: OrderId::new(1 << 32, Side::Ask, sequence),
+            base_size: 1,
+            delete: true,
+            slot: 0,
+            unix_timestamp: 0,
+            callback_info: vec![0; 8],
+        }
+    }
+
+    fn sequences(batch: &EventBatch) -> Vec<u64> {
+        batch
+            .events
+            .iter()
+            .map(|event| match event {
+                Event::Out { order_id, .. } => order_id.sequence(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn event_cursor() {
+        let mut event_queue = EventQueue::new_in_memory(4, 8);
+        event_queue.overflow_policy = EventQueueOverflowPolicy::EvictOldest;
+        let mut cursor = EventCursor::new(Pubkey::new_unique(), 8);
+        let mut sequence = 0;
+        let mut push = |event_queue: &mut EventQueue<Vec<u8>>, n: u64| {
+            for _ in 0..n {
+                event_queue.push_back(out_event(sequence)).unwrap();
+                sequence += 1;
+            }
+        };
+
+        push(&mut event_queue, 2);
+        let batch = cursor.advance(&event_queue);
+        assert_eq!((sequences(&batch), batch.missed_events), (vec![0, 1], 0));
+        let batch = cursor.advance(&event_queue);
+        assert_eq!((sequences(&batch), batch.missed_events), (vec![], 0));
+
+        // Consumed events are only missed if they were pushed after the previous poll
+        event_queue.pop_n(1);
+        push(&mut event_queue, 2);
+        event_queue.pop_n(2);
+        let batch = cursor.advance(&event_queue);
+        assert_eq!((sequences(&batch), batch.missed_events), (vec![3], 1));
+
+        // Order ids bump the sequence number without pushing events
+        for _ in 0..4 {
+            event_queue.gen_order_id(1 << 32, Side::Bid).unwrap();
+        }
+        let batch = cursor.advance(&event_queue);
+        assert_eq!((sequences(&batch), batch.missed_events), (vec![], 0));
+
+        push(&mut event_queue, 3);
+        let batch = cursor.advance(&event_queue);
+        assert_eq!((sequences(&batch), batch.missed_events), (vec![4, 5, 6], 0));
+
+        // The whole queue is cycled through between two polls
+        push(&mut event_queue, 6);
+        let batch = cursor.advance(&event_queue);
+        assert_eq!(
+            (sequences(&batch), batch.missed_events),
+            (vec![9, 10, 11, 12], 2)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! The critbit slabs of [`aob_core`], along with their glue to the Solana accounts which hold them.
+//!
+//! The slab header starts after the space for an Anchor account discriminator, see [`Bids`] and [`Asks`].
+//!
+//! [`Bids`]: crate::state::Bids
+//! [`Asks`]: crate::state::Asks
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::account_info::AccountInfo;
+use anchor_lang::solana_program::pubkey::Pubkey;
+
+pub use aob_core::critbit::*;
+pub use aob_core::error::SlabError;
+pub use aob_core::storage::Storage;
+
+use crate::error::ErrorCode;
+use crate::log_error;
+use crate::state::{AccountTag, OrderId};
+
+pub type IoError = std::io::Error;
+
+/// Reads the order id held by a leaf's key
+pub trait LeafNodeExt {
+    fn order_id(&self) -> OrderId;
+}
+
+impl LeafNodeExt for LeafNode {
+    fn order_id(&self) -> OrderId {
+        OrderId(self.key)
+    }
+}
+
+/// Maps slabs onto the data of the bids and asks accounts.
+pub trait SlabExt<'a> {
+    /// Releases the memory temporarily held by Slab, replacing the memory that was
+    /// originally took out of the account_info
+    fn release(self, account: &AccountInfo<'a>);
+
+    fn check_account_tag(&self, account_tag: AccountTag) -> Result<()>;
+
+    /// Checks that the slab belongs to the given market, so that the bids or asks of another market are never
+    /// mixed into an instruction.
+    fn check_market(&self, market: &Pubkey) -> Result<()>;
+
+    /// Checks that the bids or asks account, as told by `account_tag`, is sized for exactly `order_capacity`
+    /// orders
+    fn check_capacity(
+        account: &AccountInfo,
+        account_tag: AccountTag,
+        order_capacity: u64,
+        callback_info_len: u64,
+    ) -> Result<()>;
+
+    fn initialize(
+        bids_account: &AccountInfo<'a>,
+        asks_account: &AccountInfo<'a>,
+        market_address: Pubkey,
+        callback_info_len: usize,
+    );
+}
+
+impl<'a> SlabExt<'a> for Slab<&'a mut [u8]> {
+    fn release(self, account: &AccountInfo<'a>) {
+        account.data.replace(self.buffer);
+    }
+
+    fn check_account_tag(&self, account_tag: AccountTag) -> Result<()> {
+        account_tag.check(self.account_tag() as u64)
+    }
+
+    fn check_market(&self, market: &Pubkey) -> Result<()> {
+        if *self.market_address() != market.to_bytes() {
+            log_error!("The orderbook account belongs to another market");
+            if self.account_tag() == AccountTag::Asks as u8 {
+                return err!(ErrorCode::WrongAsksAccount);
+            }
+            return err!(ErrorCode::WrongBidsAccount);
+        }
+        Ok(())
+    }
+
+    fn check_capacity(
+        account: &AccountInfo,
+        account_tag: AccountTag,
+        order_capacity: u64,
+        callback_info_len: u64,
+    ) -> Result<()> {
+        let data_len = account.data_len();
+        // The slab's capacity is derived from the account's size, its node arena then holding the `2 *
+        // order_capacity - 1` nodes of a full tree. Any larger account would carry bytes which no order can use.
+        if order_capacity == 0
+            || order_capacity as usize > data_len
+            || data_len
+                != Self::compute_allocation_size(
+                    order_capacity as usize,
+                    callback_info_len as usize,
+                )
+        {
+            log_error!(
+                "The {} account holds {} bytes, which doesn't fit {} orders",
+                if account_tag == AccountTag::Asks {
+                    "asks"
+                } else {
+                    "bids"
+                },
+                data_len,
+                order_capacity
+            );
+            return err!(ErrorCode::InvalidOrderbookSize);
+        }
+        Ok(())
+    }
+
+    fn initialize(
+        bids_account: &AccountInfo<'a>,
+        asks_account: &AccountInfo<'a>,
+        market_address: Pubkey,
+        callback_info_len: usize,
+    ) {
+        Self::initialize_buffer(
+            &mut asks_account.data.borrow_mut(),
+            AccountTag::Asks as u8,
+            market_address.to_bytes(),
+            callback_info_len,
+        );
+        Self::initialize_buffer(
+            &mut bids_account.data.borrow_mut(),
+            AccountTag::Bids as u8,
+            market_address.to_bytes(),
+            callback_info_len,
+        );
+    }
+}
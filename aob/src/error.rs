@@ -0,0 +1,287 @@
+use anchor_lang::error::ERROR_CODE_OFFSET;
+use anchor_lang::prelude::*;
+use aob_core::error::{NumericalOverflow, SlabError};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+#[error_code]
+#[derive(FromPrimitive, PartialEq)]
+pub enum ErrorCode {
+    #[msg("This account is already initialized")]
+    AlreadyInitialized,
+    #[msg("An invalid bids account has been provided.")]
+    WrongBidsAccount,
+    #[msg("An invalid asks account has been provided.")]
+    WrongAsksAccount,
+    #[msg("An invalid event queue account has been provided.")]
+    WrongEventQueueAccount,
+    #[msg("An invalid caller authority account has been provided.")]
+    WrongCallerAuthority,
+    #[msg("The event queue is full.")]
+    EventQueueFull,
+    #[msg("The order could not be found.")]
+    OrderNotFound,
+    #[msg("The order would self trade.")]
+    WouldSelfTrade,
+    #[msg("The market's memory is full.")]
+    SlabOutOfSpace,
+    #[msg("The due fee was not payed.")]
+    FeeNotPayed,
+    #[msg("This instruction is a No-op.")]
+    NoOperations,
+    #[msg("The market is still active")]
+    MarketStillActive,
+    #[msg("The base quantity must be > 0")]
+    InvalidBaseQuantity,
+    #[msg("The event queue should be owned by the AO program")]
+    WrongEventQueueOwner,
+    #[msg("The bids account should be owned by the AO program")]
+    WrongBidsOwner,
+    #[msg("The asks account should be owned by the AO program")]
+    WrongAsksOwner,
+    #[msg("The market account should be owned by the AO program")]
+    WrongMarketOwner,
+    #[msg("The MSRM token account should be owned by the cranker")]
+    WrongMsrmOwner,
+    #[msg("An invalid MSRM mint has been provided")]
+    WrongMsrmMint,
+    #[msg("The MSRM token account does not have enough balances")]
+    WrongMsrmBalance,
+    #[msg("Illegal MSRM token account owner")]
+    IllegalMsrmOwner,
+    #[msg("Wrong account tag")]
+    WrongAccountTag,
+    #[msg("Failed to deserialize")]
+    FailedToDeserialize,
+    #[msg("The event queue watermarks are invalid")]
+    InvalidWatermarks,
+    #[msg("The event queue size is invalid")]
+    InvalidEventQueueSize,
+    #[msg("The market is locked by an instruction in progress")]
+    MarketLocked,
+    #[msg("The post-only order is too far from the opposite best offer")]
+    PostOnlyTooFar,
+    #[msg("A taker order is pending resumption")]
+    ResumptionPending,
+    #[msg("An invalid resumption ticket has been provided")]
+    InvalidResumptionTicket,
+    #[msg("The base lot size must be > 0")]
+    InvalidBaseLotSize,
+    #[msg("The event queue's event size doesn't match the market's callback info length")]
+    EventSizeMismatch,
+    #[msg("The event queue header is inconsistent with its buffer")]
+    InvalidEventQueueHeader,
+    #[msg("The match limit must be > 0 for orders which aren't post-only")]
+    InvalidMatchLimit,
+    #[msg("The market's trade history account has not been provided")]
+    WrongTradeHistoryAccount,
+    #[msg("Not enough of the market's authority signers have signed")]
+    NotEnoughAuthoritySigners,
+    #[msg("The authority threshold is invalid")]
+    InvalidAuthorityThreshold,
+    #[msg("The market's candles account has not been provided")]
+    WrongCandlesAccount,
+    #[msg("The number of slots per candle must be > 0")]
+    InvalidCandleInterval,
+    #[msg("The market's fee vault account has not been provided")]
+    WrongFeeVaultAccount,
+    #[msg("The market account has the wrong account tag")]
+    WrongMarketTag,
+    #[msg("The event queue account has the wrong account tag")]
+    WrongEventQueueTag,
+    #[msg("The bids account has the wrong account tag")]
+    WrongBidsTag,
+    #[msg("The asks account has the wrong account tag")]
+    WrongAsksTag,
+    #[msg("The trade history account has the wrong account tag")]
+    WrongTradeHistoryTag,
+    #[msg("The candles account has the wrong account tag")]
+    WrongCandlesTag,
+    #[msg("The fee vault account has the wrong account tag")]
+    WrongFeeVaultTag,
+    #[msg("The limit price is not a multiple of the tick size")]
+    OffTickPrice,
+    #[msg("The limit price is out of bounds")]
+    InvalidLimitPrice,
+    #[msg("The order quantity is too large")]
+    QuantityTooLarge,
+    #[msg("The orderbook account is too small for the requested order capacity")]
+    InvalidOrderbookSize,
+    #[msg("The best price of the orderbook doesn't satisfy the bound")]
+    BestPriceOutOfBounds,
+    #[msg("The order's callback id doesn't match the expected one")]
+    WrongCallbackId,
+    #[msg("A numerical operation overflowed")]
+    NumericalOverflow,
+    #[msg("The tick size must be > 0")]
+    InvalidTickSize,
+    #[msg("The minimum base order size must be > 0")]
+    InvalidMinBaseOrderSize,
+    #[msg("The callback id length exceeds the callback information length")]
+    InvalidCallbackIdLen,
+    #[msg("The callback information length exceeds the maximum")]
+    CallbackInfoTooLong,
+    #[msg("A market account isn't rent exempt")]
+    AccountNotRentExempt,
+    #[msg("The market ran out of order sequence numbers")]
+    SequenceNumbersExhausted,
+    #[msg("The market is closed")]
+    MarketClosed,
+    #[msg("The market can't move to this trading session from its current one")]
+    InvalidSessionTransition,
+    #[msg("The self trade id length of an order must be between 1 and the market's callback id length")]
+    InvalidSelfTradeIdLen,
+    #[msg("The reduced size of an order must be below its size and above the market's minimum order size")]
+    InvalidReducedSize,
+}
+
+/// The failures of both the Anchor and the native AO programs.
+///
+/// Both programs fail with the variants of [`ErrorCode`], the native program re-exporting it as `AoError`. The
+/// custom error codes of the native program are the ones of the Anchor program minus Anchor's
+/// [`ERROR_CODE_OFFSET`], so clients can decode either program's failures into an [`AobError`] with
+/// [`ErrorCode::from_anchor_code`] or [`ErrorCode::from_native_code`].
+///
+/// The codes are part of the programs' interface: new variants are only ever appended to [`ErrorCode`].
+pub type AobError = ErrorCode;
+
+impl ErrorCode {
+    /// Decodes the custom error code of a failed instruction of the Anchor program.
+    pub fn from_anchor_code(code: u32) -> Option<Self> {
+        code.checked_sub(ERROR_CODE_OFFSET)
+            .and_then(Self::from_native_code)
+    }
+
+    /// Decodes the custom error code of a failed instruction of the native program.
+    pub fn from_native_code(code: u32) -> Option<Self> {
+        Self::from_u32(code)
+    }
+
+    /// The custom error code which the native program returns for this error.
+    pub fn native_code(self) -> u32 {
+        self as u32
+    }
+}
+
+impl From<SlabError> for ErrorCode {
+    fn from(error: SlabError) -> Self {
+        match error {
+            SlabError::OutOfSpace => ErrorCode::SlabOutOfSpace,
+            SlabError::FailedToDeserialize => ErrorCode::FailedToDeserialize,
+        }
+    }
+}
+
+impl From<NumericalOverflow> for ErrorCode {
+    fn from(_: NumericalOverflow) -> Self {
+        ErrorCode::NumericalOverflow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes() {
+        let mut count = 0;
+        while let Some(error) = ErrorCode::from_native_code(count) {
+            assert_eq!(error.native_code(), count);
+            assert_eq!(u32::from(error), ERROR_CODE_OFFSET + count);
+            assert_eq!(ErrorCode::from_anchor_code(u32::from(error)), Some(error));
+            count += 1;
+        }
+        assert_eq!(
+            ErrorCode::from_native_code(count - 1),
+            Some(ErrorCode::InvalidReducedSize)
+        );
+        assert_eq!(ErrorCode::from_anchor_code(count), None);
+        assert_eq!(ErrorCode::from_anchor_code(ERROR_CODE_OFFSET + count), None);
+
+        // The codes returned by the native program
+        assert_eq!(ErrorCode::AlreadyInitialized.native_code(), 0);
+        assert_eq!(ErrorCode::FeeNotPayed.native_code(), 9);
+        assert_eq!(ErrorCode::WrongEventQueueOwner.native_code(), 13);
+        assert_eq!(ErrorCode::WrongMarketOwner.native_code(), 16);
+        assert_eq!(ErrorCode::InvalidBaseLotSize.native_code(), 29);
+    }
+
+    #[test]
+    fn stable_error_codes() {
+        // Clients match on these codes: variants must never be reordered, removed or inserted in the middle
+        let errors = [
+            ErrorCode::AlreadyInitialized,
+            ErrorCode::WrongBidsAccount,
+            ErrorCode::WrongAsksAccount,
+            ErrorCode::WrongEventQueueAccount,
+            ErrorCode::WrongCallerAuthority,
+            ErrorCode::EventQueueFull,
+            ErrorCode::OrderNotFound,
+            ErrorCode::WouldSelfTrade,
+            ErrorCode::SlabOutOfSpace,
+            ErrorCode::FeeNotPayed,
+            ErrorCode::NoOperations,
+            ErrorCode::MarketStillActive,
+            ErrorCode::InvalidBaseQuantity,
+            ErrorCode::WrongEventQueueOwner,
+            ErrorCode::WrongBidsOwner,
+            ErrorCode::WrongAsksOwner,
+            ErrorCode::WrongMarketOwner,
+            ErrorCode::WrongMsrmOwner,
+            ErrorCode::WrongMsrmMint,
+            ErrorCode::WrongMsrmBalance,
+            ErrorCode::IllegalMsrmOwner,
+            ErrorCode::WrongAccountTag,
+            ErrorCode::FailedToDeserialize,
+            ErrorCode::InvalidWatermarks,
+            ErrorCode::InvalidEventQueueSize,
+            ErrorCode::MarketLocked,
+            ErrorCode::PostOnlyTooFar,
+            ErrorCode::ResumptionPending,
+            ErrorCode::InvalidResumptionTicket,
+            ErrorCode::InvalidBaseLotSize,
+            ErrorCode::EventSizeMismatch,
+            ErrorCode::InvalidEventQueueHeader,
+            ErrorCode::InvalidMatchLimit,
+            ErrorCode::WrongTradeHistoryAccount,
+            ErrorCode::NotEnoughAuthoritySigners,
+            ErrorCode::InvalidAuthorityThreshold,
+            ErrorCode::WrongCandlesAccount,
+            ErrorCode::InvalidCandleInterval,
+            ErrorCode::WrongFeeVaultAccount,
+            ErrorCode::WrongMarketTag,
+            ErrorCode::WrongEventQueueTag,
+            ErrorCode::WrongBidsTag,
+            ErrorCode::WrongAsksTag,
+            ErrorCode::WrongTradeHistoryTag,
+            ErrorCode::WrongCandlesTag,
+            ErrorCode::WrongFeeVaultTag,
+            ErrorCode::OffTickPrice,
+            ErrorCode::InvalidLimitPrice,
+            ErrorCode::QuantityTooLarge,
+            ErrorCode::InvalidOrderbookSize,
+            ErrorCode::BestPriceOutOfBounds,
+            ErrorCode::WrongCallbackId,
+            ErrorCode::NumericalOverflow,
+            ErrorCode::InvalidTickSize,
+            ErrorCode::InvalidMinBaseOrderSize,
+            ErrorCode::InvalidCallbackIdLen,
+            ErrorCode::CallbackInfoTooLong,
+            ErrorCode::AccountNotRentExempt,
+            ErrorCode::SequenceNumbersExhausted,
+            ErrorCode::MarketClosed,
+            ErrorCode::InvalidSessionTransition,
+            ErrorCode::InvalidSelfTradeIdLen,
+            ErrorCode::InvalidReducedSize,
+        ];
+        assert_eq!(
+            ErrorCode::from_native_code(errors.len() as u32),
+            None,
+            "new variants must be appended to this list"
+        );
+        for (code, error) in errors.into_iter().enumerate() {
+            assert_eq!(error.native_code(), code as u32);
+            assert_eq!(u32::from(error), ERROR_CODE_OFFSET + code as u32);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Anchor events emitted through the program logs.
+//!
+//! These are distinct from the [`Event`][`crate::state::Event`] objects written to the event queue: they are
+//! not consumed by the caller program and only serve as a lightweight notification channel for off-chain monitoring.
+use anchor_lang::prelude::*;
+
+use crate::{state::Side, utils::Quantity};
+
+/// Emitted when the event queue occupancy rises to or above the market's high watermark.
+#[event]
+pub struct EventQueueHighWatermark {
+    #[allow(missing_docs)]
+    pub market: Pubkey,
+    /// The number of events in the queue after the instruction
+    pub count: u64,
+    /// The maximum number of events the queue can hold
+    pub capacity: u64,
+}
+
+/// Emitted when the event queue occupancy falls to or below the market's low watermark.
+#[event]
+pub struct EventQueueLowWatermark {
+    #[allow(missing_docs)]
+    pub market: Pubkey,
+    /// The number of events in the queue after the instruction
+    pub count: u64,
+    /// The maximum number of events the queue can hold
+    pub capacity: u64,
+}
+
+/// Emitted for each [`Fill`][`crate::state::Event::Fill`] pushed to the event queue, when the program is built
+/// with the `emit-events` feature.
+#[event]
+pub struct FillEvent {
+    #[allow(missing_docs)]
+    pub market: Pubkey,
+    #[allow(missing_docs)]
+    pub taker_side: Side,
+    /// The order id of the maker order
+    pub maker_order_id: u128,
+    /// The total quote size of the transaction
+    pub quote_size: Quantity,
+    /// The total base size of the transaction
+    pub base_size: Quantity,
+    /// The slot at which the match occurred
+    pub slot: u64,
+    /// The unix timestamp at which the match occurred
+    pub unix_timestamp: i64,
+    /// The callback information for the maker
+    pub maker_callback_info: Vec<u8>,
+    /// The callback information for the taker
+    pub taker_callback_info: Vec<u8>,
+}
+
+/// Emitted for each [`Out`][`crate::state::Event::Out`] pushed to the event queue, when the program is built
+/// with the `emit-events` feature.
+#[event]
+pub struct OutEvent {
+    #[allow(missing_docs)]
+    pub market: Pubkey,
+    #[allow(missing_docs)]
+    pub side: Side,
+    #[allow(missing_docs)]
+    pub order_id: u128,
+    #[allow(missing_docs)]
+    pub base_size: Quantity,
+    #[allow(missing_docs)]
+    pub delete: bool,
+    /// The slot at which the order was taken out
+    pub slot: u64,
+    /// The unix timestamp at which the order was taken out
+    pub unix_timestamp: i64,
+    #[allow(missing_docs)]
+    pub callback_info: Vec<u8>,
+}
@@ -0,0 +1,121 @@
+//! A fixed-capacity ring buffer which can be laid out in zero-copy account data.
+use std::mem::{align_of, size_of};
+
+use bytemuck::{Pod, Zeroable};
+
+/// Holds the `N` most recent values pushed to it, overwriting the oldest one once full.
+///
+/// The buffer is `repr(C)` and can be cast from account data. In order for it to be free of padding bytes, the
+/// alignment of `T` must not exceed 8 bytes and the size of `[T; N]` must be a multiple of 8 bytes, which is
+/// checked at compile time by [`HistoryBuffer::push`].
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct HistoryBuffer<T, const N: usize> {
+    /// The index at which the next value will be written
+    head: u64,
+    /// The number of values held by the buffer
+    len: u64,
+    values: [T; N],
+}
+
+unsafe impl<T: Pod, const N: usize> Zeroable for HistoryBuffer<T, N> {}
+unsafe impl<T: Pod, const N: usize> Pod for HistoryBuffer<T, N> {}
+
+impl<T: Pod, const N: usize> HistoryBuffer<T, N> {
+    const LAYOUT_CHECK: () = assert!(
+        N > 0 && align_of::<T>() <= 8 && (N * size_of::<T>()) % 8 == 0,
+        "HistoryBuffer must be free of padding bytes"
+    );
+
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        Self::zeroed()
+    }
+
+    /// Pushes a value, overwriting the oldest one if the buffer is full.
+    pub fn push(&mut self, value: T) {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::LAYOUT_CHECK;
+        self.values[self.head as usize] = value;
+        self.head = (self.head + 1) % N as u64;
+        self.len = std::cmp::min(self.len + 1, N as u64);
+    }
+
+    /// The number of values held by the buffer.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if no value was ever pushed to the buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The maximum number of values held by the buffer.
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the `n`-th most recent value, starting from 0.
+    pub fn recent(&self, n: usize) -> Option<&T> {
+        if n >= self.len() {
+            return None;
+        }
+        Some(&self.values[(self.head as usize + N - 1 - n) % N])
+    }
+
+    /// Returns a mutable reference to the `n`-th most recent value, starting from 0.
+    pub fn recent_mut(&mut self, n: usize) -> Option<&mut T> {
+        if n >= self.len() {
+            return None;
+        }
+        Some(&mut self.values[(self.head as usize + N - 1 - n) % N])
+    }
+
+    /// Returns an iterator over the values held by the buffer, most recent first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (0..self.len()).map(move |n| self.recent(n).unwrap())
+    }
+}
+
+impl<T: Pod, const N: usize> Default for HistoryBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_buffer() {
+        let mut buffer = HistoryBuffer::<u64, 4>::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.recent(0), None);
+
+        for value in 1..=3 {
+            buffer.push(value);
+        }
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![3, 2, 1]);
+
+        for value in 4..=6 {
+            buffer.push(value);
+        }
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.recent(0), Some(&6));
+        assert_eq!(buffer.recent(3), Some(&3));
+        assert_eq!(buffer.recent(4), None);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![6, 5, 4, 3]);
+
+        *buffer.recent_mut(0).unwrap() = 7;
+        assert_eq!(buffer.recent_mut(4), None);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![7, 5, 4, 3]);
+
+        let bytes = bytemuck::bytes_of(&buffer).to_vec();
+        assert_eq!(bytes.len(), 16 + 4 * 8);
+        let mut copy = HistoryBuffer::<u64, 4>::new();
+        bytemuck::bytes_of_mut(&mut copy).copy_from_slice(&bytes);
+        assert_eq!(copy.iter().copied().collect::<Vec<_>>(), vec![7, 5, 4, 3]);
+    }
+}
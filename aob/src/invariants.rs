@@ -0,0 +1,345 @@
+//! Property-based checks of the matching engine's invariants.
+//!
+//! Random order flows are run against the orderbook, with the [`Auditor`] checking that every taker's base and
+//! quote totals match the sum of its fills, that no order is ever overfilled and that order ids are unique. After
+//! each operation, both slabs have to stay well-formed and hold exactly the orders which the auditor expects.
+//!
+//! The flows are run against both the in-memory backend and account buffers.
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+
+use anchor_lang::prelude::Pubkey;
+use proptest::prelude::*;
+
+use crate::{
+    critbit::{LeafNodeExt, Slab, Storage},
+    orderbook::{OrderBookState, OrderSummary, MAX_QUOTE_QTY},
+    params::NewOrderParams,
+    sim::Auditor,
+    state::{
+        AccountTag, EventQueue, EventQueueHeader, MarketState, OrderId, SelfTradeBehavior, Side,
+    },
+    utils::{fp32_mul, lots_to_base_qty, Quantity},
+};
+
+const ORDER_CAPACITY: usize = 256;
+// Self trades which cancel the resting orders don't count towards the match limit, so an order can cancel the whole
+// opposite side
+const EVENT_CAPACITY: usize = 512;
+const CALLBACK_INFO_LEN: usize = 8;
+
+#[derive(Clone, Debug)]
+enum Operation {
+    NewOrder {
+        side: Side,
+        limit_price: u64,
+        max_base_qty: Quantity,
+        max_quote_qty: Quantity,
+        owner: u8,
+        match_limit: u64,
+        post_allowed: bool,
+        cancel_provide: bool,
+    },
+    /// Cancels the resting order of the given side at the given index, modulo the number of orders
+    CancelOrder { side: Side, index: usize },
+}
+
+fn side() -> impl Strategy<Value = Side> {
+    prop_oneof![Just(Side::Bid), Just(Side::Ask)]
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        4 => (
+            side(),
+            1..20u64,
+            1..1_000 as Quantity,
+            prop_oneof![Just(MAX_QUOTE_QTY), 1..20_000 as Quantity],
+            0..4u8,
+            1..10u64,
+            any::<bool>(),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(
+                    side,
+                    price,
+                    max_base_qty,
+                    max_quote_qty,
+                    owner,
+                    match_limit,
+                    post_allowed,
+                    cancel_provide,
+                )| Operation::NewOrder {
+                    side,
+                    limit_price: price << 32,
+                    max_base_qty,
+                    max_quote_qty,
+                    owner,
+                    match_limit,
+                    post_allowed,
+                    cancel_provide,
+                }
+            ),
+        1 => (side(), any::<usize>()).prop_map(|(side, index)| Operation::CancelOrder { side, index }),
+    ]
+}
+
+fn market_state(base_lot_size: u64, min_base_order_size: u64) -> MarketState {
+    MarketState {
+        callback_info_len: CALLBACK_INFO_LEN as u64,
+        callback_id_len: CALLBACK_INFO_LEN as u64,
+        tick_size: 1,
+        base_lot_size,
+        min_base_order_size,
+        ..MarketState::default()
+    }
+}
+
+struct Harness<S: Storage> {
+    order_book: OrderBookState<S>,
+    event_queue: EventQueue<S>,
+    market_state: MarketState,
+    auditor: Auditor,
+    posted_order_ids: BTreeSet<OrderId>,
+}
+
+impl<S: Storage> Harness<S> {
+    fn run(&mut self, operations: &[Operation]) -> Result<(), TestCaseError> {
+        for operation in operations {
+            self.apply(operation)?;
+            self.check_books()?;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, operation: &Operation) -> Result<(), TestCaseError> {
+        match *operation {
+            Operation::NewOrder {
+                side,
+                limit_price,
+                max_base_qty,
+                max_quote_qty,
+                owner,
+                match_limit,
+                post_allowed,
+                cancel_provide,
+            } => {
+                // Aborting orders would leave the book half-matched, as only a failed transaction rolls it back
+                let self_trade_behavior = if cancel_provide {
+                    SelfTradeBehavior::CancelProvide
+                } else {
+                    SelfTradeBehavior::DecrementTake
+                };
+                let params = NewOrderParams {
+                    max_base_qty,
+                    max_quote_qty,
+                    limit_price,
+                    side,
+                    match_limit,
+                    callback_info: vec![owner; CALLBACK_INFO_LEN],
+                    post_only: false,
+                    post_allowed,
+                    self_trade_behavior,
+                    self_trade_id_len: None,
+                    max_avg_price: None,
+                };
+                let summary = self
+                    .order_book
+                    .new_order(
+                        params.clone(),
+                        &mut self.event_queue,
+                        &mut self.market_state,
+                        None,
+                        None,
+                    )
+                    .map_err(|e| TestCaseError::fail(format!("{:?}", e)))?;
+                self.order_book.commit_changes();
+                let count = self.event_queue.header.count;
+                let events = self.event_queue.peek_n(count);
+                self.event_queue.pop_n(count);
+
+                self.auditor
+                    .on_new_order(&params, &summary, &events)
+                    .map_err(|e| TestCaseError::fail(format!("{:?}", e)))?;
+                if let Some(order_id) = summary.posted_order_id {
+                    prop_assert!(self.posted_order_ids.insert(order_id));
+                    prop_assert_eq!(order_id.side(), side);
+                    prop_assert_eq!(order_id.price(), limit_price);
+                }
+            }
+            Operation::CancelOrder { side, index } => {
+                let leaves = self.order_book.get_tree(side).leaves(false);
+                if leaves.is_empty() {
+                    return Ok(());
+                }
+                let order_id = leaves[index % leaves.len()].order_id();
+                let node = self
+                    .order_book
+                    .get_tree(side)
+                    .remove_by_key(order_id.0)
+                    .unwrap();
+                self.order_book.commit_changes();
+                let leaf_node = node.as_leaf().unwrap();
+                let total_base_qty =
+                    lots_to_base_qty(leaf_node.base_quantity, self.market_state.base_lot_size);
+                let summary = OrderSummary {
+                    posted_order_id: None,
+                    total_base_qty,
+                    total_quote_qty: fp32_mul(total_base_qty, leaf_node.price()),
+                    total_fee_qty: 0,
+                    total_base_qty_posted: 0,
+                    avg_fill_price_fp32: 0,
+                    worst_fill_price_fp32: 0,
+                };
+                self.auditor
+                    .on_cancel(order_id, &summary)
+                    .map_err(|e| TestCaseError::fail(format!("{:?}", e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_books(&mut self) -> Result<(), TestCaseError> {
+        let base_lot_size = self.market_state.base_lot_size;
+        let mut open_orders = 0;
+        let mut best_prices = [None, None];
+        for side in [Side::Bid, Side::Ask] {
+            let slab = self.order_book.get_tree(side);
+            slab.check_invariants();
+            let leaves = slab.leaves(false);
+            for pair in leaves.windows(2) {
+                prop_assert!(pair[0].key < pair[1].key);
+            }
+            for leaf in &leaves {
+                prop_assert_eq!(leaf.order_id().side(), side);
+                prop_assert!(leaf.base_quantity > 0);
+                prop_assert_eq!(
+                    self.auditor.resting_base_qty(leaf.order_id()),
+                    Some(lots_to_base_qty(leaf.base_quantity, base_lot_size))
+                );
+            }
+            open_orders += leaves.len();
+            best_prices[side as usize] = match side {
+                Side::Bid => leaves.last(),
+                Side::Ask => leaves.first(),
+            }
+            .map(|leaf| leaf.price());
+        }
+        prop_assert_eq!(open_orders, self.auditor.open_orders());
+
+        // Orders only post once they no longer cross the book
+        if let [Some(best_bid), Some(best_ask)] = best_prices {
+            prop_assert!(best_bid < best_ask);
+        }
+        Ok(())
+    }
+}
+
+fn in_memory_harness(market_state: MarketState) -> Harness<Vec<u8>> {
+    let bids = Slab::new_in_memory(
+        ORDER_CAPACITY,
+        CALLBACK_INFO_LEN,
+        AccountTag::Bids as u8,
+        [0; 32],
+    );
+    let asks = Slab::new_in_memory(
+        ORDER_CAPACITY,
+        CALLBACK_INFO_LEN,
+        AccountTag::Asks as u8,
+        [0; 32],
+    );
+    Harness {
+        order_book: OrderBookState::from_slabs(bids, asks, CALLBACK_INFO_LEN).unwrap(),
+        event_queue: EventQueue::new_in_memory(EVENT_CAPACITY, CALLBACK_INFO_LEN),
+        market_state,
+        auditor: Auditor::new(),
+        posted_order_ids: BTreeSet::new(),
+    }
+}
+
+/// Account-like buffers, which the harness borrows as the program would borrow the accounts' data
+struct AccountBuffers {
+    bids: Vec<u8>,
+    asks: Vec<u8>,
+    event_queue: Vec<u8>,
+}
+
+impl AccountBuffers {
+    fn new() -> Self {
+        let book_len = Slab::compute_allocation_size(ORDER_CAPACITY, CALLBACK_INFO_LEN);
+        let mut buffers = Self {
+            bids: vec![0; book_len],
+            asks: vec![0; book_len],
+            event_queue: vec![
+                0;
+                EventQueue::compute_allocation_size(EVENT_CAPACITY, CALLBACK_INFO_LEN)
+            ],
+        };
+        Slab::initialize_buffer(
+            &mut buffers.bids,
+            AccountTag::Bids as u8,
+            [0; 32],
+            CALLBACK_INFO_LEN,
+        );
+        Slab::initialize_buffer(
+            &mut buffers.asks,
+            AccountTag::Asks as u8,
+            [0; 32],
+            CALLBACK_INFO_LEN,
+        );
+        EventQueueHeader::initialize(
+            Pubkey::default(),
+            CALLBACK_INFO_LEN,
+            buffers.event_queue.len(),
+        )
+        .write(&mut buffers.event_queue);
+        buffers
+    }
+
+    fn harness(&mut self, market_state: MarketState) -> Harness<&mut [u8]> {
+        let header = EventQueueHeader::initialize(
+            Pubkey::default(),
+            CALLBACK_INFO_LEN,
+            self.event_queue.len(),
+        );
+        Harness {
+            order_book: OrderBookState::from_slabs(
+                Slab::new(&mut self.bids[..], CALLBACK_INFO_LEN).unwrap(),
+                Slab::new(&mut self.asks[..], CALLBACK_INFO_LEN).unwrap(),
+                CALLBACK_INFO_LEN,
+            )
+            .unwrap(),
+            event_queue: EventQueue::new(
+                header,
+                Rc::new(RefCell::new(&mut self.event_queue[..])),
+                CALLBACK_INFO_LEN,
+            ),
+            market_state,
+            auditor: Auditor::new(),
+            posted_order_ids: BTreeSet::new(),
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn in_memory_order_flow(
+        operations in prop::collection::vec(operation(), 1..100),
+        base_lot_size in 1..4u64,
+        min_base_order_size in 0..3u64,
+    ) {
+        let mut harness = in_memory_harness(market_state(base_lot_size, min_base_order_size));
+        harness.run(&operations)?;
+    }
+
+    #[test]
+    fn account_order_flow(
+        operations in prop::collection::vec(operation(), 1..100),
+        base_lot_size in 1..4u64,
+        min_base_order_size in 0..3u64,
+    ) {
+        let mut buffers = AccountBuffers::new();
+        let mut harness = buffers.harness(market_state(base_lot_size, min_base_order_size));
+        harness.run(&operations)?;
+    }
+}
@@ -0,0 +1,64 @@
+//! Metrics for the bots which crank AO markets, reported through the [`metrics`] facade.
+//!
+//! Nothing is recorded until the keeper installs a `metrics` recorder, such as a Prometheus exporter, which lets
+//! operators plug their existing dashboards into a cranking fleet. Every metric is labelled with the market's key.
+use std::time::Duration;
+
+use anchor_lang::prelude::Pubkey;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+
+/// Counter of the events popped off event queues
+pub const EVENTS_CONSUMED: &str = "aob_events_consumed_total";
+/// Counter of the lamports earned as cranker rewards
+pub const REWARDS_EARNED: &str = "aob_rewards_earned_lamports_total";
+/// Counter of the consume_events transactions which failed
+pub const CRANK_FAILURES: &str = "aob_crank_failures_total";
+/// Gauge of the number of events waiting in an event queue
+pub const QUEUE_DEPTH: &str = "aob_event_queue_depth";
+/// Gauge of the number of events an event queue can hold
+pub const QUEUE_CAPACITY: &str = "aob_event_queue_capacity";
+/// Histogram of the time taken by consume_events transactions, in seconds
+pub const CRANK_LATENCY: &str = "aob_crank_latency_seconds";
+
+/// Registers the descriptions of the keeper metrics with the installed recorder.
+pub fn describe_metrics() {
+    describe_counter!(EVENTS_CONSUMED, "Events popped off the event queue");
+    describe_counter!(REWARDS_EARNED, "Lamports earned as cranker rewards");
+    describe_counter!(CRANK_FAILURES, "Failed consume_events transactions");
+    describe_gauge!(QUEUE_DEPTH, "Events waiting in the event queue");
+    describe_gauge!(QUEUE_CAPACITY, "Events the event queue can hold");
+    describe_histogram!(CRANK_LATENCY, "Time taken by consume_events transactions");
+}
+
+/// Reports the activity of a keeper on one market.
+pub struct CrankMetrics {
+    market: String,
+}
+
+impl CrankMetrics {
+    #[allow(missing_docs)]
+    pub fn new(market: &Pubkey) -> Self {
+        Self {
+            market: market.to_string(),
+        }
+    }
+
+    /// Records the occupancy of the market's event queue, as read from its header.
+    pub fn record_queue_depth(&self, count: u64, capacity: u64) {
+        gauge!(QUEUE_DEPTH, count as f64, "market" => self.market.clone());
+        gauge!(QUEUE_CAPACITY, capacity as f64, "market" => self.market.clone());
+    }
+
+    /// Records a successful consume_events transaction.
+    pub fn record_crank(&self, events_consumed: u64, reward: u64, latency: Duration) {
+        counter!(EVENTS_CONSUMED, events_consumed, "market" => self.market.clone());
+        counter!(REWARDS_EARNED, reward, "market" => self.market.clone());
+        histogram!(CRANK_LATENCY, latency, "market" => self.market.clone());
+    }
+
+    /// Records a failed consume_events transaction.
+    pub fn record_crank_failure(&self, latency: Duration) {
+        counter!(CRANK_FAILURES, 1, "market" => self.market.clone());
+        histogram!(CRANK_LATENCY, latency, "market" => self.market.clone());
+    }
+}
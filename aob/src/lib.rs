@@ -0,0 +1,32 @@
+/*!
+The core of the agnostic orderbook: the matching engine, the market accounts and their parameters, on top of the
+critbit slabs of [`aob_core`].
+
+This crate is shared by the Anchor program and the native program, so that both deployments run the exact same
+matching logic. The accounts are laid out as Anchor accounts, owned by [`ID`].
+*/
+use anchor_lang::prelude::*;
+
+#[cfg(any(test, feature = "client"))]
+pub mod client;
+pub mod critbit;
+pub mod error;
+pub mod events;
+pub mod histbuf;
+#[cfg(test)]
+mod invariants;
+#[cfg(feature = "keeper")]
+pub mod keeper;
+pub mod log;
+#[cfg(any(test, feature = "no-entrypoint"))]
+pub mod loader;
+pub mod orderbook;
+pub mod params;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+#[cfg(any(test, feature = "snapshot"))]
+pub mod snapshot;
+pub mod state;
+pub mod utils;
+
+declare_id!("aaobKniTtDGvCZces7GH5UReLYP671bBkB96ahr9x3e");
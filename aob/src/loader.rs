@@ -0,0 +1,249 @@
+//! Read-only views over the raw data of a market's accounts, for off-chain tooling which fetches them over RPC.
+use anchor_lang::{prelude::*, Discriminator};
+use bytemuck::Zeroable;
+
+use crate::{
+    critbit::{LeafNodeExt, Slab},
+    error::ErrorCode,
+    state::{
+        AccountTag, Candle, Candles, MarketState, OrderId, Side, CANDLES_LEN, MARKET_STATE_LEN,
+    },
+    utils::{lots_to_base_qty, Quantity},
+};
+
+/// A typed view of a market, loaded from the data of its market, bids and asks accounts.
+pub struct MarketLoader {
+    /// The market's configuration and state
+    pub market_state: MarketState,
+    /// (order id, base quantity) pairs, best first
+    bids: Vec<(OrderId, Quantity)>,
+    /// (order id, base quantity) pairs, best first
+    asks: Vec<(OrderId, Quantity)>,
+    /// The total base quantity resting on the bids, as tracked by the slab header
+    resting_bids: u64,
+    /// The total base quantity resting on the asks, as tracked by the slab header
+    resting_asks: u64,
+}
+
+/// A summary of the top of a market's orderbook
+#[derive(Debug, PartialEq)]
+pub struct BookSummary {
+    /// The best bid price (FP32)
+    pub best_bid: Option<u64>,
+    /// The best ask price (FP32)
+    pub best_ask: Option<u64>,
+    /// The best bid price levels as (price, base quantity) pairs
+    pub bids: Vec<(u64, Quantity)>,
+    /// The best ask price levels as (price, base quantity) pairs
+    pub asks: Vec<(u64, Quantity)>,
+    /// The number of orders resting on the bids
+    pub open_bids: usize,
+    /// The number of orders resting on the asks
+    pub open_asks: usize,
+    /// The total base quantity resting on the bids
+    pub resting_bids: u64,
+    /// The total base quantity resting on the asks
+    pub resting_asks: u64,
+}
+
+impl MarketLoader {
+    /// Parses the data of a market's accounts, as returned by `getAccountInfo`.
+    pub fn new(market: &[u8], bids: &[u8], asks: &[u8]) -> Result<Self> {
+        if market.len() < 8 + MARKET_STATE_LEN || market[..8] != MarketState::discriminator() {
+            return err!(ErrorCode::WrongMarketTag);
+        }
+        let mut market_state = MarketState::default();
+        bytemuck::bytes_of_mut(&mut market_state).copy_from_slice(&market[8..8 + MARKET_STATE_LEN]);
+        AccountTag::Market.check(market_state.tag)?;
+        let (bids, resting_bids) = Self::load_orders(&market_state, bids, Side::Bid)?;
+        let (asks, resting_asks) = Self::load_orders(&market_state, asks, Side::Ask)?;
+        Ok(Self {
+            market_state,
+            bids,
+            asks,
+            resting_bids,
+            resting_asks,
+        })
+    }
+
+    fn load_orders(
+        market_state: &MarketState,
+        data: &[u8],
+        side: Side,
+    ) -> Result<(Vec<(OrderId, Quantity)>, u64)> {
+        let slab = Slab::from_bytes(data, market_state.callback_info_len as usize)
+            .map_err(ErrorCode::from)?;
+        match side {
+            Side::Bid => AccountTag::Bids,
+            Side::Ask => AccountTag::Asks,
+        }
+        .check(slab.account_tag() as u64)?;
+        let orders = slab
+            .leaves(side == Side::Bid)
+            .iter()
+            .map(|leaf| {
+                (
+                    leaf.order_id(),
+                    lots_to_base_qty(leaf.base_quantity, market_state.base_lot_size),
+                )
+            })
+            .collect();
+        Ok((orders, market_state.resting_base_qty(&slab)))
+    }
+
+    /// Returns the orders resting on the given side as (order id, base quantity) pairs, best first.
+    pub fn orders(&self, side: Side) -> &[(OrderId, Quantity)] {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    /// Returns the best price (FP32) on the given side, if any.
+    pub fn best_price(&self, side: Side) -> Option<u64> {
+        self.orders(side)
+            .first()
+            .map(|(order_id, _)| order_id.price())
+    }
+
+    /// Returns up to `depth` price levels of the given side as (price, base quantity) pairs, best first.
+    pub fn depth(&self, side: Side, depth: usize) -> Vec<(u64, Quantity)> {
+        let mut levels: Vec<(u64, Quantity)> = Vec::new();
+        for (order_id, base_qty) in self.orders(side) {
+            let price = order_id.price();
+            let level_count = levels.len();
+            match levels.last_mut() {
+                Some((level_price, level_qty)) if *level_price == price => *level_qty += base_qty,
+                _ if level_count == depth => break,
+                _ => levels.push((price, *base_qty)),
+            }
+        }
+        levels
+    }
+
+    /// Returns the number of orders resting on the given side.
+    pub fn open_order_count(&self, side: Side) -> usize {
+        self.orders(side).len()
+    }
+
+    /// Returns the total base quantity resting on the given side.
+    pub fn resting_base_qty(&self, side: Side) -> u64 {
+        match side {
+            Side::Bid => self.resting_bids,
+            Side::Ask => self.resting_asks,
+        }
+    }
+
+    /// Summarizes the top `depth` price levels of the orderbook.
+    pub fn summary(&self, depth: usize) -> BookSummary {
+        BookSummary {
+            best_bid: self.best_price(Side::Bid),
+            best_ask: self.best_price(Side::Ask),
+            bids: self.depth(Side::Bid, depth),
+            asks: self.depth(Side::Ask, depth),
+            open_bids: self.open_order_count(Side::Bid),
+            open_asks: self.open_order_count(Side::Ask),
+            resting_bids: self.resting_base_qty(Side::Bid),
+            resting_asks: self.resting_base_qty(Side::Ask),
+        }
+    }
+}
+
+/// Decodes the data of a market's [`Candles`] account, returning its candles oldest first along with the number
+/// of slots covered by each candle.
+pub fn load_candles(data: &[u8]) -> Result<(u64, Vec<Candle>)> {
+    if data.len() < CANDLES_LEN {
+        return err!(ErrorCode::WrongCandlesTag);
+    }
+    let mut candles = Candles::zeroed();
+    bytemuck::bytes_of_mut(&mut candles).copy_from_slice(&data[..CANDLES_LEN]);
+    AccountTag::Candles.check(candles.tag)?;
+    let mut history: Vec<Candle> = candles.candles.iter().copied().collect();
+    history.reverse();
+    Ok((candles.slots_per_candle, history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::critbit::{LeafNode, Node, SlabExt};
+
+    fn initialize_slabs(bids: &mut [u8], asks: &mut [u8]) {
+        let key = Pubkey::new_unique();
+        let (mut bids_lamports, mut asks_lamports) = (0, 0);
+        let bids = AccountInfo::new(&key, false, true, &mut bids_lamports, bids, &key, false, 0);
+        let asks = AccountInfo::new(&key, false, true, &mut asks_lamports, asks, &key, false, 0);
+        Slab::initialize(&bids, &asks, key, 32);
+    }
+
+    fn insert_order(data: &mut [u8], order_id: OrderId, base_quantity: u64) {
+        let mut slab = Slab::new(data, 32).unwrap();
+        let callback_info_pt = slab.write_callback_info(&[0; 32]).unwrap();
+        slab.insert_leaf(&Node::Leaf(LeafNode {
+            key: order_id.0,
+            callback_info_pt,
+            base_quantity,
+        }))
+        .unwrap();
+        slab.write_header();
+    }
+
+    #[test]
+    fn market_loader() {
+        let market_state = MarketState {
+            tag: AccountTag::Market as u64,
+            callback_info_len: 32,
+            callback_id_len: 32,
+            base_lot_size: 10,
+            ..MarketState::default()
+        };
+        let mut market = MarketState::discriminator().to_vec();
+        market.extend_from_slice(bytemuck::bytes_of(&market_state));
+
+        let (mut bids, mut asks) = (vec![0; 10_000], vec![0; 10_000]);
+        initialize_slabs(&mut bids, &mut asks);
+        insert_order(&mut bids, OrderId::new(100, Side::Bid, 0), 1);
+        insert_order(&mut bids, OrderId::new(100, Side::Bid, 1), 2);
+        insert_order(&mut bids, OrderId::new(90, Side::Bid, 2), 3);
+        insert_order(&mut asks, OrderId::new(110, Side::Ask, 3), 4);
+
+        let loader = MarketLoader::new(&market, &bids, &asks).unwrap();
+        assert_eq!(
+            loader.summary(1),
+            BookSummary {
+                best_bid: Some(100),
+                best_ask: Some(110),
+                bids: vec![(100, 30)],
+                asks: vec![(110, 40)],
+                open_bids: 3,
+                open_asks: 1,
+                resting_bids: 60,
+                resting_asks: 40,
+            }
+        );
+        assert_eq!(loader.depth(Side::Bid, 5), vec![(100, 30), (90, 30)]);
+
+        assert!(MarketLoader::new(&market, &asks, &bids).is_err());
+        assert!(MarketLoader::new(&market[8..], &bids, &asks).is_err());
+    }
+
+    #[test]
+    fn candles_decoder() {
+        let mut candles = Candles::zeroed();
+        candles.slots_per_candle = 10;
+        candles.record(5, 100, 1, 100, 1);
+        candles.record(12, 90, 2, 180, -2);
+        let mut data = bytemuck::bytes_of(&candles).to_vec();
+        assert!(load_candles(&data).is_err());
+
+        candles.tag = AccountTag::Candles as u64;
+        data = bytemuck::bytes_of(&candles).to_vec();
+        let (slots_per_candle, history) = load_candles(&data).unwrap();
+        assert_eq!(slots_per_candle, 10);
+        assert_eq!(
+            history.iter().map(|c| c.start_slot).collect::<Vec<_>>(),
+            vec![0, 10]
+        );
+        assert!(load_candles(&data[1..]).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Program logs with a verbosity level chosen at compile time.
+//!
+//! Logging costs compute units and log space on every instruction, so the programs log through the
+//! [`log_error!`][crate::log_error], [`log_debug!`][crate::log_debug] and
+//! [`log_compute_units!`][crate::log_compute_units] macros rather than through `msg!` directly:
+//! - errors explain why an instruction is rejected, and are logged unless the `log-off` feature is enabled
+//! - debug logs trace the progress of an instruction along with its compute unit consumption, and are only logged
+//!   with the `log-debug` feature
+//!
+//! The `log-off` feature takes precedence over `log-debug`. Disabled logs are compiled out, their arguments still
+//! being type checked.
+#[doc(hidden)]
+pub use anchor_lang::solana_program::{log::sol_log_compute_units, msg};
+
+/// The verbosity levels of the program logs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Level {
+    #[allow(missing_docs)]
+    Off,
+    #[allow(missing_docs)]
+    Error,
+    #[allow(missing_docs)]
+    Debug,
+}
+
+/// The verbosity level the crate is built with
+pub const LEVEL: Level = if cfg!(feature = "log-off") {
+    Level::Off
+} else if cfg!(feature = "log-debug") {
+    Level::Debug
+} else {
+    Level::Error
+};
+
+/// Returns whether the logs of the given level are enabled.
+pub const fn enabled(level: Level) -> bool {
+    level as u8 != Level::Off as u8 && level as u8 <= LEVEL as u8
+}
+
+/// Logs why an instruction is rejected, with the arguments of `msg!`.
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Error) {
+            $crate::log::msg!($($arg)*);
+        }
+    };
+}
+
+/// Logs the progress of an instruction, with the arguments of `msg!`.
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            $crate::log::msg!($($arg)*);
+        }
+    };
+}
+
+/// Logs the remaining compute units, at the debug level.
+#[macro_export]
+macro_rules! log_compute_units {
+    () => {
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            $crate::log::sol_log_compute_units();
+        }
+    };
+}
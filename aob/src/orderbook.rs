@@ -0,0 +1,1999 @@
+use std::{
+    mem::size_of,
+    ops::{Deref, DerefMut},
+};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::account_info::AccountInfo;
+use borsh::{BorshDeserialize, BorshSerialize};
+use num_traits::FromPrimitive;
+
+use crate::params::NewOrderParams;
+use crate::state::AccountTag;
+use crate::{
+    critbit::{LeafNode, LeafNodeExt, Node, NodeHandle, Slab, SlabError, SlabExt, Storage},
+    error::ErrorCode,
+    histbuf::HistoryBuffer,
+    state::{
+        Asks, Bids, Candles, Event, EventQueue, MarketState, OrderId, OrderPriority,
+        SelfTradeBehavior, Side, TradingSession, TRADE_HISTORY_CAPACITY,
+    },
+    utils::{
+        base_qty_to_lots, fp32_div, fp32_mul_ceil, fp32_mul_floor, lots_to_base_qty, low_u64,
+        round_to_lot, Quantity,
+    },
+};
+use crate::{log_debug, log_error};
+
+/// The maximum number of price levels per side in an [`L2Snapshot`], so that it fits into the return data.
+pub const MAX_L2_SNAPSHOT_DEPTH: usize = (1024 - 8) / (2 * (8 + size_of::<Quantity>()));
+
+/// An aggregated price level of the orderbook.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    any(test, feature = "snapshot"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct PriceLevel {
+    /// The price of the level, as FP32.
+    pub price: u64,
+    /// The total base quantity resting at this price.
+    pub base_quantity: Quantity,
+}
+
+/// The top price levels of both sides of the orderbook, best first.
+///
+/// This struct is written into the return data by the `get_l2_snapshot` instruction.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct L2Snapshot {
+    #[allow(missing_docs)]
+    pub bids: Vec<PriceLevel>,
+    #[allow(missing_docs)]
+    pub asks: Vec<PriceLevel>,
+}
+
+/// The opening auction of a crossed orderbook, see [`indicative_auction`].
+///
+/// This struct is written into the return data by the `open_session` and `get_indicative_auction` instructions.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Auction {
+    /// The price at which all the crossed orders trade, as FP32.
+    pub price: u64,
+    /// The base quantity which trades at that price.
+    pub base_qty: Quantity,
+}
+
+/// This struct is written back into the event queue's register after new_order or cancel_order.
+///
+/// In the case of a new order, the quantities describe the total order amounts which
+/// were either matched against other orders or written into the orderbook.
+///
+/// In the case of an order cancellation, the quantities describe what was left of the order in the orderbook.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct OrderSummary {
+    /// When applicable, the order id of the newly created order.
+    pub posted_order_id: Option<OrderId>,
+    #[allow(missing_docs)]
+    pub total_base_qty: Quantity,
+    /// The quote quantity exchanged against the base quantity, excluding any fees.
+    pub total_quote_qty: Quantity,
+    /// The quote quantity charged as fees on top of `total_quote_qty`.
+    ///
+    /// The orderbook does not charge fees itself yet, so this is currently always 0.
+    pub total_fee_qty: Quantity,
+    #[allow(missing_docs)]
+    pub total_base_qty_posted: Quantity,
+    /// The quote quantity matched per unit of base quantity matched, as FP32 rounded down, or 0 when the order
+    /// wasn't matched.
+    pub avg_fill_price_fp32: u64,
+    /// The least favorable price at which the order was matched, as FP32, or 0 when the order wasn't matched.
+    pub worst_fill_price_fp32: u64,
+}
+
+/// The serialized size of an OrderSummary object.
+pub const ORDER_SUMMARY_SIZE: u32 = 33 + 4 * size_of::<Quantity>() as u32;
+
+/// The maximum number of price levels in an [`OrderResult`], so that it fits into the return data.
+pub const MAX_FILL_LEVELS: usize =
+    (1024 - 4 - ORDER_SUMMARY_SIZE as usize) / (8 + size_of::<Quantity>());
+
+/// The [`OrderSummary`] of a new order, along with the price levels which it was matched against.
+///
+/// This struct is written into the return data by the `new_order` and `continue_order` instructions, while the
+/// event queue's register only holds the summary.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct OrderResult {
+    #[allow(missing_docs)]
+    pub summary: OrderSummary,
+    /// The price levels which the order was matched against, in matching order. The base quantity of each level is
+    /// the quantity which the order took at its price.
+    ///
+    /// Only the first [`MAX_FILL_LEVELS`] levels are listed. The order was matched against further levels when the
+    /// quantities of the levels add up to less than the summary's matched base quantity, that is its
+    /// `total_base_qty` minus its `total_base_qty_posted`.
+    pub fill_levels: Vec<PriceLevel>,
+}
+
+/// The largest limit price (FP32) accepted by [`OrderBookState::new_order`].
+pub const MAX_PRICE_FP32: u64 = (1 << 56) - 1;
+/// The largest base quantity accepted by [`OrderBookState::new_order`].
+///
+/// Since `MAX_BASE_QTY * MAX_PRICE_FP32 < 2^96`, the quote quantity of any fill fits into a `u64`.
+#[cfg(not(feature = "u128-quantities"))]
+pub const MAX_BASE_QTY: Quantity = (1 << 40) - 1;
+/// The largest base quantity accepted by [`OrderBookState::new_order`].
+///
+/// With 128-bit quantities, the base quantity of an order is only bounded by its number of base lots, which the
+/// slabs store as a `u64`. The quote quantity of any fill then stays under `2^88`.
+#[cfg(feature = "u128-quantities")]
+pub const MAX_BASE_QTY: Quantity = u64::MAX as Quantity;
+/// The largest quote quantity accepted by [`OrderBookState::new_order`].
+///
+/// This is the quote quantity of a fill of [`MAX_BASE_QTY`] at [`MAX_PRICE_FP32`], which no order can exceed. It
+/// is rounded up like the quote quantity a bid pays for its fills.
+pub const MAX_QUOTE_QTY: Quantity =
+    ((MAX_BASE_QTY as u128 * MAX_PRICE_FP32 as u128 + (1 << 32) - 1) >> 32) as Quantity;
+
+/// The bids and asks of a market, laid out in a [`Storage`] each.
+///
+/// On-chain, the slabs borrow the data of the bids and asks accounts, see [`OrderBookState::new`]. Off-chain,
+/// any buffer can be used through [`OrderBookState::from_slabs`].
+pub struct OrderBookState<S> {
+    bids: Slab<S>,
+    asks: Slab<S>,
+    callback_id_len: usize,
+    /// Whether the bids were accessed mutably since their header was last written
+    bids_dirty: bool,
+    /// Whether the asks were accessed mutably since their header was last written
+    asks_dirty: bool,
+}
+
+/// An [`OrderBookState`] loaded through [`OrderBookState::load`], which releases the memory of the bids and
+/// asks accounts when dropped.
+pub struct OrderBookGuard<'a> {
+    order_book: Option<OrderBookState<&'a mut [u8]>>,
+    bids_account: AccountInfo<'a>,
+    asks_account: AccountInfo<'a>,
+}
+
+impl<'a> Deref for OrderBookGuard<'a> {
+    type Target = OrderBookState<&'a mut [u8]>;
+
+    fn deref(&self) -> &Self::Target {
+        self.order_book.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for OrderBookGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.order_book.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for OrderBookGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(order_book) = self.order_book.take() {
+            order_book.release(&self.bids_account, &self.asks_account);
+        }
+    }
+}
+
+impl<'a> OrderBookState<&'a mut [u8]> {
+    /// Takes the buffer out of the AccountInfo's data field, replacing it with an
+    /// empty buffer. The memory will be replaced with the original. See `release`.
+    ///
+    /// This is useful for separating the lifetime of the buffer from the `RefMut`.
+    ///
+    /// AccountInfo.data holds a RefCell. RefCell::borrow_mut() returns a RefMut
+    ///
+    /// RefMut is basically runtime-metadata that “borrow checks”, making sure
+    /// nothing else can borrow that thing This RefMut has it’s own lifetime 'b,
+    /// distinct from the lifetime of the T it holds (RefMut<'b, T>) But what it’s
+    /// holding as T is a mutable reference to a buffer &mut [u8] which has a
+    /// separate lifetime of 'a  (the lifetime we actually care about)
+    ///
+    /// RefMut itself implements deref_mut.  This is for ergonomics: now you can
+    /// use RefMut just like &'b mut. But it’s not really opaque, because note the
+    /// introduction of the new lifetime 'b Specifically, you now can imagine we
+    /// really have a &'b mut &'a mut [u8]: RefMut::deref_mut => &'b mut
+    ///
+    /// But this is a problem, because Rust shortens that to &'b mut T (you are
+    /// tied to the lifetime of RefMut, which is tied to the lifetime of the
+    /// original RefCell) That is not what we want, because now we have to prove
+    /// to the borrow checker how long the RefMut lives,
+    /// when all we care about is the original lifetime of the buffer 'a If we
+    /// instead .take(), we are mutably moving the reference to the buffer (&'a mut
+    /// [u8]) into the current function   (note .take() is distinct from taking
+    /// ownership). By doing this, we separate the lifetime of the buffer from the
+    /// lifetime of RefCell (and transitively, RefMut). After .take(), RefCell is
+    /// now holding a RefCell(T::default())
+
+    /// It’s important to note the only copying that is happening when we .take()
+    /// is the usize pointer (i.e. the reference), not the buffer itself.
+
+    /// Now there’s no more 'b because the function where we do .take() now has
+    /// ownership of &'a mut [u8] vs. the RefCell. More accurately, it’s tied to
+    /// the lifetime of the function which can be elided in all subsequent function
+    /// calls within that function. Remember: by definition, the lifetime of
+    /// variables on a caller function outlive the callee function given how stacks
+    /// work.
+
+    /// In the meantime, RefCell holds a (rather useless) empty mutable buffer &mut
+    /// []. So we do our business, and once we’re done we make sure to practice
+    /// good manners and replace the mutable buffer we took back into the AccountInfo
+    pub fn new(
+        bids_account: &AccountInfo<'a>,
+        asks_account: &AccountInfo<'a>,
+        callback_info_len: usize,
+        callback_id_len: usize,
+    ) -> Result<Self> {
+        let bids =
+            Slab::new(bids_account.data.take(), callback_info_len).map_err(ErrorCode::from)?;
+        bids.check_account_tag(AccountTag::Bids)?;
+        let asks =
+            Slab::new(asks_account.data.take(), callback_info_len).map_err(ErrorCode::from)?;
+        asks.check_account_tag(AccountTag::Asks)?;
+        Ok(Self {
+            bids,
+            asks,
+            callback_id_len,
+            bids_dirty: false,
+            asks_dirty: false,
+        })
+    }
+
+    /// Loads the orderbook from the typed bids and asks accounts of a market.
+    ///
+    /// The owners and discriminators of the accounts are checked by their [`AccountLoader`]s, and the rest of
+    /// their data is mapped as the critbit arena of each [`Slab`]. The memory is given back to the accounts when
+    /// the returned [`OrderBookGuard`] is dropped, so there's no need to [`release`][OrderBookState::release] it.
+    pub fn load(
+        bids: &AccountLoader<'a, Bids>,
+        asks: &AccountLoader<'a, Asks>,
+        market_state: &MarketState,
+    ) -> Result<OrderBookGuard<'a>> {
+        bids.load()?;
+        asks.load()?;
+        let bids_account = bids.to_account_info();
+        let asks_account = asks.to_account_info();
+        let order_book = Self::new(
+            &bids_account,
+            &asks_account,
+            market_state.callback_info_len as usize,
+            market_state.callback_id_len as usize,
+        )?;
+        Ok(OrderBookGuard {
+            order_book: Some(order_book),
+            bids_account,
+            asks_account,
+        })
+    }
+
+    /// Checks that both slabs belong to the given market.
+    pub fn check_market(&self, market: &Pubkey) -> Result<()> {
+        self.bids.check_market(market)?;
+        self.asks.check_market(market)
+    }
+
+    /// Releases the memory temporarily held by OrderBookState, replacing the memory that was
+    /// originally took out of the `bids_account` and `asks_account`
+    pub fn release(self, bids_account: &AccountInfo<'a>, asks_account: &AccountInfo<'a>) {
+        self.bids.release(bids_account);
+        self.asks.release(asks_account);
+    }
+}
+
+impl<S: Storage> OrderBookState<S> {
+    /// Wraps the slabs of both sides of the orderbook, which should hold the [`AccountTag::Bids`] and
+    /// [`AccountTag::Asks`] tags respectively.
+    pub fn from_slabs(bids: Slab<S>, asks: Slab<S>, callback_id_len: usize) -> Result<Self> {
+        AccountTag::Bids.check(bids.account_tag() as u64)?;
+        AccountTag::Asks.check(asks.account_tag() as u64)?;
+        Ok(Self {
+            bids,
+            asks,
+            callback_id_len,
+            bids_dirty: false,
+            asks_dirty: false,
+        })
+    }
+
+    pub fn find_bbo(&self, side: Side) -> Option<NodeHandle> {
+        match side {
+            Side::Bid => self.bids.find_max(),
+            Side::Ask => self.asks.find_min(),
+        }
+    }
+
+    /// Returns the price of the best order on the given side.
+    pub fn find_best_price(&self, side: Side) -> Option<u64> {
+        let slab = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        let h = self.find_bbo(side)?;
+        Some(slab.get_node(h).unwrap().as_leaf().unwrap().price())
+    }
+
+    /// Checks that the callback id of a resting order matches `expected_callback_id`.
+    pub fn check_order_callback_id(
+        &self,
+        order_id: OrderId,
+        expected_callback_id: &[u8],
+    ) -> Result<()> {
+        let slab = match order_id.side() {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        let handle = slab
+            .find_by_key(order_id.0)
+            .ok_or(ErrorCode::OrderNotFound)?;
+        let leaf_node = slab.get_node(handle).unwrap().as_leaf().unwrap();
+        let callback_info = slab.get_callback_info(leaf_node.callback_info_pt as usize);
+        if callback_info[..self.callback_id_len] != *expected_callback_id {
+            log_error!("The order's callback id doesn't match the expected one");
+            return err!(ErrorCode::WrongCallbackId);
+        }
+        Ok(())
+    }
+
+    /// Removes an order from the book, after checking its callback id against `expected_callback_id` if given, and
+    /// returns its leaf along with its callback information.
+    ///
+    /// The order is left in the book when the check fails.
+    pub fn cancel_order(
+        &mut self,
+        order_id: OrderId,
+        expected_callback_id: Option<&[u8]>,
+    ) -> Result<(LeafNode, Vec<u8>)> {
+        if let Some(expected_callback_id) = expected_callback_id {
+            self.check_order_callback_id(order_id, expected_callback_id)?;
+        }
+        let (node, callback_info) = self
+            .get_tree(order_id.side())
+            .remove_by_key_with_callback(order_id.0)
+            .ok_or(ErrorCode::OrderNotFound)?;
+        Ok((*node.as_leaf().unwrap(), callback_info))
+    }
+
+    /// Shrinks a resting order to `new_base_qty`, rounded down to a whole number of lots, after checking its callback
+    /// id against `expected_callback_id` if given, and returns the base quantity it released.
+    ///
+    /// Unlike a cancel followed by a new order, the order keeps its place in its slab and so its priority at its
+    /// price level. The released quantity is reported through a partial out event, which leaves the order resting.
+    pub fn reduce_order(
+        &mut self,
+        order_id: OrderId,
+        new_base_qty: Quantity,
+        expected_callback_id: Option<&[u8]>,
+        event_queue: &mut EventQueue<impl Storage>,
+        market_state: &MarketState,
+    ) -> Result<Quantity> {
+        if let Some(expected_callback_id) = expected_callback_id {
+            self.check_order_callback_id(order_id, expected_callback_id)?;
+        }
+        let base_lot_size = market_state.base_lot_size;
+        let new_base_lots = base_qty_to_lots(new_base_qty, base_lot_size);
+        let side = order_id.side();
+        let tree = self.get_tree(side);
+        let handle = tree
+            .find_by_key(order_id.0)
+            .ok_or(ErrorCode::OrderNotFound)?;
+        let leaf = *tree.get_node(handle).unwrap().as_leaf().unwrap();
+        // An order left at or under the minimum order size would be taken out of the book by its next match
+        if new_base_lots >= leaf.base_quantity
+            || lots_to_base_qty(new_base_lots, base_lot_size)
+                <= market_state.min_base_order_size as Quantity
+        {
+            log_error!("Invalid reduced order size");
+            return err!(ErrorCode::InvalidReducedSize);
+        }
+        let released_base_qty = lots_to_base_qty(leaf.base_quantity - new_base_lots, base_lot_size);
+        event_queue.push_out(
+            side,
+            order_id,
+            released_base_qty,
+            false,
+            tree.get_callback_info(leaf.callback_info_pt as usize),
+        )?;
+        tree.set_leaf_base_quantity(handle, new_base_lots);
+        Ok(released_base_qty)
+    }
+
+    /// Returns up to `depth` aggregated price levels of the given side, best first.
+    pub fn price_levels(&self, side: Side, depth: usize, base_lot_size: u64) -> Vec<PriceLevel> {
+        let (slab, descending) = match side {
+            Side::Bid => (&self.bids, true),
+            Side::Ask => (&self.asks, false),
+        };
+        slab.price_levels(descending, depth)
+            .into_iter()
+            .map(|(price, base_lots)| PriceLevel {
+                price,
+                base_quantity: lots_to_base_qty(base_lots, base_lot_size),
+            })
+            .collect()
+    }
+
+    /// Computes the opening auction of the book, if it is crossed, see [`indicative_auction`].
+    pub fn auction(&self, reference_price: u64, base_lot_size: u64) -> Option<Auction> {
+        let levels = |side: Side| -> Vec<PriceLevel> {
+            self.tree(side)
+                .leaves(side == Side::Bid)
+                .iter()
+                .map(|leaf| PriceLevel {
+                    price: leaf.price(),
+                    base_quantity: lots_to_base_qty(leaf.base_quantity, base_lot_size),
+                })
+                .collect()
+        };
+        indicative_auction(&levels(Side::Bid), &levels(Side::Ask), reference_price)
+    }
+
+    /// Runs the opening auction of a market leaving its pre-open session: the crossed orders are matched against
+    /// each other at the [`OrderBookState::auction`] price, best orders first, until the book no longer crosses.
+    ///
+    /// At most `match_limit` pairs of orders are matched per call. When the book still crosses once the limit is
+    /// reached, the auction's price is kept in [`MarketState::auction_price`], so that the next call resumes the
+    /// auction at the same price, and the returned [`Auction`] only holds the base quantity traded by this call.
+    /// The auction is complete once [`MarketState::auction_price`] is back to 0.
+    ///
+    /// Each match pushes a regular fill event, whose taker is the later of the two orders, and an out event for
+    /// each order which it takes out of the book. Orders which share a callback id are matched like
+    /// [`DecrementTake`][`SelfTradeBehavior::DecrementTake`] orders. Unlike [`OrderBookState::new_order`], a failed
+    /// auction isn't rolled back: the instruction which runs it has to fail along with it.
+    pub fn uncross(
+        &mut self,
+        event_queue: &mut EventQueue<impl Storage>,
+        market_state: &mut MarketState,
+        mut trade_history: Option<&mut HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>>,
+        mut candles: Option<&mut Candles>,
+        match_limit: u64,
+    ) -> Result<Option<Auction>> {
+        let base_lot_size = market_state.base_lot_size;
+        let auction = if market_state.auction_price != 0 {
+            Auction {
+                price: market_state.auction_price,
+                base_qty: 0,
+            }
+        } else {
+            match self.auction(market_state.last_trade_price, base_lot_size) {
+                Some(auction) => auction,
+                None => return Ok(None),
+            }
+        };
+        let min_base_order_size = market_state.min_base_order_size as Quantity;
+        let order_priority = OrderPriority::from_u64(market_state.order_priority)
+            .ok_or(ErrorCode::FailedToDeserialize)?;
+
+        // Orders whose remainder is taken out of the book can leave less to trade than the auction's estimate
+        let mut base_qty_traded = 0;
+        let mut matches = 0;
+        market_state.auction_price = 0;
+        while let (Some(bid_h), Some(ask_h)) = (
+            self.find_best_order(Side::Bid, order_priority),
+            self.find_best_order(Side::Ask, order_priority),
+        ) {
+            let bid = *self.bids.get_node(bid_h).unwrap().as_leaf().unwrap();
+            let ask = *self.asks.get_node(ask_h).unwrap().as_leaf().unwrap();
+            if bid.price() < auction.price || ask.price() > auction.price {
+                break;
+            }
+            if matches == match_limit {
+                market_state.auction_price = auction.price;
+                break;
+            }
+            matches += 1;
+            let (taker_side, taker, maker) =
+                if bid.order_id().sequence() > ask.order_id().sequence() {
+                    (Side::Bid, bid, ask)
+                } else {
+                    (Side::Ask, ask, bid)
+                };
+
+            let base_trade_qty =
+                lots_to_base_qty(bid.base_quantity.min(ask.base_quantity), base_lot_size);
+            let quote_trade_qty = match taker_side {
+                Side::Bid => fp32_mul_ceil(base_trade_qty, auction.price),
+                Side::Ask => fp32_mul_floor(base_trade_qty, auction.price),
+            }
+            .map_err(ErrorCode::from)?;
+            event_queue.push_fill(
+                taker_side,
+                maker.order_id(),
+                quote_trade_qty,
+                base_trade_qty,
+                self.tree(taker_side.opposite())
+                    .get_callback_info(maker.callback_info_pt as usize),
+                self.tree(taker_side)
+                    .get_callback_info(taker.callback_info_pt as usize),
+            )?;
+            record_fill(
+                market_state,
+                event_queue,
+                trade_history.as_deref_mut(),
+                candles.as_deref_mut(),
+                taker_side,
+                auction.price,
+                base_trade_qty,
+                quote_trade_qty,
+            );
+            base_qty_traded += base_trade_qty;
+
+            for (side, leaf, handle) in [(Side::Bid, bid, bid_h), (Side::Ask, ask, ask_h)] {
+                let base_qty_left =
+                    lots_to_base_qty(leaf.base_quantity, base_lot_size) - base_trade_qty;
+                if base_qty_left <= min_base_order_size {
+                    event_queue.push_out(
+                        side,
+                        leaf.order_id(),
+                        base_qty_left,
+                        true,
+                        self.tree(side)
+                            .get_callback_info(leaf.callback_info_pt as usize),
+                    )?;
+                    self.get_tree(side).remove_by_key(leaf.key).unwrap();
+                } else {
+                    self.get_tree(side).set_leaf_base_quantity(
+                        handle,
+                        base_qty_to_lots(base_qty_left, base_lot_size),
+                    );
+                }
+            }
+        }
+        Ok(Some(Auction {
+            base_qty: base_qty_traded,
+            ..auction
+        }))
+    }
+
+    /// Checks whether a taker order with the given remaining quantities would still match against the book.
+    ///
+    /// Right after a call to [`OrderBookState::new_order`], this means that matching was cut short by the order's match limit.
+    pub fn can_match(
+        &self,
+        side: Side,
+        limit_price: u64,
+        base_qty_remaining: Quantity,
+        quote_qty_remaining: Quantity,
+        base_lot_size: u64,
+    ) -> bool {
+        let trade_price = match self.find_best_price(side.opposite()) {
+            Some(p) => p,
+            None => return false,
+        };
+        let crossed = match side {
+            Side::Bid => limit_price >= trade_price,
+            Side::Ask => limit_price <= trade_price,
+        };
+        crossed
+            && round_to_lot(
+                base_qty_remaining.min(fp32_div(quote_qty_remaining, trade_price)),
+                base_lot_size,
+            ) > 0
+    }
+
+    /// Returns the order which should be matched first on the given side, according to the market's
+    /// intra-level priority policy.
+    pub fn find_best_order(&self, side: Side, order_priority: OrderPriority) -> Option<NodeHandle> {
+        let bbo_h = self.find_bbo(side)?;
+        match order_priority {
+            OrderPriority::Fifo => Some(bbo_h),
+            OrderPriority::Size => {
+                let slab = match side {
+                    Side::Bid => &self.bids,
+                    Side::Ask => &self.asks,
+                };
+                let price = slab.get_node(bbo_h)?.as_leaf()?.price();
+                slab.find_largest_at_price(price, side == Side::Bid)
+            }
+        }
+    }
+
+    #[cfg(feature = "no-entrypoint")]
+    pub fn get_spread(&self) -> (Option<u64>, Option<u64>) {
+        let best_bid_price = self
+            .bids
+            .find_max()
+            .map(|h| self.bids.get_node(h).unwrap().as_leaf().unwrap().price());
+        let best_ask_price = self
+            .asks
+            .find_max()
+            .map(|h| self.asks.get_node(h).unwrap().as_leaf().unwrap().price());
+        (best_bid_price, best_ask_price)
+    }
+
+    /// Returns the slab of one side of the book, for reading only.
+    pub fn tree(&self, side: Side) -> &Slab<S> {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    /// Returns the slab of one side of the book, whose header [`OrderBookState::commit_changes`] then writes back.
+    pub fn get_tree(&mut self, side: Side) -> &mut Slab<S> {
+        match side {
+            Side::Bid => {
+                self.bids_dirty = true;
+                &mut self.bids
+            }
+            Side::Ask => {
+                self.asks_dirty = true;
+                &mut self.asks
+            }
+        }
+    }
+
+    /// Writes back the headers of the slabs which were accessed mutably since the last commit.
+    pub fn commit_changes(&mut self) {
+        if self.bids_dirty {
+            self.bids.write_header();
+            self.bids_dirty = false;
+        }
+        if self.asks_dirty {
+            self.asks.write_header();
+            self.asks_dirty = false;
+        }
+    }
+
+    /// Matches a new order against the opposite side of the book, then posts what remains of it if allowed.
+    ///
+    /// Quote quantities are rounded against the order, so that rounding dust always stays with the book: a bid
+    /// pays the quote quantity of each fill and of what it posts rounded up, while an ask receives it rounded down.
+    ///
+    /// Orders are only matched while the market's [`TradingSession`] is open. They are posted without matching
+    /// during a pre-open session, and rejected while the market is closed.
+    ///
+    /// An order which fails halfway through matching, for instance on a full event queue, is rolled back: the
+    /// events it pushed are taken back off the queue, and the book and the market state are restored to what they
+    /// were. The trade history and the candles are left for the runtime to roll back along with the instruction.
+    pub fn new_order(
+        &mut self,
+        params: NewOrderParams,
+        event_queue: &mut EventQueue<impl Storage>,
+        market_state: &mut MarketState,
+        trade_history: Option<&mut HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>>,
+        candles: Option<&mut Candles>,
+    ) -> Result<OrderSummary> {
+        self.place_order(
+            params,
+            event_queue,
+            market_state,
+            trade_history,
+            candles,
+            None,
+        )
+    }
+
+    /// Places a new order like [`OrderBookState::new_order`], and also lists the price levels which it was
+    /// matched against.
+    pub fn new_order_with_fill_levels(
+        &mut self,
+        params: NewOrderParams,
+        event_queue: &mut EventQueue<impl Storage>,
+        market_state: &mut MarketState,
+        trade_history: Option<&mut HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>>,
+        candles: Option<&mut Candles>,
+    ) -> Result<OrderResult> {
+        let mut fill_levels = Vec::new();
+        let summary = self.place_order(
+            params,
+            event_queue,
+            market_state,
+            trade_history,
+            candles,
+            Some(&mut fill_levels),
+        )?;
+        Ok(OrderResult {
+            summary,
+            fill_levels,
+        })
+    }
+
+    fn place_order(
+        &mut self,
+        params: NewOrderParams,
+        event_queue: &mut EventQueue<impl Storage>,
+        market_state: &mut MarketState,
+        trade_history: Option<&mut HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>>,
+        candles: Option<&mut Candles>,
+        fill_levels: Option<&mut Vec<PriceLevel>>,
+    ) -> Result<OrderSummary> {
+        let checkpoint = (event_queue.checkpoint(), *market_state);
+        let result = self.match_order(
+            params,
+            event_queue,
+            market_state,
+            trade_history,
+            candles,
+            fill_levels,
+        );
+        match result {
+            Ok(_) => event_queue.commit(),
+            Err(_) => {
+                let ((header, events_pushed), market_state_before) = checkpoint;
+                let events = event_queue.rewind(&header, events_pushed);
+                self.revert_events(&events, market_state.base_lot_size)?;
+                *market_state = market_state_before;
+            }
+        }
+        result
+    }
+
+    /// Undoes the changes which an order made to the book, given the events it pushed, oldest first.
+    ///
+    /// Every change to a resting order comes with an event which describes it: fills and partial outs give their
+    /// base quantity back to the order, while the orders which were taken out are inserted back along with the
+    /// callback information held by their out event.
+    ///
+    /// A coalesced fill event gives back its whole base quantity to its maker order, which took all of it.
+    ///
+    /// Since the events describe the book as it was left by the order, the orders they name are found in the book
+    /// unless it was changed in the meantime, which fails with [`ErrorCode::OrderNotFound`].
+    fn revert_events(&mut self, events: &[Event], base_lot_size: u64) -> Result<()> {
+        for event in events.iter().rev() {
+            let (side, order_id, base_size, removed_callback_info) = match event {
+                Event::Fill {
+                    taker_side,
+                    maker_order_id,
+                    base_size,
+                    ..
+                } => (taker_side.opposite(), *maker_order_id, *base_size, None),
+                Event::Out {
+                    side,
+                    order_id,
+                    base_size,
+                    delete,
+                    callback_info,
+                    ..
+                } => (*side, *order_id, *base_size, delete.then(|| callback_info)),
+            };
+            let base_lots = base_qty_to_lots(base_size, base_lot_size);
+            let tree = self.get_tree(side);
+            match removed_callback_info {
+                Some(callback_info) => {
+                    let callback_info_pt = tree
+                        .write_callback_info(callback_info)
+                        .map_err(ErrorCode::from)?;
+                    tree.insert_leaf(&Node::Leaf(LeafNode {
+                        key: order_id.0,
+                        callback_info_pt,
+                        base_quantity: base_lots,
+                    }))
+                    .map_err(ErrorCode::from)?;
+                }
+                None => {
+                    let handle = tree
+                        .find_by_key(order_id.0)
+                        .ok_or(ErrorCode::OrderNotFound)?;
+                    let leaf = *tree.get_node(handle).unwrap().as_leaf().unwrap();
+                    tree.set_leaf_base_quantity(handle, leaf.base_quantity + base_lots);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn match_order(
+        &mut self,
+        params: NewOrderParams,
+        event_queue: &mut EventQueue<impl Storage>,
+        market_state: &mut MarketState,
+        mut trade_history: Option<&mut HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>>,
+        mut candles: Option<&mut Candles>,
+        mut fill_levels: Option<&mut Vec<PriceLevel>>,
+    ) -> Result<OrderSummary> {
+        let NewOrderParams {
+            max_base_qty,
+            max_quote_qty,
+            side,
+            limit_price,
+            callback_info,
+            post_only,
+            post_allowed,
+            self_trade_behavior,
+            mut match_limit,
+            self_trade_id_len,
+            max_avg_price,
+        } = params;
+        // With the no-self-trade feature, the self-trade check below is optimized out of the matching loop.
+        let self_trade_behavior =
+            if cfg!(feature = "no-self-trade") || market_state.self_trade_detection_disabled != 0 {
+                SelfTradeBehavior::DecrementTake
+            } else {
+                self_trade_behavior
+            };
+
+        check_order_bounds(limit_price, max_base_qty, max_quote_qty)?;
+
+        let min_base_order_size = market_state.min_base_order_size as Quantity;
+        let base_lot_size = market_state.base_lot_size;
+        let order_priority = OrderPriority::from_u64(market_state.order_priority)
+            .ok_or(ErrorCode::FailedToDeserialize)?;
+        let session = market_state.get_trading_session()?;
+        if session == TradingSession::Closed {
+            log_error!("The market is closed");
+            return err!(ErrorCode::MarketClosed);
+        }
+
+        if post_only {
+            self.check_post_only_spread(side, limit_price, market_state)?;
+        } else if match_limit == 0 {
+            log_error!("Orders which aren't post-only must be allowed to match");
+            return err!(ErrorCode::InvalidMatchLimit);
+        }
+        if market_state.max_match_iterations != 0 {
+            match_limit = match_limit.min(market_state.max_match_iterations);
+        }
+
+        let mut base_qty_remaining = max_base_qty;
+        let mut quote_qty_remaining = max_quote_qty;
+        let quote_qty = |base_qty, price| {
+            match side {
+                Side::Bid => fp32_mul_ceil(base_qty, price),
+                Side::Ask => fp32_mul_floor(base_qty, price),
+            }
+            .map_err(ErrorCode::from)
+        };
+
+        // Only the prefix of the callback ids chosen by the order identifies self trades
+        let self_trade_id_len = match self_trade_id_len {
+            None => self.callback_id_len,
+            Some(len) if len > 0 && len <= self.callback_id_len as u64 => len as usize,
+            Some(_) => {
+                log_error!(
+                    "The self trade id length must be between 1 and {}",
+                    self.callback_id_len
+                );
+                return err!(ErrorCode::InvalidSelfTradeIdLen);
+            }
+        };
+
+        // New bid
+        let mut crossed = true;
+        let mut worst_fill_price = 0;
+        // The maker order named by the order's last fill event, along with the number of events pushed to the queue
+        // once it was, when the market coalesces fills
+        let mut last_fill: Option<(OrderId, u64)> = None;
+        loop {
+            // Orders accumulate in the book without matching until the market opens
+            if session == TradingSession::PreOpen {
+                crossed = false;
+                break;
+            }
+            if match_limit == 0 {
+                break;
+            }
+            let best_bo_h = match self.find_best_order(side.opposite(), order_priority) {
+                None => {
+                    crossed = false;
+                    break;
+                }
+                Some(h) => h,
+            };
+
+            // The best offer is only borrowed for as long as it takes to read it, and is then updated in place
+            // through its handle
+            let (best_offer_id, trade_price, offer_lots, callback_info_pt) = {
+                let best_bo = self
+                    .tree(side.opposite())
+                    .get_node(best_bo_h)
+                    .unwrap()
+                    .as_leaf()
+                    .unwrap();
+                (
+                    best_bo.order_id(),
+                    best_bo.price(),
+                    best_bo.base_quantity,
+                    best_bo.callback_info_pt as usize,
+                )
+            };
+            crossed = match side {
+                Side::Bid => limit_price >= trade_price,
+                Side::Ask => limit_price <= trade_price,
+            };
+
+            if post_only || !crossed {
+                break;
+            }
+
+            let offer_size = lots_to_base_qty(offer_lots, base_lot_size);
+            let mut base_trade_qty = round_to_lot(
+                offer_size
+                    .min(base_qty_remaining)
+                    .min(fp32_div(quote_qty_remaining, trade_price)),
+                base_lot_size,
+            );
+
+            if base_trade_qty == 0 {
+                break;
+            }
+
+            // The decrement take case can be handled by the caller program on event consumption, so no special logic
+            // is needed for it.
+            if self_trade_behavior != SelfTradeBehavior::DecrementTake {
+                let order_would_self_trade = &callback_info[..self_trade_id_len]
+                    == (&self
+                        .tree(side.opposite())
+                        .get_callback_info(callback_info_pt)[..self_trade_id_len]
+                        as &[u8]);
+                if order_would_self_trade {
+                    match self_trade_behavior {
+                        // The whole provide order is cancelled, cancelling only the overlapping quantity would
+                        // leave the rest of it at the top of the book and match it again on the next pass
+                        SelfTradeBehavior::CancelProvide => {}
+                        SelfTradeBehavior::AbortTransaction => {
+                            return Err(error!(ErrorCode::WouldSelfTrade))
+                        }
+                        SelfTradeBehavior::DecrementTake => unreachable!(),
+                    };
+
+                    event_queue.push_out(
+                        side.opposite(),
+                        best_offer_id,
+                        offer_size,
+                        true,
+                        self.tree(side.opposite())
+                            .get_callback_info(callback_info_pt),
+                    )?;
+                    self.get_tree(side.opposite())
+                        .remove_by_key(best_offer_id.0)
+                        .unwrap();
+
+                    continue;
+                }
+            }
+
+            // Only the part of the offer which keeps the order's average price within its bound is taken, the
+            // number of lots being searched for when the whole offer would exceed it
+            if let Some(max_avg_price) = max_avg_price {
+                let matched_base_qty = max_base_qty - base_qty_remaining;
+                let matched_quote_qty = max_quote_qty - quote_qty_remaining;
+                let exceeds_bound = |base_qty: Quantity| -> Result<bool> {
+                    let avg_price = avg_fill_price(
+                        matched_quote_qty + quote_qty(base_qty, trade_price)?,
+                        matched_base_qty + base_qty,
+                    );
+                    Ok(match side {
+                        Side::Bid => avg_price > max_avg_price,
+                        Side::Ask => avg_price < max_avg_price,
+                    })
+                };
+                if exceeds_bound(base_trade_qty)? {
+                    let lot_size = base_lot_size as Quantity;
+                    let (mut within, mut exceeding) = (0, base_trade_qty / lot_size);
+                    while exceeding - within > 1 {
+                        let lots = within + (exceeding - within) / 2;
+                        if exceeds_bound(lots * lot_size)? {
+                            exceeding = lots;
+                        } else {
+                            within = lots;
+                        }
+                    }
+                    base_trade_qty = within * lot_size;
+                    // The order still crosses the book, so the loop leaves `crossed` set and nothing is posted
+                    if base_trade_qty == 0 {
+                        break;
+                    }
+                }
+            }
+
+            let quote_maker_qty = quote_qty(base_trade_qty, trade_price)?;
+
+            let maker_callback_info = self
+                .tree(side.opposite())
+                .get_callback_info(callback_info_pt);
+            // Only the fills of the same maker order are coalesced, so that the event keeps describing a single order
+            let coalesced = match last_fill {
+                Some((event_order_id, events_pushed)) if event_order_id == best_offer_id => {
+                    event_queue.coalesce_fill(
+                        event_queue.events_pushed() - events_pushed,
+                        quote_maker_qty,
+                        base_trade_qty,
+                    )
+                }
+                _ => false,
+            };
+            if !coalesced {
+                event_queue.push_fill(
+                    side,
+                    best_offer_id,
+                    quote_maker_qty,
+                    base_trade_qty,
+                    maker_callback_info,
+                    &callback_info,
+                )?;
+                if market_state.coalesce_fills != 0 {
+                    last_fill = Some((best_offer_id, event_queue.events_pushed()));
+                }
+            }
+
+            record_fill(
+                market_state,
+                event_queue,
+                trade_history.as_deref_mut(),
+                candles.as_deref_mut(),
+                side,
+                trade_price,
+                base_trade_qty,
+                quote_maker_qty,
+            );
+            worst_fill_price = match side {
+                Side::Bid => worst_fill_price.max(trade_price),
+                Side::Ask if worst_fill_price == 0 => trade_price,
+                Side::Ask => worst_fill_price.min(trade_price),
+            };
+            if let Some(fill_levels) = fill_levels.as_deref_mut() {
+                let level_count = fill_levels.len();
+                match fill_levels.last_mut() {
+                    Some(level) if level.price == trade_price => {
+                        level.base_quantity += base_trade_qty
+                    }
+                    _ if level_count < MAX_FILL_LEVELS => fill_levels.push(PriceLevel {
+                        price: trade_price,
+                        base_quantity: base_trade_qty,
+                    }),
+                    _ => {}
+                }
+            }
+
+            base_qty_remaining -= base_trade_qty;
+            quote_qty_remaining -= quote_maker_qty;
+
+            if offer_size - base_trade_qty <= min_base_order_size {
+                let cur_side = side.opposite();
+                event_queue.push_out(
+                    cur_side,
+                    best_offer_id,
+                    offer_size - base_trade_qty,
+                    true,
+                    self.tree(cur_side).get_callback_info(callback_info_pt),
+                )?;
+                self.get_tree(cur_side)
+                    .remove_by_key(best_offer_id.0)
+                    .unwrap();
+            } else {
+                self.get_tree(side.opposite()).set_leaf_base_quantity(
+                    best_bo_h,
+                    base_qty_to_lots(offer_size - base_trade_qty, base_lot_size),
+                );
+            }
+
+            match_limit -= 1;
+        }
+        let avg_fill_price = avg_fill_price(
+            max_quote_qty - quote_qty_remaining,
+            max_base_qty - base_qty_remaining,
+        );
+
+        let base_qty_to_post = round_to_lot(
+            std::cmp::min(
+                fp32_div(quote_qty_remaining, limit_price),
+                base_qty_remaining,
+            ),
+            base_lot_size,
+        );
+
+        if crossed || !post_allowed || base_qty_to_post <= min_base_order_size {
+            return Ok(OrderSummary {
+                posted_order_id: None,
+                total_base_qty: max_base_qty - base_qty_remaining,
+                total_quote_qty: max_quote_qty - quote_qty_remaining,
+                total_fee_qty: 0,
+                total_base_qty_posted: 0,
+                avg_fill_price_fp32: avg_fill_price,
+                worst_fill_price_fp32: worst_fill_price,
+            });
+        }
+
+        let new_leaf_order_id = event_queue.gen_order_id(limit_price, side)?;
+        let quote_qty_to_post = quote_qty(base_qty_to_post, limit_price)?;
+        let callback_info_offset = self
+            .get_tree(side)
+            .write_callback_info(&callback_info)
+            .unwrap();
+        let new_leaf = Node::Leaf(LeafNode {
+            key: new_leaf_order_id.0,
+            callback_info_pt: callback_info_offset,
+            base_quantity: base_qty_to_lots(base_qty_to_post, base_lot_size),
+        });
+        let insert_result = self.get_tree(side).insert_leaf(&new_leaf);
+        if let Err(SlabError::OutOfSpace) = insert_result {
+            // Boot out the least aggressive orders. The out event is pushed before the order is removed, so that a
+            // full event queue leaves the book as it was.
+            log_debug!("Orderbook is full! booting lest aggressive orders...");
+            let booted_h = match side {
+                Side::Bid => self.tree(side).find_min(),
+                Side::Ask => self.tree(side).find_max(),
+            }
+            .unwrap();
+            let booted = *self
+                .tree(side)
+                .get_node(booted_h)
+                .unwrap()
+                .as_leaf()
+                .unwrap();
+            let pushed = event_queue.push_out(
+                side,
+                booted.order_id(),
+                lots_to_base_qty(booted.base_quantity, base_lot_size),
+                true,
+                self.tree(side)
+                    .get_callback_info(booted.callback_info_pt as usize),
+            );
+            if let Err(error) = pushed {
+                self.get_tree(side)
+                    .clear_callback_info(callback_info_offset as usize);
+                return Err(error.into());
+            }
+            self.get_tree(side).remove_by_key(booted.key).unwrap();
+            self.get_tree(side).insert_leaf(&new_leaf).unwrap();
+        } else {
+            insert_result.unwrap();
+        }
+        base_qty_remaining -= base_qty_to_post;
+        quote_qty_remaining -= quote_qty_to_post;
+        Ok(OrderSummary {
+            posted_order_id: Some(new_leaf_order_id),
+            total_base_qty: max_base_qty - base_qty_remaining,
+            total_quote_qty: max_quote_qty - quote_qty_remaining,
+            total_fee_qty: 0,
+            total_base_qty_posted: base_qty_to_post,
+            avg_fill_price_fp32: avg_fill_price,
+            worst_fill_price_fp32: worst_fill_price,
+        })
+    }
+
+    /// Verifies that a post-only order isn't priced further than the market's maximum spread from the
+    /// opposite best offer, or from the last trade price when the opposite side of the book is empty.
+    pub fn check_post_only_spread(
+        &self,
+        side: Side,
+        limit_price: u64,
+        market_state: &MarketState,
+    ) -> Result<()> {
+        if market_state.max_post_only_spread_ticks == 0 {
+            return Ok(());
+        }
+        let reference_price = match self.find_best_price(side.opposite()) {
+            Some(price) => price,
+            None if market_state.last_trade_price != 0 => market_state.last_trade_price,
+            None => return Ok(()),
+        };
+        let distance = match side {
+            Side::Bid => reference_price.saturating_sub(limit_price),
+            Side::Ask => limit_price.saturating_sub(reference_price),
+        };
+        if distance
+            > market_state
+                .max_post_only_spread_ticks
+                .saturating_mul(market_state.tick_size)
+        {
+            log_error!("The post-only order is too far from the opposite best offer");
+            return Err(error!(ErrorCode::PostOnlyTooFar));
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.asks.root().is_none() && self.bids.root().is_none()
+    }
+}
+
+/// Computes the opening auction of a crossed book from the price levels of both sides, best first: the price which
+/// trades the largest base quantity when the crossed orders are matched against each other.
+///
+/// Among the prices which trade the most, the one which leaves the smallest quantity unmatched on either side is
+/// chosen, then the one closest to `reference_price`, typically the market's last trade price. Any remaining tie goes
+/// to the lowest price. The levels don't need to be aggregated, so that the orders of a side can be listed as they
+/// are.
+pub fn indicative_auction(
+    bids: &[PriceLevel],
+    asks: &[PriceLevel],
+    reference_price: u64,
+) -> Option<Auction> {
+    let best_bid = bids.first()?.price;
+    let best_ask = asks.first()?.price;
+    if best_bid < best_ask {
+        return None;
+    }
+    // Only the levels priced between the best ask and the best bid can trade, and the auction price is one of
+    // their prices
+    let bids = &bids[..bids.iter().take_while(|l| l.price >= best_ask).count()];
+    let asks = &asks[..asks.iter().take_while(|l| l.price <= best_bid).count()];
+    let mut prices: Vec<u64> = bids.iter().chain(asks).map(|l| l.price).collect();
+    prices.sort_unstable();
+    prices.dedup();
+
+    // The prices are walked upwards, which takes the bids out of the demand from the worst one, at the end of
+    // their levels, and adds the asks to the supply from the best one
+    let mut demand: Quantity = bids
+        .iter()
+        .map(|l| l.base_quantity)
+        .fold(0, Quantity::saturating_add);
+    let mut supply: Quantity = 0;
+    let (mut bids_left, mut next_ask) = (bids.len(), 0);
+    // The traded quantity, the unmatched quantity and the distance to the reference price of the best price so far
+    let mut best: Option<(u64, (Quantity, Quantity, u64))> = None;
+    for price in prices {
+        while bids_left > 0 && bids[bids_left - 1].price < price {
+            bids_left -= 1;
+            demand -= bids[bids_left].base_quantity.min(demand);
+        }
+        while next_ask < asks.len() && asks[next_ask].price <= price {
+            supply = supply.saturating_add(asks[next_ask].base_quantity);
+            next_ask += 1;
+        }
+        let traded = demand.min(supply);
+        let rank = (
+            traded,
+            demand.max(supply) - traded,
+            price.max(reference_price) - price.min(reference_price),
+        );
+        let better = match best {
+            None => true,
+            Some((_, best_rank)) => {
+                rank.0 > best_rank.0
+                    || (rank.0 == best_rank.0 && (rank.1, rank.2) < (best_rank.1, best_rank.2))
+            }
+        };
+        if better {
+            best = Some((price, rank));
+        }
+    }
+    best.map(|(price, (base_qty, _, _))| Auction { price, base_qty })
+}
+
+/// Records a fill in the market's price accumulator and volume counters, and in its trade history and candles if
+/// it has them.
+#[allow(clippy::too_many_arguments)]
+fn record_fill(
+    market_state: &mut MarketState,
+    event_queue: &EventQueue<impl Storage>,
+    trade_history: Option<&mut HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>>,
+    candles: Option<&mut Candles>,
+    taker_side: Side,
+    trade_price: u64,
+    base_trade_qty: Quantity,
+    quote_trade_qty: Quantity,
+) {
+    market_state.update_price_cumulative(event_queue.unix_timestamp);
+    market_state.last_trade_price = trade_price;
+    if let Some(prices) = trade_history {
+        prices.push(trade_price);
+    }
+    // A single fill of more than `i64::MAX` base quantity, which only 128-bit quantities allow, saturates the flow
+    let signed_base_trade_qty = i64::try_from(base_trade_qty).unwrap_or(i64::MAX);
+    let signed_base_trade_qty = match taker_side {
+        Side::Bid => signed_base_trade_qty,
+        Side::Ask => -signed_base_trade_qty,
+    };
+    if let Some(candles) = candles {
+        candles.record(
+            event_queue.slot,
+            trade_price,
+            low_u64(base_trade_qty),
+            low_u64(quote_trade_qty),
+            signed_base_trade_qty,
+        );
+    }
+    market_state.base_volume = market_state
+        .base_volume
+        .wrapping_add(low_u64(base_trade_qty));
+    market_state.quote_volume = market_state
+        .quote_volume
+        .wrapping_add(low_u64(quote_trade_qty));
+    market_state.net_taker_flow = market_state
+        .net_taker_flow
+        .wrapping_add(signed_base_trade_qty);
+}
+
+/// The quote quantity per unit of base quantity, as FP32 rounded down and saturated to `u64::MAX`, or 0 when the
+/// base quantity is 0.
+fn avg_fill_price(quote_qty: Quantity, base_qty: Quantity) -> u64 {
+    if base_qty == 0 {
+        return 0;
+    }
+    let (quote_qty, base_qty) = (u128::from(quote_qty), u128::from(base_qty));
+    let integer_part = quote_qty / base_qty;
+    if integer_part >> 32 != 0 {
+        return u64::MAX;
+    }
+    let fractional_part = ((quote_qty % base_qty) << 32) / base_qty;
+    ((integer_part << 32) | fractional_part) as u64
+}
+
+/// Verifies that the parameters of a new order are within the bounds which keep the matching arithmetic
+/// free of overflows, see [`MAX_PRICE_FP32`], [`MAX_BASE_QTY`] and [`MAX_QUOTE_QTY`].
+pub fn check_order_bounds(
+    limit_price: u64,
+    max_base_qty: Quantity,
+    max_quote_qty: Quantity,
+) -> Result<()> {
+    if limit_price == 0 || limit_price > MAX_PRICE_FP32 {
+        log_error!("The limit price must be in 1..={}", MAX_PRICE_FP32);
+        return err!(ErrorCode::InvalidLimitPrice);
+    }
+    if max_base_qty > MAX_BASE_QTY || max_quote_qty > MAX_QUOTE_QTY {
+        log_error!(
+            "The base and quote quantities must be at most {} and {}",
+            MAX_BASE_QTY,
+            MAX_QUOTE_QTY
+        );
+        return err!(ErrorCode::QuantityTooLarge);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ReduceOrderParams;
+    use crate::sim::tests::{order, params, test_market};
+    use crate::sim::SimError;
+
+    #[test]
+    fn self_trade_detection() {
+        let mut market = test_market(16);
+        let price = 10 << 32;
+        let order = |side, self_trade_behavior| NewOrderParams {
+            max_quote_qty: 1 << 40,
+            callback_info: vec![1; 32],
+            self_trade_behavior,
+            ..params(side, price, 10)
+        };
+
+        market
+            .new_order(order(Side::Ask, SelfTradeBehavior::DecrementTake))
+            .unwrap();
+        if !cfg!(feature = "no-self-trade") {
+            assert!(matches!(
+                market.new_order(order(Side::Bid, SelfTradeBehavior::AbortTransaction)),
+                Err(SimError::Orderbook(_))
+            ));
+        }
+
+        market.market_state.self_trade_detection_disabled = 1;
+        let bid = market
+            .new_order(order(Side::Bid, SelfTradeBehavior::AbortTransaction))
+            .unwrap();
+        assert_eq!(bid.total_base_qty, 10);
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancel_provide() {
+        let mut market = test_market(16);
+        let price = 10 << 32;
+        let order = |side, base_qty| NewOrderParams {
+            max_quote_qty: 1 << 40,
+            callback_info: vec![1; 32],
+            self_trade_behavior: SelfTradeBehavior::CancelProvide,
+            ..params(side, price, base_qty)
+        };
+
+        market.new_order(order(Side::Ask, 10)).unwrap();
+        let bid = market.new_order(order(Side::Bid, 2)).unwrap();
+        if !cfg!(feature = "no-self-trade") {
+            // The whole self-provided order leaves the book, not only the part the bid would have taken
+            assert_eq!(bid.total_base_qty_posted, 2);
+            assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+            assert_eq!(market.l2(Side::Bid, 10).unwrap(), vec![(price, 2)]);
+        }
+    }
+
+    #[test]
+    fn self_trade_id_len() {
+        let mut market = test_market(16);
+        let price = 10 << 32;
+        // The callback ids of two sub-accounts of the same firm
+        let sub_account = |id| [vec![1; 16], vec![id; 16]].concat();
+        let order = |side, sub_account, self_trade_id_len| NewOrderParams {
+            max_quote_qty: 1 << 40,
+            callback_info: sub_account,
+            self_trade_behavior: SelfTradeBehavior::AbortTransaction,
+            self_trade_id_len,
+            ..params(side, price, 10)
+        };
+
+        market
+            .new_order(order(Side::Ask, sub_account(1), None))
+            .unwrap();
+        for self_trade_id_len in [Some(0), Some(33)] {
+            assert!(matches!(
+                market.new_order(order(Side::Bid, sub_account(2), self_trade_id_len)),
+                Err(SimError::Orderbook(_))
+            ));
+        }
+        // The firm's own id is a self trade, unless whole sub-accounts are compared
+        if !cfg!(feature = "no-self-trade") {
+            assert!(matches!(
+                market.new_order(order(Side::Bid, sub_account(2), Some(16))),
+                Err(SimError::Orderbook(_))
+            ));
+        }
+        let bid = market
+            .new_order(order(Side::Bid, sub_account(2), Some(32)))
+            .unwrap();
+        assert_eq!(bid.total_base_qty, 10);
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rounding_favors_the_book() {
+        let mut market = test_market(16);
+        // 3 base is worth slightly less than 1 quote
+        let price = (1 << 32) / 3;
+        let order = |side, owner| NewOrderParams {
+            max_quote_qty: 1 << 40,
+            callback_info: vec![owner; 32],
+            ..params(side, price, 3)
+        };
+
+        // A bid pays for what it posts rounded up, while an ask taking it receives the fill rounded down
+        let bid = market.new_order(order(Side::Bid, 1)).unwrap();
+        assert_eq!(bid.total_quote_qty, 1);
+        let ask = market.new_order(order(Side::Ask, 2)).unwrap();
+        assert_eq!((ask.total_base_qty, ask.total_quote_qty), (3, 0));
+
+        // A bid taking an ask pays the fill rounded up
+        let ask = market.new_order(order(Side::Ask, 1)).unwrap();
+        assert_eq!(ask.total_quote_qty, 0);
+        let bid = market.new_order(order(Side::Bid, 2)).unwrap();
+        assert_eq!((bid.total_base_qty, bid.total_quote_qty), (3, 1));
+        assert!(market.l2(Side::Bid, 10).unwrap().is_empty());
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn order_bounds() {
+        let mut market = test_market(16);
+        let order = |side, limit_price, max_base_qty, max_quote_qty, owner| NewOrderParams {
+            max_quote_qty,
+            callback_info: vec![owner; 32],
+            ..params(side, limit_price, max_base_qty)
+        };
+
+        for (price, base_qty, quote_qty) in [
+            (0, 1, 1),
+            (MAX_PRICE_FP32 + 1, 1, 1),
+            (MAX_PRICE_FP32, MAX_BASE_QTY + 1, 1),
+            (MAX_PRICE_FP32, 1, MAX_QUOTE_QTY + 1),
+        ] {
+            assert!(matches!(
+                market.new_order(order(Side::Bid, price, base_qty, quote_qty, 1)),
+                Err(SimError::Orderbook(_))
+            ));
+        }
+
+        // The largest orders at the highest price
+        market
+            .new_order(order(
+                Side::Ask,
+                MAX_PRICE_FP32,
+                MAX_BASE_QTY,
+                MAX_QUOTE_QTY,
+                1,
+            ))
+            .unwrap();
+        let bid = market
+            .new_order(order(
+                Side::Bid,
+                MAX_PRICE_FP32,
+                MAX_BASE_QTY,
+                MAX_QUOTE_QTY,
+                2,
+            ))
+            .unwrap();
+        assert_eq!(bid.total_base_qty, MAX_BASE_QTY);
+        assert!(bid.total_quote_qty <= MAX_QUOTE_QTY);
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+
+        // The largest order at the lowest price
+        let bid = market
+            .new_order(order(Side::Bid, 1, MAX_BASE_QTY, MAX_QUOTE_QTY, 2))
+            .unwrap();
+        assert_eq!(bid.total_base_qty_posted, MAX_BASE_QTY);
+    }
+
+    #[test]
+    fn trading_sessions() {
+        let mut market = test_market(16);
+        market.market_state.trading_session = TradingSession::PreOpen as u8;
+
+        // Crossing orders rest side by side during the pre-open session
+        let ask = market.new_order(order(Side::Ask, 10 << 32, 10, 1)).unwrap();
+        let bid = market.new_order(order(Side::Bid, 11 << 32, 10, 2)).unwrap();
+        assert_eq!(ask.total_base_qty_posted, 10);
+        assert_eq!(bid.total_base_qty_posted, 10);
+        assert!(market.events().is_empty());
+
+        // Closed markets only accept cancels
+        market
+            .market_state
+            .set_trading_session(TradingSession::Closed)
+            .unwrap();
+        assert!(matches!(
+            market.new_order(order(Side::Bid, 11 << 32, 10, 2)),
+            Err(SimError::Orderbook(_))
+        ));
+        market.cancel_order(bid.posted_order_id.unwrap()).unwrap();
+
+        // Once open, orders match again
+        market
+            .market_state
+            .set_trading_session(TradingSession::Open)
+            .unwrap();
+        let bid = market.new_order(order(Side::Bid, 10 << 32, 10, 2)).unwrap();
+        assert_eq!(bid.total_base_qty, 10);
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn opening_auction() {
+        let mut market = test_market(16);
+        market.market_state.trading_session = TradingSession::PreOpen as u8;
+        let order = |side, price: u64, base_qty, owner| NewOrderParams {
+            max_quote_qty: 1 << 50,
+            callback_info: vec![owner; 32],
+            ..params(side, price << 32, base_qty)
+        };
+        let mut ids = Vec::new();
+        for (side, price, base_qty, owner) in [
+            (Side::Bid, 12, 10, 1),
+            (Side::Bid, 11, 10, 2),
+            (Side::Bid, 9, 10, 3),
+            (Side::Ask, 10, 5, 4),
+            (Side::Ask, 11, 10, 5),
+            (Side::Ask, 13, 10, 6),
+        ] {
+            let summary = market
+                .new_order(order(side, price, base_qty, owner))
+                .unwrap();
+            ids.push(summary.posted_order_id.unwrap());
+        }
+
+        // 15 trade at 11, against 5 at 10 and 10 at 12
+        let auction = market.open_session(10).unwrap().unwrap();
+        assert_eq!(
+            auction,
+            Auction {
+                price: 11 << 32,
+                base_qty: 15
+            }
+        );
+        assert_eq!(
+            market.market_state.get_trading_session().unwrap(),
+            TradingSession::Open
+        );
+        assert_eq!(market.market_state.last_trade_price, 11 << 32);
+        assert_eq!(market.auditor.total_base_filled, 15);
+        assert_eq!(
+            market.l2(Side::Bid, 10).unwrap(),
+            vec![(11 << 32, 5), (9 << 32, 10)]
+        );
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(13 << 32, 10)]);
+
+        // The later order of each match is its taker
+        let fills: Vec<_> = market
+            .events()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Fill {
+                    taker_side,
+                    maker_order_id,
+                    quote_size,
+                    base_size,
+                    ..
+                } => Some((taker_side, maker_order_id, quote_size, base_size)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (Side::Ask, ids[0], 55, 5),
+                (Side::Ask, ids[0], 55, 5),
+                (Side::Ask, ids[1], 55, 5),
+            ]
+        );
+
+        // Only pre-open markets hold an auction
+        assert!(matches!(
+            market.open_session(10),
+            Err(SimError::Orderbook(_))
+        ));
+        market
+            .market_state
+            .set_trading_session(TradingSession::Closed)
+            .unwrap();
+        market
+            .market_state
+            .set_trading_session(TradingSession::PreOpen)
+            .unwrap();
+        assert_eq!(market.open_session(10).unwrap(), None);
+    }
+
+    #[test]
+    fn resumed_opening_auction() {
+        let mut market = test_market(16);
+        market.market_state.trading_session = TradingSession::PreOpen as u8;
+        let order = |side, price: u64, base_qty, owner| NewOrderParams {
+            max_quote_qty: 1 << 50,
+            callback_info: vec![owner; 32],
+            ..params(side, price << 32, base_qty)
+        };
+        for (side, price, base_qty, owner) in [
+            (Side::Bid, 12, 10, 1),
+            (Side::Bid, 11, 10, 2),
+            (Side::Bid, 9, 10, 3),
+            (Side::Ask, 10, 5, 4),
+            (Side::Ask, 11, 10, 5),
+            (Side::Ask, 13, 10, 6),
+        ] {
+            market
+                .new_order(order(side, price, base_qty, owner))
+                .unwrap();
+        }
+
+        // The auction takes 3 matches, which are run one at a time at the price of the first step
+        for step in 0..3 {
+            let auction = market.open_session(1).unwrap().unwrap();
+            assert_eq!(
+                auction,
+                Auction {
+                    price: 11 << 32,
+                    base_qty: 5
+                }
+            );
+            let session = market.market_state.get_trading_session().unwrap();
+            if step < 2 {
+                assert_eq!(session, TradingSession::PreOpen);
+                assert_eq!(market.market_state.auction_price, 11 << 32);
+            } else {
+                assert_eq!(session, TradingSession::Open);
+                assert_eq!(market.market_state.auction_price, 0);
+            }
+        }
+        assert_eq!(market.auditor.total_base_filled, 15);
+        assert_eq!(
+            market.l2(Side::Bid, 10).unwrap(),
+            vec![(11 << 32, 5), (9 << 32, 10)]
+        );
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(13 << 32, 10)]);
+    }
+
+    #[test]
+    fn rollback_on_full_event_queue() {
+        let mut market = test_market(2);
+        for price in [10 << 32, 11 << 32] {
+            market.new_order(order(Side::Ask, price, 10, 1)).unwrap();
+        }
+        market.new_order(order(Side::Bid, 10 << 32, 5, 2)).unwrap();
+        assert_eq!(market.consume_events(10), 1);
+        let asks = market.l2(Side::Ask, 10).unwrap();
+        let market_state = market.market_state;
+
+        // The fill and the out of the first ask fill the queue, and the fill of the second one fails. The order is
+        // run without the backup of the simulator, so that only the order book's own rollback restores the market.
+        let (result, events) = market
+            .transact_unchecked(|order_book, event_queue, market_state| {
+                Ok(order_book.new_order(
+                    order(Side::Bid, 11 << 32, 20, 2),
+                    event_queue,
+                    market_state,
+                    None,
+                    None,
+                ))
+            })
+            .unwrap();
+        assert!(result.is_err());
+        assert!(events.is_empty());
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), asks);
+        assert!(market.l2(Side::Bid, 10).unwrap().is_empty());
+        assert_eq!(market.market_state.base_volume, market_state.base_volume);
+        assert_eq!(
+            market.market_state.last_trade_price,
+            market_state.last_trade_price
+        );
+
+        // The restored ask can still be taken, with events which fit in the queue
+        let bid = market.new_order(order(Side::Bid, 10 << 32, 5, 2)).unwrap();
+        assert_eq!(bid.total_base_qty, 5);
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(11 << 32, 10)]);
+    }
+
+    #[test]
+    fn rollback_with_evictions() {
+        use crate::state::EventQueueOverflowPolicy;
+
+        // The order below fails by trading with its own owner
+        if cfg!(feature = "no-self-trade") {
+            return;
+        }
+        let mut market = test_market(2);
+        market.market_state.event_queue_overflow_policy =
+            EventQueueOverflowPolicy::EvictOldest as u64;
+        let order = |side, price: u64, base_qty, owner| NewOrderParams {
+            max_quote_qty: 1 << 50,
+            callback_info: vec![owner; 32],
+            self_trade_behavior: SelfTradeBehavior::AbortTransaction,
+            ..params(side, price << 32, base_qty)
+        };
+        market.new_order(order(Side::Ask, 9, 10, 3)).unwrap();
+        market.new_order(order(Side::Bid, 9, 5, 4)).unwrap();
+        for (price, owner) in [(10, 1), (11, 2)] {
+            market
+                .new_order(order(Side::Ask, price, 10, owner))
+                .unwrap();
+        }
+        let serialize = |events: Vec<Event>| -> Vec<Vec<u8>> {
+            events.iter().map(|e| e.try_to_vec().unwrap()).collect()
+        };
+        let events = serialize(market.events());
+        assert_eq!(events.len(), 1);
+        let asks = market.l2(Side::Ask, 10).unwrap();
+        let market_state = market.market_state;
+
+        // The fills and outs of the first two asks evict the fill left in the queue, and then each other, before
+        // the third ask fails the order. Only the order book's own rollback restores the market.
+        let (result, pushed) = market
+            .transact_unchecked(|order_book, event_queue, market_state| {
+                Ok(order_book.new_order(
+                    order(Side::Bid, 11, 30, 2),
+                    event_queue,
+                    market_state,
+                    None,
+                    None,
+                ))
+            })
+            .unwrap();
+        assert!(result.is_err());
+        assert!(pushed.is_empty());
+        assert_eq!(serialize(market.events()), events);
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), asks);
+        assert!(market.l2(Side::Bid, 10).unwrap().is_empty());
+        assert_eq!(market.market_state.base_volume, market_state.base_volume);
+
+        // The restored asks can still be taken once the old fill is consumed
+        assert_eq!(market.consume_events(10), 1);
+        let bid = market.new_order(order(Side::Bid, 9, 5, 4)).unwrap();
+        assert_eq!(bid.total_base_qty, 5);
+        assert_eq!(
+            market.l2(Side::Ask, 10).unwrap(),
+            vec![(10 << 32, 10), (11 << 32, 10)]
+        );
+    }
+
+    #[test]
+    fn fill_levels() {
+        let mut market = test_market(16);
+        for (price, base_qty, owner) in [(10 << 32, 3, 1), (10 << 32, 2, 2), (11 << 32, 4, 1)] {
+            market
+                .new_order(order(Side::Ask, price, base_qty, owner))
+                .unwrap();
+        }
+
+        // The fills against both asks at 10 make up a single level, and what the bid can't take is posted
+        let (result, _) = market
+            .transact_unchecked(|order_book, event_queue, market_state| {
+                order_book.new_order_with_fill_levels(
+                    order(Side::Bid, 11 << 32, 20, 3),
+                    event_queue,
+                    market_state,
+                    None,
+                    None,
+                )
+            })
+            .unwrap();
+        assert_eq!(
+            result.fill_levels,
+            vec![
+                PriceLevel {
+                    price: 10 << 32,
+                    base_quantity: 5,
+                },
+                PriceLevel {
+                    price: 11 << 32,
+                    base_quantity: 4,
+                },
+            ]
+        );
+        assert_eq!(result.summary.total_base_qty, 20);
+        assert_eq!(result.summary.total_base_qty_posted, 11);
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fill_prices() {
+        let mut market = test_market(16);
+        for (price, base_qty) in [(10 << 32, 5), (11 << 32, 4)] {
+            let ask = market
+                .new_order(order(Side::Ask, price, base_qty, 1))
+                .unwrap();
+            assert_eq!((ask.avg_fill_price_fp32, ask.worst_fill_price_fp32), (0, 0));
+        }
+        for (price, base_qty) in [(8 << 32, 2), (9 << 32, 6)] {
+            market
+                .new_order(order(Side::Bid, price, base_qty, 1))
+                .unwrap();
+        }
+
+        // A bid's worst fill is its highest one, and an ask's its lowest one
+        let bid = market.new_order(order(Side::Bid, 12 << 32, 9, 2)).unwrap();
+        assert_eq!(bid.total_quote_qty, 94);
+        assert_eq!(bid.avg_fill_price_fp32, (94 << 32) / 9);
+        assert_eq!(bid.worst_fill_price_fp32, 11 << 32);
+        let ask = market.new_order(order(Side::Ask, 8 << 32, 8, 2)).unwrap();
+        assert_eq!(ask.avg_fill_price_fp32, (70 << 32) / 8);
+        assert_eq!(ask.worst_fill_price_fp32, 8 << 32);
+    }
+
+    #[test]
+    fn max_avg_price() {
+        let mut market = test_market(16);
+        let order = |side, price, base_qty, owner, max_avg_price| NewOrderParams {
+            max_quote_qty: 1 << 50,
+            callback_info: vec![owner; 32],
+            max_avg_price,
+            ..params(side, price, base_qty)
+        };
+        for (side, price) in [
+            (Side::Ask, 10 << 32),
+            (Side::Ask, 12 << 32),
+            (Side::Bid, 9 << 32),
+        ] {
+            market.new_order(order(side, price, 5, 1, None)).unwrap();
+        }
+
+        // Taking the whole ask at 12 would bring the average price above 10.5, which leaves room for 1 lot of it.
+        // The rest of the bid isn't posted.
+        let bid = market
+            .new_order(order(Side::Bid, 12 << 32, 10, 2, Some(21 << 31)))
+            .unwrap();
+        assert_eq!((bid.total_base_qty, bid.total_quote_qty), (6, 62));
+        assert_eq!(bid.avg_fill_price_fp32, (62 << 32) / 6);
+        assert_eq!(bid.posted_order_id, None);
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(12 << 32, 4)]);
+
+        // An ask whose bound is above the best bid isn't matched at all
+        let ask = market
+            .new_order(order(Side::Ask, 9 << 32, 5, 2, Some(10 << 32)))
+            .unwrap();
+        assert_eq!(ask.total_base_qty, 0);
+        assert_eq!(market.l2(Side::Bid, 10).unwrap(), vec![(9 << 32, 5)]);
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(12 << 32, 4)]);
+    }
+
+    #[test]
+    fn max_match_iterations() {
+        let mut market = test_market(16);
+        market.market_state.max_match_iterations = 2;
+        for price in [10 << 32, 11 << 32, 12 << 32] {
+            market.new_order(order(Side::Ask, price, 5, 1)).unwrap();
+        }
+
+        // The bid's own match limit of 10 is capped by the market
+        let bid = market.new_order(order(Side::Bid, 12 << 32, 15, 2)).unwrap();
+        assert_eq!(bid.total_base_qty, 10);
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(12 << 32, 5)]);
+    }
+
+    #[test]
+    fn coalesce_fills() {
+        let mut market = test_market(16);
+        market.market_state.coalesce_fills = 1;
+        let mut ids = vec![];
+        for (price, owner) in [(10 << 32, 1), (11 << 32, 1), (12 << 32, 3)] {
+            let summary = market.new_order(order(Side::Ask, price, 5, owner)).unwrap();
+            ids.push(summary.posted_order_id.unwrap());
+        }
+
+        // Fills against distinct orders keep their own events, even when they share an owner, so that each fill
+        // is attributed to the order it took from
+        let (bid, events) = market
+            .new_order_with_events(order(Side::Bid, 12 << 32, 15, 2))
+            .unwrap();
+        assert_eq!((bid.total_base_qty, bid.total_quote_qty), (15, 165));
+        let fills = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Fill {
+                    maker_order_id,
+                    quote_size,
+                    base_size,
+                    ..
+                } => Some((*maker_order_id, *quote_size, *base_size)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fills,
+            vec![(ids[0], 50, 5), (ids[1], 55, 5), (ids[2], 60, 5)]
+        );
+        assert_eq!(events.len(), 6);
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancel_order_callback_id() {
+        let mut market = test_market(16);
+        let price = 10 << 32;
+        let ask = market
+            .new_order(NewOrderParams {
+                max_quote_qty: 1 << 40,
+                callback_info: vec![1; 32],
+                ..params(Side::Ask, price, 10)
+            })
+            .unwrap();
+        let order_id = ask.posted_order_id.unwrap();
+
+        // A wrong callback id leaves the order in the book, even without a rollback of the market
+        assert!(market
+            .transact_unchecked(
+                |order_book, _, _| order_book.cancel_order(order_id, Some(&[2; 32][..]))
+            )
+            .is_err());
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(price, 10)]);
+        market
+            .transact_unchecked(|order_book, _, _| {
+                order_book.cancel_order(order_id, Some(&[1; 32][..]))
+            })
+            .unwrap();
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reduce_order() {
+        let mut market = test_market(16);
+        market.market_state.min_base_order_size = 1;
+        let price = 10 << 32;
+        let order = |side, base_qty, owner| NewOrderParams {
+            max_quote_qty: 1 << 40,
+            callback_info: vec![owner; 32],
+            ..params(side, price, base_qty)
+        };
+        let reduce = |order_id, new_base_qty, expected_callback_id| ReduceOrderParams {
+            order_id,
+            new_base_qty,
+            expected_callback_id,
+        };
+        let first = market.new_order(order(Side::Ask, 10, 1)).unwrap();
+        let first = first.posted_order_id.unwrap();
+        market.new_order(order(Side::Ask, 10, 2)).unwrap();
+
+        // Orders can only shrink, and not down to the minimum order size
+        for new_base_qty in [10, 11, 1, 0] {
+            assert!(matches!(
+                market.reduce_order(reduce(first, new_base_qty, None)),
+                Err(SimError::Orderbook(_))
+            ));
+        }
+        assert!(matches!(
+            market.reduce_order(reduce(first, 4, Some(vec![2; 32]))),
+            Err(SimError::Orderbook(_))
+        ));
+
+        let summary = market
+            .reduce_order(reduce(first, 4, Some(vec![1; 32])))
+            .unwrap();
+        assert_eq!((summary.total_base_qty, summary.total_quote_qty), (6, 60));
+        assert!(matches!(
+            market.events().last(),
+            Some(Event::Out {
+                order_id,
+                base_size: 6,
+                delete: false,
+                ..
+            }) if *order_id == first
+        ));
+        assert_eq!(market.auditor.resting_base_qty(first), Some(4));
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(price, 14)]);
+
+        // The reduced order is still the first one matched at its price
+        market.new_order(order(Side::Bid, 5, 3)).unwrap();
+        assert_eq!(market.auditor.resting_base_qty(first), None);
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(price, 9)]);
+    }
+}
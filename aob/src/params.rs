@@ -0,0 +1,150 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use bonfida_utils::BorshSize;
+
+use crate::state::{
+    EventQueueOverflowPolicy, OrderId, OrderPriority, PriceRoundingPolicy, SelfTradeBehavior, Side,
+};
+use crate::utils::Quantity;
+
+#[derive(BorshDeserialize, BorshSerialize, BorshSize)]
+/**
+The required arguments for a create_market instruction.
+ */
+pub struct CreateMarketParams {
+    /// The caller authority will be the required signer for all market instructions.
+    ///
+    /// In practice, it will almost always be a program-derived address..
+    pub caller_authority: [u8; 32],
+    /// Callback information can be used by the caller to attach specific information to all orders.
+    ///
+    /// An example of this would be to store a public key to uniquely identify the owner of a particular order.
+    /// This example would thus require a value of 32
+    pub callback_info_len: u64,
+    /// The prefix length of callback information which is used to identify self-trading
+    pub callback_id_len: u64,
+    /// The minimum order size that can be inserted into the orderbook after matching.
+    pub min_base_order_size: u64,
+    /// Enables the limiting of price precision on the orderbook (price ticks)
+    pub tick_size: u64,
+    /// Enables the limiting of base quantity precision on the orderbook (base lots). Must be > 0.
+    pub base_lot_size: u64,
+    /// Flat part of the crank reward. A higher reward increases incentives for cranking.
+    pub cranker_base_reward: u64,
+    /// Per-event part of the crank reward.
+    pub cranker_per_event_reward: u64,
+    /// Describes how orders resting at the same price level are prioritized during matching.
+    pub order_priority: OrderPriority,
+    /// Describes what happens when an event is pushed to a full event queue.
+    pub event_queue_overflow_policy: EventQueueOverflowPolicy,
+    /// The maximum distance (in ticks) between a post-only order and the opposite best offer, or the last
+    /// trade price when the opposite side of the orderbook is empty. A value of 0 disables the check.
+    pub max_post_only_spread_ticks: u64,
+    /// Disables self-trade detection: all orders are then matched as if their self trade behavior was
+    /// [`DecrementTake`][`SelfTradeBehavior::DecrementTake`].
+    pub disable_self_trade_detection: bool,
+    /// Describes how the limit prices of new orders are fitted to the tick size.
+    pub price_rounding_policy: PriceRoundingPolicy,
+    /// The maximum number of orders a new order is matched against, which bounds the compute units and the event
+    /// queue space taken by a single order whatever match limit the caller passes. A value of 0 disables the cap.
+    pub max_match_iterations: u64,
+    /// Coalesces the fills of an order against the same maker order into a single fill event, which saves event
+    /// queue space and crank work when a taker comes back to a maker over several match iterations.
+    pub coalesce_fills: bool,
+    /// The number of orders each side of the orderbook can hold. The bids and asks accounts must be large
+    /// enough, see [`Slab::compute_allocation_size`][`crate::critbit::Slab::compute_allocation_size`].
+    pub order_capacity: u64,
+    /// The number of events the event queue holds. The event queue account must have the exact matching size,
+    /// see [`EventQueue::compute_allocation_size`][`crate::state::EventQueue::compute_allocation_size`].
+    pub event_capacity: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, BorshSize)]
+/**
+The required arguments for a new_order instruction.
+ */
+pub struct NewOrderParams {
+    /// The maximum quantity of base to be traded.
+    pub max_base_qty: Quantity,
+    /// The maximum quantity of quote to be traded.
+    pub max_quote_qty: Quantity,
+    /// The limit price of the order. This value is understood as a 32-bit fixed point number.
+    pub limit_price: u64,
+    /// The order's side.
+    pub side: Side,
+    /// The maximum number of orders to match against before performing a partial fill.
+    ///
+    /// It is then possible for a caller program to detect a partial fill by reading the [`OrderSummary`][`crate::orderbook::OrderSummary`]
+    /// in the event queue register.
+    ///
+    /// Only post-only orders, which are never matched, may have a match limit of 0.
+    pub match_limit: u64,
+    /// The callback information is used to attach metadata to an order. This callback information will be transmitted back through the event queue.
+    ///
+    /// The size of this vector should not exceed the current market's [`callback_info_len`][`MarketState::callback_info_len`].
+    pub callback_info: Vec<u8>,
+    /// The order will not be matched against the orderbook and will be direcly written into it.
+    ///
+    /// The operation will fail if the order's limit_price crosses the spread.
+    pub post_only: bool,
+    /// The order will be matched against the orderbook, but what remains will not be written as a new order into the orderbook.
+    pub post_allowed: bool,
+    /// Describes what would happen if this order was matched against an order with an equal `callback_info` field.
+    pub self_trade_behavior: SelfTradeBehavior,
+    /// The length of the prefix of the order's callback information which identifies it as the same trader as a
+    /// resting order, between 1 and the market's [`callback_id_len`][`MarketState::callback_id_len`], which is
+    /// used when unset.
+    ///
+    /// For instance, a firm whose callback ids start with the firm's id followed by the id of a sub-account can
+    /// let its sub-accounts trade with each other by comparing whole callback ids, or prevent it by only comparing
+    /// the firm's id.
+    pub self_trade_id_len: Option<u64>,
+    /// When set, bounds the order's average fill price, as reported by its
+    /// [`OrderSummary`][`crate::orderbook::OrderSummary`], to this FP32 price: a bid stops matching before its
+    /// average price would rise above it, and an ask before its average price would fall below it. Despite its
+    /// name, the bound is a floor for asks.
+    ///
+    /// An order which stopped matching on this bound is immediate or cancel: what remains of it isn't posted, even
+    /// when `post_allowed` is set, as it would rest across the offer it stopped at.
+    pub max_avg_price: Option<u64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, BorshSize)]
+/**
+The required arguments for a cancel_order instruction.
+ */
+pub struct CancelOrderParams {
+    /// The order id is a unique identifier for a particular order
+    pub order_id: OrderId,
+    /// When set, the order is only cancelled if its callback id matches this one
+    pub expected_callback_id: Option<Vec<u8>>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, BorshSize)]
+/**
+The required arguments for a reduce_order instruction.
+ */
+pub struct ReduceOrderParams {
+    /// The order id is a unique identifier for a particular order
+    pub order_id: OrderId,
+    /// The base quantity which the order is left with, rounded down to a whole number of lots
+    pub new_base_qty: Quantity,
+    /// When set, the order is only reduced if its callback id matches this one
+    pub expected_callback_id: Option<Vec<u8>>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, BorshSize)]
+/**
+The required arguments for a consume_events instruction.
+ */
+pub struct ConsumeEventsParams {
+    /// Depending on applications, it might be optimal to process several events at a time
+    pub number_of_entries_to_consume: u64,
+    /// Stop consuming once the remaining compute budget falls under this threshold. 0 disables the check.
+    pub min_remaining_compute_units: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, BorshSize)]
+/**
+The required arguments for a close_market instruction.
+ */
+pub struct CloseMarketParams {}
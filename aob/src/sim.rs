@@ -0,0 +1,1052 @@
+//! Off-chain simulation utilities.
+//!
+//! The [`Auditor`] consumes the events and order summaries produced by the orderbook and continuously checks
+//! that they are consistent with each other. It can be used to validate the matching engine as well as a caller
+//! program's settlement logic.
+//!
+//! The [`SimMarket`] runs the orderbook in memory, auditing every operation, so that it can be exercised
+//! without a validator. A sequence of [`Operation`]s can be replayed against it through [`SimMarket::replay`],
+//! which yields the same order summaries and events as the on-chain instructions, for fill estimation and
+//! backtesting.
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use anchor_lang::prelude::{Clock, Error, ProgramError, Pubkey};
+
+use crate::{
+    critbit::{LeafNodeExt, Slab},
+    error::ErrorCode,
+    histbuf::HistoryBuffer,
+    orderbook::{Auction, OrderBookState, OrderSummary},
+    params::{CancelOrderParams, NewOrderParams, ReduceOrderParams},
+    state::{
+        AccountTag, Event, EventQueue, EventQueueHeader, MarketState, OrderId, Side,
+        TradingSession, MARKET_STATE_LEN, TRADE_HISTORY_CAPACITY,
+    },
+    utils::{fp32_mul, fp32_mul_ceil, fp32_mul_floor, lots_to_base_qty, Quantity},
+};
+
+/// Describes an inconsistency detected by the [`Auditor`]
+#[derive(Debug, PartialEq)]
+pub enum AuditError {
+    /// An event references an order which is not resting in the orderbook
+    UnknownOrder(OrderId),
+    /// An order id was posted while another order with the same id is still resting
+    DuplicateOrder(OrderId),
+    /// A maker order was matched against a taker order on the same side
+    WrongMakerSide(OrderId),
+    /// An event takes more base quantity out of an order than what was resting
+    Overfill {
+        #[allow(missing_docs)]
+        order_id: OrderId,
+        #[allow(missing_docs)]
+        resting: Quantity,
+        #[allow(missing_docs)]
+        taken: Quantity,
+    },
+    /// The base quantity reported by an order summary doesn't match the events
+    BaseMismatch {
+        #[allow(missing_docs)]
+        expected: Quantity,
+        #[allow(missing_docs)]
+        actual: Quantity,
+    },
+    /// The quote quantity reported by an order summary doesn't match the events
+    QuoteMismatch {
+        #[allow(missing_docs)]
+        expected: Quantity,
+        #[allow(missing_docs)]
+        actual: Quantity,
+    },
+    /// An order summary reports more than the order's maximum quantities
+    ExceedsOrderLimits,
+}
+
+/// Tracks the lifecycle of every order (post → fills → out) and the base and quote quantities exchanged.
+#[derive(Default, Debug)]
+pub struct Auditor {
+    resting: BTreeMap<OrderId, Quantity>,
+    /// The total base quantity exchanged through fills
+    pub total_base_filled: Quantity,
+    /// The total quote quantity exchanged through fills
+    pub total_quote_filled: Quantity,
+}
+
+impl Auditor {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the base quantity currently resting in the orderbook for the given order
+    pub fn resting_base_qty(&self, order_id: OrderId) -> Option<Quantity> {
+        self.resting.get(&order_id).copied()
+    }
+
+    /// Returns the number of orders currently resting in the orderbook
+    pub fn open_orders(&self) -> usize {
+        self.resting.len()
+    }
+
+    /// Audits the outcome of a new_order instruction, given the events it pushed to the event queue.
+    pub fn on_new_order(
+        &mut self,
+        params: &NewOrderParams,
+        summary: &OrderSummary,
+        events: &[Event],
+    ) -> Result<(), AuditError> {
+        if summary.total_base_qty > params.max_base_qty
+            || summary.total_quote_qty > params.max_quote_qty
+            || summary.total_base_qty_posted > summary.total_base_qty
+        {
+            return Err(AuditError::ExceedsOrderLimits);
+        }
+
+        let mut taker_base_qty = 0;
+        let mut taker_quote_qty = 0;
+        for event in events {
+            match event {
+                Event::Fill {
+                    taker_side,
+                    maker_order_id,
+                    quote_size,
+                    base_size,
+                    ..
+                } => {
+                    if maker_order_id.side() != taker_side.opposite() {
+                        return Err(AuditError::WrongMakerSide(*maker_order_id));
+                    }
+                    self.take(*maker_order_id, *base_size)?;
+                    taker_base_qty += base_size;
+                    taker_quote_qty += quote_size;
+                }
+                Event::Out {
+                    order_id,
+                    base_size,
+                    delete,
+                    ..
+                } => {
+                    self.take(*order_id, *base_size)?;
+                    if *delete {
+                        self.resting.remove(order_id);
+                    }
+                }
+            }
+        }
+        self.total_base_filled += taker_base_qty;
+        self.total_quote_filled += taker_quote_qty;
+
+        let expected_base_qty = summary.total_base_qty - summary.total_base_qty_posted;
+        if taker_base_qty != expected_base_qty {
+            return Err(AuditError::BaseMismatch {
+                expected: expected_base_qty,
+                actual: taker_base_qty,
+            });
+        }
+
+        let mut expected_quote_qty = summary.total_quote_qty;
+        if let Some(order_id) = summary.posted_order_id {
+            // Bids pay for what they post rounded up
+            let posted_quote_qty = match params.side {
+                Side::Bid => fp32_mul_ceil(summary.total_base_qty_posted, order_id.price()),
+                Side::Ask => fp32_mul_floor(summary.total_base_qty_posted, order_id.price()),
+            }
+            .map_err(|_| AuditError::ExceedsOrderLimits)?;
+            expected_quote_qty = expected_quote_qty
+                .checked_sub(posted_quote_qty)
+                .ok_or(AuditError::ExceedsOrderLimits)?;
+            if self.resting.contains_key(&order_id) {
+                return Err(AuditError::DuplicateOrder(order_id));
+            }
+            self.resting.insert(order_id, summary.total_base_qty_posted);
+        }
+        if taker_quote_qty != expected_quote_qty {
+            return Err(AuditError::QuoteMismatch {
+                expected: expected_quote_qty,
+                actual: taker_quote_qty,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Audits the outcome of a cancel_order instruction.
+    pub fn on_cancel(
+        &mut self,
+        order_id: OrderId,
+        summary: &OrderSummary,
+    ) -> Result<(), AuditError> {
+        let resting = self
+            .resting
+            .remove(&order_id)
+            .ok_or(AuditError::UnknownOrder(order_id))?;
+        if summary.total_base_qty != resting {
+            return Err(AuditError::BaseMismatch {
+                expected: resting,
+                actual: summary.total_base_qty,
+            });
+        }
+        Ok(())
+    }
+
+    /// Audits the outcome of a reduce_order instruction, given the events it pushed to the event queue.
+    pub fn on_reduce(
+        &mut self,
+        order_id: OrderId,
+        summary: &OrderSummary,
+        events: &[Event],
+    ) -> Result<(), AuditError> {
+        let mut released = 0;
+        for event in events {
+            if let Event::Out {
+                order_id: out_order_id,
+                base_size,
+                delete: false,
+                ..
+            } = event
+            {
+                if *out_order_id != order_id {
+                    return Err(AuditError::UnknownOrder(*out_order_id));
+                }
+                released += base_size;
+            }
+        }
+        if released != summary.total_base_qty {
+            return Err(AuditError::BaseMismatch {
+                expected: summary.total_base_qty,
+                actual: released,
+            });
+        }
+        self.take(order_id, released)
+    }
+
+    /// Audits the outcome of an opening auction, given the events it pushed to the event queue and the orders left
+    /// resting in the book.
+    ///
+    /// The fills of an auction don't name their taker order, so the orders left resting are only checked to have
+    /// been resting before, with at least as much base quantity.
+    pub fn on_auction(
+        &mut self,
+        events: &[Event],
+        resting: impl IntoIterator<Item = (OrderId, Quantity)>,
+    ) -> Result<(), AuditError> {
+        for event in events {
+            if let Event::Fill {
+                taker_side,
+                maker_order_id,
+                quote_size,
+                base_size,
+                ..
+            } = event
+            {
+                if maker_order_id.side() != taker_side.opposite() {
+                    return Err(AuditError::WrongMakerSide(*maker_order_id));
+                }
+                self.total_base_filled += base_size;
+                self.total_quote_filled += quote_size;
+            }
+        }
+        let mut left = BTreeMap::new();
+        for (order_id, base_qty) in resting {
+            let before = *self
+                .resting
+                .get(&order_id)
+                .ok_or(AuditError::UnknownOrder(order_id))?;
+            if base_qty > before {
+                return Err(AuditError::Overfill {
+                    order_id,
+                    resting: before,
+                    taken: base_qty,
+                });
+            }
+            left.insert(order_id, base_qty);
+        }
+        self.resting = left;
+        Ok(())
+    }
+
+    fn take(&mut self, order_id: OrderId, base_qty: Quantity) -> Result<(), AuditError> {
+        let resting = self
+            .resting
+            .get_mut(&order_id)
+            .ok_or(AuditError::UnknownOrder(order_id))?;
+        *resting = resting.checked_sub(base_qty).ok_or(AuditError::Overfill {
+            order_id,
+            resting: *resting,
+            taken: base_qty,
+        })?;
+        Ok(())
+    }
+}
+
+/// Describes why a [`SimMarket`] operation failed
+#[derive(Debug)]
+pub enum SimError {
+    /// The orderbook rejected the operation, which was rolled back
+    Orderbook(Error),
+    /// The [`Auditor`] detected an inconsistency in the outcome of the operation
+    Audit(AuditError),
+}
+
+impl From<Error> for SimError {
+    fn from(e: Error) -> Self {
+        SimError::Orderbook(e)
+    }
+}
+
+impl From<AuditError> for SimError {
+    fn from(e: AuditError) -> Self {
+        SimError::Audit(e)
+    }
+}
+
+/// An operation replayed against a [`SimMarket`]
+#[derive(Clone)]
+pub enum Operation {
+    /// Places a new order, as the new_order instruction would
+    NewOrder(NewOrderParams),
+    /// Cancels a resting order, as the cancel_order instruction would
+    CancelOrder(CancelOrderParams),
+    /// Pops up to the given number of events off the event queue
+    ConsumeEvents(u64),
+    /// Sets the clock with which the following events are stamped
+    SetClock(Clock),
+}
+
+/// The outcome of an [`Operation`] which went through
+#[derive(Debug)]
+pub struct Outcome {
+    /// The summary written to the event queue's register, for new_order and cancel_order operations
+    pub summary: Option<OrderSummary>,
+    /// The events pushed to the event queue by the operation
+    pub events: Vec<Event>,
+}
+
+/// An in-memory market, which runs the orderbook on owned buffers instead of Solana accounts.
+///
+/// Operations which the orderbook rejects are rolled back, as a failed transaction would be. Every operation
+/// which goes through is audited.
+pub struct SimMarket {
+    /// The market's configuration and state
+    pub market_state: MarketState,
+    /// The clock with which new events are stamped
+    pub clock: Clock,
+    /// Audits every order placed on or cancelled from the market
+    pub auditor: Auditor,
+    /// The prices of the market's most recent fills, as a trade history account would hold them
+    pub trade_history: HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>,
+    bids: Vec<u8>,
+    asks: Vec<u8>,
+    event_queue: Vec<u8>,
+}
+
+impl SimMarket {
+    /// Creates an empty market with the given configuration, `book_len` bytes for each side of the orderbook
+    /// and `event_queue_len` bytes for the event queue.
+    pub fn new(market_state: MarketState, book_len: usize, event_queue_len: usize) -> Self {
+        let key = Pubkey::new_unique();
+        let callback_info_len = market_state.callback_info_len as usize;
+        let mut market = Self {
+            market_state,
+            clock: Clock::default(),
+            auditor: Auditor::new(),
+            trade_history: HistoryBuffer::new(),
+            bids: vec![0; book_len],
+            asks: vec![0; book_len],
+            event_queue: vec![0; event_queue_len],
+        };
+        for (buffer, account_tag) in [
+            (&mut market.bids, AccountTag::Bids),
+            (&mut market.asks, AccountTag::Asks),
+        ] {
+            Slab::initialize_buffer(buffer, account_tag as u8, key.to_bytes(), callback_info_len);
+        }
+        EventQueueHeader::initialize(key, callback_info_len, event_queue_len)
+            .write(&mut market.event_queue);
+        market
+    }
+
+    /// Loads a market from a snapshot of its accounts' data.
+    ///
+    /// The orders resting in the snapshot are registered with the [`Auditor`].
+    pub fn from_snapshot(
+        market: &[u8],
+        bids: Vec<u8>,
+        asks: Vec<u8>,
+        event_queue: Vec<u8>,
+    ) -> std::result::Result<Self, SimError> {
+        // The market state follows the Anchor discriminator
+        let market_data = market
+            .get(8..8 + MARKET_STATE_LEN)
+            .ok_or_else(|| Error::from(ProgramError::InvalidAccountData))?;
+        let mut market_state = MarketState::default();
+        bytemuck::bytes_of_mut(&mut market_state).copy_from_slice(market_data);
+        let mut market = Self {
+            market_state,
+            clock: Clock::default(),
+            auditor: Auditor::new(),
+            trade_history: HistoryBuffer::new(),
+            bids,
+            asks,
+            event_queue,
+        };
+        for side in [Side::Bid, Side::Ask] {
+            for (order_id, base_qty) in market.orders(side)? {
+                market.auditor.resting.insert(order_id, base_qty);
+            }
+        }
+        Ok(market)
+    }
+
+    /// Places a new order, see [`OrderBookState::new_order`].
+    pub fn new_order(
+        &mut self,
+        params: NewOrderParams,
+    ) -> std::result::Result<OrderSummary, SimError> {
+        self.new_order_with_events(params)
+            .map(|(summary, _)| summary)
+    }
+
+    /// Places a new order like [`SimMarket::new_order`], and also returns the events it pushed.
+    pub(crate) fn new_order_with_events(
+        &mut self,
+        mut params: NewOrderParams,
+    ) -> std::result::Result<(OrderSummary, Vec<Event>), SimError> {
+        if !params.post_only && self.market_state.resumption_ticket != [0; 32] {
+            return Err(Error::from(ErrorCode::ResumptionPending).into());
+        }
+        params.limit_price = self
+            .market_state
+            .round_price(params.limit_price, params.side)?;
+        if params.callback_info.len() != self.market_state.callback_info_len as usize {
+            return Err(Error::from(ProgramError::InvalidArgument).into());
+        }
+        let order_params = params.clone();
+        let mut trade_history = self.trade_history;
+        let (summary, events) = self.transact(|order_book, event_queue, market_state| {
+            let summary = order_book.new_order(
+                order_params,
+                event_queue,
+                market_state,
+                Some(&mut trade_history),
+                None,
+            )?;
+            event_queue.write_to_register(summary.clone());
+            Ok(summary)
+        })?;
+        self.trade_history = trade_history;
+        self.auditor.on_new_order(&params, &summary, &events)?;
+        Ok((summary, events))
+    }
+
+    /// Cancels a resting order, returning what was left of it.
+    pub fn cancel_order(
+        &mut self,
+        order_id: OrderId,
+    ) -> std::result::Result<OrderSummary, SimError> {
+        self.cancel_order_checked(CancelOrderParams {
+            order_id,
+            expected_callback_id: None,
+        })
+    }
+
+    /// Cancels a resting order, only if its callback id matches the expected one when it is set.
+    pub fn cancel_order_checked(
+        &mut self,
+        params: CancelOrderParams,
+    ) -> std::result::Result<OrderSummary, SimError> {
+        let CancelOrderParams {
+            order_id,
+            expected_callback_id,
+        } = params;
+        let (summary, _) = self.transact(|order_book, event_queue, market_state| {
+            let (leaf_node, _) =
+                order_book.cancel_order(order_id, expected_callback_id.as_deref())?;
+            let total_base_qty =
+                lots_to_base_qty(leaf_node.base_quantity, market_state.base_lot_size);
+            let summary = OrderSummary {
+                posted_order_id: None,
+                total_base_qty,
+                total_quote_qty: fp32_mul_floor(total_base_qty, leaf_node.price())
+                    .map_err(ErrorCode::from)?,
+                total_fee_qty: 0,
+                total_base_qty_posted: 0,
+                avg_fill_price_fp32: 0,
+                worst_fill_price_fp32: 0,
+            };
+            event_queue.write_to_register(summary.clone());
+            Ok(summary)
+        })?;
+        self.auditor.on_cancel(order_id, &summary)?;
+        Ok(summary)
+    }
+
+    /// Shrinks a resting order while keeping its priority, returning what it released, see
+    /// [`OrderBookState::reduce_order`].
+    pub fn reduce_order(
+        &mut self,
+        params: ReduceOrderParams,
+    ) -> std::result::Result<OrderSummary, SimError> {
+        let ReduceOrderParams {
+            order_id,
+            new_base_qty,
+            expected_callback_id,
+        } = params;
+        let (summary, events) = self.transact(|order_book, event_queue, market_state| {
+            let total_base_qty = order_book.reduce_order(
+                order_id,
+                new_base_qty,
+                expected_callback_id.as_deref(),
+                event_queue,
+                market_state,
+            )?;
+            let summary = OrderSummary {
+                posted_order_id: None,
+                total_base_qty,
+                total_quote_qty: fp32_mul_floor(total_base_qty, order_id.price())
+                    .map_err(ErrorCode::from)?,
+                total_fee_qty: 0,
+                total_base_qty_posted: 0,
+                avg_fill_price_fp32: 0,
+                worst_fill_price_fp32: 0,
+            };
+            event_queue.write_to_register(summary.clone());
+            Ok(summary)
+        })?;
+        self.auditor.on_reduce(order_id, &summary, &events)?;
+        Ok(summary)
+    }
+
+    /// Runs up to `match_limit` matches of the opening auction of a pre-open market, and then opens it if the
+    /// auction is complete, see [`OrderBookState::uncross`].
+    pub fn open_session(
+        &mut self,
+        match_limit: u64,
+    ) -> std::result::Result<Option<Auction>, SimError> {
+        let mut trade_history = self.trade_history;
+        let (auction, events) = self.transact(|order_book, event_queue, market_state| {
+            if market_state.get_trading_session()? != TradingSession::PreOpen {
+                return Err(Error::from(ErrorCode::InvalidSessionTransition));
+            }
+            let auction = order_book.uncross(
+                event_queue,
+                market_state,
+                Some(&mut trade_history),
+                None,
+                match_limit,
+            )?;
+            if market_state.auction_price == 0 {
+                market_state.set_trading_session(TradingSession::Open)?;
+            }
+            Ok(auction)
+        })?;
+        self.trade_history = trade_history;
+        let mut resting = self.orders(Side::Bid)?;
+        resting.extend(self.orders(Side::Ask)?);
+        self.auditor.on_auction(&events, resting)?;
+        Ok(auction)
+    }
+
+    /// Replays the operations in order, stopping at the first one which fails.
+    ///
+    /// Rejected operations are rolled back, so that the market is left in the state which follows the last
+    /// operation which went through. The outcomes of these operations are returned along with the error, if any.
+    pub fn replay(
+        &mut self,
+        operations: impl IntoIterator<Item = Operation>,
+    ) -> (Vec<Outcome>, Option<SimError>) {
+        let mut outcomes = Vec::new();
+        for operation in operations {
+            let outcome = match operation {
+                Operation::NewOrder(params) => {
+                    self.new_order_with_events(params)
+                        .map(|(summary, events)| Outcome {
+                            summary: Some(summary),
+                            events,
+                        })
+                }
+                Operation::CancelOrder(params) => {
+                    self.cancel_order_checked(params).map(|summary| Outcome {
+                        summary: Some(summary),
+                        events: vec![],
+                    })
+                }
+                Operation::ConsumeEvents(number_of_entries) => {
+                    self.consume_events(number_of_entries);
+                    Ok(Outcome {
+                        summary: None,
+                        events: vec![],
+                    })
+                }
+                Operation::SetClock(clock) => {
+                    self.clock = clock;
+                    Ok(Outcome {
+                        summary: None,
+                        events: vec![],
+                    })
+                }
+            };
+            match outcome {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(e) => return (outcomes, Some(e)),
+            }
+        }
+        (outcomes, None)
+    }
+
+    /// Returns the orders resting on the given side as (order id, base quantity) pairs, best first.
+    pub fn orders(&mut self, side: Side) -> std::result::Result<Vec<(OrderId, Quantity)>, Error> {
+        let base_lot_size = self.market_state.base_lot_size;
+        let buffer = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        let slab = Slab::new(
+            &mut buffer[..],
+            self.market_state.callback_info_len as usize,
+        )
+        .map_err(ErrorCode::from)?;
+        Ok(slab
+            .leaves(side == Side::Bid)
+            .iter()
+            .map(|leaf| {
+                (
+                    leaf.order_id(),
+                    lots_to_base_qty(leaf.base_quantity, base_lot_size),
+                )
+            })
+            .collect())
+    }
+
+    /// Returns up to `depth` price levels of the given side as (price, base quantity) pairs, best first.
+    pub fn l2(
+        &mut self,
+        side: Side,
+        depth: usize,
+    ) -> std::result::Result<Vec<(u64, Quantity)>, Error> {
+        let base_lot_size = self.market_state.base_lot_size;
+        let buffer = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        let slab = Slab::new(
+            &mut buffer[..],
+            self.market_state.callback_info_len as usize,
+        )
+        .map_err(ErrorCode::from)?;
+        Ok(slab
+            .price_levels(side == Side::Bid, depth)
+            .into_iter()
+            .map(|(price, base_lots)| (price, lots_to_base_qty(base_lots, base_lot_size)))
+            .collect())
+    }
+
+    /// Returns the events currently in the event queue, oldest first.
+    pub fn events(&mut self) -> Vec<Event> {
+        let event_queue = self.load_event_queue();
+        event_queue.peek_n(event_queue.header.count)
+    }
+
+    /// Pops up to `number_of_entries` events off the event queue, returning the number of events consumed.
+    pub fn consume_events(&mut self, number_of_entries: u64) -> u64 {
+        let mut event_queue = self.load_event_queue();
+        let count_before = event_queue.header.count;
+        event_queue.pop_n(number_of_entries);
+        event_queue.write_header();
+        count_before - event_queue.header.count
+    }
+
+    fn load_event_queue(&mut self) -> EventQueue<&mut [u8]> {
+        let header = EventQueueHeader::load(&self.event_queue).unwrap();
+        EventQueue::new(
+            header,
+            Rc::new(RefCell::new(&mut self.event_queue[..])),
+            self.market_state.callback_info_len as usize,
+        )
+    }
+
+    /// Runs `f` against the orderbook and commits its changes, returning its result along with the events
+    /// it pushed. The market is left untouched when `f` fails.
+    fn transact<R, F>(&mut self, f: F) -> std::result::Result<(R, Vec<Event>), SimError>
+    where
+        F: for<'a> FnOnce(
+            &mut OrderBookState<&'a mut [u8]>,
+            &mut EventQueue<&'a mut [u8]>,
+            &mut MarketState,
+        ) -> std::result::Result<R, Error>,
+    {
+        let backup = (
+            self.bids.clone(),
+            self.asks.clone(),
+            self.event_queue.clone(),
+            self.market_state,
+        );
+        let outcome = run(
+            &mut self.bids,
+            &mut self.asks,
+            &mut self.event_queue,
+            &mut self.market_state,
+            &self.clock,
+            f,
+        );
+        outcome.map_err(|e| {
+            let (bids, asks, event_queue, market_state) = backup;
+            self.bids = bids;
+            self.asks = asks;
+            self.event_queue = event_queue;
+            self.market_state = market_state;
+            e.into()
+        })
+    }
+
+    /// Runs `f` against the orderbook like [`SimMarket::transact`], but without the backup which restores the
+    /// market when `f` fails, so that only the orderbook's own rollback undoes a failed order.
+    #[cfg(test)]
+    pub(crate) fn transact_unchecked<R, F>(
+        &mut self,
+        f: F,
+    ) -> std::result::Result<(R, Vec<Event>), Error>
+    where
+        F: for<'a> FnOnce(
+            &mut OrderBookState<&'a mut [u8]>,
+            &mut EventQueue<&'a mut [u8]>,
+            &mut MarketState,
+        ) -> std::result::Result<R, Error>,
+    {
+        run(
+            &mut self.bids,
+            &mut self.asks,
+            &mut self.event_queue,
+            &mut self.market_state,
+            &self.clock,
+            f,
+        )
+    }
+}
+
+fn run<'a, R>(
+    bids: &'a mut [u8],
+    asks: &'a mut [u8],
+    event_queue_data: &'a mut [u8],
+    market_state: &mut MarketState,
+    clock: &Clock,
+    f: impl FnOnce(
+        &mut OrderBookState<&'a mut [u8]>,
+        &mut EventQueue<&'a mut [u8]>,
+        &mut MarketState,
+    ) -> std::result::Result<R, Error>,
+) -> std::result::Result<(R, Vec<Event>), Error> {
+    let callback_info_len = market_state.callback_info_len as usize;
+    let mut order_book = OrderBookState::from_slabs(
+        Slab::new(bids, callback_info_len).map_err(ErrorCode::from)?,
+        Slab::new(asks, callback_info_len).map_err(ErrorCode::from)?,
+        market_state.callback_id_len as usize,
+    )?;
+    let header = EventQueueHeader::load(event_queue_data)?.check()?;
+    header.check_layout(event_queue_data.len(), callback_info_len)?;
+    let mut event_queue = EventQueue::new(
+        header,
+        Rc::new(RefCell::new(event_queue_data)),
+        callback_info_len,
+    );
+    event_queue.clear_register();
+    event_queue.set_clock(clock);
+    event_queue.overflow_policy = market_state.get_event_queue_overflow_policy()?;
+
+    let result = f(&mut order_book, &mut event_queue, market_state)?;
+
+    let count = event_queue.header.count;
+    let events = event_queue
+        .peek_n(count)
+        .split_off((count - event_queue.events_pushed().min(count)) as usize);
+    event_queue.write_header();
+    order_book.commit_changes();
+    Ok((result, events))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use borsh::{BorshDeserialize, BorshSerialize};
+
+    use super::*;
+    use crate::state::{SelfTradeBehavior, Side, EVENT_QUEUE_HEADER_LEN};
+    use crate::utils::low_u64;
+
+    pub(crate) fn params(side: Side, limit_price: u64, max_base_qty: Quantity) -> NewOrderParams {
+        NewOrderParams {
+            max_base_qty,
+            max_quote_qty: Quantity::MAX,
+            limit_price,
+            side,
+            match_limit: 10,
+            callback_info: vec![],
+            post_only: false,
+            post_allowed: true,
+            self_trade_behavior: SelfTradeBehavior::DecrementTake,
+            self_trade_id_len: None,
+            max_avg_price: None,
+        }
+    }
+
+    /// The order of `owner`, whose callback info is 32 bytes of its id, in a [`test_market`].
+    pub(crate) fn order(
+        side: Side,
+        limit_price: u64,
+        max_base_qty: Quantity,
+        owner: u8,
+    ) -> NewOrderParams {
+        NewOrderParams {
+            max_quote_qty: 1 << 50,
+            callback_info: vec![owner; 32],
+            ..params(side, limit_price, max_base_qty)
+        }
+    }
+
+    /// A market whose owners are identified by 32 bytes of callback info, with unit tick and lot sizes and an
+    /// event queue which holds `event_capacity` events. Tests tune the rest of its configuration in place.
+    pub(crate) fn test_market(event_capacity: usize) -> SimMarket {
+        let market_state = MarketState {
+            callback_info_len: 32,
+            callback_id_len: 32,
+            tick_size: 1,
+            base_lot_size: 1,
+            ..MarketState::default()
+        };
+        let event_queue_len = EventQueue::compute_allocation_size(event_capacity, 32);
+        SimMarket::new(market_state, 20_000, event_queue_len)
+    }
+
+    fn fill(taker_side: Side, maker_order_id: OrderId, base_size: Quantity) -> Event {
+        Event::Fill {
+            taker_side,
+            maker_order_id,
+            quote_size: fp32_mul(base_size, maker_order_id.price()),
+            base_size,
+            slot: 0,
+            unix_timestamp: 0,
+            maker_callback_info: vec![],
+            taker_callback_info: vec![],
+        }
+    }
+
+    #[test]
+    fn order_lifecycle() {
+        let mut auditor = Auditor::new();
+        let price = 10 << 32;
+        let ask_id = OrderId::new(price, Side::Ask, 1);
+
+        // Post an ask
+        let summary = OrderSummary {
+            posted_order_id: Some(ask_id),
+            total_base_qty: 100,
+            total_quote_qty: 1_000,
+            total_fee_qty: 0,
+            total_base_qty_posted: 100,
+            avg_fill_price_fp32: 0,
+            worst_fill_price_fp32: 0,
+        };
+        auditor
+            .on_new_order(&params(Side::Ask, price, 100), &summary, &[])
+            .unwrap();
+        assert_eq!(auditor.resting_base_qty(ask_id), Some(100));
+
+        // Partially take it
+        let summary = OrderSummary {
+            posted_order_id: None,
+            total_base_qty: 40,
+            total_quote_qty: 400,
+            total_fee_qty: 0,
+            total_base_qty_posted: 0,
+            avg_fill_price_fp32: price,
+            worst_fill_price_fp32: price,
+        };
+        auditor
+            .on_new_order(
+                &params(Side::Bid, price, 40),
+                &summary,
+                &[fill(Side::Bid, ask_id, 40)],
+            )
+            .unwrap();
+        assert_eq!(auditor.resting_base_qty(ask_id), Some(60));
+        assert_eq!(auditor.total_base_filled, 40);
+        assert_eq!(auditor.total_quote_filled, 400);
+
+        // A summary which doesn't match the events is detected
+        let summary = OrderSummary {
+            posted_order_id: None,
+            total_base_qty: 20,
+            total_quote_qty: 200,
+            total_fee_qty: 0,
+            total_base_qty_posted: 0,
+            avg_fill_price_fp32: price,
+            worst_fill_price_fp32: price,
+        };
+        assert_eq!(
+            auditor.on_new_order(
+                &params(Side::Bid, price, 20),
+                &summary,
+                &[fill(Side::Bid, ask_id, 10)],
+            ),
+            Err(AuditError::BaseMismatch {
+                expected: 20,
+                actual: 10
+            })
+        );
+
+        // Overfills and fills against the wrong side are detected
+        assert_eq!(
+            auditor.take(ask_id, 1_000),
+            Err(AuditError::Overfill {
+                order_id: ask_id,
+                resting: 50,
+                taken: 1_000
+            })
+        );
+        assert_eq!(
+            auditor.on_new_order(
+                &params(Side::Ask, price, 20),
+                &summary,
+                &[fill(Side::Ask, ask_id, 10)],
+            ),
+            Err(AuditError::WrongMakerSide(ask_id))
+        );
+
+        // Cancel the remainder
+        let summary = OrderSummary {
+            posted_order_id: None,
+            total_base_qty: 50,
+            total_quote_qty: 500,
+            total_fee_qty: 0,
+            total_base_qty_posted: 0,
+            avg_fill_price_fp32: 0,
+            worst_fill_price_fp32: 0,
+        };
+        auditor.on_cancel(ask_id, &summary).unwrap();
+        assert_eq!(auditor.open_orders(), 0);
+        assert_eq!(
+            auditor.on_cancel(ask_id, &summary),
+            Err(AuditError::UnknownOrder(ask_id))
+        );
+    }
+
+    #[test]
+    fn sim_market() {
+        let mut market = test_market(16);
+        let price = 10 << 32;
+        let order = |side, base_qty, owner| NewOrderParams {
+            max_quote_qty: 1 << 40,
+            callback_info: vec![owner; 32],
+            ..params(side, price, base_qty)
+        };
+
+        let ask = market.new_order(order(Side::Ask, 100, 1)).unwrap();
+        let ask_id = ask.posted_order_id.unwrap();
+        let bid = market.new_order(order(Side::Bid, 40, 2)).unwrap();
+        assert_eq!(bid.posted_order_id, None);
+        assert_eq!(bid.total_base_qty, 40);
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(price, 60)]);
+        assert_eq!(market.market_state.last_trade_price, price);
+        assert_eq!(market.market_state.base_volume, 40);
+        assert_eq!(
+            market.market_state.quote_volume,
+            low_u64(bid.total_quote_qty)
+        );
+        assert_eq!(market.market_state.net_taker_flow, 40);
+        assert_eq!(market.trade_history.recent(0), Some(&price));
+        match &market.events()[..] {
+            [Event::Fill {
+                maker_order_id,
+                base_size,
+                ..
+            }] => assert_eq!((*maker_order_id, *base_size), (ask_id, 40)),
+            events => panic!("unexpected events {:?}", events),
+        }
+        assert_eq!(market.consume_events(10), 1);
+
+        // Orders which aren't post-only must be allowed to match
+        assert!(matches!(
+            market.new_order(NewOrderParams {
+                match_limit: 0,
+                ..order(Side::Bid, 10, 2)
+            }),
+            Err(SimError::Orderbook(_))
+        ));
+
+        // A rejected operation leaves the market untouched
+        assert!(matches!(
+            market.cancel_order(OrderId(ask_id.0 + 1)),
+            Err(SimError::Orderbook(_))
+        ));
+        // Only the owner's callback id passes the ownership check
+        assert!(market
+            .transact(|order_book, _, _| order_book.check_order_callback_id(ask_id, &[2; 32]))
+            .is_err());
+        assert!(market
+            .transact(|order_book, _, _| order_book.check_order_callback_id(ask_id, &[1; 32]))
+            .is_ok());
+        assert_eq!(market.cancel_order(ask_id).unwrap().total_base_qty, 60);
+        assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+        assert_eq!(market.auditor.open_orders(), 0);
+    }
+
+    #[test]
+    fn replay() {
+        use crate::state::Register;
+
+        let mut market = test_market(16);
+        let price = 10 << 32;
+        let order = |side, base_qty, owner| NewOrderParams {
+            max_quote_qty: 1 << 40,
+            callback_info: vec![owner; 32],
+            ..params(side, price, base_qty)
+        };
+        let ask_id = OrderId::new(price, Side::Ask, 0);
+
+        let (outcomes, error) = market.replay([
+            Operation::NewOrder(order(Side::Ask, 100, 1)),
+            Operation::SetClock(Clock {
+                slot: 5,
+                ..Clock::default()
+            }),
+            Operation::NewOrder(order(Side::Bid, 40, 2)),
+            Operation::ConsumeEvents(10),
+            Operation::CancelOrder(CancelOrderParams {
+                order_id: ask_id,
+                expected_callback_id: Some(vec![2; 32]),
+            }),
+            Operation::CancelOrder(CancelOrderParams {
+                order_id: ask_id,
+                expected_callback_id: None,
+            }),
+        ]);
+        assert!(matches!(error, Some(SimError::Orderbook(_))));
+        assert_eq!(outcomes.len(), 4);
+        assert_eq!(
+            outcomes[0].summary.as_ref().unwrap().posted_order_id,
+            Some(ask_id)
+        );
+        match &outcomes[2].events[..] {
+            [Event::Fill {
+                maker_order_id,
+                base_size,
+                slot,
+                ..
+            }] => assert_eq!((*maker_order_id, *base_size, *slot), (ask_id, 40, 5)),
+            events => panic!("unexpected events {:?}", events),
+        }
+        assert!(market.events().is_empty());
+
+        // The register holds the summary of the last order, as it would on-chain
+        let register = Register::<OrderSummary>::deserialize(
+            &mut &market.event_queue[EVENT_QUEUE_HEADER_LEN..],
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            register.try_to_vec().unwrap(),
+            outcomes[2].summary.as_ref().unwrap().try_to_vec().unwrap()
+        );
+
+        // The replay stopped at the rejected cancellation
+        assert_eq!(market.l2(Side::Ask, 10).unwrap(), vec![(price, 60)]);
+    }
+}
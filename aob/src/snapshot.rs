@@ -0,0 +1,456 @@
+//! Serializable snapshots of a market's orderbook and event queue, for indexers which expose them through JSON APIs.
+//!
+//! The snapshots are built from the slabs and the event queue, which can be loaded from raw account data with
+//! [`Slab::from_bytes`] and [`EventQueue::from_bytes`]. Prices are FP32 and quantities are in native units.
+//!
+//! Incremental feeds can publish the [`LevelDelta`]s between two consecutive L2 snapshots instead of full refreshes,
+//! which their subscribers apply to their own copy of the book.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    critbit::{Slab, Storage},
+    orderbook::{indicative_auction, Auction, PriceLevel},
+    state::{Event, EventQueue, OrderId, Side},
+    utils::{lots_to_base_qty, Quantity},
+};
+
+/// The top price levels of both sides of the orderbook, best first.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct L2Snapshot {
+    #[allow(missing_docs)]
+    pub bids: Vec<PriceLevel>,
+    #[allow(missing_docs)]
+    pub asks: Vec<PriceLevel>,
+}
+
+impl L2Snapshot {
+    /// Aggregates up to `depth` price levels of each side.
+    pub fn from_slabs(
+        bids: &Slab<impl Storage>,
+        asks: &Slab<impl Storage>,
+        base_lot_size: u64,
+        depth: usize,
+    ) -> Self {
+        Self {
+            bids: price_levels(bids, true, base_lot_size, depth),
+            asks: price_levels(asks, false, base_lot_size, depth),
+        }
+    }
+
+    /// Computes the indicative opening auction of the book, see [`indicative_auction`]. Orders beyond the depth of
+    /// the snapshot are left out, which only changes the outcome when the book crosses deeper than that.
+    pub fn indicative_auction(&self, reference_price: u64) -> Option<Auction> {
+        indicative_auction(&self.bids, &self.asks, reference_price)
+    }
+
+    /// Lists the changes which turn this snapshot into `next`, bids first and by ascending price.
+    ///
+    /// Both snapshots should be taken with the same depth, so that the levels which fall out of the depth of `next`
+    /// are removed.
+    pub fn diff(&self, next: &Self) -> Vec<LevelDelta> {
+        let mut deltas = diff_levels(Side::Bid, &self.bids, &next.bids);
+        deltas.extend(diff_levels(Side::Ask, &self.asks, &next.asks));
+        deltas
+    }
+
+    /// Applies changes listed by [`L2Snapshot::diff`], keeping both sides sorted best first.
+    pub fn apply(&mut self, deltas: &[LevelDelta]) {
+        for delta in deltas {
+            let (side, price) = match *delta {
+                LevelDelta::Add { side, price, .. }
+                | LevelDelta::Update { side, price, .. }
+                | LevelDelta::Remove { side, price } => (side, price),
+            };
+            let levels = match side {
+                Side::Bid => &mut self.bids,
+                Side::Ask => &mut self.asks,
+            };
+            let position = levels.binary_search_by(|level| match side {
+                Side::Bid => price.cmp(&level.price),
+                Side::Ask => level.price.cmp(&price),
+            });
+            match (delta, position) {
+                (LevelDelta::Add { base_quantity, .. }, Err(index)) => levels.insert(
+                    index,
+                    PriceLevel {
+                        price,
+                        base_quantity: *base_quantity,
+                    },
+                ),
+                (LevelDelta::Add { base_quantity, .. }, Ok(index))
+                | (LevelDelta::Update { base_quantity, .. }, Ok(index)) => {
+                    levels[index].base_quantity = *base_quantity
+                }
+                (LevelDelta::Remove { .. }, Ok(index)) => {
+                    levels.remove(index);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A change to a price level between two L2 snapshots
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "type")]
+#[allow(missing_docs)]
+pub enum LevelDelta {
+    Add {
+        side: Side,
+        price: u64,
+        base_quantity: Quantity,
+    },
+    Update {
+        side: Side,
+        price: u64,
+        base_quantity: Quantity,
+    },
+    Remove {
+        side: Side,
+        price: u64,
+    },
+}
+
+fn diff_levels(side: Side, levels: &[PriceLevel], next_levels: &[PriceLevel]) -> Vec<LevelDelta> {
+    let mut changes = BTreeMap::new();
+    for level in levels {
+        changes.insert(level.price, (Some(level.base_quantity), None));
+    }
+    for level in next_levels {
+        changes.entry(level.price).or_insert((None, None)).1 = Some(level.base_quantity);
+    }
+    changes
+        .into_iter()
+        .filter_map(|(price, change)| match change {
+            (None, Some(base_quantity)) => Some(LevelDelta::Add {
+                side,
+                price,
+                base_quantity,
+            }),
+            (Some(old), Some(base_quantity)) if old != base_quantity => Some(LevelDelta::Update {
+                side,
+                price,
+                base_quantity,
+            }),
+            (Some(_), None) => Some(LevelDelta::Remove { side, price }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn price_levels(
+    slab: &Slab<impl Storage>,
+    descending: bool,
+    base_lot_size: u64,
+    depth: usize,
+) -> Vec<PriceLevel> {
+    slab.price_levels(descending, depth)
+        .into_iter()
+        .map(|(price, base_lots)| PriceLevel {
+            price,
+            base_quantity: lots_to_base_qty(base_lots, base_lot_size),
+        })
+        .collect()
+}
+
+/// An order resting in the orderbook, as listed in an L3 view of the book.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenOrder {
+    #[allow(missing_docs)]
+    pub order_id: u128,
+    #[allow(missing_docs)]
+    pub side: Side,
+    #[allow(missing_docs)]
+    pub price: u64,
+    /// The base quantity which remains to be matched
+    pub base_quantity: Quantity,
+    /// The callback information which the caller program attached to the order
+    pub callback_info: Vec<u8>,
+}
+
+impl OpenOrder {
+    /// Lists the orders resting in a slab, best first.
+    pub fn from_slab(slab: &Slab<impl Storage>, side: Side, base_lot_size: u64) -> Vec<Self> {
+        slab.leaves(side == Side::Bid)
+            .iter()
+            .map(|leaf| Self {
+                order_id: leaf.key,
+                side,
+                price: leaf.price(),
+                base_quantity: lots_to_base_qty(leaf.base_quantity, base_lot_size),
+                callback_info: slab
+                    .get_callback_info(leaf.callback_info_pt as usize)
+                    .to_vec(),
+            })
+            .collect()
+    }
+}
+
+/// An event which is waiting in the event queue to be consumed, see [`Event`].
+///
+/// The order ids are serialized as the hex strings of [`OrderId`], as the internal tagging buffers the fields in a
+/// form which has no room for a `u128`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+#[allow(missing_docs)]
+pub enum PendingEvent {
+    Fill {
+        taker_side: Side,
+        #[serde(with = "order_id_hex")]
+        maker_order_id: u128,
+        quote_size: Quantity,
+        base_size: Quantity,
+        slot: u64,
+        unix_timestamp: i64,
+        maker_callback_info: Vec<u8>,
+        taker_callback_info: Vec<u8>,
+    },
+    Out {
+        side: Side,
+        #[serde(with = "order_id_hex")]
+        order_id: u128,
+        base_size: Quantity,
+        delete: bool,
+        slot: u64,
+        unix_timestamp: i64,
+        callback_info: Vec<u8>,
+    },
+}
+
+mod order_id_hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(order_id: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&OrderId(*order_id))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        let order_id: OrderId = String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)?;
+        Ok(order_id.0)
+    }
+}
+
+impl PendingEvent {
+    /// Lists the events held in an event queue, oldest first.
+    pub fn from_event_queue(event_queue: &EventQueue<impl Storage>) -> Vec<Self> {
+        event_queue
+            .peek_n(event_queue.header.count)
+            .into_iter()
+            .map(Self::from)
+            .collect()
+    }
+}
+
+impl From<Event> for PendingEvent {
+    fn from(event: Event) -> Self {
+        match event {
+            Event::Fill {
+                taker_side,
+                maker_order_id,
+                quote_size,
+                base_size,
+                slot,
+                unix_timestamp,
+                maker_callback_info,
+                taker_callback_info,
+            } => PendingEvent::Fill {
+                taker_side,
+                maker_order_id: maker_order_id.0,
+                quote_size,
+                base_size,
+                slot,
+                unix_timestamp,
+                maker_callback_info,
+                taker_callback_info,
+            },
+            Event::Out {
+                side,
+                order_id,
+                base_size,
+                delete,
+                slot,
+                unix_timestamp,
+                callback_info,
+            } => PendingEvent::Out {
+                side,
+                order_id: order_id.0,
+                base_size,
+                delete,
+                slot,
+                unix_timestamp,
+                callback_info,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        critbit::{LeafNode, Node},
+        state::{AccountTag, OrderId},
+    };
+
+    fn insert_order(slab: &mut Slab<Vec<u8>>, order_id: OrderId, base_lots: u64, owner: u8) {
+        let callback_info_pt = slab.write_callback_info(&[owner; 4]).unwrap();
+        slab.insert_leaf(&Node::Leaf(LeafNode {
+            key: order_id.0,
+            callback_info_pt,
+            base_quantity: base_lots,
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut bids = Slab::new_in_memory(8, 4, AccountTag::Bids as u8, [0; 32]);
+        let mut asks = Slab::new_in_memory(8, 4, AccountTag::Asks as u8, [0; 32]);
+        insert_order(&mut bids, OrderId::new(9 << 32, Side::Bid, 0), 2, 1);
+        insert_order(&mut bids, OrderId::new(10 << 32, Side::Bid, 1), 3, 2);
+        insert_order(&mut bids, OrderId::new(10 << 32, Side::Bid, 2), 4, 3);
+        insert_order(&mut asks, OrderId::new(11 << 32, Side::Ask, 3), 5, 4);
+
+        let l2 = L2Snapshot::from_slabs(&bids, &asks, 10, 1);
+        assert_eq!(
+            l2,
+            L2Snapshot {
+                bids: vec![PriceLevel {
+                    price: 10 << 32,
+                    base_quantity: 70
+                }],
+                asks: vec![PriceLevel {
+                    price: 11 << 32,
+                    base_quantity: 50
+                }],
+            }
+        );
+
+        let open_bids = OpenOrder::from_slab(&bids, Side::Bid, 10);
+        assert_eq!(
+            open_bids
+                .iter()
+                .map(|order| (
+                    order.price >> 32,
+                    order.base_quantity,
+                    order.callback_info[0]
+                ))
+                .collect::<Vec<_>>(),
+            vec![(10, 30, 2), (10, 40, 3), (9, 20, 1)]
+        );
+
+        let mut event_queue = EventQueue::new_in_memory(4, 4);
+        event_queue
+            .push_back(Event::Out {
+                side: Side::Ask,
+                order_id: OrderId::new(11 << 32, Side::Ask, 3),
+                base_size: 50,
+                delete: true,
+                slot: 1,
+                unix_timestamp: 2,
+                callback_info: vec![4; 4],
+            })
+            .unwrap();
+        let events = PendingEvent::from_event_queue(&event_queue);
+        let json = serde_json::to_string(&events).unwrap();
+        assert!(json.starts_with(&format!(
+            r#"[{{"type":"Out","side":"Ask","order_id":"{}""#,
+            OrderId::new(11 << 32, Side::Ask, 3)
+        )));
+        assert_eq!(
+            serde_json::from_str::<Vec<PendingEvent>>(&json).unwrap(),
+            events
+        );
+    }
+
+    fn level(price: u64, base_quantity: Quantity) -> PriceLevel {
+        PriceLevel {
+            price,
+            base_quantity,
+        }
+    }
+
+    #[test]
+    fn l2_diff() {
+        let mut l2 = L2Snapshot {
+            bids: vec![level(10, 5), level(9, 3), level(8, 1)],
+            asks: vec![level(11, 2)],
+        };
+        let next = L2Snapshot {
+            bids: vec![level(10, 4), level(8, 1), level(7, 6)],
+            asks: vec![level(12, 2)],
+        };
+
+        let deltas = l2.diff(&next);
+        assert_eq!(
+            deltas,
+            vec![
+                LevelDelta::Add {
+                    side: Side::Bid,
+                    price: 7,
+                    base_quantity: 6
+                },
+                LevelDelta::Remove {
+                    side: Side::Bid,
+                    price: 9
+                },
+                LevelDelta::Update {
+                    side: Side::Bid,
+                    price: 10,
+                    base_quantity: 4
+                },
+                LevelDelta::Remove {
+                    side: Side::Ask,
+                    price: 11
+                },
+                LevelDelta::Add {
+                    side: Side::Ask,
+                    price: 12,
+                    base_quantity: 2
+                },
+            ]
+        );
+        l2.apply(&deltas);
+        assert_eq!(l2, next);
+        assert!(l2.diff(&next).is_empty());
+    }
+
+    #[test]
+    fn indicative_auction() {
+        let auction = |bids, asks, reference_price| {
+            L2Snapshot { bids, asks }
+                .indicative_auction(reference_price)
+                .map(|auction| (auction.price, auction.base_qty))
+        };
+        assert_eq!(auction(vec![level(10, 5)], vec![level(11, 5)], 0), None);
+        assert_eq!(auction(vec![], vec![level(11, 5)], 0), None);
+
+        // The price which trades the most
+        assert_eq!(
+            auction(
+                vec![level(12, 10), level(11, 10), level(9, 10)],
+                vec![level(10, 5), level(11, 10), level(13, 10)],
+                0
+            ),
+            Some((11, 15))
+        );
+        // Then the one which leaves the least unmatched
+        assert_eq!(
+            auction(vec![level(12, 10), level(11, 5)], vec![level(10, 10)], 10),
+            Some((12, 10))
+        );
+        // Then the one closest to the reference price, and then the lowest
+        assert_eq!(
+            auction(vec![level(12, 10)], vec![level(10, 10)], 12),
+            Some((12, 10))
+        );
+        assert_eq!(
+            auction(vec![level(12, 10)], vec![level(10, 10)], 11),
+            Some((10, 10))
+        );
+    }
+}
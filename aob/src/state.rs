@@ -0,0 +1,2928 @@
+use std::{
+    cell::{RefCell, RefMut},
+    fmt,
+    io::Write,
+    mem::size_of,
+    num::ParseIntError,
+    rc::Rc,
+    str::FromStr,
+};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::account_info::AccountInfo;
+use bonfida_utils::BorshSize;
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{try_from_bytes_mut, Pod, Zeroable};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+
+use crate::critbit::{IoError, Slab, Storage};
+use crate::error::ErrorCode;
+use crate::histbuf::HistoryBuffer;
+pub use crate::orderbook::{OrderSummary, ORDER_SUMMARY_SIZE};
+#[cfg(feature = "no-entrypoint")]
+pub use crate::utils::get_spread;
+use crate::utils::{round_price_with_policy, Quantity};
+use crate::{log_debug, log_error};
+
+#[derive(BorshDeserialize, BorshSerialize, Copy, Clone, Debug, PartialEq)]
+#[allow(missing_docs)]
+#[repr(u8)]
+pub enum AccountTag {
+    Uninitialized,
+    Market,
+    EventQueue,
+    Bids,
+    Asks,
+    TradeHistory,
+    Candles,
+    FeeVault,
+}
+
+/// TODO this is done for the sake of Anchor-space sizing, but it's probably conflating.
+///
+/// Anchor uses `T::default()` to derive `space`, but really, there should be a separate `Space`
+/// trait.
+impl Default for AccountTag {
+    fn default() -> Self {
+        AccountTag::Uninitialized
+    }
+}
+
+impl AccountTag {
+    /// The error which identifies an account expected to hold this tag
+    pub fn wrong_tag_error(self) -> ErrorCode {
+        match self {
+            AccountTag::Market => ErrorCode::WrongMarketTag,
+            AccountTag::EventQueue => ErrorCode::WrongEventQueueTag,
+            AccountTag::Bids => ErrorCode::WrongBidsTag,
+            AccountTag::Asks => ErrorCode::WrongAsksTag,
+            AccountTag::TradeHistory => ErrorCode::WrongTradeHistoryTag,
+            AccountTag::Candles => ErrorCode::WrongCandlesTag,
+            AccountTag::FeeVault => ErrorCode::WrongFeeVaultTag,
+            AccountTag::Uninitialized => ErrorCode::WrongAccountTag,
+        }
+    }
+
+    /// Checks the tag found in an account's data against this expected tag.
+    ///
+    /// This is the single place where the AO accounts' tags are verified.
+    pub fn check(self, found: u64) -> Result<()> {
+        if found != self as u64 {
+            log_error!("Expected an account tagged {:?}, found tag {}", self, found);
+            return Err(error!(self.wrong_tag_error()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    PartialEq,
+    FromPrimitive,
+    ToPrimitive,
+    Debug,
+    BorshSize,
+)]
+#[cfg_attr(
+    any(test, feature = "snapshot"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+impl Side {
+    /// Helper function to get the opposite side.
+    pub fn opposite(&self) -> Self {
+        match self {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        }
+    }
+}
+
+/// Describes what happens when two order with identical callback informations are matched together
+#[derive(
+    BorshDeserialize, BorshSerialize, Clone, PartialEq, FromPrimitive, ToPrimitive, BorshSize,
+)]
+#[repr(u8)]
+pub enum SelfTradeBehavior {
+    /// The orders are matched together
+    DecrementTake,
+    /// The order on the provide side is cancelled. Matching for the current order continues and essentially bypasses
+    /// the self-provided order.
+    CancelProvide,
+    /// The entire transaction fails and the program returns an error.
+    AbortTransaction,
+}
+
+/// Describes how orders resting at the same price level are prioritized during matching
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    PartialEq,
+    FromPrimitive,
+    ToPrimitive,
+    Debug,
+    BorshSize,
+)]
+#[repr(u8)]
+pub enum OrderPriority {
+    /// Orders are matched in the order in which they were posted
+    Fifo,
+    /// Larger orders are matched first. Orders of equal size are matched in the order in which they were posted.
+    ///
+    /// Only the [`SIZE_PRIORITY_DEPTH`][crate::critbit::SIZE_PRIORITY_DEPTH] earliest orders of a price level are
+    /// compared, so that a larger order posted behind them waits until some of them are taken out.
+    Size,
+}
+
+/// Describes what happens when an event is pushed to a full event queue
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    PartialEq,
+    FromPrimitive,
+    ToPrimitive,
+    Debug,
+    Default,
+    BorshSize,
+)]
+#[repr(u8)]
+pub enum EventQueueOverflowPolicy {
+    /// The event is rejected, which makes the current instruction fail.
+    #[default]
+    Reject,
+    /// The oldest event in the queue is evicted to make room for the new one.
+    ///
+    /// This should only be used by caller programs which process fills synchronously, for instance by
+    /// reading the event queue right after the new_order instruction.
+    EvictOldest,
+}
+
+/// Describes how the limit price of a new order is fitted to the market's tick size
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    PartialEq,
+    FromPrimitive,
+    ToPrimitive,
+    Debug,
+    Default,
+    BorshSize,
+)]
+#[repr(u8)]
+pub enum PriceRoundingPolicy {
+    /// Off-tick prices are rounded toward the passive side: bids are rounded down and asks are rounded up.
+    #[default]
+    Passive,
+    /// Off-tick prices are rejected.
+    Strict,
+    /// Off-tick prices are rounded to the nearest tick. Prices halfway between two ticks are rounded up.
+    Nearest,
+}
+
+/// The trading session a market is in, which decides what new orders do. Cancels are allowed in every session.
+///
+/// The caller authority moves the market through its sessions, see [`MarketState::set_trading_session`]:
+/// ```text
+/// Closed -> PreOpen -> Open -> Closed
+///    \_________________^
+/// ```
+/// A pre-open session can also be called off back to closed.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    PartialEq,
+    FromPrimitive,
+    ToPrimitive,
+    Debug,
+    Default,
+    BorshSize,
+)]
+#[repr(u8)]
+pub enum TradingSession {
+    /// Orders are matched against the book, and what remains of them is posted.
+    #[default]
+    Open,
+    /// Orders are posted without being matched, even when they cross the book.
+    PreOpen,
+    /// New orders are rejected.
+    Closed,
+}
+
+/// The orderbook market's central state
+/// TODO zero-copy for Anchor
+#[account(zero_copy)]
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct MarketState {
+    /// Identifies the account as a [`MarketState`] object.
+    pub tag: u64,
+    /// The required signer for all market operations.
+    pub caller_authority: [u8; 32],
+    /// The public key of the orderbook's event queue account
+    pub event_queue: [u8; 32],
+    /// The public key of the orderbook's bids account
+    pub bids: [u8; 32],
+    /// The public key of the orderbook's asks account
+    pub asks: [u8; 32],
+    /// The length of an order actor's callback identifier.
+    pub callback_id_len: u64,
+    /// The length of an order's callback metadata.
+    pub callback_info_len: u64,
+    /// The current budget of fees that have been collected.
+    /// Cranker rewards are taken from this. This value allows
+    /// for a verification that the fee was payed in the caller program
+    /// runtime while not having to add a CPI call to the serum-core.
+    ///
+    /// The budget is held by the market's [`FeeVault`] if it has one, and by the market account otherwise.
+    pub fee_budget: u64,
+    /// The amount of lamports the market account was created with.
+    /// Only used to account for fees by markets which don't have a [`FeeVault`].
+    pub initial_lamports: u64,
+    /// The minimum order size that can be inserted into the orderbook after matching.
+    pub min_base_order_size: u64,
+    /// Tick size (FP32)
+    pub tick_size: u64,
+    /// The granularity of base quantities. Orderbook leaves store their quantity as a number of lots.
+    pub base_lot_size: u64,
+    /// Flat part of the cranker reward (in lamports), see [`MarketState::crank_reward`]
+    pub cranker_base_reward: u64,
+    /// Per-event part of the cranker reward (in lamports), see [`MarketState::crank_reward`]
+    pub cranker_per_event_reward: u64,
+    /// Event queue occupancy (in basis points of its capacity) at which a high watermark event is emitted.
+    /// A value of 0 disables the high watermark.
+    pub event_queue_high_watermark_bps: u64,
+    /// Event queue occupancy (in basis points of its capacity) at which a low watermark event is emitted.
+    /// A value of 0 disables the low watermark.
+    pub event_queue_low_watermark_bps: u64,
+    /// The market's intra-level [`OrderPriority`] policy
+    pub order_priority: u64,
+    /// The market's [`EventQueueOverflowPolicy`]
+    pub event_queue_overflow_policy: u64,
+    /// Set for the duration of any instruction which mutates the orderbook, in order to detect re-entrant calls.
+    pub locked: u64,
+    /// The maximum distance (in ticks) between a post-only order and the opposite best offer.
+    /// A value of 0 disables the check.
+    pub max_post_only_spread_ticks: u64,
+    /// The price of the last match (FP32)
+    pub last_trade_price: u64,
+    /// The total base quantity matched on the market. This counter wraps around on overflow.
+    pub base_volume: u64,
+    /// The total quote quantity matched on the market. This counter wraps around on overflow.
+    pub quote_volume: u64,
+    /// The base quantity bought by takers minus the base quantity sold by takers. This counter wraps around on
+    /// overflow, so the flow over an interval is the wrapping difference of two readings.
+    pub net_taker_flow: i64,
+    /// The [`ResumptionTicket`] of a taker order which ran into its match limit, if any.
+    /// While it is set, orders which aren't post-only are rejected until the ticket is resumed.
+    pub resumption_ticket: [u8; 32],
+    /// The public key of the market's [`TradeHistory`] account, if any.
+    /// While it is set, the account has to be provided to the instructions which match orders.
+    pub trade_history: [u8; 32],
+    /// The public key of the market's [`Candles`] account, if any.
+    /// While it is set, the account has to be provided to the instructions which match orders.
+    pub candles: [u8; 32],
+    /// The public key of the market's [`FeeVault`] account, if any.
+    /// While it is set, fees are paid into and cranker rewards are paid out of the vault, which has to be provided
+    /// to the instructions which match orders or consume events.
+    pub fee_vault: [u8; 32],
+    /// The number of [`authority_signers`][`MarketState::authority_signers`] which have to sign the market's
+    /// administrative instructions, see [`MarketState::check_admin_authority`].
+    /// A value of 0 leaves them to the caller authority alone.
+    pub authority_threshold: u64,
+    /// The keys which can co-sign the market's administrative instructions. Unused entries are zeroed.
+    pub authority_signers: [[u8; 32]; MAX_AUTHORITY_SIGNERS],
+    /// The sum over time of the last trade price (FP32) multiplied by the number of seconds it held for.
+    /// This accumulator wraps around on overflow, see [`MarketState::observe`].
+    pub price_cumulative: u128,
+    /// The unix timestamp at which [`price_cumulative`][`MarketState::price_cumulative`] was last updated
+    pub price_cumulative_timestamp: i64,
+    /// When non-zero, orders are never checked for self-trading and every order behaves as if its
+    /// [`SelfTradeBehavior`] was [`DecrementTake`][`SelfTradeBehavior::DecrementTake`].
+    /// This saves a comparison of the callback ids for each matched order.
+    pub self_trade_detection_disabled: u64,
+    /// The market's [`PriceRoundingPolicy`]
+    pub price_rounding_policy: u64,
+    /// The maximum number of orders a new order is matched against, whatever its own match limit.
+    /// A value of 0 leaves the match limit to the orders.
+    pub max_match_iterations: u64,
+    /// When non-zero, a fill against the maker order of the previous fill of the taker order is added to that
+    /// fill's event instead of being pushed as a new event, see [`EventQueue::coalesce_fill`].
+    pub coalesce_fills: u64,
+    /// The FP32 price of the opening auction which is being run over several `open_session` instructions, see
+    /// [`OrderBookState::uncross`][`crate::orderbook::OrderBookState::uncross`]. 0 when no auction is in progress.
+    pub auction_price: u64,
+    /// The seed from which the market's accounts were derived, see [`MarketAddresses`].
+    /// Zeroed for markets whose accounts aren't program-derived addresses.
+    pub seed: [u8; 32],
+    /// The bump of the market's program-derived address
+    pub market_bump: u8,
+    /// The bump of the event queue's program-derived address
+    pub event_queue_bump: u8,
+    /// The bump of the bids' program-derived address
+    pub bids_bump: u8,
+    /// The bump of the asks' program-derived address
+    pub asks_bump: u8,
+    /// When non-zero, anyone can consume events and collect the cranker reward.
+    /// Otherwise, `consume_events` has to be signed by the caller authority.
+    pub permissionless_crank: u8,
+    /// The market's current [`TradingSession`]
+    pub trading_session: u8,
+    /// Keeps the struct free of padding bytes
+    pub _padding: [u8; 2],
+}
+
+/// A reading of a market's price accumulator, see [`MarketState::observe`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceObservation {
+    /// The value of the market's [`price_cumulative`][`MarketState::price_cumulative`] accumulator
+    pub price_cumulative: u128,
+    /// The unix timestamp of the observation
+    pub unix_timestamp: i64,
+}
+
+impl PriceObservation {
+    /// Computes the time-weighted average of the last trade price (FP32) between two observations.
+    ///
+    /// Returns `None` unless `later` was observed strictly after `self`.
+    pub fn twap(&self, later: &PriceObservation) -> Option<u64> {
+        if later.unix_timestamp <= self.unix_timestamp {
+            return None;
+        }
+        let elapsed = (later.unix_timestamp - self.unix_timestamp) as u128;
+        let twap = later.price_cumulative.wrapping_sub(self.price_cumulative) / elapsed;
+        Some(twap as u64)
+    }
+}
+
+/// The maximum number of keys in a market's [`authority_signers`][`MarketState::authority_signers`] table
+pub const MAX_AUTHORITY_SIGNERS: usize = 5;
+
+/// The maximum callback information length of a market, which bounds the size of the slab nodes and event queue slots
+pub const MAX_CALLBACK_INFO_LEN: u64 = 256;
+
+/// Expected size in bytes of MarketState
+pub const MARKET_STATE_LEN: usize = size_of::<MarketState>();
+
+impl MarketState {
+    /// The seed from which market addresses are derived, along with the caller authority and a caller-provided seed
+    pub const SEED: &'static [u8] = b"market";
+    /// The size of a market account, Anchor discriminator included
+    pub const SPACE: usize = 8 + MARKET_STATE_LEN;
+
+    /// Checks the parameters of a new market, which the matching engine relies on once the market is created.
+    pub fn check_parameters(
+        callback_info_len: u64,
+        callback_id_len: u64,
+        min_base_order_size: u64,
+        tick_size: u64,
+    ) -> Result<()> {
+        if tick_size == 0 {
+            log_error!("The tick size must be > 0");
+            return err!(ErrorCode::InvalidTickSize);
+        }
+        if min_base_order_size == 0 {
+            log_error!("The minimum base order size must be > 0");
+            return err!(ErrorCode::InvalidMinBaseOrderSize);
+        }
+        if callback_id_len > callback_info_len {
+            log_error!(
+                "The callback id length {} exceeds the callback information length {}",
+                callback_id_len,
+                callback_info_len
+            );
+            return err!(ErrorCode::InvalidCallbackIdLen);
+        }
+        if callback_info_len > MAX_CALLBACK_INFO_LEN {
+            log_error!(
+                "The callback information length must be <= {}",
+                MAX_CALLBACK_INFO_LEN
+            );
+            return err!(ErrorCode::CallbackInfoTooLong);
+        }
+        Ok(())
+    }
+
+    #[allow(missing_docs)]
+    pub fn get<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> Result<RefMut<'a, Self>> {
+        let a = Self::get_unchecked(account_info);
+        AccountTag::Market.check(a.tag)?;
+        Ok(a)
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_unchecked<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> RefMut<'a, Self> {
+        let a = RefMut::map(account_info.data.borrow_mut(), |s| {
+            try_from_bytes_mut::<Self>(&mut s[0..MARKET_STATE_LEN]).unwrap()
+        });
+        a
+    }
+
+    /// Marks the market as being in use by the current instruction.
+    ///
+    /// Fails if the market is already locked, which means that the current instruction was invoked
+    /// through a CPI chain originating from another instruction on the same market.
+    pub fn lock(&mut self) -> Result<()> {
+        if self.locked != 0 {
+            log_error!("Re-entrant call on a locked market");
+            return err!(ErrorCode::MarketLocked);
+        }
+        self.locked = 1;
+        Ok(())
+    }
+
+    /// Releases the lock taken by [`MarketState::lock`].
+    pub fn unlock(&mut self) {
+        self.locked = 0;
+    }
+
+    /// The total base quantity of the orders resting on one side of the book, given the market's bids or asks slab.
+    ///
+    /// This only reads the slab header, which keeps a running total of the resting quantity.
+    pub fn resting_base_qty(&self, book_side: &Slab<impl Storage>) -> u64 {
+        book_side
+            .resting_base_lots()
+            .wrapping_mul(self.base_lot_size)
+    }
+
+    /// The reward for a batch of `number_of_events` events: `cranker_base_reward + cranker_per_event_reward * n`.
+    ///
+    /// The same formula is used for the fee charged to a new order over the events it pushes to the queue,
+    /// and for the payout of a consume_events instruction over the events it pops.
+    pub fn crank_reward(&self, number_of_events: u64) -> Result<u64> {
+        u64::try_from(self.wide_crank_reward(number_of_events)).map_err(|_| {
+            log_error!("The crank reward overflows");
+            error!(ErrorCode::NumericalOverflow)
+        })
+    }
+
+    /// The crank reward computed over 128 bits, which can't overflow.
+    fn wide_crank_reward(&self, number_of_events: u64) -> u128 {
+        self.cranker_base_reward as u128
+            + self.cranker_per_event_reward as u128 * number_of_events as u128
+    }
+
+    /// Takes the reward for consuming `number_of_events` events out of the fee budget.
+    ///
+    /// The reward is capped by the fee budget. Whatever isn't paid out stays in the budget for
+    /// subsequent cranks.
+    pub fn take_crank_reward(&mut self, number_of_events: u64) -> u64 {
+        if number_of_events == 0 {
+            return 0;
+        }
+        // The reward is capped before it's narrowed back to 64 bits, so that it never overflows
+        let reward = std::cmp::min(
+            self.fee_budget as u128,
+            self.wide_crank_reward(number_of_events),
+        ) as u64;
+        self.fee_budget -= reward;
+        reward
+    }
+
+    /// Checks that the market account's `lamports` cover the fee budget along with the fee for the
+    /// `number_of_events` events pushed by an order, then credits the lamports on top of the account's
+    /// initial lamports to the fee budget.
+    ///
+    /// This is how markets without a fee vault charge fees, which caller programs transfer to the market
+    /// account during their runtime.
+    pub fn verify_and_update_fee_budget(
+        &mut self,
+        lamports: u64,
+        number_of_events: u64,
+    ) -> Result<()> {
+        let required = self
+            .crank_reward(number_of_events)?
+            .checked_add(self.fee_budget)
+            .ok_or_else(|| error!(ErrorCode::NumericalOverflow))?;
+        let available = lamports.saturating_sub(self.initial_lamports);
+        if available < required {
+            log_error!("Fees were not correctly payed during caller runtime.");
+            return err!(ErrorCode::FeeNotPayed);
+        }
+        self.fee_budget = available;
+        Ok(())
+    }
+
+    /// Accounts for the time elapsed since the last update of the price accumulator at the last trade price.
+    ///
+    /// This has to be called before the last trade price changes.
+    pub fn update_price_cumulative(&mut self, unix_timestamp: i64) {
+        self.price_cumulative = self.observe(unix_timestamp).price_cumulative;
+        self.price_cumulative_timestamp = unix_timestamp;
+    }
+
+    /// Reads the price accumulator as it would be at the given unix timestamp, see [`PriceObservation::twap`].
+    pub fn observe(&self, unix_timestamp: i64) -> PriceObservation {
+        let elapsed = unix_timestamp
+            .saturating_sub(self.price_cumulative_timestamp)
+            .max(0);
+        PriceObservation {
+            price_cumulative: self
+                .price_cumulative
+                .wrapping_add(self.last_trade_price as u128 * elapsed as u128),
+            unix_timestamp,
+        }
+    }
+
+    /// Checks that an administrative instruction is authorized.
+    ///
+    /// Without an [`authority_threshold`][`MarketState::authority_threshold`], the authority has to be the
+    /// caller authority. Otherwise, at least that many distinct keys of the
+    /// [`authority_signers`][`MarketState::authority_signers`] table have to sign, among the authority and the
+    /// additional `co_signers`.
+    pub fn check_admin_authority<'a>(
+        &self,
+        authority: &AccountInfo<'a>,
+        co_signers: &[AccountInfo<'a>],
+    ) -> Result<()> {
+        if self.authority_threshold == 0 {
+            if authority.key.to_bytes() != self.caller_authority || !authority.is_signer {
+                return err!(ErrorCode::WrongCallerAuthority);
+            }
+            return Ok(());
+        }
+        let mut signed = [false; MAX_AUTHORITY_SIGNERS];
+        for account in std::iter::once(authority).chain(co_signers) {
+            if !account.is_signer {
+                continue;
+            }
+            let key = account.key.to_bytes();
+            if let Some(i) = self.authority_signers.iter().position(|s| *s == key) {
+                signed[i] = true;
+            }
+        }
+        if (signed.iter().filter(|s| **s).count() as u64) < self.authority_threshold {
+            log_error!(
+                "The instruction requires {} of the market's authority signers",
+                self.authority_threshold
+            );
+            return err!(ErrorCode::NotEnoughAuthoritySigners);
+        }
+        Ok(())
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_event_queue_overflow_policy(&self) -> Result<EventQueueOverflowPolicy> {
+        EventQueueOverflowPolicy::from_u64(self.event_queue_overflow_policy)
+            .ok_or_else(|| error!(ErrorCode::FailedToDeserialize))
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_price_rounding_policy(&self) -> Result<PriceRoundingPolicy> {
+        PriceRoundingPolicy::from_u64(self.price_rounding_policy)
+            .ok_or_else(|| error!(ErrorCode::FailedToDeserialize))
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_trading_session(&self) -> Result<TradingSession> {
+        TradingSession::from_u8(self.trading_session)
+            .ok_or_else(|| error!(ErrorCode::FailedToDeserialize))
+    }
+
+    /// Moves the market to another [`TradingSession`], failing with
+    /// [`InvalidSessionTransition`][`ErrorCode::InvalidSessionTransition`] unless the transition is allowed.
+    ///
+    /// Leaving the pre-open session drops the opening auction in progress, if any.
+    pub fn set_trading_session(&mut self, session: TradingSession) -> Result<()> {
+        let current = self.get_trading_session()?;
+        let allowed = match current {
+            TradingSession::Closed => session != TradingSession::Closed,
+            TradingSession::PreOpen => session != TradingSession::PreOpen,
+            TradingSession::Open => session == TradingSession::Closed,
+        };
+        if !allowed {
+            log_error!("The market can't move from {:?} to {:?}", current, session);
+            return err!(ErrorCode::InvalidSessionTransition);
+        }
+        self.trading_session = session as u8;
+        self.auction_price = 0;
+        Ok(())
+    }
+
+    /// Fits the limit price of a new order to the market's tick size according to its [`PriceRoundingPolicy`]
+    pub fn round_price(&self, limit_price: u64, side: Side) -> Result<u64> {
+        round_price_with_policy(
+            self.tick_size,
+            limit_price,
+            side,
+            self.get_price_rounding_policy()?,
+        )
+    }
+
+    /// Checks whether an event queue occupancy change from `count_before` to `count_after` crosses
+    /// one of the market's event queue watermarks.
+    ///
+    /// The high watermark is crossed when the occupancy rises to or above it, the low watermark when
+    /// the occupancy falls to or below it.
+    pub fn check_watermarks(
+        &self,
+        count_before: u64,
+        count_after: u64,
+        capacity: u64,
+    ) -> Option<WatermarkCrossing> {
+        let threshold = |bps: u64| ((capacity as u128 * bps as u128) / MAX_BPS as u128) as u64;
+        if self.event_queue_high_watermark_bps != 0 {
+            let high = threshold(self.event_queue_high_watermark_bps);
+            if count_before < high && count_after >= high {
+                return Some(WatermarkCrossing::High);
+            }
+        }
+        if self.event_queue_low_watermark_bps != 0 {
+            let low = threshold(self.event_queue_low_watermark_bps);
+            if count_before > low && count_after <= low {
+                return Some(WatermarkCrossing::Low);
+            }
+        }
+        None
+    }
+}
+
+/// The number of basis points representing a full event queue
+pub const MAX_BPS: u64 = 10_000;
+
+/// Describes which of the market's event queue watermarks was crossed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatermarkCrossing {
+    /// The event queue occupancy rose to or above the high watermark
+    High,
+    /// The event queue occupancy fell to or below the low watermark
+    Low,
+}
+
+////////////////////////////////////////////////////
+// Orderbook Sides
+
+/// Typed Anchor view of a bids account.
+///
+/// The discriminator is followed by the bids [`Slab`], whose header starts with the address of the market.
+/// The rest of the account is accessed through the slab, see [`OrderBookState::new`].
+///
+/// [`Slab`]: crate::critbit::Slab
+/// [`OrderBookState::new`]: crate::orderbook::OrderBookState::new
+#[account(zero_copy)]
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct Bids {
+    /// The public key of the market the bids belong to
+    pub market: [u8; 32],
+}
+
+impl Bids {
+    /// The seed from which bids addresses are derived, along with the market's key
+    pub const SEED: &'static [u8] = b"bids";
+
+    /// The size of a bids account which holds `order_capacity` orders, see [`Slab::compute_allocation_size`].
+    pub fn space(order_capacity: usize, callback_info_len: usize) -> usize {
+        Slab::compute_allocation_size(order_capacity, callback_info_len)
+    }
+}
+
+/// Typed Anchor view of an asks account, see [`Bids`].
+#[account(zero_copy)]
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct Asks {
+    /// The public key of the market the asks belong to
+    pub market: [u8; 32],
+}
+
+impl Asks {
+    /// The seed from which asks addresses are derived, along with the market's key
+    pub const SEED: &'static [u8] = b"asks";
+
+    /// The size of an asks account which holds `order_capacity` orders, see [`Slab::compute_allocation_size`].
+    pub fn space(order_capacity: usize, callback_info_len: usize) -> usize {
+        Slab::compute_allocation_size(order_capacity, callback_info_len)
+    }
+}
+
+/// The addresses of the accounts of a market created through `create_market_with_seed`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarketAddresses {
+    #[allow(missing_docs)]
+    pub market: Pubkey,
+    #[allow(missing_docs)]
+    pub event_queue: Pubkey,
+    #[allow(missing_docs)]
+    pub bids: Pubkey,
+    #[allow(missing_docs)]
+    pub asks: Pubkey,
+}
+
+impl MarketAddresses {
+    /// Derives the addresses of the accounts of the market created by `caller_authority` from `seed`.
+    pub fn find(program_id: &Pubkey, caller_authority: &Pubkey, seed: &[u8; 32]) -> Self {
+        let (market, _) = Pubkey::find_program_address(
+            &[MarketState::SEED, caller_authority.as_ref(), seed],
+            program_id,
+        );
+        let find = |account_seed: &[u8]| {
+            Pubkey::find_program_address(&[account_seed, market.as_ref()], program_id).0
+        };
+        MarketAddresses {
+            market,
+            event_queue: find(EventQueue::SEED),
+            bids: find(Bids::SEED),
+            asks: find(Asks::SEED),
+        }
+    }
+}
+
+////////////////////////////////////////////////////
+// Resumption Tickets
+
+/// What remains of a taker order which ran into its match limit while still crossing the book.
+///
+/// The ticket account is created by the caller with [`ResumptionTicket::space`] bytes and owned by the AO program.
+/// It is released once the order has been fully resumed, and can then be reused for another order.
+///
+/// Orders whose average price is bounded, see [`NewOrderParams::max_avg_price`][`crate::params::NewOrderParams::max_avg_price`],
+/// are never resumed.
+#[account]
+#[derive(Default)]
+pub struct ResumptionTicket {
+    /// Set while the ticket holds an order which remains to be resumed
+    pub active: bool,
+    /// The market the order was placed on
+    pub market: Pubkey,
+    /// The order's [`Side`]
+    pub side: u8,
+    /// The limit price of the order (FP32)
+    pub limit_price: u64,
+    /// The remaining quantity of base to be traded
+    pub max_base_qty: Quantity,
+    /// The remaining quantity of quote to be traded
+    pub max_quote_qty: Quantity,
+    /// Whether what remains of the order once the book stops crossing should be posted
+    pub post_allowed: bool,
+    /// The order's [`SelfTradeBehavior`]
+    pub self_trade_behavior: u8,
+    /// The length of the prefix of the order's callback information which identifies its self trades, see
+    /// [`NewOrderParams`][`crate::params::NewOrderParams`]
+    pub self_trade_id_len: Option<u64>,
+    /// The order's callback information
+    pub callback_info: Vec<u8>,
+}
+
+impl ResumptionTicket {
+    /// The size of a ticket account for a market with the given `callback_info_len`.
+    pub fn space(callback_info_len: usize) -> usize {
+        8 + 1 + 32 + 1 + 3 * 8 + 1 + 1 + 9 + 4 + callback_info_len
+    }
+}
+
+/// The number of trade prices held by a [`TradeHistory`] account
+pub const TRADE_HISTORY_CAPACITY: usize = 64;
+
+/// Holds the prices of the most recent fills of a market, for use by circuit breakers and on-chain TWAPs.
+///
+/// The account is a PDA of the AO program derived from the `"trade_history"` seed and the market's key.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct TradeHistory {
+    /// Identifies the account as a [`TradeHistory`] object.
+    pub tag: u64,
+    /// The public key of the market the trades belong to
+    pub market: [u8; 32],
+    /// The prices (FP32) of the market's most recent fills
+    pub prices: HistoryBuffer<u64, TRADE_HISTORY_CAPACITY>,
+}
+
+/// Expected size in bytes of TradeHistory
+pub const TRADE_HISTORY_LEN: usize = size_of::<TradeHistory>();
+
+impl TradeHistory {
+    /// The seed from which trade history addresses are derived, along with the market's key
+    pub const SEED: &'static [u8] = b"trade_history";
+
+    #[allow(missing_docs)]
+    pub fn get<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> Result<RefMut<'a, Self>> {
+        let a = Self::get_unchecked(account_info);
+        AccountTag::TradeHistory.check(a.tag)?;
+        Ok(a)
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_unchecked<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> RefMut<'a, Self> {
+        RefMut::map(account_info.data.borrow_mut(), |s| {
+            try_from_bytes_mut::<Self>(&mut s[0..TRADE_HISTORY_LEN]).unwrap()
+        })
+    }
+}
+
+/// Holds the fees which pay for the cranking of a market, see [`MarketState::fee_budget`].
+///
+/// Fees are deposited through the `deposit_fee` instruction, which credits
+/// [`pending_fees`][`FeeVault::pending_fees`]. Each order then takes its fee out of the pending deposits, so that
+/// lamports transferred to the vault by other means never count as fees.
+///
+/// The account is a PDA of the AO program derived from the `"fee_vault"` seed and the market's key.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct FeeVault {
+    /// Identifies the account as a [`FeeVault`] object.
+    pub tag: u64,
+    /// The public key of the market the fees belong to
+    pub market: [u8; 32],
+    /// The lamports deposited which haven't been charged to an order yet
+    pub pending_fees: u64,
+}
+
+/// Expected size in bytes of FeeVault
+pub const FEE_VAULT_LEN: usize = size_of::<FeeVault>();
+
+impl FeeVault {
+    /// The seed from which fee vault addresses are derived, along with the market's key
+    pub const SEED: &'static [u8] = b"fee_vault";
+
+    #[allow(missing_docs)]
+    pub fn get<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> Result<RefMut<'a, Self>> {
+        let a = Self::get_unchecked(account_info);
+        AccountTag::FeeVault.check(a.tag)?;
+        Ok(a)
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_unchecked<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> RefMut<'a, Self> {
+        RefMut::map(account_info.data.borrow_mut(), |s| {
+            try_from_bytes_mut::<Self>(&mut s[0..FEE_VAULT_LEN]).unwrap()
+        })
+    }
+
+    /// Credits lamports which were transferred to the vault as fees.
+    pub fn deposit(&mut self, amount: u64) {
+        self.pending_fees = self.pending_fees.checked_add(amount).unwrap();
+    }
+
+    /// Charges a fee to the pending deposits.
+    pub fn take_fee(&mut self, fee: u64) -> Result<()> {
+        if self.pending_fees < fee {
+            log_error!("Fees were not correctly payed during caller runtime.");
+            return err!(ErrorCode::FeeNotPayed);
+        }
+        self.pending_fees -= fee;
+        Ok(())
+    }
+}
+
+/// The number of candles held by a [`Candles`] account
+pub const CANDLES_CAPACITY: usize = 128;
+
+/// The open, high, low and close prices (FP32), the volume and the taker flow of the fills of a market over a range
+/// of slots
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct Candle {
+    /// The first slot of the range, which is a multiple of the candle interval
+    pub start_slot: u64,
+    #[allow(missing_docs)]
+    pub open: u64,
+    #[allow(missing_docs)]
+    pub high: u64,
+    #[allow(missing_docs)]
+    pub low: u64,
+    #[allow(missing_docs)]
+    pub close: u64,
+    /// The base quantity matched over the range
+    pub base_volume: u64,
+    /// The quote quantity matched over the range
+    pub quote_volume: u64,
+    /// The base quantity bought by takers minus the base quantity they sold over the range, see
+    /// [`MarketState::net_taker_flow`]
+    pub taker_flow: i64,
+}
+
+/// Holds OHLCV candles of a market's most recent fills, bucketed by a fixed number of slots, so that charts can
+/// be rendered from chain state alone.
+///
+/// The account is a PDA of the AO program derived from the `"candles"` seed and the market's key.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct Candles {
+    /// Identifies the account as a [`Candles`] object.
+    pub tag: u64,
+    /// The public key of the market the candles belong to
+    pub market: [u8; 32],
+    /// The number of slots covered by each candle
+    pub slots_per_candle: u64,
+    /// The market's most recent candles. Ranges without any fill have no candle.
+    pub candles: HistoryBuffer<Candle, CANDLES_CAPACITY>,
+}
+
+/// Expected size in bytes of Candles
+pub const CANDLES_LEN: usize = size_of::<Candles>();
+
+impl Candles {
+    /// The seed from which candles addresses are derived, along with the market's key
+    pub const SEED: &'static [u8] = b"candles";
+
+    #[allow(missing_docs)]
+    pub fn get<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> Result<RefMut<'a, Self>> {
+        let a = Self::get_unchecked(account_info);
+        AccountTag::Candles.check(a.tag)?;
+        Ok(a)
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_unchecked<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> RefMut<'a, Self> {
+        RefMut::map(account_info.data.borrow_mut(), |s| {
+            try_from_bytes_mut::<Self>(&mut s[0..CANDLES_LEN]).unwrap()
+        })
+    }
+
+    /// Records a fill in the candle covering `slot`, starting a new candle if needed. The `taker_flow` of the fill
+    /// is its base quantity, negated when the taker sold.
+    pub fn record(
+        &mut self,
+        slot: u64,
+        price: u64,
+        base_qty: u64,
+        quote_qty: u64,
+        taker_flow: i64,
+    ) {
+        let start_slot = slot - slot % self.slots_per_candle;
+        match self.candles.recent_mut(0) {
+            Some(candle) if candle.start_slot == start_slot => {
+                candle.high = std::cmp::max(candle.high, price);
+                candle.low = std::cmp::min(candle.low, price);
+                candle.close = price;
+                candle.base_volume = candle.base_volume.wrapping_add(base_qty);
+                candle.quote_volume = candle.quote_volume.wrapping_add(quote_qty);
+                candle.taker_flow = candle.taker_flow.wrapping_add(taker_flow);
+            }
+            _ => self.candles.push(Candle {
+                start_slot,
+                open: price,
+                high: price,
+                low: price,
+                close: price,
+                base_volume: base_qty,
+                quote_volume: quote_qty,
+                taker_flow,
+            }),
+        }
+    }
+}
+
+////////////////////////////////////////////////////
+// Events
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+/// Events are the primary output of the asset agnostic orderbook
+pub enum Event {
+    /// A fill event describes a match between a taker order and a provider order
+    Fill {
+        #[allow(missing_docs)]
+        taker_side: Side,
+        /// The order id of the maker order
+        maker_order_id: OrderId,
+        /// The total quote size of the transaction
+        quote_size: Quantity,
+        /// The total base size of the transaction
+        base_size: Quantity,
+        /// The slot at which the match occurred
+        slot: u64,
+        /// The unix timestamp at which the match occurred
+        unix_timestamp: i64,
+        /// The callback information for the maker
+        maker_callback_info: Vec<u8>,
+        /// The callback information for the taker
+        taker_callback_info: Vec<u8>,
+    },
+    /// An out event describes an order which has been taken out of the orderbook
+    Out {
+        #[allow(missing_docs)]
+        side: Side,
+        #[allow(missing_docs)]
+        order_id: OrderId,
+        #[allow(missing_docs)]
+        base_size: Quantity,
+        #[allow(missing_docs)]
+        delete: bool,
+        /// The slot at which the order was taken out
+        slot: u64,
+        /// The unix timestamp at which the order was taken out
+        unix_timestamp: i64,
+        #[allow(missing_docs)]
+        callback_info: Vec<u8>,
+    },
+}
+
+/// The fixed-size head of an event queue slot, which is followed by the callback information of the event's orders:
+/// the maker's and then the taker's for fills, the order's for outs.
+///
+/// The head is packed, so that off-chain readers can cast the slots of an event queue account to it wherever they
+/// lie, see [`EventHead::from_slot`]. Pushing and popping events copies it in and out of the slots as is.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct EventHead {
+    /// Either [`EventHead::FILL`] or [`EventHead::OUT`]
+    pub tag: u8,
+    /// The taker's side for fills, and the order's side for outs
+    pub side: u8,
+    /// Whether the order of an out event was deleted
+    pub delete: u8,
+    /// The maker's order id for fills, and the order's id for outs
+    pub order_id: u128,
+    /// The total quote size of a fill, zero for outs
+    pub quote_size: Quantity,
+    #[allow(missing_docs)]
+    pub base_size: Quantity,
+    #[allow(missing_docs)]
+    pub slot: u64,
+    #[allow(missing_docs)]
+    pub unix_timestamp: i64,
+}
+
+/// Size in bytes of an [`EventHead`]
+pub const EVENT_HEAD_LEN: usize = size_of::<EventHead>();
+
+impl EventHead {
+    /// The tag of fill events
+    pub const FILL: u8 = 0;
+    /// The tag of out events
+    pub const OUT: u8 = 1;
+
+    /// Casts the head of an event queue slot.
+    pub fn from_slot(slot: &[u8]) -> &Self {
+        bytemuck::from_bytes(&slot[..EVENT_HEAD_LEN])
+    }
+}
+
+impl Event {
+    /// The fixed-size part of the event.
+    pub fn head(&self) -> EventHead {
+        match self {
+            Event::Fill {
+                taker_side,
+                maker_order_id,
+                quote_size,
+                base_size,
+                slot,
+                unix_timestamp,
+                ..
+            } => EventHead {
+                tag: EventHead::FILL,
+                side: *taker_side as u8,
+                delete: 0,
+                order_id: maker_order_id.0,
+                quote_size: *quote_size,
+                base_size: *base_size,
+                slot: *slot,
+                unix_timestamp: *unix_timestamp,
+            },
+            Event::Out {
+                side,
+                order_id,
+                base_size,
+                delete,
+                slot,
+                unix_timestamp,
+                ..
+            } => EventHead {
+                tag: EventHead::OUT,
+                side: *side as u8,
+                delete: *delete as u8,
+                order_id: order_id.0,
+                quote_size: 0,
+                base_size: *base_size,
+                slot: *slot,
+                unix_timestamp: *unix_timestamp,
+            },
+        }
+    }
+
+    /// Used to serialize an event object into a generic byte writer.
+    pub fn serialize<W: Write>(&self, writer: &mut W) -> std::result::Result<(), IoError> {
+        writer.write_all(bytemuck::bytes_of(&self.head()))?;
+        match self {
+            Event::Fill {
+                maker_callback_info,
+                taker_callback_info,
+                ..
+            } => {
+                writer.write_all(maker_callback_info)?;
+                writer.write_all(taker_callback_info)?;
+            }
+            Event::Out { callback_info, .. } => writer.write_all(callback_info)?,
+        };
+        Ok(())
+    }
+
+    /// Used to deserialize an event object from bytes.
+    pub fn deserialize(buf: &mut &[u8], callback_info_len: usize) -> Self {
+        let head = *EventHead::from_slot(buf);
+        let callback_info = &buf[EVENT_HEAD_LEN..];
+        match head.tag {
+            EventHead::FILL => Event::Fill {
+                taker_side: Side::from_u8(head.side).unwrap(),
+                maker_order_id: OrderId(head.order_id),
+                quote_size: head.quote_size,
+                base_size: head.base_size,
+                slot: head.slot,
+                unix_timestamp: head.unix_timestamp,
+                maker_callback_info: callback_info[..callback_info_len].to_owned(),
+                taker_callback_info: callback_info[callback_info_len..callback_info_len << 1]
+                    .to_owned(),
+            },
+            EventHead::OUT => Event::Out {
+                side: Side::from_u8(head.side).unwrap(),
+                order_id: OrderId(head.order_id),
+                base_size: head.base_size,
+                delete: head.delete == 1,
+                slot: head.slot,
+                unix_timestamp: head.unix_timestamp,
+                callback_info: callback_info[..callback_info_len].to_owned(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Serializes events into consecutive event queue slots of `buffer`.
+    ///
+    /// Events don't necessarily fill their slot, the remaining bytes of which are left untouched.
+    pub fn serialize_batch(
+        events: &[Event],
+        buffer: &mut [u8],
+        callback_info_len: usize,
+    ) -> std::result::Result<(), IoError> {
+        let slot_size = Self::compute_slot_size(callback_info_len);
+        if buffer.len() < events.len() * slot_size {
+            return Err(IoError::new(
+                std::io::ErrorKind::WriteZero,
+                "The buffer is too small for the events",
+            ));
+        }
+        for (event, mut slot) in events.iter().zip(buffer.chunks_exact_mut(slot_size)) {
+            event.serialize(&mut slot)?;
+        }
+        Ok(())
+    }
+
+    /// Deserializes the events held in consecutive event queue slots of `buffer`.
+    pub fn deserialize_batch(buffer: &[u8], callback_info_len: usize) -> Vec<Self> {
+        buffer
+            .chunks_exact(Self::compute_slot_size(callback_info_len))
+            .map(|mut slot| Self::deserialize(&mut slot, callback_info_len))
+            .collect()
+    }
+
+    /// An event queue is divided into slots. The size of these slots depend on the particular market's `callback_info_len` constant.
+    pub fn compute_slot_size(callback_info_len: usize) -> usize {
+        EVENT_HEAD_LEN + 2 * callback_info_len
+    }
+}
+
+////////////////////////////////////////////////////
+// Event Queue
+
+/// Describes the current state of the event queue
+///
+/// The header is a packed [`Pod`] struct laid out at the start of the event queue account, so that it is read and
+/// written in place rather than deserialized, see [`EventQueueHeader::get`] and [`EventQueue::write_header`]. Since
+/// it is packed, its fields have to be copied out before they are borrowed, as in `{ header.count }`.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C, packed)]
+pub struct EventQueueHeader {
+    tag: u8, // Initialized, EventQueue
+    head: u64,
+    /// The current event queue length
+    pub count: u64,
+    event_size: u64,
+    seq_num: u64,
+    /// The total base quantity of all fills pushed to the queue
+    pub base_volume: u128,
+    /// The total quote quantity of all fills pushed to the queue
+    pub quote_volume: u128,
+    /// The number of fills pushed to the queue
+    pub fill_count: u64,
+    /// The number of events the queue's buffer holds, set when the account is initialized or resized
+    capacity: u32,
+    /// The address of the market which the queue belongs to
+    market_address: [u8; 32],
+}
+#[allow(missing_docs)]
+pub const EVENT_QUEUE_HEADER_LEN: usize = size_of::<EventQueueHeader>();
+#[allow(missing_docs)]
+pub const REGISTER_SIZE: usize = ORDER_SUMMARY_SIZE as usize + 1; // Option<OrderSummary>
+
+impl EventQueueHeader {
+    /// The header of an empty event queue of the given market, whose account holds `data_len` bytes.
+    pub fn initialize(market_address: Pubkey, callback_info_len: usize, data_len: usize) -> Self {
+        let mut header = Self {
+            tag: AccountTag::EventQueue as u8,
+            head: 0,
+            count: 0,
+            event_size: Event::compute_slot_size(callback_info_len) as u64,
+            seq_num: 0,
+            base_volume: 0,
+            quote_volume: 0,
+            fill_count: 0,
+            capacity: 0,
+            market_address: market_address.to_bytes(),
+        };
+        header.resize(data_len);
+        header
+    }
+
+    /// Updates the capacity of the queue after its account was reallocated to `data_len` bytes.
+    pub fn resize(&mut self, data_len: usize) {
+        let buf_len = data_len.saturating_sub(EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE) as u64;
+        self.capacity = (buf_len / self.event_size) as u32;
+    }
+
+    /// The number of events the queue's buffer holds.
+    pub fn capacity(&self) -> u64 {
+        self.capacity as u64
+    }
+
+    pub fn check(self) -> Result<Self> {
+        AccountTag::EventQueue.check(self.tag as u64)?;
+        Ok(self)
+    }
+
+    /// Copies the header out of the data of an event queue account, without checking its tag.
+    pub fn load(data: &[u8]) -> Result<Self> {
+        data.get(..EVENT_QUEUE_HEADER_LEN)
+            .map(|header| *bytemuck::from_bytes::<Self>(header))
+            .ok_or_else(|| error!(ErrorCode::FailedToDeserialize))
+    }
+
+    /// Writes the header at the start of the data of an event queue account.
+    pub fn write(&self, data: &mut [u8]) {
+        data[..EVENT_QUEUE_HEADER_LEN].copy_from_slice(bytemuck::bytes_of(self));
+    }
+
+    /// Borrows the header of an event queue account in place, after checking its tag.
+    pub fn get<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> Result<RefMut<'a, Self>> {
+        let a = Self::get_unchecked(account_info);
+        AccountTag::EventQueue.check(a.tag as u64)?;
+        Ok(a)
+    }
+
+    #[allow(missing_docs)]
+    pub fn get_unchecked<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> RefMut<'a, Self> {
+        RefMut::map(account_info.data.borrow_mut(), |s| {
+            bytemuck::from_bytes_mut::<Self>(&mut s[0..EVENT_QUEUE_HEADER_LEN])
+        })
+    }
+
+    /// The next sequence number, which is incremented for every event pushed and every order id generated.
+    pub fn seq_num(&self) -> u64 {
+        self.seq_num
+    }
+
+    /// The address of the market which the queue belongs to
+    pub fn market_address(&self) -> Pubkey {
+        Pubkey::new_from_array(self.market_address)
+    }
+
+    /// Checks that the queue belongs to the given market, so that the event queue of another market is never
+    /// mixed into an instruction.
+    pub fn check_market(&self, market: &Pubkey) -> Result<()> {
+        if self.market_address != market.to_bytes() {
+            log_error!("The event queue belongs to another market");
+            return err!(ErrorCode::WrongEventQueueAccount);
+        }
+        Ok(())
+    }
+
+    /// Checks that the header is consistent with the market's callback info length and with the length of the
+    /// event queue account's data.
+    pub fn check_layout(&self, data_len: usize, callback_info_len: usize) -> Result<()> {
+        if self.event_size != Event::compute_slot_size(callback_info_len) as u64 {
+            log_error!(
+                "The event queue's event size doesn't match the market's callback info length"
+            );
+            return err!(ErrorCode::EventSizeMismatch);
+        }
+        let buf_len = self.capacity() * self.event_size;
+        if buf_len == 0
+            || data_len.checked_sub(EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE)
+                != Some(buf_len as usize)
+        {
+            log_error!("The event queue's capacity doesn't match the size of its account");
+            return err!(ErrorCode::InvalidEventQueueSize);
+        }
+        if self.head >= buf_len || self.head % self.event_size != 0 || self.count > self.capacity()
+        {
+            log_error!("The event queue's head or count is out of bounds");
+            return err!(ErrorCode::InvalidEventQueueHeader);
+        }
+        Ok(())
+    }
+}
+
+/// The event queue account contains a serialized header, a register
+/// and a circular buffer of serialized events.
+///
+/// This struct is used at runtime but doesn't represent a serialized event queue. On-chain, the queue shares the
+/// data of its account, see [`EventQueue::new_safe`]. Off-chain, it can be laid out in any [`Storage`].
+pub struct EventQueue<S> {
+    /// A copy of the account's header, which the queue's operations update and [`EventQueue::write_header`] writes
+    /// back
+    pub header: EventQueueHeader,
+    /// Whether the queue's operations changed the header since it was loaded
+    header_dirty: bool,
+    pub(crate) buffer: Rc<RefCell<S>>, //The whole account data
+    callback_info_len: usize,
+    /// Describes what happens when an event is pushed to a full queue
+    pub overflow_policy: EventQueueOverflowPolicy,
+    /// The slot with which new events are stamped
+    pub(crate) slot: u64,
+    /// The unix timestamp with which new events are stamped
+    pub(crate) unix_timestamp: i64,
+    /// The number of events pushed through this object
+    events_pushed: u64,
+    /// The slots of the events evicted since the checkpoint opened by [`EventQueue::checkpoint`], if any, oldest
+    /// first
+    evicted: Option<Vec<u8>>,
+}
+
+/// The event queue register can hold arbitrary data returned by the AAOB. Currently only used to return [`OrderSummary`] objects.
+pub type Register<T> = Option<T>;
+
+impl<'a> EventQueue<&'a mut [u8]> {
+    /// The seed from which event queue addresses are derived, along with the market's key
+    pub const SEED: &'static [u8] = b"event_queue";
+
+    /// Initialize a new EventQueue object, after checking that the header is consistent with the account and
+    /// with the market's callback info length.
+    pub fn new_safe(
+        header: EventQueueHeader,
+        account: &AccountInfo<'a>,
+        callback_info_len: usize,
+    ) -> Result<Self> {
+        let header = header.check()?;
+        header.check_layout(account.data_len(), callback_info_len)?;
+        let q = Self {
+            header,
+            buffer: Rc::clone(&account.data),
+            callback_info_len,
+            overflow_policy: EventQueueOverflowPolicy::Reject,
+            slot: 0,
+            unix_timestamp: 0,
+            events_pushed: 0,
+            header_dirty: false,
+            evicted: None,
+        };
+        q.clear_register();
+        Ok(q)
+    }
+}
+
+impl<S: Storage> EventQueue<S> {
+    /// Initialize a new EventQueue object.
+    ///
+    /// Within a CPI context, the account parameter can be supplied through
+    /// ```ignore
+    /// use std::rc::Rc;
+    /// let a: AccountInfo;
+    ///
+    /// let event_queue_header = EventQueueHeader::load(&a.data.borrow()).unwrap();
+    /// let event_queue = EventQueue::new(event_queue_header, Rc::clone(&a.data), callback_info_len);
+    ///
+    /// ```
+    pub fn new(
+        header: EventQueueHeader,
+        account: Rc<RefCell<S>>,
+        callback_info_len: usize,
+    ) -> Self {
+        Self {
+            header,
+            buffer: account,
+            callback_info_len,
+            overflow_policy: EventQueueOverflowPolicy::Reject,
+            slot: 0,
+            unix_timestamp: 0,
+            events_pushed: 0,
+            header_dirty: false,
+            evicted: None,
+        }
+    }
+
+    /// Sets the slot and unix timestamp with which the events pushed from now on are stamped.
+    pub fn set_clock(&mut self, clock: &Clock) {
+        self.slot = clock.slot;
+        self.unix_timestamp = clock.unix_timestamp;
+    }
+
+    /// The number of events pushed to the queue since this object was created.
+    pub fn events_pushed(&self) -> u64 {
+        self.events_pushed
+    }
+
+    /// Opens a checkpoint to which [`EventQueue::rewind`] can take the queue back, and returns the copy of the
+    /// header and the value of [`EventQueue::events_pushed`] it expects.
+    ///
+    /// Until the checkpoint is rewound or [`EventQueue::commit`]ted, the events evicted under
+    /// [`EventQueueOverflowPolicy::EvictOldest`] are kept aside, so that rewinding the queue restores them.
+    pub(crate) fn checkpoint(&mut self) -> (EventQueueHeader, u64) {
+        self.evicted = Some(Vec::new());
+        (self.header, self.events_pushed)
+    }
+
+    /// Closes the open checkpoint, whose evicted events are then dropped for good.
+    pub(crate) fn commit(&mut self) {
+        self.evicted = None;
+    }
+}
+
+impl<'a> EventQueue<&'a mut [u8]> {
+    /// The size of an event queue account which holds `event_capacity` events
+    pub fn compute_allocation_size(event_capacity: usize, callback_info_len: usize) -> usize {
+        EVENT_QUEUE_HEADER_LEN
+            + REGISTER_SIZE
+            + event_capacity * Event::compute_slot_size(callback_info_len)
+    }
+
+    /// Checks that an event queue account holds exactly `event_capacity` events
+    pub fn check_capacity(
+        account: &AccountInfo,
+        event_capacity: u64,
+        callback_info_len: u64,
+    ) -> Result<()> {
+        let data_len = account.data_len();
+        if event_capacity == 0
+            || event_capacity as usize > data_len
+            || data_len
+                != Self::compute_allocation_size(
+                    event_capacity as usize,
+                    callback_info_len as usize,
+                )
+        {
+            log_error!(
+                "The event queue account holds {} bytes, which doesn't fit {} events",
+                data_len,
+                event_capacity
+            );
+            return err!(ErrorCode::InvalidEventQueueSize);
+        }
+        Ok(())
+    }
+
+    pub fn check_buffer_size(account: &AccountInfo, callback_info_len: u64) -> Result<()> {
+        let event_size = Event::compute_slot_size(callback_info_len as usize);
+        if (account.data_len() - EVENT_QUEUE_HEADER_LEN - REGISTER_SIZE) % event_size != 0 {
+            log_error!("Event queue buffer size must be a multiple of the event size");
+            return Err(Error::from(ProgramError::InvalidAccountData).with_source(source!()));
+        }
+        Ok(())
+    }
+}
+
+impl EventQueue<Vec<u8>> {
+    /// Creates an empty event queue backed by a heap buffer which holds `event_capacity` events.
+    pub fn new_in_memory(event_capacity: usize, callback_info_len: usize) -> Self {
+        let mut buffer =
+            vec![0; EventQueue::compute_allocation_size(event_capacity, callback_info_len)];
+        let header =
+            EventQueueHeader::initialize(Pubkey::default(), callback_info_len, buffer.len());
+        header.write(&mut buffer);
+        Self::new(header, Rc::new(RefCell::new(buffer)), callback_info_len)
+    }
+
+    /// Loads an event queue from a copy of raw account data, such as the data of an event queue account fetched
+    /// over RPC, after checking its header against the market's callback info length.
+    ///
+    /// The queue owns its copy, so changes made to it are never written back to `data`.
+    pub fn from_bytes(data: &[u8], callback_info_len: usize) -> Result<Self> {
+        let header = EventQueueHeader::load(data)?.check()?;
+        header.check_layout(data.len(), callback_info_len)?;
+        Ok(Self::new(
+            header,
+            Rc::new(RefCell::new(data.to_vec())),
+            callback_info_len,
+        ))
+    }
+}
+
+impl<S: Storage> EventQueue<S> {
+    /// Assigns the next sequence number to a new order.
+    ///
+    /// Fails once the sequence numbers reach [`ORDER_ID_SIDE_FLAG`], past which the negated sequence number of a
+    /// bid would no longer carry the side flag, and its id could collide with the id of an ask.
+    pub(crate) fn gen_order_id(
+        &mut self,
+        limit_price: u64,
+        side: Side,
+    ) -> std::result::Result<OrderId, ErrorCode> {
+        if self.header.seq_num > MAX_ORDER_SEQUENCE {
+            log_error!("The market ran out of order sequence numbers");
+            return Err(ErrorCode::SequenceNumbersExhausted);
+        }
+        let seq_num = self.gen_seq_num();
+        Ok(OrderId::new(limit_price, side, seq_num))
+    }
+
+    fn gen_seq_num(&mut self) -> u64 {
+        let seq_num = self.header.seq_num;
+        self.header.seq_num += 1;
+        self.header_dirty = true;
+        seq_num
+    }
+
+    pub(crate) fn get_buf_len(&self) -> usize {
+        (self.header.capacity() * self.header.event_size) as usize
+    }
+
+    /// The maximum number of events the queue can hold.
+    pub fn capacity(&self) -> u64 {
+        self.header.capacity()
+    }
+
+    /// The index of the slot which holds the oldest event of the queue
+    #[cfg(any(test, feature = "client"))]
+    pub(crate) fn head_slot(&self) -> u64 {
+        self.header.head / self.header.event_size
+    }
+
+    /// Copies the raw content of a slot, whether or not it holds an event of the queue.
+    #[cfg(any(test, feature = "client"))]
+    pub(crate) fn slot_data(&self, slot: u64) -> Vec<u8> {
+        let offset =
+            EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + (slot * self.header.event_size) as usize;
+        self.buffer.borrow()[offset..offset + self.header.event_size as usize].to_vec()
+    }
+
+    pub(crate) fn full(&self) -> bool {
+        self.header.count == self.capacity()
+    }
+
+    pub(crate) fn push_back(&mut self, event: Event) -> std::result::Result<(), Event> {
+        let head = event.head();
+        let pushed = match &event {
+            Event::Fill {
+                maker_callback_info,
+                taker_callback_info,
+                ..
+            } => self.push_head(head, maker_callback_info, taker_callback_info),
+            Event::Out { callback_info, .. } => self.push_head(head, callback_info, &[]),
+        };
+        pushed.map_err(|_| event)
+    }
+
+    /// Pushes a fill event, stamped with the queue's slot and timestamp.
+    pub(crate) fn push_fill(
+        &mut self,
+        taker_side: Side,
+        maker_order_id: OrderId,
+        quote_size: Quantity,
+        base_size: Quantity,
+        maker_callback_info: &[u8],
+        taker_callback_info: &[u8],
+    ) -> std::result::Result<(), ErrorCode> {
+        let head = EventHead {
+            tag: EventHead::FILL,
+            side: taker_side as u8,
+            delete: 0,
+            order_id: maker_order_id.0,
+            quote_size,
+            base_size,
+            slot: self.slot,
+            unix_timestamp: self.unix_timestamp,
+        };
+        self.push_head(head, maker_callback_info, taker_callback_info)
+    }
+
+    /// Adds the sizes of a fill to the fill event which was pushed `distance` events before the last one, returning
+    /// whether that event is still in the queue.
+    ///
+    /// The added sizes have to be taken from the maker order named by the event, so that it still describes a
+    /// single order. The queue's volumes account for them, but its fill count doesn't.
+    pub(crate) fn coalesce_fill(
+        &mut self,
+        distance: u64,
+        quote_size: Quantity,
+        base_size: Quantity,
+    ) -> bool {
+        if distance >= self.header.count {
+            return false;
+        }
+        let index = self.header.count - 1 - distance;
+        let offset = EVENT_QUEUE_HEADER_LEN
+            + REGISTER_SIZE
+            + ((self.header.head + index * self.header.event_size) as usize % self.get_buf_len());
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            let head: &mut EventHead =
+                bytemuck::from_bytes_mut(&mut buffer[offset..offset + EVENT_HEAD_LEN]);
+            if head.tag != EventHead::FILL {
+                return false;
+            }
+            head.quote_size += quote_size;
+            head.base_size += base_size;
+        }
+        self.header.base_volume += base_size as u128;
+        self.header.quote_volume += quote_size as u128;
+        self.header_dirty = true;
+        true
+    }
+
+    /// Pushes an out event, stamped with the queue's slot and timestamp.
+    pub(crate) fn push_out(
+        &mut self,
+        side: Side,
+        order_id: OrderId,
+        base_size: Quantity,
+        delete: bool,
+        callback_info: &[u8],
+    ) -> std::result::Result<(), ErrorCode> {
+        let head = EventHead {
+            tag: EventHead::OUT,
+            side: side as u8,
+            delete: delete as u8,
+            order_id: order_id.0,
+            quote_size: 0,
+            base_size,
+            slot: self.slot,
+            unix_timestamp: self.unix_timestamp,
+        };
+        self.push_head(head, callback_info, &[])
+    }
+
+    /// Writes an event head into the next slot, followed by the given callback information, which is copied
+    /// straight into the queue's buffer.
+    fn push_head(
+        &mut self,
+        head: EventHead,
+        callback_info: &[u8],
+        taker_callback_info: &[u8],
+    ) -> std::result::Result<(), ErrorCode> {
+        if self.full() {
+            match self.overflow_policy {
+                EventQueueOverflowPolicy::Reject => return Err(ErrorCode::EventQueueFull),
+                EventQueueOverflowPolicy::EvictOldest => {
+                    log_debug!("Event queue is full! evicting the oldest event...");
+                    if let Some(evicted) = &mut self.evicted {
+                        let offset =
+                            EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + self.header.head as usize;
+                        evicted.extend_from_slice(
+                            &self.buffer.borrow()
+                                [offset..offset + (self.header.event_size as usize)],
+                        );
+                    }
+                    self.pop_n(1);
+                }
+            }
+        }
+        // The head lies within the buffer and the events fill it at most, so that the ring wraps around once at most
+        let mut position = (self.header.head + self.header.count * self.header.event_size) as usize;
+        if position >= self.get_buf_len() {
+            position -= self.get_buf_len();
+        }
+        let offset = EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + position;
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            let slot = &mut buffer[offset..offset + (self.header.event_size as usize)];
+            let (head_data, callback_data) = slot.split_at_mut(EVENT_HEAD_LEN);
+            head_data.copy_from_slice(bytemuck::bytes_of(&head));
+            let (maker_data, taker_data) = callback_data.split_at_mut(callback_info.len());
+            maker_data.copy_from_slice(callback_info);
+            taker_data[..taker_callback_info.len()].copy_from_slice(taker_callback_info);
+        }
+
+        self.header.count += 1;
+        self.header.seq_num += 1;
+        self.header_dirty = true;
+        self.events_pushed += 1;
+        if head.tag == EventHead::FILL {
+            self.header.base_volume += head.base_size as u128;
+            self.header.quote_volume += head.quote_size as u128;
+            self.header.fill_count += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Relocates the queue's events after its buffer has been grown from `old_buf_len` bytes, so that
+    /// they remain contiguous within the new ring.
+    ///
+    /// When the events wrap around the end of the old buffer, the segment between the head and the end of
+    /// the old buffer is moved to the end of the new buffer. The header, whose capacity was grown along with the
+    /// buffer, is written back by [`EventQueue::write_header`] in any case.
+    pub fn relocate_after_growth(&mut self, old_buf_len: usize) {
+        self.header_dirty = true;
+        let new_buf_len = self.get_buf_len();
+        let head = self.header.head as usize;
+        let used = (self.header.count * self.header.event_size) as usize;
+        if head + used <= old_buf_len {
+            return;
+        }
+        let shift = new_buf_len - old_buf_len;
+        let offset = EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE;
+        self.buffer
+            .borrow_mut()
+            .copy_within(offset + head..offset + old_buf_len, offset + head + shift);
+        self.header.head += shift as u64;
+    }
+
+    /// Retrieves the event at position index in the queue.
+    pub fn peek_at(&self, index: u64) -> Option<Event> {
+        if self.header.count <= index {
+            return None;
+        }
+
+        let header_offset = EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE;
+        let offset = ((self
+            .header
+            .head
+            .checked_add(index.checked_mul(self.header.event_size).unwrap())
+            .unwrap()) as usize
+            % self.get_buf_len())
+            + header_offset;
+        let mut event_data =
+            &self.buffer.borrow()[offset..offset + (self.header.event_size as usize)];
+        Some(Event::deserialize(&mut event_data, self.callback_info_len))
+    }
+
+    /// Retrieves up to n events from the front of the queue, oldest first.
+    ///
+    /// The events are read from at most two contiguous runs of slots, see [`Event::deserialize_batch`].
+    pub fn peek_n(&self, n: u64) -> Vec<Event> {
+        let header_offset = EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE;
+        let buffer = self.buffer.borrow();
+        let slots = &buffer[header_offset..header_offset + self.get_buf_len()];
+        let head = self.header.head as usize;
+        let len = (std::cmp::min(self.header.count, n) * self.header.event_size) as usize;
+        let first_len = std::cmp::min(len, slots.len() - head);
+        let mut events =
+            Event::deserialize_batch(&slots[head..head + first_len], self.callback_info_len);
+        events.extend(Event::deserialize_batch(
+            &slots[..len - first_len],
+            self.callback_info_len,
+        ));
+        events
+    }
+
+    /// Takes the queue back to the checkpoint opened by [`EventQueue::checkpoint`], which returned `checkpoint`
+    /// and `events_pushed`, and returns the events pushed since then, oldest first.
+    ///
+    /// The events which were evicted in the meantime are written back to their slots, while the events pushed
+    /// since the checkpoint are returned whether they are still in the queue or were evicted as well, which
+    /// happens when a single order pushes more events than the queue holds.
+    pub(crate) fn rewind(
+        &mut self,
+        checkpoint: &EventQueueHeader,
+        events_pushed: u64,
+    ) -> Vec<Event> {
+        let evicted = self.evicted.take().unwrap_or_default();
+        let event_size = self.header.event_size as usize;
+        let pushed = self.events_pushed - events_pushed;
+        let count = self.header.count;
+        let rewound = std::cmp::min(pushed, count);
+        // The queue is a FIFO, so that the events pushed since the checkpoint are evicted after the older ones
+        let restored = evicted.len() / event_size - (pushed - rewound) as usize;
+        let (restored_slots, pushed_slots) = evicted.split_at(restored * event_size);
+        let mut events = Event::deserialize_batch(pushed_slots, self.callback_info_len);
+        events.extend((count - rewound..count).map(|index| self.peek_at(index).unwrap()));
+
+        let buf_len = self.get_buf_len();
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            for (index, slot) in restored_slots.chunks_exact(event_size).enumerate() {
+                let position = (checkpoint.head as usize + index * event_size) % buf_len;
+                let offset = EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + position;
+                buffer[offset..offset + event_size].copy_from_slice(slot);
+            }
+        }
+        self.header.head = checkpoint.head;
+        self.header.count = checkpoint.count;
+        self.header.seq_num = checkpoint.seq_num;
+        self.header.base_volume = checkpoint.base_volume;
+        self.header.quote_volume = checkpoint.quote_volume;
+        self.header.fill_count = checkpoint.fill_count;
+        self.header_dirty = true;
+        self.events_pushed = events_pushed;
+        events
+    }
+
+    /// Pop n entries from the event queue
+    pub fn pop_n(&mut self, number_of_entries_to_pop: u64) {
+        let capped_number_of_entries_to_pop =
+            std::cmp::min(self.header.count, number_of_entries_to_pop);
+        if capped_number_of_entries_to_pop == 0 {
+            return;
+        }
+        self.header.count -= capped_number_of_entries_to_pop;
+        let buf_len = self.get_buf_len() as u64;
+        self.header.head += capped_number_of_entries_to_pop * self.header.event_size;
+        if self.header.head >= buf_len {
+            self.header.head -= buf_len;
+        }
+        self.header_dirty = true;
+    }
+
+    /// Writes the queue's copy of the header back to the start of its buffer, unless the queue's operations left it
+    /// unchanged.
+    pub fn write_header(&self) {
+        if self.header_dirty {
+            self.header.write(&mut self.buffer.borrow_mut());
+        }
+    }
+
+    pub fn write_to_register<T: BorshSerialize + BorshDeserialize>(&self, object: T) {
+        let mut register = &mut self.buffer.borrow_mut()
+            [EVENT_QUEUE_HEADER_LEN..EVENT_QUEUE_HEADER_LEN + (REGISTER_SIZE)];
+        Register::Some(object).serialize(&mut register).unwrap();
+    }
+
+    pub(crate) fn clear_register(&self) {
+        let mut register = &mut self.buffer.borrow_mut()
+            [EVENT_QUEUE_HEADER_LEN..EVENT_QUEUE_HEADER_LEN + (REGISTER_SIZE)];
+        Register::<u8>::None.serialize(&mut register).unwrap();
+    }
+
+    /// This method is used to deserialize the event queue's register
+    ///
+    /// The nature of the serialized object should be deductible from caller context
+    pub fn read_register<T: BorshSerialize + BorshDeserialize>(
+        &self,
+    ) -> std::result::Result<Register<T>, IoError> {
+        let mut register =
+            &self.buffer.borrow()[EVENT_QUEUE_HEADER_LEN..EVENT_QUEUE_HEADER_LEN + (REGISTER_SIZE)];
+        Register::deserialize(&mut register)
+    }
+
+    /// Returns an iterator over all the queue's events
+    #[cfg(feature = "no-entrypoint")]
+    pub fn iter(&self) -> QueueIterator<'_, S> {
+        QueueIterator {
+            queue_header: &self.header,
+            buffer: Rc::clone(&self.buffer),
+            current_index: self.header.head as usize,
+            callback_info_len: self.callback_info_len,
+            buffer_length: self.get_buf_len(),
+            header_offset: EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE,
+            remaining: self.header.count,
+        }
+    }
+}
+
+/// This method is used to deserialize the event queue's register
+/// without constructing an EventQueue instance
+///
+/// The nature of the serialized object should be deductible from caller context
+pub fn read_register<T: BorshSerialize + BorshDeserialize>(
+    event_q_acc: &AccountInfo,
+) -> std::result::Result<Register<T>, IoError> {
+    let mut register =
+        &event_q_acc.data.borrow()[EVENT_QUEUE_HEADER_LEN..EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE];
+    Register::deserialize(&mut register)
+}
+
+#[cfg(feature = "no-entrypoint")]
+impl<'b, S: Storage> IntoIterator for &'b EventQueue<S> {
+    type Item = Event;
+
+    type IntoIter = QueueIterator<'b, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+#[cfg(feature = "no-entrypoint")]
+/// Utility struct for iterating over a queue
+pub struct QueueIterator<'b, S> {
+    queue_header: &'b EventQueueHeader,
+    buffer: Rc<RefCell<S>>, //The whole account data
+    current_index: usize,
+    callback_info_len: usize,
+    buffer_length: usize,
+    header_offset: usize,
+    remaining: u64,
+}
+
+#[cfg(feature = "no-entrypoint")]
+impl<'b, S: Storage> Iterator for QueueIterator<'b, S> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let result = Event::deserialize(
+            &mut &self.buffer.borrow()[self.header_offset + self.current_index..],
+            self.callback_info_len,
+        );
+        self.current_index =
+            (self.current_index + self.queue_header.event_size as usize) % self.buffer_length;
+        self.remaining -= 1;
+        Some(result)
+    }
+}
+
+/// This byte flag is set for order_ids with side Bid, and unset for side Ask
+pub const ORDER_ID_SIDE_FLAG: u128 = 1 << 63;
+
+/// The largest sequence number which can be assigned to an order, see [`OrderId::new`]
+pub const MAX_ORDER_SEQUENCE: u64 = (ORDER_ID_SIDE_FLAG - 1) as u64;
+
+/// Uniquely identifies an order on a market.
+///
+/// The layout of an order id is stable: the upper 64 bits hold the order's limit price (FP32), and the lower
+/// 64 bits hold the sequence number which the event queue assigned to the order, bitwise negated for bids.
+/// Since sequence numbers stay below 2^63, the top bit of the lower half tells the order's side (see
+/// [`ORDER_ID_SIDE_FLAG`]), and sorting bids in descending order or asks in ascending order yields price-time
+/// priority.
+///
+/// Order ids are displayed and parsed as 32 hexadecimal digits, optionally prefixed with `0x`.
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+pub struct OrderId(pub u128);
+
+impl OrderId {
+    /// Builds the id of an order from its limit price (FP32), side and sequence number.
+    ///
+    /// The sequence number can't exceed [`MAX_ORDER_SEQUENCE`], so that the side flag of the id is set for bids
+    /// only.
+    pub fn new(price: u64, side: Side, sequence: u64) -> Self {
+        debug_assert!(sequence <= MAX_ORDER_SEQUENCE);
+        let lower = match side {
+            Side::Bid => !sequence,
+            Side::Ask => sequence,
+        };
+        Self(((price as u128) << 64) | lower as u128)
+    }
+
+    /// The order's limit price (FP32)
+    pub fn price(&self) -> u64 {
+        (self.0 >> 64) as u64
+    }
+
+    /// The order's side
+    pub fn side(&self) -> Side {
+        if ORDER_ID_SIDE_FLAG & self.0 != 0 {
+            Side::Bid
+        } else {
+            Side::Ask
+        }
+    }
+
+    /// The sequence number which the event queue assigned to the order
+    pub fn sequence(&self) -> u64 {
+        match self.side() {
+            Side::Bid => !(self.0 as u64),
+            Side::Ask => self.0 as u64,
+        }
+    }
+}
+
+impl BorshSize for OrderId {
+    fn borsh_len(&self) -> usize {
+        16
+    }
+}
+
+impl From<u128> for OrderId {
+    fn from(order_id: u128) -> Self {
+        Self(order_id)
+    }
+}
+
+impl From<OrderId> for u128 {
+    fn from(order_id: OrderId) -> Self {
+        order_id.0
+    }
+}
+
+impl fmt::Display for OrderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl FromStr for OrderId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        u128::from_str_radix(digits, 16).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watermark_crossings() {
+        let market_state = MarketState {
+            event_queue_high_watermark_bps: 8_000,
+            event_queue_low_watermark_bps: 5_000,
+            ..MarketState::default()
+        };
+        assert_eq!(
+            market_state.check_watermarks(79, 80, 100),
+            Some(WatermarkCrossing::High)
+        );
+        assert_eq!(market_state.check_watermarks(80, 85, 100), None);
+        assert_eq!(
+            market_state.check_watermarks(60, 50, 100),
+            Some(WatermarkCrossing::Low)
+        );
+        assert_eq!(market_state.check_watermarks(50, 0, 100), None);
+        assert_eq!(market_state.check_watermarks(60, 55, 100), None);
+
+        let disabled = MarketState::default();
+        assert_eq!(disabled.check_watermarks(0, 100, 100), None);
+        assert_eq!(disabled.check_watermarks(100, 0, 100), None);
+    }
+
+    fn out_event(order_id: u128) -> Event {
+        Event::Out {
+            side: Side::Ask,
+            order_id: OrderId(order_id),
+            base_size: 1,
+            delete: true,
+            slot: 0,
+            unix_timestamp: 0,
+            callback_info: vec![0; 32],
+        }
+    }
+
+    fn order_ids(event_queue: &EventQueue<impl Storage>) -> Vec<u128> {
+        (0..event_queue.header.count)
+            .map(|i| match event_queue.peek_at(i).unwrap() {
+                Event::Out { order_id, .. } => order_id.0,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn order_id() {
+        let bid = OrderId::new(10 << 32, Side::Bid, 7);
+        assert_eq!(bid.price(), 10 << 32);
+        assert_eq!(bid.side(), Side::Bid);
+        assert_eq!(bid.sequence(), 7);
+
+        let ask = OrderId::new(10 << 32, Side::Ask, 7);
+        assert_eq!(ask, OrderId(((10u128 << 32) << 64) | 7));
+        assert_eq!(
+            (ask.price(), ask.side(), ask.sequence()),
+            (10 << 32, Side::Ask, 7)
+        );
+        assert_eq!(u128::from(ask), ask.0);
+        assert_eq!(OrderId::from(ask.0), ask);
+
+        assert_eq!(ask.to_string(), "0000000a000000000000000000000007");
+        assert_eq!("0000000a000000000000000000000007".parse(), Ok(ask));
+        assert_eq!("0xa000000000000000000000007".parse(), Ok(ask));
+        assert_eq!(bid.to_string().parse(), Ok(bid));
+        assert!("not an order id".parse::<OrderId>().is_err());
+        assert!(format!("1{}", bid).parse::<OrderId>().is_err());
+
+        // Bids sort in reverse time priority, asks in time priority
+        assert!(OrderId::new(1, Side::Bid, 1) < OrderId::new(1, Side::Bid, 0));
+        assert!(OrderId::new(1, Side::Ask, 0) < OrderId::new(1, Side::Ask, 1));
+    }
+
+    #[test]
+    fn candles() {
+        let mut candles = Candles::zeroed();
+        candles.slots_per_candle = 10;
+        candles.record(10, 100, 1, 100, 1);
+        candles.record(15, 120, 2, 240, -2);
+        candles.record(19, 90, 1, 90, 1);
+        candles.record(35, 110, 3, 330, -3);
+        assert_eq!(candles.candles.len(), 2);
+        assert_eq!(
+            candles.candles.recent(1),
+            Some(&Candle {
+                start_slot: 10,
+                open: 100,
+                high: 120,
+                low: 90,
+                close: 90,
+                base_volume: 4,
+                quote_volume: 430,
+                taker_flow: 0,
+            })
+        );
+        assert_eq!(
+            candles.candles.recent(0),
+            Some(&Candle {
+                start_slot: 30,
+                open: 110,
+                high: 110,
+                low: 110,
+                close: 110,
+                base_volume: 3,
+                quote_volume: 330,
+                taker_flow: -3,
+            })
+        );
+    }
+
+    #[test]
+    fn account_space() {
+        assert_eq!(MarketState::SPACE, 8 + size_of::<MarketState>());
+        // The layouts which the JS client reads the accounts of the native program with
+        assert_eq!(MARKET_STATE_LEN, 688);
+        assert_eq!(EVENT_QUEUE_HEADER_LEN, 109);
+        if size_of::<Quantity>() == 8 {
+            assert_eq!(REGISTER_SIZE, 66);
+        }
+        assert_eq!(Bids::space(100, 32), Asks::space(100, 32));
+        assert!(Bids::space(100, 32) > Bids::space(99, 32));
+        assert_eq!(
+            EventQueue::compute_allocation_size(10, 32),
+            EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + 10 * Event::compute_slot_size(32)
+        );
+    }
+
+    #[test]
+    fn market_lock() {
+        let mut market_state = MarketState::default();
+        market_state.lock().unwrap();
+        assert!(market_state.lock().is_err());
+        market_state.unlock();
+        market_state.lock().unwrap();
+    }
+
+    #[test]
+    fn admin_authority() {
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let mut lamports = vec![0; 4];
+        let mut data = vec![vec![]; 4];
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                AccountInfo::new(key, true, false, lamports, data, key, false, 0)
+            })
+            .collect();
+
+        let mut market_state = MarketState {
+            caller_authority: keys[0].to_bytes(),
+            ..MarketState::default()
+        };
+        assert!(market_state
+            .check_admin_authority(&accounts[0], &[])
+            .is_ok());
+        assert!(market_state
+            .check_admin_authority(&accounts[1], &[])
+            .is_err());
+
+        market_state.authority_threshold = 2;
+        market_state.authority_signers[0] = keys[1].to_bytes();
+        market_state.authority_signers[1] = keys[2].to_bytes();
+        assert!(market_state
+            .check_admin_authority(&accounts[0], &[])
+            .is_err());
+        // The same signer doesn't count twice
+        assert!(market_state
+            .check_admin_authority(&accounts[1], &accounts[1..2])
+            .is_err());
+        assert!(market_state
+            .check_admin_authority(&accounts[1], &accounts[2..])
+            .is_ok());
+        assert!(market_state
+            .check_admin_authority(&accounts[3], &accounts[1..3])
+            .is_ok());
+    }
+
+    #[test]
+    fn price_accumulator() {
+        let mut market_state = MarketState {
+            price_cumulative_timestamp: 100,
+            ..MarketState::default()
+        };
+        let start = market_state.observe(100);
+
+        // The price is 10 for 10 seconds, then 40 for 30 seconds
+        market_state.update_price_cumulative(100);
+        market_state.last_trade_price = 10;
+        market_state.update_price_cumulative(110);
+        market_state.last_trade_price = 40;
+        let middle = market_state.observe(110);
+        let end = market_state.observe(140);
+
+        assert_eq!(start.twap(&middle), Some(10));
+        assert_eq!(middle.twap(&end), Some(40));
+        assert_eq!(start.twap(&end), Some(32));
+        assert_eq!(end.twap(&start), None);
+
+        // Observations remain consistent across a wrap around of the accumulator
+        market_state.price_cumulative = u128::MAX - 1_300;
+        let before = market_state.observe(140);
+        let after = market_state.observe(150);
+        assert!(after.price_cumulative < before.price_cumulative);
+        assert_eq!(before.twap(&after), Some(40));
+    }
+
+    #[test]
+    fn crank_reward() {
+        let market_state = MarketState {
+            cranker_base_reward: 1_000,
+            cranker_per_event_reward: 10,
+            ..MarketState::default()
+        };
+        assert_eq!(market_state.crank_reward(0).unwrap(), 1_000);
+        assert_eq!(market_state.crank_reward(5).unwrap(), 1_050);
+
+        let market_state = MarketState {
+            cranker_per_event_reward: u64::MAX,
+            ..market_state
+        };
+        assert_eq!(
+            layout_error(market_state.crank_reward(2).map(|_| ())),
+            u32::from(ErrorCode::NumericalOverflow)
+        );
+    }
+
+    #[test]
+    fn take_crank_reward() {
+        let mut market_state = MarketState {
+            fee_budget: 2_500,
+            cranker_base_reward: 1_000,
+            cranker_per_event_reward: 10,
+            ..MarketState::default()
+        };
+        assert_eq!(market_state.take_crank_reward(0), 0);
+        assert_eq!(market_state.take_crank_reward(5), 1_050);
+        assert_eq!(market_state.fee_budget, 1_450);
+        assert_eq!(market_state.take_crank_reward(100), 1_450);
+        assert_eq!(market_state.fee_budget, 0);
+
+        // Rewards which overflow 64 bits are capped by the fee budget
+        market_state.fee_budget = 2_500;
+        market_state.cranker_per_event_reward = u64::MAX;
+        assert_eq!(market_state.take_crank_reward(u64::MAX), 2_500);
+    }
+
+    #[test]
+    fn fee_budget_verification() {
+        let mut market_state = MarketState {
+            initial_lamports: 10_000,
+            fee_budget: 500,
+            cranker_base_reward: 1_000,
+            cranker_per_event_reward: 10,
+            ..MarketState::default()
+        };
+        assert_eq!(
+            layout_error(market_state.verify_and_update_fee_budget(11_519, 2)),
+            u32::from(ErrorCode::FeeNotPayed)
+        );
+        assert_eq!(
+            layout_error(market_state.verify_and_update_fee_budget(5_000, 0)),
+            u32::from(ErrorCode::FeeNotPayed)
+        );
+        assert!(market_state.verify_and_update_fee_budget(11_600, 2).is_ok());
+        assert_eq!(market_state.fee_budget, 1_600);
+
+        market_state.cranker_per_event_reward = u64::MAX;
+        assert_eq!(
+            layout_error(market_state.verify_and_update_fee_budget(u64::MAX, 2)),
+            u32::from(ErrorCode::NumericalOverflow)
+        );
+    }
+
+    #[test]
+    fn market_parameters() {
+        assert!(MarketState::check_parameters(33, 32, 1, 1).is_ok());
+        assert!(MarketState::check_parameters(MAX_CALLBACK_INFO_LEN, 0, 1, 1).is_ok());
+        assert_eq!(
+            layout_error(MarketState::check_parameters(33, 32, 1, 0)),
+            u32::from(ErrorCode::InvalidTickSize)
+        );
+        assert_eq!(
+            layout_error(MarketState::check_parameters(33, 32, 0, 1)),
+            u32::from(ErrorCode::InvalidMinBaseOrderSize)
+        );
+        assert_eq!(
+            layout_error(MarketState::check_parameters(32, 33, 1, 1)),
+            u32::from(ErrorCode::InvalidCallbackIdLen)
+        );
+        assert_eq!(
+            layout_error(MarketState::check_parameters(
+                MAX_CALLBACK_INFO_LEN + 1,
+                32,
+                1,
+                1
+            )),
+            u32::from(ErrorCode::CallbackInfoTooLong)
+        );
+    }
+
+    #[test]
+    fn trading_sessions() {
+        let mut market_state = MarketState::default();
+        assert_eq!(
+            market_state.get_trading_session().unwrap(),
+            TradingSession::Open
+        );
+        for (from, to, allowed) in [
+            (TradingSession::Open, TradingSession::Closed, true),
+            (TradingSession::Open, TradingSession::PreOpen, false),
+            (TradingSession::Open, TradingSession::Open, false),
+            (TradingSession::Closed, TradingSession::PreOpen, true),
+            (TradingSession::Closed, TradingSession::Open, true),
+            (TradingSession::Closed, TradingSession::Closed, false),
+            (TradingSession::PreOpen, TradingSession::Open, true),
+            (TradingSession::PreOpen, TradingSession::Closed, true),
+            (TradingSession::PreOpen, TradingSession::PreOpen, false),
+        ] {
+            market_state.trading_session = from as u8;
+            let result = market_state.set_trading_session(to);
+            if allowed {
+                assert!(result.is_ok());
+                assert_eq!(market_state.get_trading_session().unwrap(), to);
+            } else {
+                assert_eq!(
+                    layout_error(result),
+                    u32::from(ErrorCode::InvalidSessionTransition)
+                );
+                assert_eq!(market_state.get_trading_session().unwrap(), from);
+            }
+        }
+    }
+
+    #[test]
+    fn order_sequence_exhaustion() {
+        let mut event_queue = EventQueue::new_in_memory(2, 32);
+        event_queue.header.seq_num = MAX_ORDER_SEQUENCE;
+        let order_id = event_queue.gen_order_id(1 << 32, Side::Bid).unwrap();
+        assert_eq!(order_id.side(), Side::Bid);
+        assert_eq!(order_id.sequence(), MAX_ORDER_SEQUENCE);
+        assert_eq!(
+            event_queue.gen_order_id(1 << 32, Side::Ask).unwrap_err(),
+            ErrorCode::SequenceNumbersExhausted
+        );
+        assert_eq!(event_queue.header.seq_num(), MAX_ORDER_SEQUENCE + 1);
+    }
+
+    #[test]
+    fn accounts_bound_to_market() {
+        use crate::critbit::SlabExt;
+
+        let market = Pubkey::new_unique();
+        let other_market = Pubkey::new_unique();
+        let header = EventQueueHeader::initialize(market, 32, 0);
+        assert_eq!(header.market_address(), market);
+        assert!(header.check_market(&market).is_ok());
+        assert_eq!(
+            layout_error(header.check_market(&other_market)),
+            u32::from(ErrorCode::WrongEventQueueAccount)
+        );
+
+        for (account_tag, error) in [
+            (AccountTag::Bids, ErrorCode::WrongBidsAccount),
+            (AccountTag::Asks, ErrorCode::WrongAsksAccount),
+        ] {
+            let mut data = vec![0; Slab::compute_allocation_size(4, 32)];
+            Slab::initialize_buffer(&mut data, account_tag as u8, market.to_bytes(), 32);
+            let slab = Slab::new(&mut data[..], 32).unwrap();
+            assert!(slab.check_market(&market).is_ok());
+            assert_eq!(
+                layout_error(slab.check_market(&other_market)),
+                u32::from(error)
+            );
+        }
+    }
+
+    #[test]
+    fn account_sizes() {
+        use crate::critbit::{LeafNode, Node, SlabExt};
+        use crate::utils::check_rent_exempt;
+
+        let key = Pubkey::new_unique();
+        let rent = Rent::default();
+        let book_len = Slab::compute_allocation_size(4, 32);
+        for (data_len, expected) in [
+            (book_len, None),
+            (book_len - 1, Some(ErrorCode::InvalidOrderbookSize)),
+            // A trailing byte which no order can use
+            (book_len + 1, Some(ErrorCode::InvalidOrderbookSize)),
+            (0, Some(ErrorCode::InvalidOrderbookSize)),
+        ] {
+            let mut lamports = rent.minimum_balance(data_len);
+            let mut data = vec![0; data_len];
+            let account =
+                AccountInfo::new(&key, false, true, &mut lamports, &mut data, &key, false, 0);
+            let result = Slab::check_capacity(&account, AccountTag::Asks, 4, 32);
+            match expected {
+                None => assert!(result.is_ok()),
+                Some(error) => assert_eq!(layout_error(result), u32::from(error)),
+            }
+            assert!(check_rent_exempt(&account, &rent, "asks").is_ok());
+        }
+
+        // The accepted size holds the declared number of orders
+        let mut data = vec![0; book_len];
+        Slab::initialize_buffer(&mut data, AccountTag::Asks as u8, key.to_bytes(), 32);
+        let mut slab = Slab::new(&mut data[..], 32).unwrap();
+        for i in 0..4 {
+            let callback_info_pt = slab.write_callback_info(&[1; 32]).unwrap();
+            slab.insert_leaf(&Node::Leaf(LeafNode {
+                key: (10 << 64) | i,
+                callback_info_pt,
+                base_quantity: 1,
+            }))
+            .unwrap();
+        }
+        assert!(slab.write_callback_info(&[1; 32]).is_err());
+
+        let mut lamports = rent.minimum_balance(book_len) - 1;
+        let mut data = vec![0; book_len];
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &key, false, 0);
+        assert_eq!(
+            layout_error(check_rent_exempt(&account, &rent, "bids")),
+            u32::from(ErrorCode::AccountNotRentExempt)
+        );
+    }
+
+    #[test]
+    fn fee_vault() {
+        let mut fee_vault = FeeVault::zeroed();
+        assert!(fee_vault.take_fee(0).is_ok());
+        assert!(fee_vault.take_fee(1).is_err());
+
+        fee_vault.deposit(1_500);
+        fee_vault.take_fee(1_000).unwrap();
+        assert_eq!(fee_vault.pending_fees, 500);
+        assert!(fee_vault.take_fee(501).is_err());
+        assert_eq!(fee_vault.pending_fees, 500);
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let event_size = Event::compute_slot_size(32);
+        let mut data = vec![0u8; EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + 2 * event_size];
+        let header = EventQueueHeader::initialize(Pubkey::default(), 32, data.len());
+        let buffer = Rc::new(RefCell::new(&mut data[..]));
+        let mut event_queue = EventQueue::new(header, buffer, 32);
+        event_queue.push_back(out_event(0)).unwrap();
+        event_queue.push_back(out_event(1)).unwrap();
+
+        assert!(event_queue.push_back(out_event(2)).is_err());
+        assert_eq!(order_ids(&event_queue), vec![0, 1]);
+
+        event_queue.overflow_policy = EventQueueOverflowPolicy::EvictOldest;
+        event_queue.push_back(out_event(2)).unwrap();
+        event_queue.push_back(out_event(3)).unwrap();
+        assert_eq!(order_ids(&event_queue), vec![2, 3]);
+    }
+
+    #[test]
+    fn fill_statistics() {
+        let event_size = Event::compute_slot_size(32);
+        let mut data = vec![0u8; EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + 4 * event_size];
+        let header = EventQueueHeader::initialize(Pubkey::default(), 32, data.len());
+        let buffer = Rc::new(RefCell::new(&mut data[..]));
+        let mut event_queue = EventQueue::new(header, buffer, 32);
+        for base_size in [10, 20] {
+            event_queue
+                .push_back(Event::Fill {
+                    taker_side: Side::Bid,
+                    maker_order_id: OrderId(0),
+                    quote_size: 3 * base_size,
+                    base_size,
+                    slot: 0,
+                    unix_timestamp: 0,
+                    maker_callback_info: vec![0; 32],
+                    taker_callback_info: vec![0; 32],
+                })
+                .unwrap();
+        }
+        event_queue.push_back(out_event(0)).unwrap();
+        event_queue.pop_n(3);
+
+        assert_eq!({ event_queue.header.base_volume }, 30);
+        assert_eq!({ event_queue.header.quote_volume }, 90);
+        assert_eq!({ event_queue.header.fill_count }, 2);
+    }
+
+    #[test]
+    fn push_fill_in_place() {
+        let event_size = Event::compute_slot_size(32);
+        let mut data = vec![0u8; EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + 2 * event_size];
+        let header = EventQueueHeader::initialize(Pubkey::default(), 32, data.len());
+        let buffer = Rc::new(RefCell::new(&mut data[..]));
+        let mut event_queue = EventQueue::new(header, buffer, 32);
+        event_queue.slot = 5;
+        event_queue.unix_timestamp = 6;
+        event_queue
+            .push_back(Event::Fill {
+                taker_side: Side::Ask,
+                maker_order_id: OrderId(7),
+                quote_size: 30,
+                base_size: 10,
+                slot: 5,
+                unix_timestamp: 6,
+                maker_callback_info: vec![1; 32],
+                taker_callback_info: vec![2; 32],
+            })
+            .unwrap();
+        event_queue
+            .push_fill(Side::Ask, OrderId(7), 30, 10, &[1; 32], &[2; 32])
+            .unwrap();
+        assert!(matches!(
+            event_queue.push_out(Side::Bid, OrderId(8), 0, true, &[3; 32]),
+            Err(ErrorCode::EventQueueFull)
+        ));
+        drop(event_queue);
+
+        let slots = &data[EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE..];
+        assert_eq!(slots[..event_size], slots[event_size..]);
+    }
+
+    #[test]
+    fn coalesce_fill() {
+        let mut event_queue = EventQueue::new_in_memory(4, 32);
+        event_queue
+            .push_fill(Side::Ask, OrderId(7), 30, 10, &[1; 32], &[2; 32])
+            .unwrap();
+        event_queue
+            .push_out(Side::Bid, OrderId(7), 5, false, &[1; 32])
+            .unwrap();
+
+        // The fill is found behind the out, while the out itself and distances past the front of the queue are
+        // left alone
+        assert!(event_queue.coalesce_fill(1, 15, 5));
+        assert!(!event_queue.coalesce_fill(0, 15, 5));
+        assert!(!event_queue.coalesce_fill(2, 15, 5));
+        match event_queue.peek_at(0) {
+            Some(Event::Fill {
+                maker_order_id,
+                quote_size,
+                base_size,
+                ..
+            }) => assert_eq!(
+                (maker_order_id, quote_size, base_size),
+                (OrderId(7), 45, 15)
+            ),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!({ event_queue.header.base_volume }, 15);
+        assert_eq!({ event_queue.header.quote_volume }, 45);
+        assert_eq!({ event_queue.header.fill_count }, 1);
+    }
+
+    #[test]
+    fn header_written_when_dirty() {
+        let mut event_queue = EventQueue::new_in_memory(2, 32);
+        let header = EventQueueHeader {
+            seq_num: 7,
+            ..event_queue.header
+        };
+        event_queue.header = header;
+        event_queue.pop_n(1);
+        event_queue.write_header();
+        let written = EventQueueHeader::load(&event_queue.buffer.borrow()).unwrap();
+        assert_eq!({ written.seq_num }, 0);
+
+        event_queue.push_back(out_event(1)).unwrap();
+        event_queue.write_header();
+        let written = EventQueueHeader::load(&event_queue.buffer.borrow()).unwrap();
+        assert_eq!({ written.seq_num }, 8);
+        assert_eq!({ written.count }, 1);
+    }
+
+    fn layout_error(result: Result<()>) -> u32 {
+        match result {
+            Err(Error::AnchorError(e)) => e.error_code_number,
+            _ => panic!("Expected an orderbook error"),
+        }
+    }
+
+    #[test]
+    fn market_addresses() {
+        let program_id = Pubkey::new_unique();
+        let caller_authority = Pubkey::new_unique();
+        let addresses = MarketAddresses::find(&program_id, &caller_authority, &[1; 32]);
+        assert_eq!(
+            addresses,
+            MarketAddresses::find(&program_id, &caller_authority, &[1; 32])
+        );
+        assert_ne!(
+            addresses.market,
+            MarketAddresses::find(&program_id, &caller_authority, &[2; 32]).market
+        );
+        assert_ne!(
+            addresses.market,
+            MarketAddresses::find(&program_id, &Pubkey::new_unique(), &[1; 32]).market
+        );
+        let (bids, _) =
+            Pubkey::find_program_address(&[Bids::SEED, addresses.market.as_ref()], &program_id);
+        assert_eq!(addresses.bids, bids);
+        assert_ne!(addresses.bids, addresses.asks);
+        assert_ne!(addresses.event_queue, addresses.asks);
+    }
+
+    #[test]
+    fn price_rounding() {
+        let mut market_state = MarketState {
+            tick_size: 10,
+            ..MarketState::default()
+        };
+        let round = |market_state: &MarketState, price, side| {
+            market_state.round_price(price, side).map_err(|e| match e {
+                Error::AnchorError(e) => e.error_code_number,
+                _ => panic!("Expected an orderbook error"),
+            })
+        };
+        assert_eq!(round(&market_state, 24, Side::Bid), Ok(20));
+        assert_eq!(round(&market_state, 24, Side::Ask), Ok(30));
+        assert_eq!(round(&market_state, 30, Side::Ask), Ok(30));
+
+        market_state.price_rounding_policy = PriceRoundingPolicy::Strict as u64;
+        assert_eq!(round(&market_state, 30, Side::Bid), Ok(30));
+        assert_eq!(
+            round(&market_state, 24, Side::Bid),
+            Err(u32::from(ErrorCode::OffTickPrice))
+        );
+
+        market_state.price_rounding_policy = PriceRoundingPolicy::Nearest as u64;
+        assert_eq!(round(&market_state, 24, Side::Ask), Ok(20));
+        assert_eq!(round(&market_state, 25, Side::Bid), Ok(30));
+        assert_eq!(round(&market_state, 26, Side::Bid), Ok(30));
+
+        market_state.price_rounding_policy = 3;
+        assert_eq!(
+            round(&market_state, 24, Side::Bid),
+            Err(u32::from(ErrorCode::FailedToDeserialize))
+        );
+    }
+
+    #[test]
+    fn account_tags() {
+        assert!(AccountTag::Market.check(AccountTag::Market as u64).is_ok());
+        assert_eq!(
+            layout_error(AccountTag::Market.check(AccountTag::Bids as u64)),
+            u32::from(ErrorCode::WrongMarketTag)
+        );
+        assert_eq!(
+            layout_error(AccountTag::Asks.check(AccountTag::Bids as u64)),
+            u32::from(ErrorCode::WrongAsksTag)
+        );
+
+        let mut header = EventQueueHeader::initialize(Pubkey::default(), 32, 0);
+        assert!(header.check().is_ok());
+        header.tag = AccountTag::Market as u8;
+        assert_eq!(
+            layout_error(header.check().map(|_| ())),
+            u32::from(ErrorCode::WrongEventQueueTag)
+        );
+    }
+
+    #[test]
+    fn batch_serialization() {
+        let event_size = Event::compute_slot_size(32);
+        let events: Vec<Event> = (0..3).map(out_event).collect();
+        let mut buffer = vec![0u8; 3 * event_size];
+        Event::serialize_batch(&events, &mut buffer, 32).unwrap();
+        let deserialized: Vec<u128> = Event::deserialize_batch(&buffer, 32)
+            .into_iter()
+            .map(|event| match event {
+                Event::Out { order_id, .. } => order_id.0,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(deserialized, vec![0, 1, 2]);
+        assert!(Event::serialize_batch(&events, &mut buffer[1..], 32).is_err());
+
+        let mut data = vec![0u8; EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + 3 * event_size];
+        let header = EventQueueHeader::initialize(Pubkey::default(), 32, data.len());
+        let buffer = Rc::new(RefCell::new(&mut data[..]));
+        let mut event_queue = EventQueue::new(header, buffer, 32);
+        for order_id in 0..3 {
+            event_queue.push_back(out_event(order_id)).unwrap();
+        }
+        event_queue.pop_n(2);
+        event_queue.push_back(out_event(3)).unwrap();
+        let peeked: Vec<u128> = event_queue
+            .peek_n(u64::MAX)
+            .into_iter()
+            .map(|event| match event {
+                Event::Out { order_id, .. } => order_id.0,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(peeked, order_ids(&event_queue));
+        assert_eq!(peeked, vec![2, 3]);
+    }
+
+    #[test]
+    fn event_head_cast() {
+        assert_eq!(EVENT_HEAD_LEN, 35 + 2 * size_of::<Quantity>());
+        let event = Event::Fill {
+            taker_side: Side::Bid,
+            maker_order_id: OrderId(7),
+            quote_size: 30,
+            base_size: 3,
+            slot: 11,
+            unix_timestamp: 13,
+            maker_callback_info: vec![1; 32],
+            taker_callback_info: vec![2; 32],
+        };
+        let mut slot = vec![0u8; Event::compute_slot_size(32)];
+        event.serialize(&mut &mut slot[..]).unwrap();
+        let head = EventHead::from_slot(&slot);
+        assert_eq!(
+            (head.tag, head.side, { head.order_id }, { head.quote_size }),
+            (EventHead::FILL, Side::Bid as u8, 7, 30)
+        );
+        assert_eq!(slot[EVENT_HEAD_LEN..EVENT_HEAD_LEN + 32], [1; 32]);
+        assert_eq!(slot[EVENT_HEAD_LEN + 32..], [2; 32]);
+        // The offsets at which the JS readers decode the head of 64-bit quantity events
+        if size_of::<Quantity>() == 8 {
+            assert_eq!(slot[3..19], 7u128.to_le_bytes());
+            assert_eq!(slot[19..27], 30u64.to_le_bytes());
+            assert_eq!(slot[27..35], 3u64.to_le_bytes());
+            assert_eq!(slot[35..43], 11u64.to_le_bytes());
+            assert_eq!(slot[43..51], 13i64.to_le_bytes());
+        }
+        match Event::deserialize(&mut &slot[..], 32) {
+            Event::Fill {
+                base_size,
+                unix_timestamp,
+                taker_callback_info,
+                ..
+            } => assert_eq!(
+                (base_size, unix_timestamp, taker_callback_info),
+                (3, 13, vec![2; 32])
+            ),
+            _ => panic!("Expected a fill"),
+        }
+    }
+
+    #[test]
+    fn event_queue_layout() {
+        assert_eq!(EVENT_QUEUE_HEADER_LEN, 109);
+        let event_size = Event::compute_slot_size(32);
+        let data_len = EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + 4 * event_size;
+        let header = EventQueueHeader::initialize(Pubkey::default(), 32, data_len);
+        assert!(header.check_layout(data_len, 32).is_ok());
+
+        assert_eq!(
+            layout_error(header.check_layout(data_len, 33)),
+            u32::from(ErrorCode::EventSizeMismatch)
+        );
+        // Accounts which don't hold the header's capacity are rejected
+        for data_len in [
+            0,
+            data_len - event_size / 2,
+            data_len - 4 * event_size,
+            data_len + event_size,
+        ] {
+            assert_eq!(
+                layout_error(header.check_layout(data_len, 32)),
+                u32::from(ErrorCode::InvalidEventQueueSize)
+            );
+        }
+
+        let wrapped = EventQueueHeader {
+            head: 3 * event_size as u64,
+            count: 4,
+            ..EventQueueHeader::initialize(Pubkey::default(), 32, data_len)
+        };
+        assert!(wrapped.check_layout(data_len, 32).is_ok());
+        for header in [
+            EventQueueHeader {
+                head: 4 * event_size as u64,
+                ..EventQueueHeader::initialize(Pubkey::default(), 32, data_len)
+            },
+            EventQueueHeader {
+                head: 1,
+                ..EventQueueHeader::initialize(Pubkey::default(), 32, data_len)
+            },
+            EventQueueHeader {
+                count: 5,
+                ..EventQueueHeader::initialize(Pubkey::default(), 32, data_len)
+            },
+        ] {
+            assert_eq!(
+                layout_error(header.check_layout(data_len, 32)),
+                u32::from(ErrorCode::InvalidEventQueueHeader)
+            );
+        }
+    }
+
+    #[test]
+    fn relocate_wrapped_events_after_growth() {
+        let event_size = Event::compute_slot_size(32);
+        let old_buf_len = 3 * event_size;
+        let mut data = vec![0u8; EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + old_buf_len];
+        let mut header = {
+            let header = EventQueueHeader::initialize(Pubkey::default(), 32, data.len());
+            let buffer = Rc::new(RefCell::new(&mut data[..]));
+            let mut event_queue = EventQueue::new(header, buffer, 32);
+            for order_id in 0..3 {
+                event_queue.push_back(out_event(order_id)).unwrap();
+            }
+            event_queue.pop_n(2);
+            // These events wrap around the end of the buffer
+            event_queue.push_back(out_event(3)).unwrap();
+            event_queue.push_back(out_event(4)).unwrap();
+            assert_eq!(order_ids(&event_queue), vec![2, 3, 4]);
+            event_queue.header
+        };
+
+        // Simulate a reallocation of the account
+        data.resize(data.len() + 2 * event_size, 0);
+        header.resize(data.len());
+        assert_eq!(header.capacity(), 5);
+        let buffer = Rc::new(RefCell::new(&mut data[..]));
+        let mut event_queue = EventQueue::new(header, buffer, 32);
+        event_queue.relocate_after_growth(old_buf_len);
+        assert_eq!(order_ids(&event_queue), vec![2, 3, 4]);
+
+        event_queue.push_back(out_event(5)).unwrap();
+        event_queue.push_back(out_event(6)).unwrap();
+        assert!(event_queue.full());
+        assert_eq!(order_ids(&event_queue), vec![2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn persist_capacity_after_growth() {
+        let event_size = Event::compute_slot_size(32);
+        let old_buf_len = 3 * event_size;
+        let mut data = vec![0u8; EVENT_QUEUE_HEADER_LEN + REGISTER_SIZE + old_buf_len];
+        let mut header = EventQueueHeader::initialize(Pubkey::default(), 32, data.len());
+        header.write(&mut data);
+        {
+            let buffer = Rc::new(RefCell::new(&mut data[..]));
+            let mut event_queue = EventQueue::new(header, buffer, 32);
+            event_queue.push_back(out_event(1)).unwrap();
+            event_queue.write_header();
+            header = event_queue.header;
+        }
+
+        // The events don't wrap, so that none of them is moved
+        data.resize(data.len() + 2 * event_size, 0);
+        header.resize(data.len());
+        {
+            let buffer = Rc::new(RefCell::new(&mut data[..]));
+            let mut event_queue = EventQueue::new(header, buffer, 32);
+            event_queue.relocate_after_growth(old_buf_len);
+            event_queue.write_header();
+        }
+
+        let header = EventQueueHeader::load(&data).unwrap();
+        assert_eq!(header.capacity(), 5);
+        header.check_layout(data.len(), 32).unwrap();
+        let buffer = Rc::new(RefCell::new(&mut data[..]));
+        let event_queue = EventQueue::new(header, buffer, 32);
+        assert_eq!(order_ids(&event_queue), vec![1]);
+    }
+
+    #[test]
+    fn in_memory_event_queue() {
+        let mut event_queue = EventQueue::new_in_memory(2, 32);
+        assert_eq!(event_queue.capacity(), 2);
+        event_queue.push_back(out_event(1)).unwrap();
+        event_queue.push_back(out_event(2)).unwrap();
+        assert!(event_queue.push_back(out_event(3)).is_err());
+        event_queue.pop_n(1);
+        assert_eq!(order_ids(&event_queue), vec![2]);
+
+        let header = EventQueueHeader::load(&event_queue.buffer.borrow()).unwrap();
+        assert_eq!({ header.event_size }, Event::compute_slot_size(32) as u64);
+    }
+
+    #[test]
+    fn event_queue_from_bytes() {
+        let mut event_queue = EventQueue::new_in_memory(4, 32);
+        for order_id in 1..4 {
+            event_queue.push_back(out_event(order_id)).unwrap();
+        }
+        event_queue.pop_n(1);
+        event_queue.write_header();
+        let data = event_queue.buffer.borrow().clone();
+
+        let loaded = EventQueue::from_bytes(&data, 32).unwrap();
+        assert_eq!(order_ids(&loaded), vec![2, 3]);
+        assert!(EventQueue::from_bytes(&data, 16).is_err());
+        assert!(EventQueue::from_bytes(&data[..EVENT_QUEUE_HEADER_LEN - 1], 32).is_err());
+    }
+}
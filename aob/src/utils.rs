@@ -0,0 +1,134 @@
+use anchor_lang::prelude::*;
+pub use aob_core::utils::{
+    base_qty_to_lots, fp32_div, fp32_div_ceil, fp32_div_floor, fp32_mul, fp32_mul_ceil,
+    fp32_mul_floor, lots_to_base_qty, low_u64, round_to_lot, saturating_u64, Quantity,
+};
+
+use crate::log_error;
+use crate::{
+    error::ErrorCode,
+    orderbook::MAX_PRICE_FP32,
+    state::{PriceRoundingPolicy, Side},
+};
+#[cfg(feature = "no-entrypoint")]
+use crate::{orderbook::OrderBookState, state::MarketState};
+
+#[allow(dead_code)]
+#[cfg(not(debug_assertions))]
+#[inline(always)]
+unsafe fn invariant(check: bool) {
+    if check {
+        std::hint::unreachable_unchecked();
+    }
+}
+
+// Safety verification functions
+pub fn check_account_key(account: &AccountInfo, key: &[u8], error: ErrorCode) -> Result<()> {
+    if account.key.to_bytes() != key {
+        return Err(error!(error));
+    }
+    Ok(())
+}
+
+pub fn check_account_owner(account: &AccountInfo, owner: &[u8], error: ErrorCode) -> Result<()> {
+    if account.owner.to_bytes() != owner {
+        return Err(error!(error));
+    }
+    Ok(())
+}
+
+pub fn check_signer(account: &AccountInfo) -> Result<()> {
+    if !(account.is_signer) {
+        return Err(Error::from(ProgramError::MissingRequiredSignature).with_source(source!()));
+    }
+    Ok(())
+}
+
+/// Checks that a market account holds enough lamports to be rent exempt, `name` naming it in the logs.
+pub fn check_rent_exempt(account: &AccountInfo, rent: &Rent, name: &str) -> Result<()> {
+    if !rent.is_exempt(account.lamports(), account.data_len()) {
+        log_error!("The {} account isn't rent exempt", name);
+        return err!(ErrorCode::AccountNotRentExempt);
+    }
+    Ok(())
+}
+
+pub fn check_unitialized(account: &AccountInfo) -> Result<()> {
+    if account.data.borrow()[0] != 0 {
+        return err!(ErrorCode::AlreadyInitialized);
+    }
+    Ok(())
+}
+
+/// Returns the number of compute units left in the current transaction.
+///
+/// Off-chain there is no compute meter, so the budget is reported as unlimited.
+pub fn remaining_compute_units() -> u64 {
+    #[cfg(target_arch = "bpf")]
+    {
+        extern "C" {
+            fn sol_remaining_compute_units() -> u64;
+        }
+        unsafe { sol_remaining_compute_units() }
+    }
+    #[cfg(not(target_arch = "bpf"))]
+    {
+        u64::MAX
+    }
+}
+
+#[cfg(feature = "no-entrypoint")]
+/// This util is used to return the orderbook's spread (best_bid_price, best_ask_price) with both values in FP32 format
+pub fn get_spread<'a>(
+    market_state_account: &AccountInfo<'a>,
+    bids_account: &AccountInfo<'a>,
+    asks_account: &AccountInfo<'a>,
+) -> (Option<u64>, Option<u64>) {
+    let market_state = MarketState::get(market_state_account).unwrap();
+    let orderbook = OrderBookState::new(
+        bids_account,
+        asks_account,
+        market_state.callback_info_len as usize,
+        market_state.callback_id_len as usize,
+    )
+    .unwrap();
+    orderbook.get_spread()
+}
+
+/// Rounds a given price the nearest tick size according to the rules of the AOB
+pub fn round_price(tick_size: u64, limit_price: u64, side: Side) -> u64 {
+    match side {
+        // Round down
+        Side::Bid => tick_size * (limit_price / tick_size),
+        // Round up
+        Side::Ask => tick_size * ((limit_price + tick_size - 1) / tick_size),
+    }
+}
+
+/// Fits a given price to the tick size according to a [`PriceRoundingPolicy`]
+pub fn round_price_with_policy(
+    tick_size: u64,
+    limit_price: u64,
+    side: Side,
+    policy: PriceRoundingPolicy,
+) -> Result<u64> {
+    if limit_price > MAX_PRICE_FP32 {
+        log_error!("The limit price must be at most {}", MAX_PRICE_FP32);
+        return err!(ErrorCode::InvalidLimitPrice);
+    }
+    match policy {
+        PriceRoundingPolicy::Passive => Ok(round_price(tick_size, limit_price, side)),
+        PriceRoundingPolicy::Strict => {
+            if limit_price % tick_size != 0 {
+                log_error!(
+                    "The limit price {} is not a multiple of the tick size {}",
+                    limit_price,
+                    tick_size
+                );
+                return err!(ErrorCode::OffTickPrice);
+            }
+            Ok(limit_price)
+        }
+        PriceRoundingPolicy::Nearest => Ok(tick_size * ((limit_price + tick_size / 2) / tick_size)),
+    }
+}
@@ -16,6 +16,9 @@ export enum AccountTag {
   Asks = 4,
 }
 
+/** The number of co-signers a market can require on its administrative instructions */
+export const MAX_AUTHORITY_SIGNERS = 5;
+
 /** @enum {number} */
 export enum SelfTradeBehavior {
   DecrementTake = 0,
@@ -24,7 +27,7 @@ export enum SelfTradeBehavior {
 }
 
 /**
- * MarketState object
+ * MarketState object, which mirrors the `MarketState` struct of the `aob` crate
  */
 export class MarketState {
   tag: BN;
@@ -38,9 +41,41 @@ export class MarketState {
   initialLamports: BN;
   minOrderSize: BN;
   tickSize: BN;
-  crankerReward: BN;
+  baseLotSize: BN;
+  crankerBaseReward: BN;
+  crankerPerEventReward: BN;
+  eventQueueHighWatermarkBps: BN;
+  eventQueueLowWatermarkBps: BN;
+  orderPriority: BN;
+  eventQueueOverflowPolicy: BN;
+  locked: BN;
+  maxPostOnlySpreadTicks: BN;
+  lastTradePrice: BN;
+  baseVolume: BN;
+  quoteVolume: BN;
+  netTakerFlow: BN;
+  resumptionTicket: PublicKey;
+  tradeHistory: PublicKey;
+  candles: PublicKey;
+  feeVault: PublicKey;
+  authorityThreshold: BN;
+  authoritySigners: PublicKey[];
+  priceCumulative: BN;
+  priceCumulativeTimestamp: BN;
+  selfTradeDetectionDisabled: BN;
+  priceRoundingPolicy: BN;
+  maxMatchIterations: BN;
+  coalesceFills: BN;
+  auctionPrice: BN;
+  seed: Uint8Array;
+  marketBump: number;
+  eventQueueBump: number;
+  bidsBump: number;
+  asksBump: number;
+  permissionlessCrank: number;
+  tradingSession: number;
 
-  static LEN: number = 192;
+  static LEN: number = 688;
 
   static schema: Schema = new Map([
     [
@@ -59,7 +94,40 @@ export class MarketState {
           ["initialLamports", "u64"],
           ["minOrderSize", "u64"],
           ["tickSize", "u64"],
-          ["crankerReward", "u64"],
+          ["baseLotSize", "u64"],
+          ["crankerBaseReward", "u64"],
+          ["crankerPerEventReward", "u64"],
+          ["eventQueueHighWatermarkBps", "u64"],
+          ["eventQueueLowWatermarkBps", "u64"],
+          ["orderPriority", "u64"],
+          ["eventQueueOverflowPolicy", "u64"],
+          ["locked", "u64"],
+          ["maxPostOnlySpreadTicks", "u64"],
+          ["lastTradePrice", "u64"],
+          ["baseVolume", "u64"],
+          ["quoteVolume", "u64"],
+          ["netTakerFlow", "u64"],
+          ["resumptionTicket", [32]],
+          ["tradeHistory", [32]],
+          ["candles", [32]],
+          ["feeVault", [32]],
+          ["authorityThreshold", "u64"],
+          ["authoritySigners", [160]],
+          ["priceCumulative", "u128"],
+          ["priceCumulativeTimestamp", "u64"],
+          ["selfTradeDetectionDisabled", "u64"],
+          ["priceRoundingPolicy", "u64"],
+          ["maxMatchIterations", "u64"],
+          ["coalesceFills", "u64"],
+          ["auctionPrice", "u64"],
+          ["seed", [32]],
+          ["marketBump", "u8"],
+          ["eventQueueBump", "u8"],
+          ["bidsBump", "u8"],
+          ["asksBump", "u8"],
+          ["permissionlessCrank", "u8"],
+          ["tradingSession", "u8"],
+          ["padding", [2]],
         ],
       },
     ],
@@ -71,26 +139,92 @@ export class MarketState {
     eventQueue: Uint8Array;
     bids: Uint8Array;
     asks: Uint8Array;
-    callBackInfoLen: BN;
     callBackIdLen: BN;
+    callBackInfoLen: BN;
     feeBudget: BN;
     initialLamports: BN;
     minOrderSize: BN;
     tickSize: BN;
-    crankerReward: BN;
+    baseLotSize: BN;
+    crankerBaseReward: BN;
+    crankerPerEventReward: BN;
+    eventQueueHighWatermarkBps: BN;
+    eventQueueLowWatermarkBps: BN;
+    orderPriority: BN;
+    eventQueueOverflowPolicy: BN;
+    locked: BN;
+    maxPostOnlySpreadTicks: BN;
+    lastTradePrice: BN;
+    baseVolume: BN;
+    quoteVolume: BN;
+    netTakerFlow: BN;
+    resumptionTicket: Uint8Array;
+    tradeHistory: Uint8Array;
+    candles: Uint8Array;
+    feeVault: Uint8Array;
+    authorityThreshold: BN;
+    authoritySigners: Uint8Array;
+    priceCumulative: BN;
+    priceCumulativeTimestamp: BN;
+    selfTradeDetectionDisabled: BN;
+    priceRoundingPolicy: BN;
+    maxMatchIterations: BN;
+    coalesceFills: BN;
+    auctionPrice: BN;
+    seed: Uint8Array;
+    marketBump: number;
+    eventQueueBump: number;
+    bidsBump: number;
+    asksBump: number;
+    permissionlessCrank: number;
+    tradingSession: number;
   }) {
     this.tag = new BN(arg.tag);
     this.callerAuthority = new PublicKey(arg.callerAuthority);
     this.eventQueue = new PublicKey(arg.eventQueue);
     this.bids = new PublicKey(arg.bids);
     this.asks = new PublicKey(arg.asks);
-    this.callBackInfoLen = arg.callBackInfoLen;
     this.callBackIdLen = arg.callBackIdLen;
+    this.callBackInfoLen = arg.callBackInfoLen;
     this.feeBudget = arg.feeBudget;
     this.initialLamports = arg.initialLamports;
     this.minOrderSize = arg.minOrderSize;
     this.tickSize = arg.tickSize;
-    this.crankerReward = arg.crankerReward;
+    this.baseLotSize = arg.baseLotSize;
+    this.crankerBaseReward = arg.crankerBaseReward;
+    this.crankerPerEventReward = arg.crankerPerEventReward;
+    this.eventQueueHighWatermarkBps = arg.eventQueueHighWatermarkBps;
+    this.eventQueueLowWatermarkBps = arg.eventQueueLowWatermarkBps;
+    this.orderPriority = arg.orderPriority;
+    this.eventQueueOverflowPolicy = arg.eventQueueOverflowPolicy;
+    this.locked = arg.locked;
+    this.maxPostOnlySpreadTicks = arg.maxPostOnlySpreadTicks;
+    this.lastTradePrice = arg.lastTradePrice;
+    this.baseVolume = arg.baseVolume;
+    this.quoteVolume = arg.quoteVolume;
+    this.netTakerFlow = arg.netTakerFlow.fromTwos(64);
+    this.resumptionTicket = new PublicKey(arg.resumptionTicket);
+    this.tradeHistory = new PublicKey(arg.tradeHistory);
+    this.candles = new PublicKey(arg.candles);
+    this.feeVault = new PublicKey(arg.feeVault);
+    this.authorityThreshold = arg.authorityThreshold;
+    this.authoritySigners = [...Array(MAX_AUTHORITY_SIGNERS).keys()].map(
+      (i) => new PublicKey(arg.authoritySigners.slice(32 * i, 32 * (i + 1)))
+    );
+    this.priceCumulative = arg.priceCumulative;
+    this.priceCumulativeTimestamp = arg.priceCumulativeTimestamp.fromTwos(64);
+    this.selfTradeDetectionDisabled = arg.selfTradeDetectionDisabled;
+    this.priceRoundingPolicy = arg.priceRoundingPolicy;
+    this.maxMatchIterations = arg.maxMatchIterations;
+    this.coalesceFills = arg.coalesceFills;
+    this.auctionPrice = arg.auctionPrice;
+    this.seed = arg.seed;
+    this.marketBump = arg.marketBump;
+    this.eventQueueBump = arg.eventQueueBump;
+    this.bidsBump = arg.bidsBump;
+    this.asksBump = arg.asksBump;
+    this.permissionlessCrank = arg.permissionlessCrank;
+    this.tradingSession = arg.tradingSession;
   }
 
   /**
@@ -270,26 +270,23 @@ export class Slab {
   //   let pointer;
   //   if (max) {
   //     pointer = find_max(
-  //       this.data,
-  //       BigInt(this.callBackInfoLen),
-  //       BigInt(this.slotSize)
+  //       this.buffer,
+  //       BigInt(this.callBackInfoLen)
   //     );
   //   } else {
   //     pointer = find_min(
-  //       this.data,
-  //       BigInt(this.callBackInfoLen),
-  //       BigInt(this.slotSize)
+  //       this.buffer,
+  //       BigInt(this.callBackInfoLen)
   //     );
   //   }
-  //   let offset = SlabHeader.LEN;
+  //   let offset = SlabHeader.PADDED_LEN;
   //   if (!pointer) {
   //     throw new Error("Empty slab");
   //   }
   //   let node = parseNode(
-  //     this.callBackInfoLen,
-  //     this.data.slice(
-  //       offset + pointer * this.slotSize,
-  //       offset + (pointer + 1) * this.slotSize
+  //     this.buffer.slice(
+  //       offset + pointer * Slab.SLOT_SIZE,
+  //       offset + (pointer + 1) * Slab.SLOT_SIZE
   //     )
   //   );
   //   return node;
@@ -337,9 +334,8 @@ export class Slab {
   //  */
   // getL2Depth(depth: number, increasing: boolean): Price[] {
   //   let raw = find_l2_depth(
-  //     this.data,
+  //     this.buffer,
   //     BigInt(this.callBackInfoLen),
-  //     BigInt(this.slotSize),
   //     BigInt(depth),
   //     increasing
   //   );
@@ -1,51 +1,97 @@
-use critbit::Slab;
-use std::{cell::RefCell, rc::Rc};
-
-mod critbit;
+//! WebAssembly bindings over the `aob-core` slabs, so that web front-ends read the bids and asks accounts and
+//! estimate fills with the same code which runs on-chain.
+use aob_core::{
+    critbit::Slab,
+    utils::{fp32_div, fp32_mul_ceil, fp32_mul_floor, round_to_lot},
+};
 
 use wasm_bindgen::prelude::*;
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    #[allow(clippy::eq_op)]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
+fn load_slab(data: &mut [u8], callback_info_len: u64) -> Result<Slab<&mut [u8]>, JsValue> {
+    Slab::new(data, callback_info_len as usize).map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
+/// Returns the handle of the node holding the largest key of the slab
 #[wasm_bindgen]
-pub fn find_max(data: &mut [u8], callback_info_len: u64, slot_size: u64) -> Option<u32> {
-    let slab = Slab::new(
-        Rc::new(RefCell::new(data)),
-        callback_info_len as usize,
-        slot_size as usize,
-    );
-    slab.find_max()
+pub fn find_max(data: &mut [u8], callback_info_len: u64) -> Result<Option<u32>, JsValue> {
+    Ok(load_slab(data, callback_info_len)?.find_max())
 }
 
+/// Returns the handle of the node holding the smallest key of the slab
 #[wasm_bindgen]
-pub fn find_min(data: &mut [u8], callback_info_len: u64, slot_size: u64) -> Option<u32> {
-    let slab = Slab::new(
-        Rc::new(RefCell::new(data)),
-        callback_info_len as usize,
-        slot_size as usize,
-    );
-    slab.find_min()
+pub fn find_min(data: &mut [u8], callback_info_len: u64) -> Result<Option<u32>, JsValue> {
+    Ok(load_slab(data, callback_info_len)?.find_min())
 }
 
+/// Returns up to `depth` price levels of the slab, flattened as `[size, price, size, price, ...]`. Prices are FP32.
 #[wasm_bindgen]
 pub fn find_l2_depth(
     data: &mut [u8],
     callback_info_len: u64,
-    slot_size: u64,
     depth: u64,
     increasing: bool,
-) -> Vec<u64> {
-    let slab = Slab::new(
-        Rc::new(RefCell::new(data)),
-        callback_info_len as usize,
-        slot_size as usize,
-    );
-    slab.find_l2_depth(depth as usize, increasing)
+) -> Result<Vec<u64>, JsValue> {
+    let slab = load_slab(data, callback_info_len)?;
+    Ok(slab
+        .price_levels(!increasing, depth as usize)
+        .into_iter()
+        .flat_map(|(price, base_lots)| [base_lots, price])
+        .collect())
+}
+
+/// Estimates the fills of a taker order against the opposite side of the orderbook, held in `data`, and returns
+/// `[base_qty, quote_qty, last_price]`. Quantities are in native units and prices are FP32.
+///
+/// Each resting order is matched in the same sequence and with the same lot and FP32 rounding as the on-chain
+/// matching engine. Fees, self trades and the market's match limit are not accounted for.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_fill(
+    data: &mut [u8],
+    callback_info_len: u64,
+    taker_is_bid: bool,
+    limit_price: u64,
+    max_base_qty: u64,
+    max_quote_qty: u64,
+    base_lot_size: u64,
+) -> Result<Vec<u64>, JsValue> {
+    let slab = load_slab(data, callback_info_len)?;
+    let mut base_qty_remaining = max_base_qty;
+    let mut quote_qty_remaining = max_quote_qty;
+    let mut last_price = 0;
+    // Bids are matched from the highest key, asks from the lowest one
+    for leaf in slab.leaves(!taker_is_bid) {
+        let trade_price = leaf.price();
+        let crossed = if taker_is_bid {
+            limit_price >= trade_price
+        } else {
+            limit_price <= trade_price
+        };
+        if !crossed {
+            break;
+        }
+        let base_trade_qty = round_to_lot(
+            (leaf.base_quantity * base_lot_size)
+                .min(base_qty_remaining)
+                .min(fp32_div(quote_qty_remaining, trade_price)),
+            base_lot_size,
+        );
+        if base_trade_qty == 0 {
+            break;
+        }
+        let quote_trade_qty = if taker_is_bid {
+            fp32_mul_ceil(base_trade_qty, trade_price)
+        } else {
+            fp32_mul_floor(base_trade_qty, trade_price)
+        }
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        base_qty_remaining -= base_trade_qty;
+        quote_qty_remaining -= quote_trade_qty;
+        last_price = trade_price;
+    }
+    Ok(vec![
+        max_base_qty - base_qty_remaining,
+        max_quote_qty - quote_qty_remaining,
+        last_price,
+    ])
 }
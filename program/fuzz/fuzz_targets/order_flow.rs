@@ -0,0 +1,157 @@
+//! Runs sequences of well-formed instructions against markets with arbitrary parameters, so that the fuzzer
+//! spends its time in the matching engine rather than in the instruction decoding.
+#![no_main]
+use agnostic_orderbook::instruction::AgnosticOrderbookInstruction;
+use agnostic_orderbook_fuzz::{market_params, FuzzMarket, CALLBACK_INFO_LEN};
+use aob::{
+    params::{CancelOrderParams, CloseMarketParams, ConsumeEventsParams, NewOrderParams},
+    state::{EventQueueOverflowPolicy, OrderId, OrderPriority, SelfTradeBehavior, Side},
+};
+use arbitrary::Arbitrary;
+use borsh::BorshSerialize;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Market {
+    min_base_order_size: u64,
+    tick_size: u64,
+    base_lot_size: u64,
+    callback_id_len: u8,
+    fifo: bool,
+    evict_oldest: bool,
+    max_post_only_spread_ticks: u64,
+    disable_self_trade_detection: bool,
+    max_match_iterations: u8,
+    coalesce_fills: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Operation {
+    NewOrder {
+        max_base_qty: u64,
+        max_quote_qty: u64,
+        limit_price: u64,
+        bid: bool,
+        match_limit: u64,
+        owner: u8,
+        post_only: bool,
+        post_allowed: bool,
+        self_trade_behavior: u8,
+    },
+    /// Cancels one of the orders posted so far, or an arbitrary order id
+    CancelOrder {
+        posted_index: Option<u8>,
+        order_id: u128,
+        expected_owner: Option<u8>,
+    },
+    ConsumeEvents {
+        number_of_entries_to_consume: u64,
+    },
+    CloseMarket,
+}
+
+fn instruction_data(tag: AgnosticOrderbookInstruction, params: impl BorshSerialize) -> Vec<u8> {
+    let mut data = vec![tag as u8];
+    params.serialize(&mut data).unwrap();
+    data
+}
+
+fuzz_target!(|input: (Market, Vec<Operation>)| {
+    let (params, operations) = input;
+    let mut market = match FuzzMarket::new(aob::params::CreateMarketParams {
+        min_base_order_size: params.min_base_order_size,
+        tick_size: params.tick_size,
+        base_lot_size: params.base_lot_size,
+        callback_id_len: params.callback_id_len as u64,
+        order_priority: if params.fifo {
+            OrderPriority::Fifo
+        } else {
+            OrderPriority::Size
+        },
+        event_queue_overflow_policy: if params.evict_oldest {
+            EventQueueOverflowPolicy::EvictOldest
+        } else {
+            EventQueueOverflowPolicy::Reject
+        },
+        max_post_only_spread_ticks: params.max_post_only_spread_ticks,
+        disable_self_trade_detection: params.disable_self_trade_detection,
+        max_match_iterations: params.max_match_iterations as u64,
+        coalesce_fills: params.coalesce_fills,
+        ..market_params()
+    }) {
+        Ok(market) => market,
+        // Invalid market parameters are rejected by create_market
+        Err(()) => return,
+    };
+
+    let mut posted_order_ids: Vec<OrderId> = vec![];
+    for operation in operations {
+        let data = match operation {
+            Operation::NewOrder {
+                max_base_qty,
+                max_quote_qty,
+                limit_price,
+                bid,
+                match_limit,
+                owner,
+                post_only,
+                post_allowed,
+                self_trade_behavior,
+            } => instruction_data(
+                AgnosticOrderbookInstruction::NewOrder,
+                NewOrderParams {
+                    max_base_qty,
+                    max_quote_qty,
+                    limit_price,
+                    side: if bid { Side::Bid } else { Side::Ask },
+                    match_limit,
+                    callback_info: vec![owner; CALLBACK_INFO_LEN],
+                    post_only,
+                    post_allowed,
+                    self_trade_behavior: match self_trade_behavior % 3 {
+                        0 => SelfTradeBehavior::DecrementTake,
+                        1 => SelfTradeBehavior::CancelProvide,
+                        _ => SelfTradeBehavior::AbortTransaction,
+                    },
+                    self_trade_id_len: None,
+                    max_avg_price: None,
+                },
+            ),
+            Operation::CancelOrder {
+                posted_index,
+                order_id,
+                expected_owner,
+            } => instruction_data(
+                AgnosticOrderbookInstruction::CancelOrder,
+                CancelOrderParams {
+                    order_id: posted_index
+                        .filter(|_| !posted_order_ids.is_empty())
+                        .map(|i| posted_order_ids[i as usize % posted_order_ids.len()])
+                        .unwrap_or(OrderId(order_id)),
+                    expected_callback_id: expected_owner
+                        .map(|owner| vec![owner; market.callback_id_len()]),
+                },
+            ),
+            Operation::ConsumeEvents {
+                number_of_entries_to_consume,
+            } => instruction_data(
+                AgnosticOrderbookInstruction::ConsumeEvents,
+                ConsumeEventsParams {
+                    number_of_entries_to_consume,
+                    min_remaining_compute_units: 0,
+                },
+            ),
+            Operation::CloseMarket => instruction_data(
+                AgnosticOrderbookInstruction::CloseMarket,
+                CloseMarketParams {},
+            ),
+        };
+        let is_new_order = data[0] == AgnosticOrderbookInstruction::NewOrder as u8;
+        if market.process(&data).is_ok() && is_new_order {
+            if let Some(order_id) = market.last_summary().posted_order_id {
+                posted_order_ids.push(order_id);
+            }
+        }
+        market.check_state();
+    }
+});
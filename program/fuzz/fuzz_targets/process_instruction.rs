@@ -0,0 +1,12 @@
+//! Feeds sequences of arbitrary instruction data to a freshly created market.
+#![no_main]
+use agnostic_orderbook_fuzz::{market_params, FuzzMarket};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|instructions: Vec<Vec<u8>>| {
+    let mut market = FuzzMarket::new(market_params()).unwrap();
+    for instruction_data in instructions {
+        let _ = market.process(&instruction_data);
+        market.check_state();
+    }
+});
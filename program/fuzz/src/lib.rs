@@ -0,0 +1,230 @@
+//! The harness shared by the fuzz targets: a market whose accounts live in memory, and which is driven through
+//! [`Processor::process_instruction`].
+//!
+//! Instructions which fail are rolled back, as the runtime would. After each instruction, the accounts of the
+//! market are checked for corruption.
+use std::sync::Once;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+
+use agnostic_orderbook::{instruction::AgnosticOrderbookInstruction, processor::Processor};
+use aob::{
+    critbit::Slab,
+    orderbook::OrderSummary,
+    params::CreateMarketParams,
+    state::{
+        AccountTag, EventQueue, EventQueueHeader, EventQueueOverflowPolicy, MarketState,
+        OrderPriority, PriceRoundingPolicy, Register, EVENT_QUEUE_HEADER_LEN, MARKET_STATE_LEN,
+    },
+};
+
+/// The callback information length of the fuzzed market
+pub const CALLBACK_INFO_LEN: usize = 8;
+const ORDER_CAPACITY: usize = 64;
+const EVENT_CAPACITY: usize = 64;
+
+const MARKET: usize = 0;
+const EVENT_QUEUE: usize = 1;
+const BIDS: usize = 2;
+const ASKS: usize = 3;
+const AUTHORITY: usize = 4;
+const TARGET: usize = 5;
+
+/// Silences the program's logs and serves a default clock and rent, which the native runtime can't provide
+struct FuzzStubs;
+
+impl SyscallStubs for FuzzStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_compute_units(&self) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+}
+
+/// A market which is created through the create_market instruction on fresh accounts
+pub struct FuzzMarket {
+    program_id: Pubkey,
+    keys: [Pubkey; 6],
+    lamports: [u64; 6],
+    data: [Vec<u8>; 6],
+}
+
+impl FuzzMarket {
+    /// Creates a market with the given parameters, apart from the ones which size the accounts.
+    pub fn new(mut params: CreateMarketParams) -> Result<Self, ()> {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(FuzzStubs));
+        });
+
+        let authority = Pubkey::new_unique();
+        params.caller_authority = authority.to_bytes();
+        params.callback_info_len = CALLBACK_INFO_LEN as u64;
+        params.callback_id_len = params.callback_id_len.min(CALLBACK_INFO_LEN as u64);
+        params.order_capacity = ORDER_CAPACITY as u64;
+        params.event_capacity = EVENT_CAPACITY as u64;
+        let book_len = Slab::compute_allocation_size(ORDER_CAPACITY, CALLBACK_INFO_LEN);
+        let event_queue_len =
+            EventQueue::compute_allocation_size(EVENT_CAPACITY, CALLBACK_INFO_LEN);
+        let rent = Rent::default();
+        let mut market = Self {
+            program_id: agnostic_orderbook::id(),
+            keys: [
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                authority,
+                Pubkey::new_unique(),
+            ],
+            lamports: [
+                1_000_000_000,
+                rent.minimum_balance(event_queue_len),
+                rent.minimum_balance(book_len),
+                rent.minimum_balance(book_len),
+                0,
+                0,
+            ],
+            data: [
+                vec![0; MARKET_STATE_LEN],
+                vec![0; event_queue_len],
+                vec![0; book_len],
+                vec![0; book_len],
+                vec![],
+                vec![],
+            ],
+        };
+        let mut instruction_data = vec![AgnosticOrderbookInstruction::CreateMarket as u8];
+        params.serialize(&mut instruction_data).unwrap();
+        market.process(&instruction_data).map_err(|_| ())?;
+        Ok(market)
+    }
+
+    /// Processes an instruction against the market's accounts, passed in the order which the instruction's tag
+    /// expects. The accounts are left untouched when the instruction fails.
+    pub fn process(&mut self, instruction_data: &[u8]) -> ProgramResult {
+        let account_indices: &[usize] = match instruction_data.first() {
+            Some(0) => &[MARKET, EVENT_QUEUE, BIDS, ASKS],
+            Some(1) | Some(3) => &[MARKET, EVENT_QUEUE, BIDS, ASKS, AUTHORITY],
+            Some(2) => &[MARKET, EVENT_QUEUE, AUTHORITY, TARGET],
+            _ => &[MARKET, EVENT_QUEUE, BIDS, ASKS, AUTHORITY, TARGET],
+        };
+        let backup = (self.lamports, self.data.clone());
+
+        let program_id = self.program_id;
+        let owners = [
+            program_id,
+            program_id,
+            program_id,
+            program_id,
+            system_program::ID,
+            system_program::ID,
+        ];
+        let account_infos = self
+            .keys
+            .iter()
+            .zip(owners.iter())
+            .zip(self.lamports.iter_mut().zip(self.data.iter_mut()))
+            .enumerate()
+            .map(|(i, ((key, owner), (lamports, data)))| {
+                AccountInfo::new(key, i == AUTHORITY, true, lamports, data, owner, false, 0)
+            })
+            .collect::<Vec<_>>();
+        let accounts = account_indices
+            .iter()
+            .map(|&i| account_infos[i].clone())
+            .collect::<Vec<_>>();
+        let result = Processor::process_instruction(&program_id, &accounts, instruction_data);
+        drop(accounts);
+        drop(account_infos);
+
+        if result.is_err() {
+            self.lamports = backup.0;
+            self.data = backup.1;
+        }
+        result
+    }
+
+    /// The callback id length which the market was created with
+    pub fn callback_id_len(&self) -> usize {
+        self.market_state().callback_id_len as usize
+    }
+
+    /// Reads the order summary which the last new_order or cancel_order instruction wrote to the register.
+    pub fn last_summary(&self) -> OrderSummary {
+        Register::<OrderSummary>::deserialize(
+            &mut &self.data[EVENT_QUEUE][EVENT_QUEUE_HEADER_LEN..],
+        )
+        .unwrap()
+        .unwrap()
+    }
+
+    fn market_state(&self) -> MarketState {
+        *bytemuck::from_bytes(&self.data[MARKET][..MARKET_STATE_LEN])
+    }
+
+    /// Panics if the market's accounts are corrupted.
+    pub fn check_state(&mut self) {
+        let market_state = self.market_state();
+        if market_state.tag != AccountTag::Market as u64 {
+            // The market was closed
+            return;
+        }
+        assert_eq!(market_state.locked, 0);
+        let callback_info_len = market_state.callback_info_len as usize;
+
+        let header = EventQueueHeader::load(&self.data[EVENT_QUEUE])
+            .unwrap()
+            .check()
+            .unwrap();
+        header
+            .check_layout(self.data[EVENT_QUEUE].len(), callback_info_len)
+            .unwrap();
+
+        for (i, account_tag) in [(BIDS, AccountTag::Bids), (ASKS, AccountTag::Asks)] {
+            let slab = Slab::new(&mut self.data[i][..], callback_info_len).unwrap();
+            assert_eq!(slab.account_tag(), account_tag as u8);
+            slab.check_invariants();
+        }
+    }
+}
+
+/// The parameters of a permissive market, which the fuzz targets can then alter
+pub fn market_params() -> CreateMarketParams {
+    CreateMarketParams {
+        caller_authority: [0; 32],
+        callback_info_len: CALLBACK_INFO_LEN as u64,
+        callback_id_len: CALLBACK_INFO_LEN as u64,
+        min_base_order_size: 1,
+        tick_size: 1,
+        base_lot_size: 1,
+        cranker_base_reward: 0,
+        cranker_per_event_reward: 0,
+        order_priority: OrderPriority::Fifo,
+        event_queue_overflow_policy: EventQueueOverflowPolicy::Reject,
+        max_post_only_spread_ticks: 0,
+        disable_self_trade_detection: false,
+        price_rounding_policy: PriceRoundingPolicy::Passive,
+        max_match_iterations: 0,
+        coalesce_fills: false,
+        order_capacity: ORDER_CAPACITY as u64,
+        event_capacity: EVENT_CAPACITY as u64,
+    }
+}
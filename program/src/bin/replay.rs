@@ -0,0 +1,85 @@
+//! Replays the logged instructions of a market, see [`agnostic_orderbook::replay`], and prints the event stream
+//! along with the final state of the orderbook.
+//!
+//! ```text
+//! replay [--depth N] [FILE]
+//! ```
+//!
+//! Instructions are read from the file, or from the standard input when no file is given. Instructions which are
+//! rejected are reported on the standard error and rolled back, and the replay goes on.
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use agnostic_orderbook::replay::{LoggedInstruction, Replayer};
+use aob::state::Side;
+
+const DEFAULT_L2_DEPTH: usize = 20;
+
+fn main() {
+    let mut depth = DEFAULT_L2_DEPTH;
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(value) => depth = value,
+                None => usage(),
+            },
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let reader: Box<dyn BufRead> = match &path {
+        Some(path) => Box::new(BufReader::new(File::open(path).unwrap_or_else(|e| {
+            eprintln!("Failed to open {}: {}", path, e);
+            std::process::exit(1);
+        }))),
+        None => Box::new(BufReader::new(std::io::stdin())),
+    };
+
+    let mut replayer = Replayer::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.unwrap_or_else(|e| {
+            eprintln!("Failed to read the instructions: {}", e);
+            std::process::exit(1);
+        });
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let outcome = line
+            .parse::<LoggedInstruction>()
+            .and_then(|instruction| replayer.apply(&instruction));
+        match outcome {
+            Ok(outcome) => {
+                if let Some(summary) = outcome.summary {
+                    println!("{}: {:?}", i + 1, summary);
+                }
+                for event in outcome.events {
+                    println!("{}: {:?}", i + 1, event);
+                }
+            }
+            Err(e) => eprintln!("{}: {}", i + 1, e),
+        }
+    }
+
+    match replayer.market.as_mut() {
+        Some(market) => {
+            for side in [Side::Ask, Side::Bid] {
+                let levels = market.l2(side, depth).unwrap();
+                println!("{:?}s:", side);
+                for (price, base_qty) in levels {
+                    println!("  {} {}", price, base_qty);
+                }
+            }
+        }
+        None => println!("The market isn't open"),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: replay [--depth N] [FILE]");
+    std::process::exit(2);
+}
@@ -1,8 +1,9 @@
 use crate::processor::Processor;
-use aob::error::AoError;
+use aob::error::ErrorCode;
+use aob::{log_debug, log_error};
 
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::PrintProgramError,
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -17,10 +18,17 @@ pub fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    msg!("Entrypoint");
+    log_debug!("Entrypoint");
     if let Err(error) = Processor::process_instruction(program_id, accounts, instruction_data) {
-        // catch the error so we can print it
-        error.print::<AoError>();
+        // The orderbook's errors carry the codes of the Anchor program, catch them so we can print
+        // them and return the codes of the native program instead
+        if let ProgramError::Custom(code) = error {
+            if let Some(error_code) = ErrorCode::from_anchor_code(code) {
+                log_error!("{}", error_code);
+                return Err(ProgramError::Custom(error_code.native_code()));
+            }
+        }
+        log_error!("{}", error);
         return Err(error);
     }
     Ok(())
@@ -22,6 +22,7 @@ The [`new_order`][`fn@instruction::new_order`] primitive will push a new order t
 the spread. The result of this is a series of matching events pushed to the event queue, as well as the writing of a new order to the orderbook, which will become
 immediately available to be matched agains other orders. An [`OrderSummary`][`state::OrderSummary`] object is also written to the event queue's register, yielding
 a unique order identifier which will be valid for the whole lifetime of the order : until it is completely matched or cancelled (if it posted).
+The [`OrderResult`][`aob::orderbook::OrderResult`] written to the return data holds the same summary, along with the price levels which the order was matched against.
 
 More information about different parameters for this primitive can be found [here][`instruction`].
 
@@ -45,6 +46,7 @@ primitive. In general, the event processing logic should be handled by a dedicat
 
 use solana_program::declare_id;
 
+pub use aob::error::AobError as AoError;
 pub use processor::msrm_token;
 
 #[doc(hidden)]
@@ -54,6 +56,14 @@ pub mod entrypoint;
 pub mod instruction;
 
 #[doc(hidden)]
-pub(crate) mod processor;
+pub mod processor;
+
+/// Reconstructs markets from their logged instructions
+#[cfg(feature = "replay")]
+pub mod replay;
+
+/// Helpers which bootstrap markets in `solana-program-test` suites
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 
 declare_id!("aaobKniTtDGvCZces7GH5UReLYP671bBkB96ahr9x3e");
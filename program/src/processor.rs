@@ -1,6 +1,6 @@
 use borsh::BorshDeserialize;
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -10,6 +10,7 @@ use aob::params::{
 use num_traits::FromPrimitive;
 
 use crate::instruction::AgnosticOrderbookInstruction;
+use aob::log_debug;
 
 pub mod cancel_order;
 pub mod close_market;
@@ -32,43 +33,45 @@ impl Processor {
         accounts: &[AccountInfo],
         instruction_data: &[u8],
     ) -> ProgramResult {
-        msg!("Beginning processing");
-        let instruction = FromPrimitive::from_u8(instruction_data[0])
+        log_debug!("Beginning processing");
+        let (&tag, instruction_data) = instruction_data
+            .split_first()
             .ok_or(ProgramError::InvalidInstructionData)?;
-        let instruction_data = &instruction_data[1..];
-        msg!("Instruction unpacked");
+        let instruction =
+            FromPrimitive::from_u8(tag).ok_or(ProgramError::InvalidInstructionData)?;
+        log_debug!("Instruction unpacked");
 
         match instruction {
             AgnosticOrderbookInstruction::CreateMarket => {
-                msg!("Instruction: Create Market");
+                log_debug!("Instruction: Create Market");
                 let accounts = create_market::Accounts::parse(accounts)?;
                 let params = CreateMarketParams::try_from_slice(instruction_data)
                     .map_err(|_| ProgramError::InvalidInstructionData)?;
                 create_market::process(program_id, accounts, params)?;
             }
             AgnosticOrderbookInstruction::NewOrder => {
-                msg!("Instruction: New Order");
+                log_debug!("Instruction: New Order");
                 let accounts = new_order::Accounts::parse(accounts)?;
                 let params = NewOrderParams::try_from_slice(instruction_data)
                     .map_err(|_| ProgramError::InvalidInstructionData)?;
                 new_order::process(program_id, accounts, params)?;
             }
             AgnosticOrderbookInstruction::ConsumeEvents => {
-                msg!("Instruction: Consume Events");
+                log_debug!("Instruction: Consume Events");
                 let accounts = consume_events::Accounts::parse(accounts)?;
                 let params = ConsumeEventsParams::try_from_slice(instruction_data)
                     .map_err(|_| ProgramError::InvalidInstructionData)?;
                 consume_events::process(program_id, accounts, params)?;
             }
             AgnosticOrderbookInstruction::CancelOrder => {
-                msg!("Instruction: Cancel Order");
+                log_debug!("Instruction: Cancel Order");
                 let accounts = cancel_order::Accounts::parse(accounts)?;
                 let params = CancelOrderParams::try_from_slice(instruction_data)
                     .map_err(|_| ProgramError::InvalidInstructionData)?;
                 cancel_order::process(program_id, accounts, params)?;
             }
             AgnosticOrderbookInstruction::CloseMarket => {
-                msg!("Instruction: Close Market");
+                log_debug!("Instruction: Close Market");
                 let accounts = close_market::Accounts::parse(accounts)?;
                 close_market::process(program_id, accounts, CloseMarketParams {})?;
             }
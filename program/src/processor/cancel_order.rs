@@ -1,7 +1,6 @@
 //! Cancel an existing order in the orderbook.
 
 use bonfida_utils::InstructionsAccount;
-use borsh::BorshDeserialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
@@ -9,14 +8,15 @@ use solana_program::{
     pubkey::Pubkey,
 };
 
+use anchor_lang::error;
 use aob::{
-    error::AoError,
+    error::ErrorCode,
     orderbook::{OrderBookState, OrderSummary},
     params::CancelOrderParams,
-    state::{
-        get_side_from_order_id, EventQueue, EventQueueHeader, MarketState,
+    state::{EventQueue, EventQueueHeader, MarketState},
+    utils::{
+        check_account_key, check_account_owner, check_signer, fp32_mul_floor, lots_to_base_qty,
     },
-    utils::{check_account_key, check_account_owner, check_signer, fp32_mul},
 };
 
 /// The required accounts for a cancel_order instruction.
@@ -58,15 +58,15 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         check_account_owner(
             self.market,
             &program_id.to_bytes(),
-            AoError::WrongMarketOwner,
+            ErrorCode::WrongMarketOwner,
         )?;
         check_account_owner(
             self.event_queue,
             &program_id.to_bytes(),
-            AoError::WrongEventQueueOwner,
+            ErrorCode::WrongEventQueueOwner,
         )?;
-        check_account_owner(self.bids, &program_id.to_bytes(), AoError::WrongBidsOwner)?;
-        check_account_owner(self.asks, &program_id.to_bytes(), AoError::WrongAsksOwner)?;
+        check_account_owner(self.bids, &program_id.to_bytes(), ErrorCode::WrongBidsOwner)?;
+        check_account_owner(self.asks, &program_id.to_bytes(), ErrorCode::WrongAsksOwner)?;
         #[cfg(not(feature = "lib"))]
         check_signer(self.authority)?;
         Ok(())
@@ -79,7 +79,8 @@ pub fn process(
     params: CancelOrderParams,
 ) -> ProgramResult {
     accounts.perform_checks(program_id)?;
-    let market_state = MarketState::get(accounts.market)?;
+    let mut market_state = MarketState::get(accounts.market)?;
+    market_state.lock()?;
 
     check_accounts(&accounts, &market_state)?;
 
@@ -91,27 +92,26 @@ pub fn process(
         market_state.callback_info_len as usize,
         market_state.callback_id_len as usize,
     )?;
+    order_book.check_market(accounts.market.key)?;
 
-    let header = {
-        let mut event_queue_data: &[u8] =
-            &accounts.event_queue.data.borrow()[0..EventQueueHeader::LEN];
-        EventQueueHeader::deserialize(&mut event_queue_data).unwrap()
-    };
+    let header = EventQueueHeader::load(&accounts.event_queue.data.borrow())?;
+    header.check_market(accounts.market.key)?;
     let event_queue = EventQueue::new_safe(header, accounts.event_queue, callback_info_len)?;
 
-    let slab = order_book.get_tree(get_side_from_order_id(params.order_id));
-    let node = slab
-        .remove_by_key(params.order_id)
-        .ok_or(AoError::OrderNotFound)?;
-    let leaf_node = node.as_leaf().unwrap();
-    let total_base_qty = leaf_node.base_quantity;
-    let total_quote_qty = fp32_mul(leaf_node.base_quantity, leaf_node.price());
+    let (leaf_node, _) =
+        order_book.cancel_order(params.order_id, params.expected_callback_id.as_deref())?;
+    let total_base_qty = lots_to_base_qty(leaf_node.base_quantity, market_state.base_lot_size);
+    let total_quote_qty = fp32_mul_floor(total_base_qty, leaf_node.price())
+        .map_err(|e| error!(ErrorCode::from(e)))?;
 
     let order_summary = OrderSummary {
         posted_order_id: None,
         total_base_qty,
         total_quote_qty,
+        total_fee_qty: 0,
         total_base_qty_posted: 0,
+        avg_fill_price_fp32: 0,
+        worst_fill_price_fp32: 0,
     };
 
     event_queue.write_to_register(order_summary);
@@ -119,6 +119,8 @@ pub fn process(
     order_book.commit_changes();
     order_book.release(accounts.bids, accounts.asks);
 
+    market_state.unlock();
+
     Ok(())
 }
 
@@ -129,15 +131,23 @@ fn check_accounts<'a, 'b: 'a>(
     check_account_key(
         accounts.event_queue,
         &market_state.event_queue,
-        AoError::WrongEventQueueAccount,
+        ErrorCode::WrongEventQueueAccount,
+    )?;
+    check_account_key(
+        accounts.bids,
+        &market_state.bids,
+        ErrorCode::WrongBidsAccount,
+    )?;
+    check_account_key(
+        accounts.asks,
+        &market_state.asks,
+        ErrorCode::WrongAsksAccount,
     )?;
-    check_account_key(accounts.bids, &market_state.bids, AoError::WrongBidsAccount)?;
-    check_account_key(accounts.asks, &market_state.asks, AoError::WrongAsksAccount)?;
     #[cfg(not(feature = "lib"))]
     check_account_key(
         accounts.authority,
         &market_state.caller_authority,
-        AoError::WrongCallerAuthority,
+        ErrorCode::WrongCallerAuthority,
     )?;
 
     Ok(())
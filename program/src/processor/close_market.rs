@@ -1,17 +1,17 @@
 //! Close an existing market.
+use anchor_lang::error;
+use aob::log_error;
 use aob::params::CloseMarketParams;
 use aob::{
-    error::AoError,
+    error::ErrorCode,
     orderbook::OrderBookState,
     state::{AccountTag, EventQueueHeader, MarketState},
     utils::{check_account_key, check_account_owner, check_signer},
 };
 use bonfida_utils::InstructionsAccount;
-use borsh::BorshDeserialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
-    msg,
     program_error::ProgramError,
     pubkey::Pubkey,
 };
@@ -57,7 +57,7 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         check_account_owner(
             self.market,
             &program_id.to_bytes(),
-            AoError::WrongMarketOwner,
+            ErrorCode::WrongMarketOwner,
         )?;
         #[cfg(not(feature = "lib"))]
         check_signer(self.authority)?;
@@ -83,20 +83,18 @@ pub fn process(
         market_state.callback_id_len as usize,
     )
     .unwrap();
+    orderbook_state.check_market(accounts.market.key)?;
     if !orderbook_state.is_empty() {
-        msg!("The orderbook must be empty");
-        return Err(ProgramError::from(AoError::MarketStillActive));
+        log_error!("The orderbook must be empty");
+        return Err(error!(ErrorCode::MarketStillActive).into());
     }
 
     // Check if all events have been processed
-    let header = {
-        let mut event_queue_data: &[u8] =
-            &accounts.event_queue.data.borrow()[0..EventQueueHeader::LEN];
-        EventQueueHeader::deserialize(&mut event_queue_data).unwrap()
-    };
-    if header.count != 0 {
-        msg!("The event queue needs to be empty");
-        return Err(ProgramError::from(AoError::MarketStillActive));
+    let event_queue_header = EventQueueHeader::get_unchecked(accounts.event_queue);
+    event_queue_header.check_market(accounts.market.key)?;
+    if event_queue_header.count != 0 {
+        log_error!("The event queue needs to be empty");
+        return Err(error!(ErrorCode::MarketStillActive).into());
     }
 
     market_state.tag = AccountTag::Uninitialized as u64;
@@ -128,15 +126,23 @@ fn check_accounts<'a, 'b: 'a>(
     check_account_key(
         accounts.event_queue,
         &market_state.event_queue,
-        AoError::WrongEventQueueAccount,
+        ErrorCode::WrongEventQueueAccount,
+    )?;
+    check_account_key(
+        accounts.bids,
+        &market_state.bids,
+        ErrorCode::WrongBidsAccount,
+    )?;
+    check_account_key(
+        accounts.asks,
+        &market_state.asks,
+        ErrorCode::WrongAsksAccount,
     )?;
-    check_account_key(accounts.bids, &market_state.bids, AoError::WrongBidsAccount)?;
-    check_account_key(accounts.asks, &market_state.asks, AoError::WrongAsksAccount)?;
     #[cfg(not(feature = "lib"))]
     check_account_key(
         accounts.authority,
         &market_state.caller_authority,
-        AoError::WrongCallerAuthority,
+        ErrorCode::WrongCallerAuthority,
     )?;
 
     Ok(())
@@ -1,20 +1,21 @@
 //! Pop a series of events off the event queue.
 use bonfida_utils::InstructionsAccount;
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
-    msg,
+    program::set_return_data,
     program_error::ProgramError,
     pubkey::Pubkey,
 };
 
+use anchor_lang::error;
 use aob::params::ConsumeEventsParams;
 use aob::{
-    error::AoError,
+    error::ErrorCode,
     state::{EventQueue, EventQueueHeader, MarketState},
-    utils::{check_account_key, check_account_owner, check_signer},
+    utils::{check_account_key, check_account_owner, check_signer, remaining_compute_units},
 };
+use aob::{log_debug, log_error};
 
 /// The required accounts for a consume_events instruction.
 #[derive(InstructionsAccount)]
@@ -50,16 +51,16 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         check_account_owner(
             self.market,
             &program_id.to_bytes(),
-            AoError::WrongMarketOwner,
+            ErrorCode::WrongMarketOwner,
         )?;
         check_account_owner(
             self.event_queue,
             &program_id.to_bytes(),
-            AoError::WrongEventQueueOwner,
+            ErrorCode::WrongEventQueueOwner,
         )?;
         #[cfg(not(feature = "lib"))]
         check_signer(self.authority).map_err(|e| {
-            msg!("The market authority should be a signer for this instruction!");
+            log_error!("The market authority should be a signer for this instruction!");
             e
         })?;
 
@@ -75,43 +76,55 @@ pub fn process<'a, 'b: 'a>(
 ) -> ProgramResult {
     accounts.perform_checks(program_id)?;
     let mut market_state = MarketState::get(accounts.market)?;
+    market_state.lock()?;
 
     check_accounts(&accounts, &market_state)?;
 
-    let header = {
-        let mut event_queue_data: &[u8] =
-            &accounts.event_queue.data.borrow()[0..EventQueueHeader::LEN];
-        EventQueueHeader::deserialize(&mut event_queue_data).unwrap()
-    };
+    let header = EventQueueHeader::load(&accounts.event_queue.data.borrow())?;
+    header.check_market(accounts.market.key)?;
     let mut event_queue = EventQueue::new_safe(
         header,
         accounts.event_queue,
         market_state.callback_info_len as usize,
     )?;
 
+    if event_queue.header.count == 0 {
+        log_error!("The event queue is empty");
+        return Err(error!(ErrorCode::NoOperations).into());
+    }
+
+    // Pop Events
+    let count_before = event_queue.header.count;
+    let capped_number_of_entries_to_consume =
+        std::cmp::min(count_before, params.number_of_entries_to_consume);
+    let number_of_entries_consumed = if params.min_remaining_compute_units == 0 {
+        event_queue.pop_n(capped_number_of_entries_to_consume);
+        capped_number_of_entries_to_consume
+    } else {
+        let mut consumed = 0;
+        while consumed < capped_number_of_entries_to_consume
+            && remaining_compute_units() >= params.min_remaining_compute_units
+        {
+            event_queue.pop_n(1);
+            consumed += 1;
+        }
+        consumed
+    };
+    event_queue.write_header();
+
     // Reward payout
-    let capped_number_of_entries_consumed = std::cmp::min(
-        event_queue.header.count,
-        params.number_of_entries_to_consume,
-    );
-    let reward = (market_state.fee_budget * capped_number_of_entries_consumed)
-        .checked_div(event_queue.header.count)
-        .ok_or(AoError::NoOperations)
-        .unwrap();
-    market_state.fee_budget -= reward;
+    let reward = market_state.take_crank_reward(number_of_entries_consumed);
     **accounts.market.try_borrow_mut_lamports().unwrap() = accounts.market.lamports() - reward;
     **accounts.reward_target.try_borrow_mut_lamports().unwrap() =
         accounts.reward_target.lamports() + reward;
 
-    // Pop Events
-    event_queue.pop_n(params.number_of_entries_to_consume);
-    let mut event_queue_data: &mut [u8] = &mut accounts.event_queue.data.borrow_mut();
-    event_queue.header.serialize(&mut event_queue_data).unwrap();
-
-    msg!(
+    log_debug!(
         "Number of events consumed: {:?}",
-        capped_number_of_entries_consumed
+        number_of_entries_consumed
     );
+    set_return_data(&number_of_entries_consumed.to_le_bytes());
+
+    market_state.unlock();
 
     Ok(())
 }
@@ -123,18 +136,20 @@ fn check_accounts<'a, 'b: 'a>(
     check_account_key(
         accounts.event_queue,
         &market_state.event_queue,
-        AoError::WrongEventQueueAccount,
+        ErrorCode::WrongEventQueueAccount,
     )?;
     #[cfg(not(feature = "lib"))]
-    check_account_key(
-        accounts.authority,
-        &market_state.caller_authority,
-        AoError::WrongCallerAuthority,
-    )?;
+    if market_state.permissionless_crank == 0 {
+        check_account_key(
+            accounts.authority,
+            &market_state.caller_authority,
+            ErrorCode::WrongCallerAuthority,
+        )?;
+    }
     check_account_key(
         accounts.event_queue,
         &market_state.event_queue,
-        AoError::WrongEventQueueAccount,
+        ErrorCode::WrongEventQueueAccount,
     )?;
     Ok(())
 }
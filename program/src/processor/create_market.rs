@@ -1,20 +1,26 @@
 //! Create and initialize a new orderbook market
 use bonfida_utils::InstructionsAccount;
-use borsh::BorshSerialize;
 
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     program_error::ProgramError,
     pubkey::Pubkey,
+    rent::Rent,
+    sysvar::Sysvar,
 };
 
+use anchor_lang::error;
+use aob::log_error;
 use aob::params::CreateMarketParams;
 use aob::{
-    critbit::Slab,
-    error::AoError,
-    state::{AccountTag, EventQueue, EventQueueHeader, MarketState},
-    utils::{check_account_owner, check_unitialized},
+    critbit::{Slab, SlabExt},
+    error::ErrorCode,
+    state::{
+        AccountTag, EventQueue, EventQueueHeader, MarketState, TradingSession,
+        MAX_AUTHORITY_SIGNERS,
+    },
+    utils::{check_account_owner, check_rent_exempt, check_unitialized},
 };
 
 /// The required accounts for a create_market instruction.
@@ -52,10 +58,10 @@ impl<'a, 'b> Accounts<'a, AccountInfo<'b>> {
         check_account_owner(
             self.event_queue,
             &program_id.to_bytes(),
-            AoError::WrongEventQueueOwner,
+            ErrorCode::WrongEventQueueOwner,
         )?;
-        check_account_owner(self.bids, &program_id.to_bytes(), AoError::WrongBidsOwner)?;
-        check_account_owner(self.asks, &program_id.to_bytes(), AoError::WrongAsksOwner)?;
+        check_account_owner(self.bids, &program_id.to_bytes(), ErrorCode::WrongBidsOwner)?;
+        check_account_owner(self.asks, &program_id.to_bytes(), ErrorCode::WrongAsksOwner)?;
         Ok(())
     }
 }
@@ -73,14 +79,52 @@ pub fn process(
         callback_id_len,
         min_base_order_size,
         tick_size,
-        cranker_reward,
+        base_lot_size,
+        cranker_base_reward,
+        cranker_per_event_reward,
+        order_priority,
+        event_queue_overflow_policy,
+        max_post_only_spread_ticks,
+        disable_self_trade_detection,
+        price_rounding_policy,
+        max_match_iterations,
+        coalesce_fills,
+        order_capacity,
+        event_capacity,
     } = params;
 
+    if base_lot_size == 0 {
+        log_error!("The base lot size must be > 0");
+        return Err(error!(ErrorCode::InvalidBaseLotSize).into());
+    }
+    MarketState::check_parameters(
+        callback_info_len,
+        callback_id_len,
+        min_base_order_size,
+        tick_size,
+    )?;
+    // The sizes are checked first, so that the accounts are never indexed out of bounds
+    EventQueue::check_capacity(accounts.event_queue, event_capacity, callback_info_len)?;
+    Slab::check_capacity(
+        accounts.bids,
+        AccountTag::Bids,
+        order_capacity,
+        callback_info_len,
+    )?;
+    Slab::check_capacity(
+        accounts.asks,
+        AccountTag::Asks,
+        order_capacity,
+        callback_info_len,
+    )?;
+    let rent = Rent::get()?;
+    check_rent_exempt(accounts.event_queue, &rent, "event queue")?;
+    check_rent_exempt(accounts.bids, &rent, "bids")?;
+    check_rent_exempt(accounts.asks, &rent, "asks")?;
     check_unitialized(accounts.event_queue)?;
     check_unitialized(accounts.bids)?;
     check_unitialized(accounts.asks)?;
     check_unitialized(accounts.market)?;
-    EventQueue::check_buffer_size(accounts.event_queue, params.callback_info_len)?;
 
     let mut market_state = MarketState::get_unchecked(accounts.market);
 
@@ -96,13 +140,48 @@ pub fn process(
         initial_lamports: accounts.market.lamports(),
         min_base_order_size,
         tick_size,
-        cranker_reward,
+        base_lot_size,
+        cranker_base_reward,
+        cranker_per_event_reward,
+        event_queue_high_watermark_bps: 0,
+        event_queue_low_watermark_bps: 0,
+        order_priority: order_priority as u64,
+        event_queue_overflow_policy: event_queue_overflow_policy as u64,
+        locked: 0,
+        max_post_only_spread_ticks,
+        last_trade_price: 0,
+        base_volume: 0,
+        quote_volume: 0,
+        net_taker_flow: 0,
+        resumption_ticket: [0; 32],
+        trade_history: [0; 32],
+        candles: [0; 32],
+        fee_vault: [0; 32],
+        authority_threshold: 0,
+        authority_signers: [[0; 32]; MAX_AUTHORITY_SIGNERS],
+        price_cumulative: 0,
+        price_cumulative_timestamp: 0,
+        self_trade_detection_disabled: disable_self_trade_detection as u64,
+        price_rounding_policy: price_rounding_policy as u64,
+        max_match_iterations,
+        coalesce_fills: coalesce_fills as u64,
+        auction_price: 0,
+        seed: [0; 32],
+        market_bump: 0,
+        event_queue_bump: 0,
+        bids_bump: 0,
+        asks_bump: 0,
+        permissionless_crank: 0,
+        trading_session: TradingSession::Open as u8,
+        _padding: [0; 2],
     };
 
-    let event_queue_header = EventQueueHeader::initialize(params.callback_info_len as usize);
-    event_queue_header
-        .serialize(&mut (&mut accounts.event_queue.data.borrow_mut() as &mut [u8]))
-        .unwrap();
+    EventQueueHeader::initialize(
+        *accounts.market.key,
+        params.callback_info_len as usize,
+        accounts.event_queue.data_len(),
+    )
+    .write(&mut accounts.event_queue.data.borrow_mut());
 
     Slab::initialize(
         accounts.bids,
@@ -1,22 +1,25 @@
 //! Execute a new order on the orderbook
 
 use bonfida_utils::InstructionsAccount;
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::BorshSerialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
-    msg,
+    program::set_return_data,
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
 
 use aob::params::NewOrderParams;
 use aob::{
-    error::AoError,
+    error::ErrorCode,
     orderbook::OrderBookState,
-    state::{EventQueue, EventQueueHeader, MarketState, EVENT_QUEUE_HEADER_LEN},
-    utils::{check_account_key, check_account_owner, check_signer, round_price},
+    state::{EventQueue, EventQueueHeader, MarketState},
+    utils::{check_account_key, check_account_owner, check_signer},
 };
+use aob::{log_compute_units, log_debug, log_error};
 
 /// The required accounts for a new_order instruction.
 #[derive(InstructionsAccount)]
@@ -55,18 +58,18 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         check_account_owner(
             self.market,
             &program_id.to_bytes(),
-            AoError::WrongMarketOwner,
+            ErrorCode::WrongMarketOwner,
         )?;
         check_account_owner(
             self.event_queue,
             &program_id.to_bytes(),
-            AoError::WrongEventQueueOwner,
+            ErrorCode::WrongEventQueueOwner,
         )?;
-        check_account_owner(self.bids, &program_id.to_bytes(), AoError::WrongBidsOwner)?;
-        check_account_owner(self.asks, &program_id.to_bytes(), AoError::WrongAsksOwner)?;
+        check_account_owner(self.bids, &program_id.to_bytes(), ErrorCode::WrongBidsOwner)?;
+        check_account_owner(self.asks, &program_id.to_bytes(), ErrorCode::WrongAsksOwner)?;
         #[cfg(not(feature = "lib"))]
         check_signer(self.authority).map_err(|e| {
-            msg!("The market authority should be a signer for this instruction!");
+            log_error!("The market authority should be a signer for this instruction!");
             e
         })?;
         Ok(())
@@ -81,74 +84,69 @@ pub fn process(
 ) -> ProgramResult {
     accounts.perform_checks(program_id)?;
     let mut market_state = MarketState::get(accounts.market)?;
+    market_state.lock()?;
 
     check_accounts(&accounts, &market_state)?;
 
-    // Round price to nearest valid price tick
-    params.limit_price = round_price(market_state.tick_size, params.limit_price, params.side);
+    // Fit the price to the tick size according to the market's rounding policy
+    params.limit_price = market_state.round_price(params.limit_price, params.side)?;
 
     let callback_info_len = market_state.callback_info_len as usize;
 
-    msg!("New Order: Creating order book");
-    // sol_log_compute_units();
+    log_debug!("New Order: Creating order book");
+    log_compute_units!();
     let mut order_book = OrderBookState::new(
         accounts.bids,
         accounts.asks,
         market_state.callback_info_len as usize,
         market_state.callback_id_len as usize,
     )?;
-    // sol_log_compute_units();
+    order_book.check_market(accounts.market.key)?;
+    log_compute_units!();
 
     if params.callback_info.len() != callback_info_len {
-        msg!("Invalid callback information");
+        log_error!("Invalid callback information");
         return Err(ProgramError::InvalidArgument);
     }
 
-    msg!("New Order: Creating event queue");
-    // sol_log_compute_units();
+    log_debug!("New Order: Creating event queue");
+    log_compute_units!();
 
-    let header = {
-        let mut event_queue_data: &[u8] =
-            &accounts.event_queue.data.borrow()[0..EVENT_QUEUE_HEADER_LEN];
-        EventQueueHeader::deserialize(&mut event_queue_data)
-            .unwrap()
-            .check()?
-    };
+    let header = EventQueueHeader::load(&accounts.event_queue.data.borrow())?.check()?;
+    header.check_market(accounts.market.key)?;
     let mut event_queue = EventQueue::new_safe(header, accounts.event_queue, callback_info_len)?;
-    // sol_log_compute_units();
-
-    msg!("New Order: Creating new order");
-    // sol_log_compute_units();
-    let order_summary =
-        order_book.new_order(params, &mut event_queue, market_state.min_base_order_size)?;
-    // sol_log_compute_units();
-    msg!("Order summary : {:?}", order_summary);
-    event_queue.write_to_register(order_summary);
-
-    let mut event_queue_header_data: &mut [u8] = &mut accounts.event_queue.data.borrow_mut();
-    event_queue
-        .header
-        .serialize(&mut event_queue_header_data)
-        .unwrap();
-    msg!("Committing changes");
-    // sol_log_compute_units();
+    event_queue.set_clock(&Clock::get()?);
+    event_queue.overflow_policy = market_state.get_event_queue_overflow_policy()?;
+    log_compute_units!();
+
+    log_debug!("New Order: Creating new order");
+    log_compute_units!();
+    let order_result = order_book.new_order_with_fill_levels(
+        params,
+        &mut event_queue,
+        &mut market_state,
+        None,
+        None,
+    )?;
+    log_compute_units!();
+    log_debug!("Order summary : {:?}", order_result.summary);
+    set_return_data(&order_result.try_to_vec().unwrap());
+    event_queue.write_to_register(order_result.summary);
+
+    event_queue.write_header();
+    log_debug!("Committing changes");
+    log_compute_units!();
     order_book.commit_changes();
-    // sol_log_compute_units();
+    log_compute_units!();
 
     //Verify that fees were transfered. Fees are expected to be transfered by the caller program in order
     // to reduce the CPI call stack depth.
-    if accounts.market.lamports() - market_state.initial_lamports
-        < market_state
-            .fee_budget
-            .checked_add(market_state.cranker_reward)
-            .unwrap()
-    {
-        msg!("Fees were not correctly payed during caller runtime.");
-        return Err(AoError::FeeNotPayed.into());
-    }
-    market_state.fee_budget = accounts.market.lamports() - market_state.initial_lamports;
+    market_state
+        .verify_and_update_fee_budget(accounts.market.lamports(), event_queue.events_pushed())?;
     order_book.release(accounts.bids, accounts.asks);
 
+    market_state.unlock();
+
     Ok(())
 }
 
@@ -159,15 +157,23 @@ fn check_accounts<'a, 'b: 'a>(
     check_account_key(
         accounts.event_queue,
         &market_state.event_queue,
-        AoError::WrongEventQueueAccount,
+        ErrorCode::WrongEventQueueAccount,
+    )?;
+    check_account_key(
+        accounts.bids,
+        &market_state.bids,
+        ErrorCode::WrongBidsAccount,
+    )?;
+    check_account_key(
+        accounts.asks,
+        &market_state.asks,
+        ErrorCode::WrongAsksAccount,
     )?;
-    check_account_key(accounts.bids, &market_state.bids, AoError::WrongBidsAccount)?;
-    check_account_key(accounts.asks, &market_state.asks, AoError::WrongAsksAccount)?;
     #[cfg(not(feature = "lib"))]
     check_account_key(
         accounts.authority,
         &market_state.caller_authority,
-        AoError::WrongCallerAuthority,
+        ErrorCode::WrongCallerAuthority,
     )?;
 
     Ok(())
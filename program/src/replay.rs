@@ -0,0 +1,193 @@
+//! Replays logged instructions of a market against an in-memory [`SimMarket`], which reconstructs its orderbook
+//! and event stream deterministically.
+//!
+//! Instructions are logged one per line, as `<slot> <unix_timestamp> <data>`, where the data is the hex encoded
+//! instruction data, tag included. Any source can produce such logs, such as a crawler which walks the market's
+//! transactions with `getSignaturesForAddress` and `getTransaction`.
+//!
+//! Instructions which failed on-chain are expected to fail again, and are rolled back in the same way. A
+//! consume_events instruction which ran out of compute units on-chain consumed fewer events than it requested, which
+//! its data alone can't tell: the replay pops all of them.
+use std::{fmt, str::FromStr};
+
+use anchor_lang::error::Error;
+use aob::{
+    critbit::Slab,
+    error::ErrorCode,
+    params::{CancelOrderParams, ConsumeEventsParams, CreateMarketParams, NewOrderParams},
+    sim::{Operation, Outcome, SimError, SimMarket},
+    state::{AccountTag, EventQueue, MarketState},
+};
+use borsh::BorshDeserialize;
+use num_traits::FromPrimitive;
+use solana_program::clock::Clock;
+
+use crate::instruction::AgnosticOrderbookInstruction;
+
+/// An instruction of the market, along with the clock of the slot it was processed in
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoggedInstruction {
+    #[allow(missing_docs)]
+    pub slot: u64,
+    #[allow(missing_docs)]
+    pub unix_timestamp: i64,
+    /// The instruction data, tag included
+    pub data: Vec<u8>,
+}
+
+impl FromStr for LoggedInstruction {
+    type Err = ReplayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ReplayError::InvalidLine(s.to_owned());
+        let mut fields = s.split_whitespace();
+        let mut next = || fields.next().ok_or_else(invalid);
+        let slot = next()?.parse().map_err(|_| invalid())?;
+        let unix_timestamp = next()?.parse().map_err(|_| invalid())?;
+        let hex = next()?;
+        if hex.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        let data = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(hex.get(i..i + 2).ok_or_else(invalid)?, 16)
+                    .map_err(|_| invalid())
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        Ok(Self {
+            slot,
+            unix_timestamp,
+            data,
+        })
+    }
+}
+
+/// Describes why a logged instruction couldn't be replayed
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The line doesn't hold a logged instruction
+    InvalidLine(String),
+    /// The instruction data doesn't deserialize
+    InvalidInstructionData,
+    /// An instruction other than create_market was logged before the market was created
+    MarketNotCreated,
+    /// A create_market instruction was logged for a market which was already created
+    MarketAlreadyCreated,
+    /// The instruction failed, and was rolled back
+    Rejected(SimError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InvalidLine(line) => write!(f, "Invalid instruction log line: {}", line),
+            ReplayError::InvalidInstructionData => write!(f, "Invalid instruction data"),
+            ReplayError::MarketNotCreated => write!(f, "The market hasn't been created yet"),
+            ReplayError::MarketAlreadyCreated => write!(f, "The market has already been created"),
+            ReplayError::Rejected(e) => write!(f, "Rejected: {:?}", e),
+        }
+    }
+}
+
+/// Reconstructs a market from the instructions it processed, starting from its create_market instruction.
+#[derive(Default)]
+pub struct Replayer {
+    /// Set once the create_market instruction has been replayed, and unset by the close_market instruction
+    pub market: Option<SimMarket>,
+}
+
+impl Replayer {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replays an instruction, returning the order summary and the events it produced.
+    pub fn apply(&mut self, instruction: &LoggedInstruction) -> Result<Outcome, ReplayError> {
+        let (&tag, data) = instruction
+            .data
+            .split_first()
+            .ok_or(ReplayError::InvalidInstructionData)?;
+        let instruction_tag = AgnosticOrderbookInstruction::from_u8(tag)
+            .ok_or(ReplayError::InvalidInstructionData)?;
+        let operation = match instruction_tag {
+            AgnosticOrderbookInstruction::CreateMarket => {
+                if self.market.is_some() {
+                    return Err(ReplayError::MarketAlreadyCreated);
+                }
+                let params = deserialize::<CreateMarketParams>(data)?;
+                if params.base_lot_size == 0 {
+                    let e = Error::from(ErrorCode::InvalidBaseLotSize);
+                    return Err(ReplayError::Rejected(e.into()));
+                }
+                self.market = Some(create_market(params));
+                return Ok(Outcome {
+                    summary: None,
+                    events: vec![],
+                });
+            }
+            AgnosticOrderbookInstruction::NewOrder => {
+                Operation::NewOrder(deserialize::<NewOrderParams>(data)?)
+            }
+            AgnosticOrderbookInstruction::CancelOrder => {
+                Operation::CancelOrder(deserialize::<CancelOrderParams>(data)?)
+            }
+            AgnosticOrderbookInstruction::ConsumeEvents => Operation::ConsumeEvents(
+                deserialize::<ConsumeEventsParams>(data)?.number_of_entries_to_consume,
+            ),
+            AgnosticOrderbookInstruction::CloseMarket => {
+                self.market.take().ok_or(ReplayError::MarketNotCreated)?;
+                return Ok(Outcome {
+                    summary: None,
+                    events: vec![],
+                });
+            }
+        };
+
+        let market = self.market.as_mut().ok_or(ReplayError::MarketNotCreated)?;
+        let clock = Clock {
+            slot: instruction.slot,
+            unix_timestamp: instruction.unix_timestamp,
+            ..Clock::default()
+        };
+        let (mut outcomes, error) = market.replay([Operation::SetClock(clock), operation]);
+        match error {
+            Some(e) => Err(ReplayError::Rejected(e)),
+            None => Ok(outcomes.pop().unwrap()),
+        }
+    }
+}
+
+fn deserialize<T: BorshDeserialize>(data: &[u8]) -> Result<T, ReplayError> {
+    T::try_from_slice(data).map_err(|_| ReplayError::InvalidInstructionData)
+}
+
+/// Creates the market as the create_market instruction would, with accounts sized for its capacities.
+fn create_market(params: CreateMarketParams) -> SimMarket {
+    let callback_info_len = params.callback_info_len as usize;
+    let market_state = MarketState {
+        tag: AccountTag::Market as u64,
+        caller_authority: params.caller_authority,
+        callback_info_len: params.callback_info_len,
+        callback_id_len: params.callback_id_len,
+        min_base_order_size: params.min_base_order_size,
+        tick_size: params.tick_size,
+        base_lot_size: params.base_lot_size,
+        cranker_base_reward: params.cranker_base_reward,
+        cranker_per_event_reward: params.cranker_per_event_reward,
+        order_priority: params.order_priority as u64,
+        event_queue_overflow_policy: params.event_queue_overflow_policy as u64,
+        max_post_only_spread_ticks: params.max_post_only_spread_ticks,
+        self_trade_detection_disabled: params.disable_self_trade_detection as u64,
+        price_rounding_policy: params.price_rounding_policy as u64,
+        max_match_iterations: params.max_match_iterations,
+        coalesce_fills: params.coalesce_fills as u64,
+        ..MarketState::default()
+    };
+    SimMarket::new(
+        market_state,
+        Slab::compute_allocation_size(params.order_capacity as usize, callback_info_len),
+        EventQueue::compute_allocation_size(params.event_capacity as usize, callback_info_len),
+    )
+}
@@ -0,0 +1,299 @@
+//! Helpers which bootstrap markets of the native program inside `solana-program-test` suites.
+//!
+//! Caller programs which are tested against the native program can register it with [`add_program`], create a market
+//! with [`TestMarket::create`], fund its crank rewards and then place, cancel and crank orders in a few lines:
+//!
+//! ```ignore
+//! let mut program_test = ProgramTest::new("caller", caller::id(), processor!(caller::entry));
+//! agnostic_orderbook::test_utils::add_program(&mut program_test);
+//! let mut ctx = program_test.start_with_context().await;
+//!
+//! let caller_authority = Keypair::new();
+//! let params = test_utils::market_params(caller_authority.pubkey());
+//! let market = TestMarket::create(&mut ctx, caller_authority, params).await?;
+//! market.fund(&mut ctx, 1_000_000).await?;
+//! let summary = market.new_order(&mut ctx, market.limit_order(Side::Bid, 10 << 32, 100)).await?;
+//! market.crank(&mut ctx).await?;
+//! ```
+use aob::critbit::Slab;
+use aob::orderbook::{OrderSummary, MAX_QUOTE_QTY};
+use aob::params::{CancelOrderParams, ConsumeEventsParams, CreateMarketParams, NewOrderParams};
+use aob::state::{
+    EventQueue, EventQueueHeader, EventQueueOverflowPolicy, OrderId, OrderPriority,
+    PriceRoundingPolicy, Register, SelfTradeBehavior, Side, EVENT_QUEUE_HEADER_LEN,
+    MARKET_STATE_LEN,
+};
+use aob::utils::Quantity;
+use borsh::BorshDeserialize;
+use solana_program::{instruction::Instruction, pubkey::Pubkey, rent::Rent};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction::{create_account, transfer};
+use solana_sdk::transaction::Transaction;
+use solana_sdk::transport::TransportError;
+
+use crate::instruction::{cancel_order, consume_events, create_market, new_order};
+
+/// The number of orders each side of a test market holds, unless specified otherwise
+pub const DEFAULT_ORDER_CAPACITY: u64 = 100;
+/// The number of events the event queue of a test market holds, unless specified otherwise
+pub const DEFAULT_EVENT_CAPACITY: u64 = 100;
+/// The callback information length of a test market, which fits a public key
+pub const DEFAULT_CALLBACK_INFO_LEN: u64 = 32;
+
+/// Registers the native program with a `solana-program-test` environment.
+pub fn add_program(program_test: &mut ProgramTest) {
+    program_test.add_program(
+        "agnostic_orderbook",
+        crate::id(),
+        processor!(crate::entrypoint::process_instruction),
+    );
+}
+
+/// The parameters of a permissive test market: unit tick size and base lots, no crank rewards, and room for
+/// [`DEFAULT_ORDER_CAPACITY`] orders and [`DEFAULT_EVENT_CAPACITY`] events.
+pub fn market_params(caller_authority: Pubkey) -> CreateMarketParams {
+    CreateMarketParams {
+        caller_authority: caller_authority.to_bytes(),
+        callback_info_len: DEFAULT_CALLBACK_INFO_LEN,
+        callback_id_len: DEFAULT_CALLBACK_INFO_LEN,
+        min_base_order_size: 1,
+        tick_size: 1,
+        base_lot_size: 1,
+        cranker_base_reward: 0,
+        cranker_per_event_reward: 0,
+        order_priority: OrderPriority::Fifo,
+        event_queue_overflow_policy: EventQueueOverflowPolicy::Reject,
+        max_post_only_spread_ticks: 0,
+        disable_self_trade_detection: false,
+        price_rounding_policy: PriceRoundingPolicy::Passive,
+        max_match_iterations: 0,
+        coalesce_fills: false,
+        order_capacity: DEFAULT_ORDER_CAPACITY,
+        event_capacity: DEFAULT_EVENT_CAPACITY,
+    }
+}
+
+/// A market created through [`TestMarket::create`], along with the keypair of its caller authority.
+pub struct TestMarket {
+    #[allow(missing_docs)]
+    pub market: Pubkey,
+    #[allow(missing_docs)]
+    pub event_queue: Pubkey,
+    #[allow(missing_docs)]
+    pub bids: Pubkey,
+    #[allow(missing_docs)]
+    pub asks: Pubkey,
+    /// Signs all the order and crank instructions
+    pub caller_authority: Keypair,
+    #[allow(missing_docs)]
+    pub callback_info_len: usize,
+}
+
+impl TestMarket {
+    /// Creates rent-exempt accounts sized for the capacities of `params`, and then the market.
+    pub async fn create(
+        ctx: &mut ProgramTestContext,
+        caller_authority: Keypair,
+        params: CreateMarketParams,
+    ) -> Result<Self, TransportError> {
+        let callback_info_len = params.callback_info_len as usize;
+        let book_len =
+            Slab::compute_allocation_size(params.order_capacity as usize, callback_info_len);
+        let accounts = [
+            (Keypair::new(), MARKET_STATE_LEN),
+            (
+                Keypair::new(),
+                EventQueue::compute_allocation_size(
+                    params.event_capacity as usize,
+                    callback_info_len,
+                ),
+            ),
+            (Keypair::new(), book_len),
+            (Keypair::new(), book_len),
+        ];
+        let mut instructions = accounts
+            .iter()
+            .map(|(account, len)| {
+                create_account(
+                    &ctx.payer.pubkey(),
+                    &account.pubkey(),
+                    Rent::default().minimum_balance(*len),
+                    *len as u64,
+                    &crate::id(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let [market, event_queue, bids, asks] = [0, 1, 2, 3].map(|i| accounts[i].0.pubkey());
+        instructions.push(create_market(
+            create_market::Accounts {
+                market: &market,
+                event_queue: &event_queue,
+                bids: &bids,
+                asks: &asks,
+            },
+            params,
+        ));
+        sign_send_instructions(
+            ctx,
+            instructions,
+            accounts.iter().map(|(account, _)| account).collect(),
+        )
+        .await?;
+
+        Ok(Self {
+            market,
+            event_queue,
+            bids,
+            asks,
+            caller_authority,
+            callback_info_len,
+        })
+    }
+
+    /// Transfers lamports from the test payer to the market account, which pays the crank rewards out of them.
+    pub async fn fund(
+        &self,
+        ctx: &mut ProgramTestContext,
+        lamports: u64,
+    ) -> Result<(), TransportError> {
+        let ix = transfer(&ctx.payer.pubkey(), &self.market, lamports);
+        sign_send_instructions(ctx, vec![ix], vec![]).await
+    }
+
+    /// The parameters of a limit order which may both match and post. Each order gets the callback information
+    /// of a fresh owner, so that orders never self trade.
+    pub fn limit_order(
+        &self,
+        side: Side,
+        limit_price: u64,
+        max_base_qty: Quantity,
+    ) -> NewOrderParams {
+        let owner = Pubkey::new_unique().to_bytes();
+        NewOrderParams {
+            max_base_qty,
+            max_quote_qty: MAX_QUOTE_QTY,
+            limit_price,
+            side,
+            match_limit: 10,
+            callback_info: owner
+                .iter()
+                .cycle()
+                .take(self.callback_info_len)
+                .copied()
+                .collect(),
+            post_only: false,
+            post_allowed: true,
+            self_trade_behavior: SelfTradeBehavior::AbortTransaction,
+            self_trade_id_len: None,
+            max_avg_price: None,
+        }
+    }
+
+    /// Places a new order and returns its [`OrderSummary`], as written to the event queue's register.
+    pub async fn new_order(
+        &self,
+        ctx: &mut ProgramTestContext,
+        params: NewOrderParams,
+    ) -> Result<OrderSummary, TransportError> {
+        let ix = new_order(
+            new_order::Accounts {
+                market: &self.market,
+                event_queue: &self.event_queue,
+                bids: &self.bids,
+                asks: &self.asks,
+                authority: &self.caller_authority.pubkey(),
+            },
+            params,
+        );
+        sign_send_instructions(ctx, vec![ix], vec![&self.caller_authority]).await?;
+        Ok(self.read_register(ctx).await)
+    }
+
+    /// Cancels an order and returns the [`OrderSummary`] of what was left of it in the orderbook.
+    pub async fn cancel_order(
+        &self,
+        ctx: &mut ProgramTestContext,
+        order_id: OrderId,
+    ) -> Result<OrderSummary, TransportError> {
+        let ix = cancel_order(
+            cancel_order::Accounts {
+                market: &self.market,
+                event_queue: &self.event_queue,
+                bids: &self.bids,
+                asks: &self.asks,
+                authority: &self.caller_authority.pubkey(),
+            },
+            CancelOrderParams {
+                order_id,
+                expected_callback_id: None,
+            },
+        );
+        sign_send_instructions(ctx, vec![ix], vec![&self.caller_authority]).await?;
+        Ok(self.read_register(ctx).await)
+    }
+
+    /// Consumes events until the event queue is empty, and returns the number of events which were consumed. The
+    /// crank rewards are paid to the test payer.
+    pub async fn crank(&self, ctx: &mut ProgramTestContext) -> Result<u64, TransportError> {
+        let mut consumed = 0;
+        loop {
+            let count = self.event_queue_header(ctx).await.count;
+            if count == 0 {
+                return Ok(consumed);
+            }
+            let ix = consume_events(
+                consume_events::Accounts {
+                    market: &self.market,
+                    event_queue: &self.event_queue,
+                    authority: &self.caller_authority.pubkey(),
+                    reward_target: &ctx.payer.pubkey(),
+                },
+                ConsumeEventsParams {
+                    number_of_entries_to_consume: count,
+                    min_remaining_compute_units: 0,
+                },
+            );
+            sign_send_instructions(ctx, vec![ix], vec![&self.caller_authority]).await?;
+            consumed += count - self.event_queue_header(ctx).await.count;
+        }
+    }
+
+    /// Reads the header of the market's event queue.
+    pub async fn event_queue_header(&self, ctx: &mut ProgramTestContext) -> EventQueueHeader {
+        let data = get_account_data(ctx, self.event_queue).await;
+        EventQueueHeader::load(&data).unwrap()
+    }
+
+    async fn read_register(&self, ctx: &mut ProgramTestContext) -> OrderSummary {
+        let data = get_account_data(ctx, self.event_queue).await;
+        Register::<OrderSummary>::deserialize(&mut &data[EVENT_QUEUE_HEADER_LEN..])
+            .unwrap()
+            .expect("The event queue register is empty")
+    }
+}
+
+/// Fetches the data of an account, which has to exist.
+pub async fn get_account_data(ctx: &mut ProgramTestContext, address: Pubkey) -> Vec<u8> {
+    ctx.banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .expect("The account doesn't exist")
+        .data
+}
+
+/// Signs the instructions with the test payer and the given signers, and processes them in one transaction.
+pub async fn sign_send_instructions(
+    ctx: &mut ProgramTestContext,
+    instructions: Vec<Instruction>,
+    signers: Vec<&Keypair>,
+) -> Result<(), TransportError> {
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&ctx.payer.pubkey()));
+    let mut payer_signers = vec![&ctx.payer];
+    for s in signers {
+        payer_signers.push(s);
+    }
+    transaction.partial_sign(&payer_signers, ctx.last_blockhash);
+    ctx.banks_client.process_transaction(transaction).await
+}
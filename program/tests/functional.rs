@@ -1,7 +1,6 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use borsh::BorshDeserialize;
 use bytemuck::try_from_bytes_mut;
 use solana_program::program_option::COption;
 use solana_program::program_pack::Pack;
@@ -23,9 +22,7 @@ use aob::{
         EventQueue, EventQueueHeader, MarketState, SelfTradeBehavior, Side,
     },
 };
-use common::utils::{create_market_and_accounts, sign_send_instructions};
-
-pub mod common;
+use agnostic_orderbook::test_utils::{market_params, sign_send_instructions, TestMarket};
 
 #[tokio::test]
 #[ignore]
@@ -109,9 +106,13 @@ async fn test_agnostic_orderbook() {
     .unwrap();
 
     let caller_authority = Keypair::new();
-    let market_account =
-        create_market_and_accounts(&mut prg_test_ctx, agnostic_orderbook::ID, &caller_authority)
-            .await;
+    let mut params = market_params(caller_authority.pubkey());
+    params.min_base_order_size = 10;
+    let test_market = TestMarket::create(&mut prg_test_ctx, caller_authority, params)
+        .await
+        .unwrap();
+    let market_account = test_market.market;
+    let caller_authority = &test_market.caller_authority;
 
     let mut market_state_data = prg_test_ctx
         .banks_client
@@ -156,12 +157,14 @@ async fn test_agnostic_orderbook() {
             post_allowed: true,
             self_trade_behavior: SelfTradeBehavior::CancelProvide,
             match_limit: 3,
+            self_trade_id_len: None,
+            max_avg_price: None,
         },
     );
     sign_send_instructions(
         &mut prg_test_ctx,
         vec![new_order_instruction],
-        vec![&caller_authority],
+        vec![caller_authority],
     )
     .await
     .unwrap();
@@ -199,12 +202,14 @@ async fn test_agnostic_orderbook() {
             post_allowed: true,
             self_trade_behavior: SelfTradeBehavior::CancelProvide,
             match_limit: 3,
+            self_trade_id_len: None,
+            max_avg_price: None,
         },
     );
     sign_send_instructions(
         &mut prg_test_ctx,
         vec![new_order_instruction],
-        vec![&caller_authority],
+        vec![caller_authority],
     )
     .await
     .unwrap();
@@ -225,11 +230,10 @@ async fn test_agnostic_orderbook() {
         .await
         .unwrap()
         .unwrap();
-    let event_queue_header =
-        EventQueueHeader::deserialize(&mut (&event_queue_acc.data as &[u8])).unwrap();
+    let event_queue_header = EventQueueHeader::load(&event_queue_acc.data).unwrap();
     let event_queue = EventQueue::new(
         event_queue_header,
-        Rc::new(RefCell::new(&mut event_queue_acc.data)),
+        Rc::new(RefCell::new(&mut event_queue_acc.data[..])),
         32,
     );
     let order_summary: OrderSummary = event_queue.read_register().unwrap().unwrap();
@@ -246,12 +250,13 @@ async fn test_agnostic_orderbook() {
         },
         CancelOrderParams {
             order_id: order_summary.posted_order_id.unwrap(),
+            expected_callback_id: None,
         },
     );
     sign_send_instructions(
         &mut prg_test_ctx,
         vec![cancel_order_instruction],
-        vec![&caller_authority],
+        vec![caller_authority],
     )
     .await
     .unwrap();
@@ -283,12 +288,13 @@ async fn test_agnostic_orderbook() {
         },
         ConsumeEventsParams {
             number_of_entries_to_consume: 10,
+            min_remaining_compute_units: 0,
         },
     );
     sign_send_instructions(
         &mut prg_test_ctx,
         vec![consume_events_instruction],
-        vec![&caller_authority],
+        vec![caller_authority],
     )
     .await
     .unwrap();
@@ -308,7 +314,7 @@ async fn test_agnostic_orderbook() {
     sign_send_instructions(
         &mut prg_test_ctx,
         vec![close_market_instruction],
-        vec![&caller_authority],
+        vec![caller_authority],
     )
     .await
     .unwrap();
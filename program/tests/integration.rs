@@ -3,20 +3,20 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use agnostic_orderbook::instruction::{cancel_order, create_market, new_order};
 use anyhow::anyhow;
-use borsh::BorshDeserialize;
 use solana_sdk::account::Account;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Keypair;
 use solana_sdk::system_instruction;
 use solana_sdk::{signature::Signer, transaction::Transaction};
 use solana_validator::test_validator::*;
-use agnostic_orderbook::instruction::{cancel_order, create_market, new_order};
 
+use aob::critbit::Slab;
 use aob::params::{CancelOrderParams, CreateMarketParams, NewOrderParams};
 use aob::state::{
-    EventQueue, EventQueueHeader, MarketState, OrderSummary, SelfTradeBehavior, Side,
-    MARKET_STATE_LEN,
+    EventQueue, EventQueueHeader, EventQueueOverflowPolicy, MarketState, OrderPriority,
+    OrderSummary, PriceRoundingPolicy, SelfTradeBehavior, Side, MARKET_STATE_LEN,
 };
 
 #[test]
@@ -27,7 +27,7 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
         .start();
     let rpc_client = test_validator.get_rpc_client();
     let blockhash = rpc_client.get_latest_blockhash()?;
-// TODO devnet
+    // TODO devnet
     // rpc_client.request_airdrop(&user.pubkey(), 1_000_000)?;
     // let rpc_client = RpcClient::new_with_commitment("https://api.devnet.solana.com".to_string(), CommitmentConfig::confirmed());
 
@@ -37,8 +37,10 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
         &[system_instruction::create_account(
             &payer.pubkey(),
             &event_queue.pubkey(),
-            rpc_client.get_minimum_balance_for_rent_exemption(1_000_000)?,
-            1_000_000,
+            rpc_client.get_minimum_balance_for_rent_exemption(
+                EventQueue::compute_allocation_size(10_000, 32),
+            )?,
+            EventQueue::compute_allocation_size(10_000, 32) as u64,
             &agnostic_orderbook::id(),
         )],
         Some(&payer.pubkey()),
@@ -53,8 +55,10 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
         &[system_instruction::create_account(
             &payer.pubkey(),
             &bids.pubkey(),
-            rpc_client.get_minimum_balance_for_rent_exemption(1_000_000)?,
-            1_000_000,
+            rpc_client.get_minimum_balance_for_rent_exemption(Slab::compute_allocation_size(
+                10_000, 32,
+            ))?,
+            Slab::compute_allocation_size(10_000, 32) as u64,
             &agnostic_orderbook::id(),
         )],
         Some(&payer.pubkey()),
@@ -69,8 +73,10 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
         &[system_instruction::create_account(
             &payer.pubkey(),
             &asks.pubkey(),
-            rpc_client.get_minimum_balance_for_rent_exemption(1_000_000)?,
-            1_000_000,
+            rpc_client.get_minimum_balance_for_rent_exemption(Slab::compute_allocation_size(
+                10_000, 32,
+            ))?,
+            Slab::compute_allocation_size(10_000, 32) as u64,
             &agnostic_orderbook::id(),
         )],
         Some(&payer.pubkey()),
@@ -105,7 +111,18 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
                     callback_id_len: 32,
                     min_base_order_size: 10,
                     tick_size: 1,
-                    cranker_reward: 0,
+                    base_lot_size: 1,
+                    cranker_base_reward: 0,
+                    cranker_per_event_reward: 0,
+                    order_priority: OrderPriority::Fifo,
+                    event_queue_overflow_policy: EventQueueOverflowPolicy::Reject,
+                    max_post_only_spread_ticks: 0,
+                    disable_self_trade_detection: false,
+                    price_rounding_policy: PriceRoundingPolicy::Passive,
+                    max_match_iterations: 0,
+                    coalesce_fills: false,
+                    order_capacity: 10_000,
+                    event_capacity: 10_000,
                 },
             ),
         ],
@@ -139,6 +156,8 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
                 post_allowed: true,
                 self_trade_behavior: SelfTradeBehavior::CancelProvide,
                 match_limit: 3,
+                self_trade_id_len: None,
+                max_avg_price: None,
             },
         )],
         Some(&payer.pubkey()),
@@ -168,6 +187,8 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
                 post_allowed: true,
                 self_trade_behavior: SelfTradeBehavior::CancelProvide,
                 match_limit: 3,
+                self_trade_id_len: None,
+                max_avg_price: None,
             },
         )],
         Some(&payer.pubkey()),
@@ -205,6 +226,7 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
             },
             CancelOrderParams {
                 order_id: order_summary.posted_order_id.unwrap(),
+                expected_callback_id: None,
             },
         )],
         Some(&payer.pubkey()),
@@ -225,12 +247,11 @@ fn test_agnostic_orderbook() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn get_event_queue(event_queue_account: &mut Account) -> anyhow::Result<EventQueue<'_>> {
-    let event_queue_header =
-        EventQueueHeader::deserialize(&mut (&event_queue_account.data as &[u8]))?;
+fn get_event_queue(event_queue_account: &mut Account) -> anyhow::Result<EventQueue<&mut [u8]>> {
+    let event_queue_header = EventQueueHeader::load(&event_queue_account.data).unwrap();
     Ok(EventQueue::new(
         event_queue_header,
-        Rc::new(RefCell::new(&mut event_queue_account.data)),
+        Rc::new(RefCell::new(&mut event_queue_account.data[..])),
         32,
     ))
 }
@@ -0,0 +1,110 @@
+use agnostic_orderbook::instruction::AgnosticOrderbookInstruction;
+use agnostic_orderbook::replay::{LoggedInstruction, ReplayError, Replayer};
+use aob::orderbook::MAX_QUOTE_QTY;
+use aob::params::{ConsumeEventsParams, CreateMarketParams, NewOrderParams};
+use aob::state::{
+    Event, EventQueueOverflowPolicy, OrderPriority, PriceRoundingPolicy, SelfTradeBehavior, Side,
+};
+use borsh::BorshSerialize;
+
+fn log_line(
+    slot: u64,
+    instruction: AgnosticOrderbookInstruction,
+    params: impl BorshSerialize,
+) -> String {
+    let mut data = vec![instruction as u8];
+    params.serialize(&mut data).unwrap();
+    let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{} {} {}", slot, 1_000 + slot, hex)
+}
+
+fn order(side: Side, limit_price: u64, max_base_qty: u64, owner: u8) -> NewOrderParams {
+    NewOrderParams {
+        max_base_qty,
+        max_quote_qty: MAX_QUOTE_QTY,
+        limit_price,
+        side,
+        match_limit: 10,
+        callback_info: vec![owner; 8],
+        post_only: false,
+        post_allowed: true,
+        self_trade_behavior: SelfTradeBehavior::DecrementTake,
+        self_trade_id_len: None,
+        max_avg_price: None,
+    }
+}
+
+#[test]
+fn replay_logged_instructions() {
+    let params = CreateMarketParams {
+        caller_authority: [0; 32],
+        callback_info_len: 8,
+        callback_id_len: 8,
+        min_base_order_size: 1,
+        tick_size: 1,
+        base_lot_size: 1,
+        cranker_base_reward: 0,
+        cranker_per_event_reward: 0,
+        order_priority: OrderPriority::Fifo,
+        event_queue_overflow_policy: EventQueueOverflowPolicy::Reject,
+        max_post_only_spread_ticks: 0,
+        disable_self_trade_detection: false,
+        price_rounding_policy: PriceRoundingPolicy::Passive,
+        max_match_iterations: 0,
+        coalesce_fills: false,
+        order_capacity: 16,
+        event_capacity: 16,
+    };
+    let lines = [
+        log_line(1, AgnosticOrderbookInstruction::CreateMarket, params),
+        log_line(
+            2,
+            AgnosticOrderbookInstruction::NewOrder,
+            order(Side::Bid, 10 << 32, 5, 1),
+        ),
+        log_line(
+            3,
+            AgnosticOrderbookInstruction::NewOrder,
+            order(Side::Ask, 10 << 32, 3, 2),
+        ),
+        log_line(
+            4,
+            AgnosticOrderbookInstruction::ConsumeEvents,
+            ConsumeEventsParams {
+                number_of_entries_to_consume: 10,
+                min_remaining_compute_units: 0,
+            },
+        ),
+    ];
+
+    let mut replayer = Replayer::new();
+    let outcomes = lines
+        .iter()
+        .map(|line| {
+            replayer
+                .apply(&line.parse::<LoggedInstruction>().unwrap())
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert!(outcomes[1].events.is_empty());
+    match &outcomes[2].events[..] {
+        [Event::Fill {
+            base_size, slot, ..
+        }] => assert_eq!((*base_size, *slot), (3, 3)),
+        events => panic!("Unexpected events {:?}", events),
+    }
+
+    let market = replayer.market.as_mut().unwrap();
+    assert_eq!(market.l2(Side::Bid, 10).unwrap(), vec![(10 << 32, 2)]);
+    assert!(market.l2(Side::Ask, 10).unwrap().is_empty());
+    assert!(market.events().is_empty());
+
+    assert!(matches!(
+        replayer.apply(&lines[0].parse().unwrap()),
+        Err(ReplayError::MarketAlreadyCreated)
+    ));
+    assert!(matches!(
+        "1 2 0".parse::<LoggedInstruction>(),
+        Err(ReplayError::InvalidLine(_))
+    ));
+}